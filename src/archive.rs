@@ -0,0 +1,238 @@
+use crate::log;
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::SystemTime;
+
+/// Endings of the archives opened like folders, read with `unzip`
+const ZIP: &[&str] = &[".zip", ".jar"];
+/// ... and with `tar`, which finds the compression by itself
+const TAR: &[&str] = &[
+    ".tar", ".tar.gz", ".tgz", ".tar.bz2", ".tbz2", ".tar.xz", ".txz",
+];
+
+/// Archives listed so far, with the time the file had then
+type Listings = Vec<(PathBuf, Option<SystemTime>, Arc<Vec<Member>>)>;
+static LISTINGS: Mutex<Listings> = Mutex::new(vec![]);
+
+#[derive(Clone, Copy, PartialEq)]
+enum Kind {
+    Zip,
+    Tar,
+}
+
+/// A file or folder stored in an archive
+struct Member {
+    /// Path inside the archive, without `./` or a trailing `/`
+    name: String,
+    /// The name as the archive has it, to extract it
+    raw: String,
+    is_dir: bool,
+}
+
+fn kind(name: &str) -> Option<Kind> {
+    let name = name.to_lowercase();
+    if ZIP.iter().any(|end| name.ends_with(end)) {
+        Some(Kind::Zip)
+    } else if TAR.iter().any(|end| name.ends_with(end)) {
+        Some(Kind::Tar)
+    } else {
+        None
+    }
+}
+
+/// Whether a file called `name` is an archive that opens like a folder
+pub fn is_archive(name: &str) -> bool {
+    kind(name).is_some()
+}
+
+/// The archive file `path` goes through, or is, and the path inside it:
+/// `/a/dados.zip/src/x.rs` is `("/a/dados.zip", "src/x.rs")`
+pub fn split(path: &Path) -> Option<(PathBuf, String)> {
+    for archive in path.ancestors() {
+        let name = archive.file_name()?.to_string_lossy();
+        if is_archive(&name) && archive.is_file() {
+            let inner = path.strip_prefix(archive).ok()?;
+            return Some((archive.to_path_buf(), inner.to_string_lossy().to_string()));
+        }
+    }
+    None
+}
+
+/// Whether `path` names something inside an archive (not the archive)
+pub fn is_inside(path: &str) -> bool {
+    split(Path::new(path)).is_some_and(|(_, inner)| !inner.is_empty())
+}
+
+/// The names directly in folder `dir` of `archive` ("" for its top), and
+/// whether each is a folder. Folders the archive only has files in count.
+pub fn list(archive: &Path, dir: &str) -> io::Result<Vec<(String, bool)>> {
+    let members = members(archive)?;
+    let mut listed: Vec<(String, bool)> = vec![];
+    for member in members.iter() {
+        let rest = match dir {
+            "" => member.name.as_str(),
+            _ => match member
+                .name
+                .strip_prefix(dir)
+                .and_then(|r| r.strip_prefix('/'))
+            {
+                Some(rest) => rest,
+                None => continue,
+            },
+        };
+        let (name, is_dir) = match rest.split_once('/') {
+            Some((name, _)) => (name, true),
+            None => (rest, member.is_dir),
+        };
+        if name.is_empty() || listed.iter().any(|(listed, _)| listed == name) {
+            continue;
+        }
+        listed.push((name.to_string(), is_dir));
+    }
+    Ok(listed)
+}
+
+/// Contents of file `inner` of `archive`
+pub fn read(archive: &Path, inner: &str) -> io::Result<Vec<u8>> {
+    let members = members(archive)?;
+    let member = members
+        .iter()
+        .find(|member| member.name == inner && !member.is_dir)
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("'{}' não está em '{}'", inner, archive.display()),
+            )
+        })?;
+    match kind(&archive.to_string_lossy()) {
+        Some(Kind::Zip) => {
+            // unzip takes options after the archive too
+            if member.raw.starts_with('-') {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("'{}': nome de arquivo não suportado", member.raw),
+                ));
+            }
+            run("unzip", &["-p"], archive, &[&literal_pattern(&member.raw)])
+        }
+        // tar reads options anywhere: `--` keeps a member named like one
+        // (`--use-compress-program=...`) a name
+        _ if gnu_tar() => run(
+            "tar",
+            &["--no-wildcards", "-xOf"],
+            archive,
+            &["--", &member.raw],
+        ),
+        _ => run("tar", &["-xOf"], archive, &["--", &member.raw]),
+    }
+}
+
+/// `name` as an unzip pattern that matches only itself: `[`, `*` and `?`
+/// in brackets
+fn literal_pattern(name: &str) -> String {
+    let mut pattern = String::new();
+    for c in name.chars() {
+        match c {
+            '[' | '*' | '?' => pattern.extend(['[', c, ']']),
+            _ => pattern.push(c),
+        }
+    }
+    pattern
+}
+
+/// Whether `tar` is GNU tar, whose member names are patterns unless told
+/// otherwise; bsdtar doesn't know `--no-wildcards`
+fn gnu_tar() -> bool {
+    static GNU: OnceLock<bool> = OnceLock::new();
+    *GNU.get_or_init(|| {
+        Command::new("tar")
+            .arg("--version")
+            .stdin(Stdio::null())
+            .stderr(Stdio::null())
+            .output()
+            .is_ok_and(|output| String::from_utf8_lossy(&output.stdout).contains("GNU tar"))
+    })
+}
+
+/// Where `extract-file` copies file `inner` of `archive`: a folder named
+/// after the archive beside it, as `unzip` would (`dados.zip` → `dados/`).
+/// `None` for names that would land outside it (`../x`, `/etc/x`).
+pub fn extract_path(archive: &Path, inner: &str) -> Option<PathBuf> {
+    let inside = Path::new(inner)
+        .components()
+        .all(|part| matches!(part, Component::Normal(_) | Component::CurDir));
+    if !inside {
+        return None;
+    }
+    let name = archive.file_name().unwrap_or_default().to_string_lossy();
+    let lower = name.to_lowercase();
+    let end = ZIP
+        .iter()
+        .chain(TAR)
+        .filter(|end| lower.ends_with(*end))
+        .map(|end| end.len())
+        .max()
+        .unwrap_or(0);
+    let folder = &name[..name.len() - end];
+    Some(archive.with_file_name(folder).join(inner))
+}
+
+/// The entries of `archive`, listed again only when the file changes
+fn members(archive: &Path) -> io::Result<Arc<Vec<Member>>> {
+    let stamp = fs::metadata(archive).and_then(|meta| meta.modified()).ok();
+    let mut listings = LISTINGS.lock().unwrap_or_else(|e| e.into_inner());
+    let listed = listings
+        .iter()
+        .find(|(path, time, _)| path == archive && *time == stamp);
+    if let Some((_, _, members)) = listed {
+        return Ok(Arc::clone(members));
+    }
+
+    let output = match kind(&archive.to_string_lossy()) {
+        Some(Kind::Zip) => run("unzip", &["-Z1"], archive, &[])?,
+        _ => run("tar", &["-tf"], archive, &[])?,
+    };
+    let members: Vec<Member> = String::from_utf8_lossy(&output)
+        .lines()
+        .filter(|line| !line.is_empty())
+        .map(|raw| {
+            let name = raw.trim_start_matches("./");
+            Member {
+                name: name.trim_end_matches('/').to_string(),
+                raw: raw.to_string(),
+                is_dir: name.ends_with('/'),
+            }
+        })
+        .filter(|member| !member.name.is_empty())
+        .collect();
+    log::info!(
+        "pacote",
+        "'{}' listado: {} itens",
+        archive.display(),
+        members.len()
+    );
+    let members = Arc::new(members);
+    listings.retain(|(path, _, _)| path != archive);
+    listings.push((archive.to_path_buf(), stamp, Arc::clone(&members)));
+    Ok(members)
+}
+
+/// Output of `program args archive members`, or its first error line
+fn run(program: &str, args: &[&str], archive: &Path, members: &[&str]) -> io::Result<Vec<u8>> {
+    let output = Command::new(program)
+        .args(args)
+        .arg(archive)
+        .args(members)
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", program, e)))?;
+    if output.status.success() {
+        return Ok(output.stdout);
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let cause = stderr.lines().next().unwrap_or("falhou").trim().to_string();
+    Err(io::Error::other(format!("{}: {}", program, cause)))
+}
@@ -0,0 +1,116 @@
+use crate::buffer_file::BufferFile;
+use crate::display::Display;
+use crate::vfs::{self, Filesystem, MemoryFs};
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Lines in the generated file when no file is given
+const SYNTHETIC_LINES: usize = 100_000;
+
+const FRAME_COLUMNS: u16 = 200;
+const FRAME_ROWS: u16 = 50;
+
+/// Name of the generated file in the in-memory filesystem
+const SYNTHETIC_PATH: &str = "bench.rs";
+
+/// `reditor --bench [arquivo]`: time the editing and rendering hot paths
+/// without a terminal, so regressions in display.rs/buffer_file.rs show up
+/// as numbers. Uses a generated Rust file, kept in memory so disk speed
+/// doesn't skew the numbers, unless `path` is given.
+pub fn run(path: Option<&str>) -> io::Result<()> {
+    match path {
+        Some(path) => run_on(vfs::for_path(path), path),
+        None => run_on(synthetic_fs()?, SYNTHETIC_PATH),
+    }
+}
+
+fn run_on(fs: Arc<dyn Filesystem>, path: &str) -> io::Result<()> {
+    let size = fs.size(Path::new(path))?;
+    let mut buf = BufferFile::open_in(fs.clone(), path)?;
+    let lines = buf.file_matrix.len();
+    println!(
+        "reditor --bench: {} ({} linhas, {:.1} MB)",
+        path,
+        lines,
+        size as f64 / (1024.0 * 1024.0)
+    );
+
+    measure("abrir arquivo", 5, || {
+        BufferFile::open_in(fs.clone(), path).map(|_| ()).unwrap();
+    });
+
+    let middle = lines / 2;
+    let mut col = 0;
+    measure("inserir caractere", 10_000, || {
+        buf.add_char('x', col, middle);
+        col += 1;
+    });
+    buf.commit_edits();
+
+    let mut display = Display::with_size(FRAME_COLUMNS, FRAME_ROWS);
+    display.set_syntax_ext(buf.syntax_ext());
+    display.set_file_size(lines);
+    for (label, row) in [
+        ("renderizar frame (início)", 0),
+        ("renderizar frame (meio)", middle),
+    ] {
+        display.initial_row = row;
+        measure(label, 100, || display.compose(&buf.file_matrix, None, None));
+    }
+    display.initial_row = 0;
+    measure("renderizar frame com busca", 100, || {
+        display.compose(&buf.file_matrix, None, Some("fn"))
+    });
+
+    measure("buscar (sem resultado)", 5, || {
+        assert!(buf.find_next("zzqqzz", 0, 0).is_none());
+    });
+    Ok(())
+}
+
+/// Run `f` `iterations` times and print the mean duration
+fn measure(label: &str, iterations: u32, mut f: impl FnMut()) {
+    let start = Instant::now();
+    for _ in 0..iterations {
+        f();
+    }
+    let mean = start.elapsed() / iterations;
+    println!(
+        "  {:<28} {:>12}  (média de {})",
+        label,
+        format_duration(mean),
+        iterations
+    );
+}
+
+fn format_duration(duration: Duration) -> String {
+    let micros = duration.as_secs_f64() * 1_000_000.0;
+    if micros >= 1000.0 {
+        format!("{:.2} ms", micros / 1000.0)
+    } else {
+        format!("{:.2} µs", micros)
+    }
+}
+
+/// A filesystem holding a Rust-looking file (comments, strings, keywords,
+/// numbers) so highlighting does realistic work
+fn synthetic_fs() -> io::Result<Arc<dyn Filesystem>> {
+    let mut text = String::new();
+    for i in 0..SYNTHETIC_LINES {
+        text.push_str(match i % 8 {
+            0 => "/// Documentação da função gerada\n",
+            1 => "pub fn calcular(valor: usize, nome: &str) -> Option<String> {\n",
+            2 => "    let mut total = valor * 42 + 0x1f; // comentário ao fim\n",
+            3 => "    if nome.starts_with(\"abc\") && total > 1000 {\n",
+            4 => "        return Some(format!(\"{} = {}\", nome, total));\n",
+            5 => "    }\n",
+            6 => "    None\n",
+            _ => "}\n",
+        });
+    }
+    let fs = MemoryFs::default();
+    fs.write(Path::new(SYNTHETIC_PATH), text.as_bytes())?;
+    Ok(Arc::new(fs))
+}
@@ -0,0 +1,202 @@
+/// Lines above which the path isn't worked out on every cursor move
+const MAX_LINES: usize = 20_000;
+
+/// One step of the path to the cursor
+enum Step {
+    Key(String),
+    Index(usize),
+}
+
+/// Path to the cursor in a JSON or YAML document, as
+/// `services.web.environment[2]`; `None` for other languages, at the top
+/// level, or in files too big to scan on each move
+pub fn at(language: &str, lines: &[Vec<char>], cursor: (usize, usize)) -> Option<String> {
+    if cursor.0 >= MAX_LINES || cursor.0 >= lines.len() {
+        return None;
+    }
+    let steps = match language {
+        "json" => json_path(lines, cursor),
+        "yaml" | "yml" => yaml_path(lines, cursor.0),
+        _ => return None,
+    };
+    let mut path = String::new();
+    for step in steps {
+        match step {
+            Step::Key(key) if is_plain(&key) => {
+                if !path.is_empty() {
+                    path.push('.');
+                }
+                path.push_str(&key);
+            }
+            Step::Key(key) => path.push_str(&format!("[\"{}\"]", key)),
+            Step::Index(index) => path.push_str(&format!("[{}]", index)),
+        }
+    }
+    Some(path).filter(|path| !path.is_empty())
+}
+
+/// Keys written as they are after a dot (no spaces, dots or brackets)
+fn is_plain(key: &str) -> bool {
+    !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '$' | '@'))
+}
+
+/// A `{` or `[` the cursor is inside of
+enum Frame {
+    /// The key whose value is being read, once its `:` is seen
+    Object(Option<String>),
+    Array(usize),
+}
+
+/// Walk the text before the cursor keeping the open objects and arrays;
+/// tolerant of the broken JSON of a file being edited
+fn json_path(lines: &[Vec<char>], cursor: (usize, usize)) -> Vec<Step> {
+    let mut frames: Vec<Frame> = vec![];
+    // A string just read, which a `:` turns into a key
+    let mut last_string: Option<String> = None;
+    let mut string: Option<String> = None;
+    let mut escaped = false;
+
+    let mut chars = lines[..cursor.0]
+        .iter()
+        .flat_map(|line| line.iter().chain(std::iter::once(&'\n')))
+        .chain(lines[cursor.0].iter().take(cursor.1));
+    for &c in chars.by_ref() {
+        if let Some(text) = string.as_mut() {
+            match c {
+                _ if escaped => {
+                    escaped = false;
+                    text.push(c);
+                }
+                '\\' => escaped = true,
+                '"' => last_string = string.take(),
+                _ => text.push(c),
+            }
+            continue;
+        }
+        match c {
+            '"' => string = Some(String::new()),
+            '{' => frames.push(Frame::Object(None)),
+            '[' => frames.push(Frame::Array(0)),
+            '}' | ']' => {
+                frames.pop();
+            }
+            ':' => {
+                if let Some(Frame::Object(key)) = frames.last_mut() {
+                    *key = last_string.take();
+                }
+            }
+            ',' => match frames.last_mut() {
+                Some(Frame::Array(index)) => *index += 1,
+                Some(Frame::Object(key)) => *key = None,
+                None => {}
+            },
+            _ => {}
+        }
+        if !matches!(c, '"' | ':') && !c.is_whitespace() {
+            last_string = None;
+        }
+    }
+
+    // On a key: the rest of it names the step
+    if let Some(mut text) = string {
+        let rest = lines[cursor.0].iter().skip(cursor.1);
+        for &c in rest.take_while(|&&c| c != '"') {
+            text.push(c);
+        }
+        last_string = Some(text);
+    }
+    if let (Some(Frame::Object(key @ None)), Some(name)) = (frames.last_mut(), last_string) {
+        *key = Some(name);
+    }
+
+    frames
+        .into_iter()
+        .filter_map(|frame| match frame {
+            Frame::Object(key) => key.map(Step::Key),
+            Frame::Array(index) => Some(Step::Index(index)),
+        })
+        .collect()
+}
+
+/// Nesting by indentation: each line's key, or `- ` item, sits under the
+/// closest line above indented less
+fn yaml_path(lines: &[Vec<char>], row: usize) -> Vec<Step> {
+    let mut stack: Vec<(usize, Step)> = vec![];
+    // Lines of a `|` or `>` text deeper than this are text, not keys
+    let mut text_block: Option<usize> = None;
+
+    for line in &lines[..=row] {
+        let text: String = line.iter().collect();
+        let content = text.trim_start();
+        let mut indent = text.len() - content.len();
+        if content.is_empty() || content.starts_with('#') {
+            continue;
+        }
+        if let Some(block) = text_block {
+            if indent > block {
+                continue;
+            }
+            text_block = None;
+        }
+        if content.starts_with("---") {
+            stack.clear();
+            continue;
+        }
+
+        let mut content = content;
+        if content == "-" || content.starts_with("- ") {
+            // Indented sequences sit deeper than their key; others don't
+            while stack.last().is_some_and(|(at, _)| *at > indent) {
+                stack.pop();
+            }
+            match stack.last_mut() {
+                Some((at, Step::Index(index))) if *at == indent => *index += 1,
+                _ => stack.push((indent, Step::Index(0))),
+            }
+            let rest = content[1..].trim_start();
+            indent += content.len() - rest.len();
+            content = rest;
+        } else {
+            while stack.last().is_some_and(|(at, _)| *at >= indent) {
+                stack.pop();
+            }
+        }
+
+        if let Some((key, value)) = yaml_key(content) {
+            if value.starts_with('|') || value.starts_with('>') {
+                text_block = Some(indent);
+            }
+            stack.push((indent, Step::Key(key)));
+        }
+    }
+    stack.into_iter().map(|(_, step)| step).collect()
+}
+
+/// The key of a `key: value` line and the value after it
+fn yaml_key(content: &str) -> Option<(String, &str)> {
+    let (key, value) = match content.chars().next()? {
+        quote @ ('"' | '\'') => {
+            let end = content[1..].find(quote)? + 1;
+            let rest = content[end + 1..].strip_prefix(':')?;
+            (content[1..end].to_string(), rest)
+        }
+        _ => {
+            let colon = content
+                .match_indices(':')
+                .map(|(i, _)| i)
+                .find(|&i| content[i + 1..].is_empty() || content[i + 1..].starts_with(' '))?;
+            let key = content[..colon].trim_end();
+            if key.starts_with(['{', '[', '&', '*', '!']) {
+                return None;
+            }
+            (key.to_string(), &content[colon + 1..])
+        }
+    };
+    if value.starts_with(|c: char| !c.is_whitespace()) {
+        return None;
+    }
+    Some((key, value.trim_start()))
+}
@@ -1,50 +1,232 @@
-use std::fs;
-use std::fs::File;
+use crate::diagnostics::{Diagnostic, Severity};
+use crate::git::BlameLine;
+use crate::log;
+use crate::undo::{Edit, UndoHistory};
+use crate::vfs::{self, Filesystem};
+use crate::view::ViewState;
 use std::io::Read;
+use std::path::Path;
+use std::sync::Arc;
+
+/// How many leading bytes are inspected to decide if a file is binary
+const BINARY_SNIFF_LEN: usize = 8192;
+
+/// Hex view only renders this many bytes of a file
+const HEX_VIEW_LIMIT: usize = 4 * 1024 * 1024;
+
+pub const UTF8_BOM: &[u8] = &[0xEF, 0xBB, 0xBF];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LineEnding {
+    Lf,
+    Crlf,
+}
+
+impl LineEnding {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LineEnding::Lf => "LF",
+            LineEnding::Crlf => "CRLF",
+        }
+    }
+
+    fn separator(&self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::Crlf => "\r\n",
+        }
+    }
+
+    /// Line ending used by the first line break of `content` (LF if none)
+    pub fn detect(content: &[u8]) -> LineEnding {
+        match content.iter().position(|&b| b == b'\n') {
+            Some(pos) if pos > 0 && content[pos - 1] == b'\r' => LineEnding::Crlf,
+            _ => LineEnding::Lf,
+        }
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct BufferFile {
     pub filename: String,
     pub file_matrix: Vec<Vec<char>>,
     pub modified: bool,
-    pub cursor_row: u16,
-    pub cursor_col: u16,
-    pub initial_row: u16,
-    pub initial_column: u16,
+    pub view: ViewState,
+    pub read_only: bool,
+    // Hexdump of the file rather than its text; it stays read-only
+    pub hex_view: bool,
+    pub line_ending: LineEnding,
+    pub has_bom: bool,
+    // Language chosen by the user, as an extension; overrides the filename's
+    pub language: Option<String>,
+    pub history: UndoHistory,
+    // Where the file was read from and is saved to
+    pub fs: Arc<dyn Filesystem>,
+    // `git blame` of the file as last saved, once fetched
+    pub blame: Option<Vec<BlameLine>>,
+    // Problems reported by checkers, stale ones included
+    pub diagnostics: Vec<Diagnostic>,
+    // Options set for this buffer alone (`:setlocal`), as (name, value)
+    pub local_options: Vec<(String, String)>,
 }
 
 impl BufferFile {
-    pub fn new(path: &str) -> BufferFile {
-        let file = File::open(path);
-        let mut contents = String::new();
+    pub fn new(path: &str) -> std::io::Result<BufferFile> {
+        BufferFile::open_in(vfs::for_path(path), path)
+    }
 
-        file.unwrap().read_to_string(&mut contents).unwrap();
+    pub fn open_in(fs: Arc<dyn Filesystem>, path: &str) -> std::io::Result<BufferFile> {
+        let bytes = fs.read(Path::new(path))?;
 
-        BufferFile {
-            filename: path.to_string(),
-            file_matrix: BufferFile::get_file_matrix(&contents),
-            modified: false,
-            cursor_row: 0,
-            cursor_col: 0,
-            initial_row: 0,
-            initial_column: 0,
+        let has_bom = bytes.starts_with(UTF8_BOM);
+        let body = if has_bom {
+            &bytes[UTF8_BOM.len()..]
+        } else {
+            &bytes[..]
+        };
+        let contents = String::from_utf8_lossy(body);
+
+        let mut matrix = BufferFile::get_file_matrix(&contents);
+        let mut line_ending = LineEnding::detect(body);
+        // With mixed endings the file counts as LF and the lines ending in
+        // CRLF keep their CR, so saving writes them back as they were
+        let breaks: Vec<&str> = contents.split('\n').collect();
+        let crlf_rows: Vec<usize> = breaks[..breaks.len() - 1]
+            .iter()
+            .enumerate()
+            .filter(|(_, line)| line.ends_with('\r'))
+            .map(|(row, _)| row)
+            .collect();
+        if !crlf_rows.is_empty() && crlf_rows.len() < breaks.len() - 1 {
+            line_ending = LineEnding::Lf;
+            for row in crlf_rows {
+                matrix[row].push('\r');
+            }
         }
+
+        let mut buffer = BufferFile::with_matrix(path, matrix);
+        buffer.line_ending = line_ending;
+        buffer.has_bom = has_bom;
+        buffer.fs = fs;
+        buffer.flag_mixed_endings();
+        log::info!(
+            "arquivo",
+            "lido '{}' ({} bytes, {} linhas)",
+            path,
+            bytes.len(),
+            buffer.file_matrix.len()
+        );
+        Ok(buffer)
+    }
+
+    /// Open a read-only hexdump of the file (offset, hex bytes, ASCII column)
+    pub fn new_hex_view(path: &str) -> std::io::Result<BufferFile> {
+        let mut bytes = vec![];
+        vfs::for_path(path)
+            .open(Path::new(path))?
+            .take(HEX_VIEW_LIMIT as u64 + 1)
+            .read_to_end(&mut bytes)?;
+        let truncated = bytes.len() > HEX_VIEW_LIMIT;
+        bytes.truncate(HEX_VIEW_LIMIT);
+
+        let mut matrix: Vec<Vec<char>> = bytes
+            .chunks(16)
+            .enumerate()
+            .map(|(i, chunk)| {
+                let hex: Vec<String> = chunk.iter().map(|b| format!("{:02x}", b)).collect();
+                let ascii: String = chunk
+                    .iter()
+                    .map(|&b| {
+                        if b.is_ascii_graphic() || b == b' ' {
+                            b as char
+                        } else {
+                            '.'
+                        }
+                    })
+                    .collect();
+                format!("{:08x}  {:<47}  |{}|", i * 16, hex.join(" "), ascii)
+                    .chars()
+                    .collect()
+            })
+            .collect();
+
+        if truncated {
+            matrix.push(
+                format!("-- truncado em {} bytes --", HEX_VIEW_LIMIT)
+                    .chars()
+                    .collect(),
+            );
+        }
+        if matrix.is_empty() {
+            matrix.push(vec![]);
+        }
+
+        let mut buffer = BufferFile::with_matrix(path, matrix);
+        buffer.read_only = true;
+        buffer.hex_view = true;
+        Ok(buffer)
+    }
+
+    /// Inspect a file before loading it: returns (size in bytes, looks binary)
+    pub fn probe(path: &str) -> std::io::Result<(u64, bool)> {
+        let fs = vfs::for_path(path);
+        let size = fs.size(Path::new(path))?;
+        let mut head = vec![];
+        fs.open(Path::new(path))?
+            .take(BINARY_SNIFF_LEN as u64)
+            .read_to_end(&mut head)?;
+
+        let is_binary = head.contains(&0)
+            || match std::str::from_utf8(&head) {
+                Ok(_) => false,
+                // A multi-byte char cut at the sniff boundary is still text
+                Err(e) => e.error_len().is_some(),
+            };
+
+        Ok((size, is_binary))
     }
 
-    #[allow(dead_code)]
     pub fn new_empty(filename: &str) -> BufferFile {
+        BufferFile::with_matrix(filename, vec![vec![]])
+    }
+
+    /// A buffer named `filename` holding `lines`, read and saved through `fs`
+    pub fn with_lines(
+        fs: Arc<dyn Filesystem>,
+        filename: &str,
+        lines: Vec<Vec<char>>,
+    ) -> BufferFile {
+        let lines = if lines.is_empty() {
+            vec![vec![]]
+        } else {
+            lines
+        };
+        BufferFile {
+            fs,
+            ..BufferFile::with_matrix(filename, lines)
+        }
+    }
+
+    fn with_matrix(filename: &str, file_matrix: Vec<Vec<char>>) -> BufferFile {
         BufferFile {
             filename: filename.to_string(),
-            file_matrix: vec![vec![]],
+            file_matrix,
             modified: false,
-            cursor_row: 0,
-            cursor_col: 0,
-            initial_row: 0,
-            initial_column: 0,
+            view: ViewState::default(),
+            read_only: false,
+            hex_view: false,
+            line_ending: LineEnding::Lf,
+            has_bom: false,
+            language: None,
+            history: UndoHistory::new(),
+            fs: vfs::for_path(filename),
+            blame: None,
+            diagnostics: vec![],
+            local_options: vec![],
         }
     }
 
-    fn get_file_matrix(content: &String) -> Vec<Vec<char>> {
+    fn get_file_matrix(content: &str) -> Vec<Vec<char>> {
         let mut matrix: Vec<Vec<char>> = vec![];
 
         content.lines().for_each(|line| {
@@ -64,89 +246,553 @@ impl BufferFile {
         matrix
     }
 
-    pub fn add_char(&mut self, character: char, column: u16, row: u16) {
-        let absolute_row = row as usize;
+    /// Replace `count` lines at `row` with `lines`, recording the change for undo
+    fn replace_lines(&mut self, row: usize, count: usize, lines: Vec<Vec<char>>) {
+        let edit = Edit {
+            row,
+            before: self.file_matrix[row..row + count].to_vec(),
+            after: lines,
+        };
+        edit.apply(&mut self.file_matrix);
+        self.history
+            .record(edit, (self.view.cursor_row, self.view.cursor_col));
+        self.modified = true;
+    }
+
+    /// Text of a selection, from `start` to `end` inclusive. Line breaks
+    /// are only part of it between its rows.
+    pub fn selected_text(&self, start: (usize, usize), end: (usize, usize)) -> String {
+        self.text_between(start, self.after(end))
+    }
+
+    /// Text from `start` up to `end` (excluded)
+    pub fn text_between(&self, start: (usize, usize), end: (usize, usize)) -> String {
+        let mut text = String::new();
+        for row in start.0..=end.0 {
+            let line = &self.file_matrix[row];
+            let from = if row == start.0 {
+                start.1.min(line.len())
+            } else {
+                0
+            };
+            let to = if row == end.0 {
+                end.1.min(line.len())
+            } else {
+                line.len()
+            };
+            text.extend(&line[from..to.max(from)]);
+            if row < end.0 {
+                text.push('\n');
+            }
+        }
+        text
+    }
+
+    /// Replace a selection (as in `selected_text`) with `text`, as one
+    /// undoable step
+    pub fn replace_selection(&mut self, start: (usize, usize), end: (usize, usize), text: &str) {
+        self.replace_between(start, self.after(end), text);
+    }
+
+    /// Replace the text from `start` up to `end` (excluded) with `text`, as
+    /// one undoable step
+    pub fn replace_between(&mut self, start: (usize, usize), end: (usize, usize), text: &str) {
+        let head: String = self.file_matrix[start.0]
+            [..start.1.min(self.file_matrix[start.0].len())]
+            .iter()
+            .collect();
+        let tail: String = self.file_matrix[end.0][end.1.min(self.file_matrix[end.0].len())..]
+            .iter()
+            .collect();
+        let joined = format!("{}{}{}", head, text, tail);
+        let lines = joined
+            .split('\n')
+            .map(|line| line.chars().collect())
+            .collect();
+        self.replace_block(start.0, end.0 + 1 - start.0, lines);
+    }
+
+    /// Position just past `pos`, kept within its line
+    fn after(&self, (row, col): (usize, usize)) -> (usize, usize) {
+        (row, (col + 1).min(self.get_line_length(row)))
+    }
+
+    /// Replace `count` lines at `row` with `lines` as one undoable step
+    pub fn replace_block(&mut self, row: usize, count: usize, lines: Vec<Vec<char>>) {
+        self.commit_edits();
+        self.replace_lines(row, count, lines);
+        if self.file_matrix.is_empty() {
+            self.replace_lines(0, 0, vec![vec![]]);
+        }
+        self.clamp_cursor();
+        self.commit_edits();
+    }
 
-        if absolute_row >= self.file_matrix.len() {
+    /// Like `replace_block`, for a change made outside the editor (the file
+    /// on disk, another participant): the cursor and viewport stay on the
+    /// same text when the block sits above them
+    pub fn replace_external(&mut self, row: usize, count: usize, lines: Vec<Vec<char>>) {
+        let inserted = lines.len();
+        self.commit_edits();
+        self.replace_lines(row, count, lines);
+        if self.file_matrix.is_empty() {
+            self.replace_lines(0, 0, vec![vec![]]);
+        }
+        if self.view.cursor_row >= row + count {
+            self.view.cursor_row = self.view.cursor_row + inserted - count;
+        }
+        if self.view.initial_row >= row + count {
+            self.view.initial_row = self.view.initial_row + inserted - count;
+        }
+        self.clamp_cursor();
+        self.commit_edits();
+    }
+
+    pub fn add_char(&mut self, character: char, column: usize, row: usize) {
+        if row >= self.file_matrix.len() {
             return;
         }
 
-        let file_row = self.file_matrix.get_mut(absolute_row).unwrap();
+        let mut line = self.file_matrix[row].clone();
 
-        if (column as usize) < file_row.len() {
-            file_row.insert(column as usize, character);
+        if column < line.len() {
+            line.insert(column, character);
         } else {
-            file_row.push(character);
+            line.push(character);
         }
-        self.modified = true;
+        self.replace_lines(row, 1, vec![line]);
     }
 
-    pub fn remove_char(&mut self, column: u16, row: u16) -> bool {
-        let absolute_row = row as usize;
-
-        if absolute_row >= self.file_matrix.len() {
+    pub fn remove_char(&mut self, column: usize, row: usize) -> bool {
+        if row >= self.file_matrix.len() {
             return false;
         }
 
-        let col = column as usize;
-
-        if col > 0 {
-            let file_row = self.file_matrix.get_mut(absolute_row).unwrap();
-            if col <= file_row.len() {
-                file_row.remove(col - 1);
+        if column > 0 {
+            let mut line = self.file_matrix[row].clone();
+            if column <= line.len() {
+                line.remove(column - 1);
             }
-            self.modified = true;
+            self.replace_lines(row, 1, vec![line]);
             false
-        } else if absolute_row > 0 {
-            let current_line = self.file_matrix.remove(absolute_row);
-            let previous_row = self.file_matrix.get_mut(absolute_row - 1).unwrap();
-            previous_row.extend(current_line);
-            self.modified = true;
+        } else if row > 0 {
+            let mut merged = self.file_matrix[row - 1].clone();
+            merged.extend_from_slice(&self.file_matrix[row]);
+            self.replace_lines(row - 1, 2, vec![merged]);
             true
         } else {
             false
         }
     }
 
-    pub fn split_line(&mut self, column: u16, row: u16) {
-        let absolute_row = row as usize;
-
-        if absolute_row >= self.file_matrix.len() {
+    pub fn split_line(&mut self, column: usize, row: usize) {
+        if row >= self.file_matrix.len() {
             return;
         }
 
-        let file_row = self.file_matrix.get_mut(absolute_row).unwrap();
-        let col = column as usize;
+        let mut line = self.file_matrix[row].clone();
 
-        let new_line = if col < file_row.len() {
-            file_row.split_off(col)
+        let new_line = if column < line.len() {
+            line.split_off(column)
         } else {
             vec![]
         };
 
-        self.file_matrix.insert(absolute_row + 1, new_line);
-        self.modified = true;
+        self.replace_lines(row, 1, vec![line, new_line]);
+    }
+
+    /// Insert a (possibly multi-line) string at the given position.
+    /// Returns the (column, row) just after the inserted text.
+    pub fn insert_text(&mut self, text: &str, column: usize, row: usize) -> (usize, usize) {
+        if row >= self.file_matrix.len() {
+            return (column, row);
+        }
+
+        let mut head = self.file_matrix[row].clone();
+        let col = column.min(head.len());
+        let tail = head.split_off(col);
+
+        let normalized = text.replace("\r\n", "\n").replace('\r', "\n");
+        let mut lines: Vec<Vec<char>> = vec![head];
+        for (i, line) in normalized.split('\n').enumerate() {
+            if i > 0 {
+                lines.push(vec![]);
+            }
+            lines.last_mut().unwrap().extend(line.chars());
+        }
+
+        let end_row = row + lines.len() - 1;
+        let end_col = lines.last().map(|l| l.len()).unwrap_or(0);
+        lines.last_mut().unwrap().extend(tail);
+        self.replace_lines(row, 1, lines);
+
+        (end_col, end_row)
+    }
+
+    /// End the current undo step (e.g. when leaving insert mode)
+    pub fn commit_edits(&mut self) {
+        self.history
+            .commit((self.view.cursor_row, self.view.cursor_col));
+    }
+
+    /// Revert the last undo step. Returns false if there was nothing to undo.
+    pub fn undo(&mut self) -> bool {
+        self.commit_edits();
+        let group = match self.history.pop_undo() {
+            Some(group) => group,
+            None => return false,
+        };
+        for edit in group.edits.iter().rev() {
+            edit.revert(&mut self.file_matrix);
+        }
+        if let Some((before, _)) = group.line_ending {
+            self.line_ending = before;
+        }
+        self.restore_after_history(group.cursor_before);
+        true
+    }
+
+    /// Re-apply the last undone step. Returns false if there was nothing to redo.
+    pub fn redo(&mut self) -> bool {
+        self.commit_edits();
+        let group = match self.history.pop_redo() {
+            Some(group) => group,
+            None => return false,
+        };
+        for edit in &group.edits {
+            edit.apply(&mut self.file_matrix);
+        }
+        if let Some((_, after)) = group.line_ending {
+            self.line_ending = after;
+        }
+        self.restore_after_history(group.cursor_after);
+        true
+    }
+
+    fn restore_after_history(&mut self, cursor: (usize, usize)) {
+        (self.view.cursor_row, self.view.cursor_col) = cursor;
+        self.clamp_cursor();
+        self.view.preferred_col = self.view.cursor_col;
+        self.modified = self.history.is_modified();
+    }
+
+    /// Re-read the file from disk. Only the changed block of lines is
+    /// replaced, as one undoable step, so history and cursor survive.
+    /// Returns false if the contents on disk were identical.
+    pub fn reload(&mut self) -> std::io::Result<bool> {
+        let fresh = BufferFile::open_in(self.fs.clone(), &self.filename)?;
+        self.commit_edits();
+        self.line_ending = fresh.line_ending;
+        self.has_bom = fresh.has_bom;
+        self.blame = None;
+        let flags = fresh
+            .diagnostics
+            .iter()
+            .filter(|d| d.source == "eol")
+            .cloned()
+            .collect();
+        self.set_diagnostics("eol", flags);
+
+        let old = &self.file_matrix;
+        let new = &fresh.file_matrix;
+        let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+        if prefix == old.len() && prefix == new.len() {
+            self.history.mark_saved();
+            self.modified = false;
+            return Ok(false);
+        }
+        let suffix = old[prefix..]
+            .iter()
+            .rev()
+            .zip(new[prefix..].iter().rev())
+            .take_while(|(a, b)| a == b)
+            .count();
+        let removed = old.len() - prefix - suffix;
+        let lines = new[prefix..new.len() - suffix].to_vec();
+        self.replace_external(prefix, removed, lines);
+        self.history.mark_saved();
+        self.modified = false;
+        Ok(true)
     }
 
     pub fn save(&mut self) -> std::io::Result<()> {
+        let fs = self.fs.clone();
+        self.save_to(fs.as_ref())
+    }
+
+    /// Write the buffer to its path through `fs` instead of its own
+    /// filesystem
+    pub fn save_to(&mut self, fs: &dyn Filesystem) -> std::io::Result<()> {
         let content: String = self
             .file_matrix
             .iter()
             .map(|row| row.iter().collect::<String>())
             .collect::<Vec<String>>()
-            .join("\n");
+            .join(self.line_ending.separator());
+
+        let mut bytes: Vec<u8> = Vec::with_capacity(content.len() + UTF8_BOM.len());
+        if self.has_bom {
+            bytes.extend_from_slice(UTF8_BOM);
+        }
+        bytes.extend_from_slice(content.as_bytes());
 
-        fs::write(&self.filename, content)?;
+        fs.write(Path::new(&self.filename), &bytes)?;
+        log::info!(
+            "arquivo",
+            "salvo '{}' ({} bytes)",
+            self.filename,
+            bytes.len()
+        );
+        self.commit_edits();
+        self.history.mark_saved();
         self.modified = false;
+        self.blame = None;
         Ok(())
     }
 
-    pub fn get_line_length(&self, row: u16) -> u16 {
-        let absolute_row = row as usize;
-        if absolute_row < self.file_matrix.len() {
-            self.file_matrix[absolute_row].len() as u16
+    /// Use `line_ending` for every line on save, dropping the CRs kept on
+    /// the lines of a mixed file, as one undo step. Returns false if
+    /// nothing changed.
+    pub fn set_line_ending(&mut self, line_ending: LineEnding) -> bool {
+        let stray: Vec<usize> = (0..self.file_matrix.len())
+            .filter(|&row| self.file_matrix[row].last() == Some(&'\r'))
+            .collect();
+        if self.line_ending == line_ending && stray.is_empty() {
+            return false;
+        }
+        self.commit_edits();
+        for row in stray {
+            let mut line = self.file_matrix[row].clone();
+            line.pop();
+            self.replace_lines(row, 1, vec![line]);
+        }
+        if self.line_ending != line_ending {
+            let cursor = (self.view.cursor_row, self.view.cursor_col);
+            self.history
+                .record_line_ending(self.line_ending, line_ending, cursor);
+            self.line_ending = line_ending;
+        }
+        self.commit_edits();
+        self.clamp_cursor();
+        self.modified = true;
+        true
+    }
+
+    /// Flag in the gutter the lines of an LF file that end in CRLF (their
+    /// CR is kept at the end of the line)
+    pub fn flag_mixed_endings(&mut self) {
+        let found = match self.line_ending {
+            LineEnding::Lf => self
+                .file_matrix
+                .iter()
+                .enumerate()
+                .filter(|(_, line)| line.last() == Some(&'\r'))
+                .map(|(row, line)| {
+                    let cr = (row, line.len() - 1);
+                    Diagnostic::new(
+                        Severity::Warning,
+                        cr,
+                        cr,
+                        "final de linha CRLF num arquivo LF",
+                        "eol",
+                        &self.file_matrix,
+                    )
+                })
+                .collect(),
+            LineEnding::Crlf => vec![],
+        };
+        self.set_diagnostics("eol", found);
+    }
+
+    /// Whether some lines end differently from the rest, as flagged
+    pub fn has_mixed_endings(&self) -> bool {
+        self.current_diagnostics().any(|d| d.source == "eol")
+    }
+
+    /// Add or strip the UTF-8 BOM on save. Returns false if nothing changed.
+    pub fn set_bom(&mut self, has_bom: bool) -> bool {
+        if self.has_bom == has_bom {
+            return false;
+        }
+        self.has_bom = has_bom;
+        self.modified = true;
+        true
+    }
+
+    /// Next case-insensitive match of `query` at or after (row, col),
+    /// wrapping around the end of the buffer
+    pub fn find_next(&self, query: &str, row: usize, col: usize) -> Option<(usize, usize)> {
+        let query: Vec<char> = query.to_lowercase().chars().collect();
+        let total_lines = self.file_matrix.len();
+        if query.is_empty() || total_lines == 0 {
+            return None;
+        }
+
+        for offset in 0..=total_lines {
+            let row_idx = (row + offset) % total_lines;
+            let line_lower: Vec<char> = self.file_matrix[row_idx]
+                .iter()
+                .flat_map(|c| c.to_lowercase())
+                .collect();
+            let start_col = if offset == 0 { col } else { 0 };
+
+            let qlen = query.len();
+            if line_lower.len() >= qlen {
+                for col in start_col..=line_lower.len() - qlen {
+                    if line_lower[col..col + qlen] == query[..] {
+                        return Some((row_idx, col));
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    /// Like `find_next`, for matches lying whole inside `scope` (first and
+    /// last (row, col), both included), wrapping around its end
+    pub fn find_next_within(
+        &self,
+        query: &str,
+        row: usize,
+        col: usize,
+        scope: ((usize, usize), (usize, usize)),
+    ) -> Option<(usize, usize)> {
+        let (start, end) = scope;
+        let len = query.chars().count();
+        let inside = |&(row, col): &(usize, usize)| {
+            (row, col) >= start && (row, col + len.saturating_sub(1)) <= end
+        };
+        let from = if (row, col) < start || (row, col) > end {
+            start
         } else {
-            0
+            (row, col)
+        };
+        self.find_next(query, from.0, from.1)
+            .filter(inside)
+            .or_else(|| self.find_next(query, start.0, start.1).filter(inside))
+    }
+
+    /// Start and end (exclusive) columns of the identifier at (row, col)
+    pub fn word_at(&self, row: usize, col: usize) -> Option<(usize, usize)> {
+        let line = self.file_matrix.get(row)?;
+        if !line.get(col).is_some_and(|&c| is_word_char(c)) {
+            return None;
+        }
+        let start = line[..col]
+            .iter()
+            .rposition(|&c| !is_word_char(c))
+            .map_or(0, |i| i + 1);
+        let end = line[col..]
+            .iter()
+            .position(|&c| !is_word_char(c))
+            .map_or(line.len(), |i| col + i);
+        Some((start, end))
+    }
+
+    /// (row, col) of every occurrence of `word` as a whole identifier, so
+    /// `id` doesn't match inside `idle` or `user_id`
+    pub fn find_word(&self, word: &[char]) -> Vec<(usize, usize)> {
+        let mut found = vec![];
+        if word.is_empty() {
+            return found;
+        }
+        for (row, line) in self.file_matrix.iter().enumerate() {
+            let mut col = 0;
+            while col + word.len() <= line.len() {
+                let boundary_before = col == 0 || !is_word_char(line[col - 1]);
+                let boundary_after = line.get(col + word.len()).is_none_or(|&c| !is_word_char(c));
+                if boundary_before && boundary_after && line[col..col + word.len()] == *word {
+                    found.push((row, col));
+                    col += word.len();
+                } else {
+                    col += 1;
+                }
+            }
+        }
+        found
+    }
+
+    /// Rough heap size of the text in bytes (undo history not included)
+    pub fn memory_estimate(&self) -> usize {
+        let line_size = std::mem::size_of::<Vec<char>>();
+        self.file_matrix
+            .iter()
+            .map(|line| line_size + line.capacity() * std::mem::size_of::<char>())
+            .sum()
+    }
+
+    /// Offset in bytes, as the file is saved (BOM and line endings
+    /// included), of the character at (row, col)
+    pub fn byte_offset(&self, row: usize, col: usize) -> usize {
+        let bom = if self.has_bom { UTF8_BOM.len() } else { 0 };
+        let separator = self.line_ending.separator().len();
+        let before: usize = self.file_matrix[..row.min(self.file_matrix.len())]
+            .iter()
+            .map(|line| line.iter().map(|ch| ch.len_utf8()).sum::<usize>() + separator)
+            .sum();
+        let within: usize = self
+            .file_matrix
+            .get(row)
+            .map(|line| line.iter().take(col).map(|ch| ch.len_utf8()).sum())
+            .unwrap_or(0);
+        bom + before + within
+    }
+
+    pub fn get_line_length(&self, row: usize) -> usize {
+        self.file_matrix
+            .get(row)
+            .map(|line| line.len())
+            .unwrap_or(0)
+    }
+
+    /// Keep the cursor inside the buffer after lines were removed or replaced
+    pub fn clamp_cursor(&mut self) {
+        self.view.cursor_row = self
+            .view
+            .cursor_row
+            .min(self.file_matrix.len().saturating_sub(1));
+        self.view.cursor_col = self
+            .view
+            .cursor_col
+            .min(self.get_line_length(self.view.cursor_row));
+
+        // The selection too, when another view or a reload removed lines
+        let lines = &self.file_matrix;
+        let clamp = |(row, col): (usize, usize)| {
+            let row = row.min(lines.len().saturating_sub(1));
+            (row, col.min(lines.get(row).map_or(0, Vec::len)))
+        };
+        self.view.anchor = self.view.anchor.map(clamp);
+        self.view.scope = self
+            .view
+            .scope
+            .map(|(start, end)| (clamp(start), clamp(end)));
+    }
+
+    /// Replace everything `source` reported about the buffer with `found`
+    pub fn set_diagnostics(&mut self, source: &str, found: Vec<Diagnostic>) {
+        self.diagnostics.retain(|d| d.source != source);
+        self.diagnostics.extend(found);
+        self.diagnostics.sort_by_key(|d| d.start);
+    }
+
+    /// Diagnostics whose line hasn't changed since they were reported
+    pub fn current_diagnostics(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.diagnostics
+            .iter()
+            .filter(|d| d.is_current(&self.file_matrix))
+    }
+
+    /// Extension used to pick syntax highlighting; files without one get
+    /// the language their first lines look like
+    pub fn syntax_ext(&self) -> String {
+        if let Some(ext) = &self.language {
+            return ext.clone();
+        }
+        let ext = crate::syntax::get_extension(&self.filename);
+        match crate::syntax::sniff_extension(&self.file_matrix) {
+            Some(sniffed) if ext.is_empty() && !self.hex_view => sniffed.to_string(),
+            _ => ext,
         }
     }
 
@@ -157,3 +803,8 @@ impl BufferFile {
             .unwrap_or_else(|| self.filename.clone())
     }
 }
+
+/// Characters that make up identifiers
+pub fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
@@ -0,0 +1,125 @@
+use std::env;
+use std::io::{self, Write};
+use std::process::{Command, Stdio};
+
+/// Programs that put text on the system clipboard, tried in order: the
+/// ones of the session's display server first
+fn copy_programs() -> Vec<(&'static str, &'static [&'static str])> {
+    if cfg!(target_os = "macos") {
+        return vec![("pbcopy", &[])];
+    }
+    if cfg!(windows) {
+        return vec![("clip", &[])];
+    }
+    let mut programs: Vec<(&str, &[&str])> = vec![];
+    if env::var_os("WAYLAND_DISPLAY").is_some() {
+        programs.push(("wl-copy", &[]));
+    }
+    if env::var_os("DISPLAY").is_some() {
+        programs.push(("xclip", &["-selection", "clipboard"]));
+        programs.push(("xsel", &["--clipboard", "--input"]));
+    }
+    programs
+}
+
+/// ... and that read it back
+fn paste_programs() -> Vec<(&'static str, &'static [&'static str])> {
+    if cfg!(target_os = "macos") {
+        return vec![("pbpaste", &[])];
+    }
+    if cfg!(windows) {
+        return vec![("powershell", &["-NoProfile", "-Command", "Get-Clipboard"])];
+    }
+    let mut programs: Vec<(&str, &[&str])> = vec![];
+    if env::var_os("WAYLAND_DISPLAY").is_some() {
+        programs.push(("wl-paste", &["--no-newline"]));
+    }
+    if env::var_os("DISPLAY").is_some() {
+        programs.push(("xclip", &["-selection", "clipboard", "-o"]));
+        programs.push(("xsel", &["--clipboard", "--output"]));
+    }
+    programs
+}
+
+/// Put `text` on the system clipboard. Without a clipboard program (over
+/// SSH, in a console) the terminal is asked to do it with OSC 52, which
+/// most support. Returns what took it.
+pub fn copy(text: &str) -> io::Result<&'static str> {
+    for (program, args) in copy_programs() {
+        let child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn();
+        let Ok(mut child) = child else {
+            continue;
+        };
+        let written = child
+            .stdin
+            .take()
+            .map(|mut stdin| stdin.write_all(text.as_bytes()));
+        // `wl-copy` and `xclip` stay behind to serve the text; they fork
+        // before exiting, so waiting doesn't hang
+        if child.wait().is_ok_and(|status| status.success()) && written.is_some_and(|w| w.is_ok()) {
+            return Ok(program);
+        }
+    }
+    let mut stdout = io::stdout();
+    write!(stdout, "\x1b]52;c;{}\x07", base64(text.as_bytes()))?;
+    stdout.flush()?;
+    Ok("OSC 52")
+}
+
+/// Text on the system clipboard
+pub fn paste() -> io::Result<String> {
+    let programs = paste_programs();
+    if programs.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            "sem área de transferência nesta sessão",
+        ));
+    }
+    let mut last_error = None;
+    for (program, args) in programs {
+        let output = Command::new(program)
+            .args(args)
+            .stdin(Stdio::null())
+            .stderr(Stdio::null())
+            .output();
+        match output {
+            Ok(output) if output.status.success() => {
+                let text = String::from_utf8_lossy(&output.stdout);
+                // `Get-Clipboard` ends it with a line break of its own
+                let text = match cfg!(windows) {
+                    true => text.trim_end_matches(['\r', '\n']),
+                    false => &text,
+                };
+                return Ok(text.replace("\r\n", "\n"));
+            }
+            Ok(_) => last_error = Some(io::Error::other(format!("{} falhou", program))),
+            Err(e) => last_error = Some(io::Error::new(e.kind(), format!("{}: {}", program, e))),
+        }
+    }
+    Err(last_error.unwrap_or_else(|| io::Error::other("falhou")))
+}
+
+/// Standard base64 with padding, as OSC 52 wants it
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::new();
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &b)| n | (b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
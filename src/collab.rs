@@ -0,0 +1,829 @@
+use crate::log;
+use crate::vfs::{DirEntry, Filesystem, LocalFs};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{
+    IpAddr, Ipv4Addr, Ipv6Addr, Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs,
+    UdpSocket,
+};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+pub const DEFAULT_PORT: u16 = 7878;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Longest header line read, in bytes: a guest that isn't in yet can't
+/// make the host hold more than this
+const MAX_HEADER: usize = 4096;
+
+/// Longest line of text read, in bytes
+const MAX_LINE: usize = 1 << 20;
+
+/// Most lines a message may carry
+const MAX_LINES: usize = 1 << 22;
+
+/// Address sessions are shared on unless another is asked for: only this
+/// machine (or an SSH tunnel to it) can join
+pub const DEFAULT_ADDRESS: &str = "127.0.0.1";
+
+/// A change made by one participant: `removed` lines at `row` were
+/// replaced by `lines`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Change {
+    pub row: usize,
+    pub removed: usize,
+    pub lines: Vec<Vec<char>>,
+}
+
+impl Change {
+    /// The block of lines that turns `old` into `new`, if they differ
+    pub fn between(old: &[Vec<char>], new: &[Vec<char>]) -> Option<Change> {
+        let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+        if prefix == old.len() && prefix == new.len() {
+            return None;
+        }
+        let suffix = old[prefix..]
+            .iter()
+            .rev()
+            .zip(new[prefix..].iter().rev())
+            .take_while(|(a, b)| a == b)
+            .count();
+        Some(Change {
+            row: prefix,
+            removed: old.len() - prefix - suffix,
+            lines: new[prefix..new.len() - suffix].to_vec(),
+        })
+    }
+
+    /// Whether the lines it replaces are all in `lines`. Changes come from
+    /// the network, so this is checked before applying them.
+    pub fn fits(&self, lines: &[Vec<char>]) -> bool {
+        self.row <= lines.len() && self.removed <= lines.len() - self.row
+    }
+
+    pub fn apply(&self, lines: &mut Vec<Vec<char>>) {
+        let end = self.row.saturating_add(self.removed).min(lines.len());
+        lines.splice(self.row.min(end)..end, self.lines.iter().cloned());
+    }
+
+    /// Whether `self` only touches lines above the ones `other` touches.
+    /// Two insertions at the same row have no order, so they clash.
+    fn precedes(&self, other: &Change) -> bool {
+        self.row
+            .checked_add(self.removed)
+            .is_some_and(|end| end <= other.row)
+            && !(self.removed == 0 && other.removed == 0 && self.row == other.row)
+    }
+
+    /// `self` moved past `other`, a change made at the same time to the
+    /// same text. None when both touch the same lines.
+    pub fn rebase(&self, other: &Change) -> Option<Change> {
+        if self.precedes(other) {
+            Some(self.clone())
+        } else if other.precedes(self) {
+            Some(Change {
+                row: self
+                    .row
+                    .checked_add(other.lines.len())?
+                    .checked_sub(other.removed)?,
+                ..self.clone()
+            })
+        } else {
+            None
+        }
+    }
+}
+
+/// What travels between host and guests. Each message is a tab-separated
+/// header line, followed by the lines of text it carries.
+#[derive(Debug)]
+enum Message {
+    /// From a guest, first thing: the session's code
+    Join {
+        token: String,
+    },
+    /// To a guest that just joined: its id and the text so far
+    Hello {
+        id: u32,
+        version: usize,
+        name: String,
+        lines: Vec<Vec<char>>,
+    },
+    /// From a guest, `version` is the text it edited; from the host, the
+    /// version the change produced and who made it
+    Change {
+        version: usize,
+        origin: u32,
+        change: Change,
+    },
+    /// The guest's last change clashed with someone else's and was dropped
+    Reject,
+    Cursor {
+        id: u32,
+        row: usize,
+        col: usize,
+    },
+    Bye {
+        id: u32,
+    },
+}
+
+fn write_lines(out: &mut dyn Write, lines: &[Vec<char>]) -> io::Result<()> {
+    for line in lines {
+        writeln!(out, "{}", line.iter().collect::<String>())?;
+    }
+    Ok(())
+}
+
+fn write_message(out: &mut dyn Write, message: &Message) -> io::Result<()> {
+    let mut bytes = vec![];
+    match message {
+        Message::Join { token } => writeln!(bytes, "join\t{}", token)?,
+        Message::Hello {
+            id,
+            version,
+            name,
+            lines,
+        } => {
+            writeln!(
+                bytes,
+                "hello\t{}\t{}\t{}\t{}",
+                id,
+                version,
+                lines.len(),
+                name
+            )?;
+            write_lines(&mut bytes, lines)?;
+        }
+        Message::Change {
+            version,
+            origin,
+            change,
+        } => {
+            writeln!(
+                bytes,
+                "change\t{}\t{}\t{}\t{}\t{}",
+                version,
+                origin,
+                change.row,
+                change.removed,
+                change.lines.len()
+            )?;
+            write_lines(&mut bytes, &change.lines)?;
+        }
+        Message::Reject => writeln!(bytes, "reject")?,
+        Message::Cursor { id, row, col } => writeln!(bytes, "cursor\t{}\t{}\t{}", id, row, col)?,
+        Message::Bye { id } => writeln!(bytes, "bye\t{}", id)?,
+    }
+    // In one write, so a message is never interleaved with another
+    out.write_all(&bytes)
+}
+
+/// One line of at most `max` bytes without its "\n"; None at the end of
+/// the stream. Unlike `BufRead::lines` a trailing '\r' is kept, as it is
+/// part of the text.
+fn read_line(reader: &mut dyn BufRead, max: usize) -> io::Result<Option<String>> {
+    let mut line = String::new();
+    let read = reader.take(max as u64 + 1).read_line(&mut line)?;
+    if read == 0 {
+        return Ok(None);
+    }
+    if line.ends_with('\n') {
+        line.pop();
+    } else if read > max {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "linha longa demais",
+        ));
+    }
+    Ok(Some(line))
+}
+
+fn read_message(reader: &mut dyn BufRead) -> io::Result<Option<Message>> {
+    let Some(header) = read_line(reader, MAX_HEADER)? else {
+        return Ok(None);
+    };
+    let invalid = || {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("mensagem inválida: {}", header),
+        )
+    };
+    let fields: Vec<&str> = header.split('\t').collect();
+    let number = |i: usize| -> io::Result<usize> {
+        fields
+            .get(i)
+            .and_then(|f| f.parse().ok())
+            .ok_or_else(invalid)
+    };
+    let mut lines = |count: usize| -> io::Result<Vec<Vec<char>>> {
+        if count > MAX_LINES {
+            return Err(invalid());
+        }
+        (0..count)
+            .map(|_| {
+                read_line(reader, MAX_LINE)?
+                    .map(|line| line.chars().collect())
+                    .ok_or_else(|| io::Error::from(io::ErrorKind::UnexpectedEof))
+            })
+            .collect()
+    };
+    let message = match fields[0] {
+        "join" => Message::Join {
+            token: fields.get(1).ok_or_else(invalid)?.to_string(),
+        },
+        "hello" => Message::Hello {
+            id: number(1)? as u32,
+            version: number(2)?,
+            name: fields.get(4).ok_or_else(invalid)?.to_string(),
+            lines: lines(number(3)?)?,
+        },
+        "change" => Message::Change {
+            version: number(1)?,
+            origin: number(2)? as u32,
+            change: Change {
+                row: number(3)?,
+                removed: number(4)?,
+                lines: lines(number(5)?)?,
+            },
+        },
+        "reject" => Message::Reject,
+        "cursor" => Message::Cursor {
+            id: number(1)? as u32,
+            row: number(2)?,
+            col: number(3)?,
+        },
+        "bye" => Message::Bye {
+            id: number(1)? as u32,
+        },
+        _ => return Err(invalid()),
+    };
+    Ok(Some(message))
+}
+
+enum Event {
+    Joined(u32, TcpStream),
+    Received(u32, Message),
+    Left(u32),
+}
+
+/// Take `stream` as guest `id` if the first thing it sends is the
+/// session's `token`; anyone else is hung up on
+fn admit(id: u32, stream: TcpStream, token: &str, events: &Sender<Event>) -> io::Result<()> {
+    stream.set_read_timeout(Some(CONNECT_TIMEOUT))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    match read_message(&mut reader)? {
+        Some(Message::Join { token: sent }) if same_token(&sent, token) => {}
+        _ => {
+            let _ = stream.shutdown(Shutdown::Both);
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "código da sessão errado",
+            ));
+        }
+    }
+    stream.set_read_timeout(None)?;
+    if events.send(Event::Joined(id, stream)).is_ok() {
+        listen_to(id, reader, events.clone());
+    }
+    Ok(())
+}
+
+/// Whether `sent` is `token`, taking as long wherever they differ so the
+/// time taken doesn't tell how much of a guess was right
+fn same_token(sent: &str, token: &str) -> bool {
+    sent.len() == token.len()
+        && sent
+            .bytes()
+            .zip(token.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+/// A code hard to guess, for guests to show they were invited: 64 bits
+/// from the hasher keys the standard library seeds from the OS
+fn new_token() -> String {
+    use std::collections::hash_map::RandomState;
+    use std::hash::BuildHasher;
+    format!("{:016x}", RandomState::new().hash_one(std::process::id()))
+}
+
+/// This machine's address on the route out, as the system picks it for a
+/// UDP socket "connected" to a documentation address (nothing is sent)
+fn outward_ip(v4: bool) -> Option<IpAddr> {
+    let (local, remote) = if v4 {
+        ("0.0.0.0:0", "192.0.2.1:9")
+    } else {
+        ("[::]:0", "[2001:db8::1]:9")
+    };
+    let socket = UdpSocket::bind(local).ok()?;
+    socket.connect(remote).ok()?;
+    let ip = socket.local_addr().ok()?.ip();
+    (!ip.is_unspecified()).then_some(ip)
+}
+
+/// Read messages from `id` on a thread until it disconnects
+fn listen_to(id: u32, mut reader: BufReader<TcpStream>, events: Sender<Event>) {
+    thread::spawn(move || {
+        loop {
+            match read_message(&mut reader) {
+                Ok(Some(message)) => {
+                    if events.send(Event::Received(id, message)).is_err() {
+                        return;
+                    }
+                }
+                Ok(None) => break,
+                Err(e) => {
+                    log::warning!("colab", "conexão {} encerrada: {}", id, e);
+                    break;
+                }
+            }
+        }
+        let _ = events.send(Event::Left(id));
+    });
+}
+
+enum Role {
+    /// Holds the authoritative text: every change goes through it, gets a
+    /// version number and is sent to everyone
+    Host {
+        // Where the listener is bound, to wake it when the session ends
+        address: SocketAddr,
+        // What guests send to be let in
+        token: String,
+        stop: Arc<AtomicBool>,
+        guests: Vec<(u32, TcpStream)>,
+        // The change that produced each version, to move late ones past
+        history: Vec<Change>,
+    },
+    Guest {
+        id: u32,
+        host: TcpStream,
+        // A change was sent and the host hasn't echoed or rejected it yet
+        waiting: bool,
+    },
+}
+
+/// What a `sync` brought
+#[derive(Default)]
+pub struct Sync {
+    /// Others' changes, to apply to the buffer in order
+    pub changes: Vec<Change>,
+    /// A local edit clashed with someone else's and was dropped
+    pub conflict: bool,
+    pub joined: usize,
+    pub left: usize,
+    /// The host closed the session
+    pub ended: bool,
+    /// Anything arrived, so the screen needs redrawing
+    pub received: bool,
+}
+
+/// A buffer edited by several instances over TCP. The host keeps the
+/// official text; guests send the block of lines they changed, and a
+/// change that arrives late is moved past the ones made meanwhile, or
+/// dropped when both touched the same lines.
+pub struct Session {
+    /// Name of the shared buffer in this instance
+    pub buffer: String,
+    role: Role,
+    events: Receiver<Event>,
+    version: usize,
+    // The text everyone agreed on, at `version`
+    synced: Vec<Vec<char>>,
+    /// The others' cursors: (participant, row, col)
+    pub cursors: Vec<(u32, usize, usize)>,
+    // Last cursor position sent
+    cursor: Option<(usize, usize)>,
+}
+
+impl Session {
+    /// Share the buffer `buffer`, with text `lines`, on `address` and
+    /// `port`. Guests need the session's code (`token`) to join.
+    pub fn host(
+        address: &str,
+        port: u16,
+        buffer: &str,
+        lines: &[Vec<char>],
+    ) -> io::Result<Session> {
+        let listener = TcpListener::bind((address, port))?;
+        let bound = listener.local_addr()?;
+        let token = new_token();
+        let (sender, events) = mpsc::channel();
+        let stop = Arc::new(AtomicBool::new(false));
+        let stopped = stop.clone();
+        let expected = token.clone();
+        thread::spawn(move || {
+            for (id, stream) in (1..).zip(listener.incoming()) {
+                if stopped.load(Ordering::Relaxed) {
+                    return;
+                }
+                let Ok(stream) = stream else {
+                    continue;
+                };
+                // Apart, so one that sends nothing doesn't hold up the rest
+                let (sender, token) = (sender.clone(), expected.clone());
+                thread::spawn(move || {
+                    let peer = stream
+                        .peer_addr()
+                        .map(|a| a.to_string())
+                        .unwrap_or_default();
+                    if let Err(e) = admit(id, stream, &token, &sender) {
+                        log::warning!("colab", "conexão de {} recusada: {}", peer, e);
+                    }
+                });
+            }
+        });
+        log::info!(
+            "colab",
+            "compartilhando '{}' em {}:{}",
+            buffer,
+            address,
+            bound.port()
+        );
+        Ok(Session {
+            buffer: buffer.to_string(),
+            role: Role::Host {
+                address: bound,
+                token,
+                stop,
+                guests: vec![],
+                history: vec![],
+            },
+            events,
+            version: 0,
+            synced: lines.to_vec(),
+            cursors: vec![],
+            cursor: None,
+        })
+    }
+
+    /// Join the session hosted at `address` ("host:porta") with its code
+    /// `token`. Returns the session and the text so far.
+    pub fn join(address: &str, token: &str) -> io::Result<(Session, Vec<Vec<char>>)> {
+        let address: SocketAddr = address
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| io::Error::other("endereço não encontrado"))?;
+        let mut host = TcpStream::connect_timeout(&address, CONNECT_TIMEOUT)?;
+        write_message(
+            &mut host,
+            &Message::Join {
+                token: token.to_string(),
+            },
+        )?;
+        let mut reader = BufReader::new(host.try_clone()?);
+        let Some(Message::Hello {
+            id,
+            version,
+            name,
+            lines,
+        }) = read_message(&mut reader)?
+        else {
+            return Err(io::Error::other(
+                "o anfitrião não respondeu ou recusou o código",
+            ));
+        };
+        let (sender, events) = mpsc::channel();
+        listen_to(0, reader, sender);
+        log::info!("colab", "entrou em '{}' em {} como {}", name, address, id);
+        let session = Session {
+            buffer: format!("{} [colab {}]", name, address),
+            role: Role::Guest {
+                id,
+                host,
+                waiting: false,
+            },
+            events,
+            version,
+            synced: lines.clone(),
+            cursors: vec![],
+            cursor: None,
+        };
+        Ok((session, lines))
+    }
+
+    pub fn is_host(&self) -> bool {
+        matches!(self.role, Role::Host { .. })
+    }
+
+    /// Address guests join with ("host:porta"), for the host. Shared on
+    /// every interface, it is this machine's address on the network.
+    pub fn join_address(&self) -> Option<String> {
+        let Role::Host { address, .. } = &self.role else {
+            return None;
+        };
+        let mut address = *address;
+        if address.ip().is_unspecified() {
+            match outward_ip(address.is_ipv4()) {
+                Some(ip) => address.set_ip(ip),
+                None => return Some(format!("<endereço>:{}", address.port())),
+            }
+        }
+        Some(address.to_string())
+    }
+
+    /// Code guests join with, for the host
+    pub fn token(&self) -> Option<&str> {
+        match &self.role {
+            Role::Host { token, .. } => Some(token),
+            Role::Guest { .. } => None,
+        }
+    }
+
+    /// Send what changed in `lines` (the buffer's text) and `cursor`, and
+    /// take what the others sent
+    pub fn sync(&mut self, lines: &[Vec<char>], cursor: (usize, usize)) -> Sync {
+        let mut sync = Sync::default();
+        let events: Vec<Event> = self.events.try_iter().collect();
+        sync.received = !events.is_empty();
+        if self.is_host() {
+            if let Some(change) = Change::between(&self.synced, lines) {
+                self.commit(0, change);
+            }
+            for event in events {
+                if let Some((origin, change)) = self.host_event(event, &mut sync) {
+                    sync.changes.push(change.clone());
+                    self.commit(origin, change);
+                }
+            }
+        } else {
+            let mut current = lines.to_vec();
+            for event in events {
+                self.guest_event(event, &mut current, &mut sync);
+            }
+            self.send_local(&current);
+        }
+
+        if self.cursor != Some(cursor) {
+            self.cursor = Some(cursor);
+            let message = Message::Cursor {
+                id: 0,
+                row: cursor.0,
+                col: cursor.1,
+            };
+            match &mut self.role {
+                Role::Host { guests, .. } => broadcast(guests, None, &message),
+                Role::Guest { host, .. } => {
+                    let _ = write_message(host, &message);
+                }
+            }
+        }
+        sync
+    }
+
+    /// Record a change to the official text and send it to everyone
+    fn commit(&mut self, origin: u32, change: Change) {
+        let Role::Host {
+            guests, history, ..
+        } = &mut self.role
+        else {
+            return;
+        };
+        change.apply(&mut self.synced);
+        history.push(change.clone());
+        self.version += 1;
+        let message = Message::Change {
+            version: self.version,
+            origin,
+            change,
+        };
+        broadcast(guests, None, &message);
+    }
+
+    /// Handle `event` on the host. Returns a guest's change to commit.
+    fn host_event(&mut self, event: Event, sync: &mut Sync) -> Option<(u32, Change)> {
+        let Role::Host {
+            guests, history, ..
+        } = &mut self.role
+        else {
+            return None;
+        };
+        match event {
+            Event::Joined(id, mut stream) => {
+                let name = Path::new(&self.buffer)
+                    .file_name()
+                    .map_or(self.buffer.clone(), |name| {
+                        name.to_string_lossy().to_string()
+                    });
+                let hello = Message::Hello {
+                    id,
+                    version: self.version,
+                    name,
+                    lines: self.synced.clone(),
+                };
+                let mut cursors = self.cursors.clone();
+                cursors.extend(self.cursor.map(|(row, col)| (0, row, col)));
+                let sent = write_message(&mut stream, &hello).and_then(|_| {
+                    cursors.iter().try_for_each(|&(id, row, col)| {
+                        write_message(&mut stream, &Message::Cursor { id, row, col })
+                    })
+                });
+                match sent {
+                    Ok(()) => {
+                        log::info!("colab", "participante {} entrou", id);
+                        guests.push((id, stream));
+                        sync.joined += 1;
+                    }
+                    Err(e) => log::warning!("colab", "erro ao receber {}: {}", id, e),
+                }
+            }
+            Event::Received(
+                id,
+                Message::Change {
+                    version, change, ..
+                },
+            ) => {
+                // Move the change past the ones committed since the guest's
+                // version
+                let rebased = history
+                    .get(version..)
+                    .and_then(|since| since.iter().try_fold(change, |c, h| c.rebase(h)));
+                let rebased = match rebased {
+                    Some(change) if !change.fits(&self.synced) => {
+                        log::warning!("colab", "alteração de {} fora do texto descartada", id);
+                        None
+                    }
+                    rebased => rebased,
+                };
+                if rebased.is_none() {
+                    log::info!("colab", "alteração de {} descartada", id);
+                    if let Some((_, stream)) = guests.iter_mut().find(|(guest, _)| *guest == id) {
+                        let _ = write_message(stream, &Message::Reject);
+                    }
+                }
+                return rebased.map(|change| (id, change));
+            }
+            Event::Received(id, Message::Cursor { row, col, .. }) => {
+                self.cursors.retain(|&(other, _, _)| other != id);
+                self.cursors.push((id, row, col));
+                broadcast(guests, Some(id), &Message::Cursor { id, row, col });
+            }
+            Event::Received(..) => {}
+            Event::Left(id) => {
+                log::info!("colab", "participante {} saiu", id);
+                guests.retain(|(guest, _)| *guest != id);
+                self.cursors.retain(|&(other, _, _)| other != id);
+                broadcast(guests, None, &Message::Bye { id });
+                sync.left += 1;
+            }
+        }
+        None
+    }
+
+    fn guest_event(&mut self, event: Event, current: &mut Vec<Vec<char>>, sync: &mut Sync) {
+        let Role::Guest { id, waiting, .. } = &mut self.role else {
+            return;
+        };
+        match event {
+            Event::Received(
+                _,
+                Message::Change {
+                    version,
+                    origin,
+                    change,
+                },
+            ) => {
+                if !change.fits(&self.synced) {
+                    log::warning!("colab", "alteração do anfitrião fora do texto descartada");
+                    return;
+                }
+                // Edits made here that the host hasn't taken yet
+                let local = Change::between(&self.synced, current);
+                change.apply(&mut self.synced);
+                self.version = version;
+                if origin == *id {
+                    // Our own change, already in the buffer
+                    *waiting = false;
+                    return;
+                }
+                let change = match &local {
+                    Some(local) => change.rebase(local),
+                    None => Some(change),
+                };
+                match change {
+                    Some(change) if change.fits(current) => {
+                        change.apply(current);
+                        sync.changes.push(change);
+                    }
+                    // Both touched the same lines: the host's text wins
+                    _ => {
+                        if let Some(fix) = Change::between(current, &self.synced) {
+                            fix.apply(current);
+                            sync.changes.push(fix);
+                        }
+                        sync.conflict = true;
+                    }
+                }
+            }
+            Event::Received(_, Message::Reject) => *waiting = false,
+            Event::Received(_, Message::Cursor { id, row, col }) => {
+                self.cursors.retain(|&(other, _, _)| other != id);
+                self.cursors.push((id, row, col));
+            }
+            Event::Received(_, Message::Bye { id }) => {
+                self.cursors.retain(|&(other, _, _)| other != id)
+            }
+            Event::Received(_, Message::Hello { .. } | Message::Join { .. })
+            | Event::Joined(..) => {}
+            Event::Left(_) => sync.ended = true,
+        }
+    }
+
+    /// Send the guest's edits, one change at a time: the next goes once
+    /// the host answered, computed against the text it confirmed
+    fn send_local(&mut self, current: &[Vec<char>]) {
+        let Role::Guest { id, host, waiting } = &mut self.role else {
+            return;
+        };
+        if *waiting {
+            return;
+        }
+        if let Some(change) = Change::between(&self.synced, current) {
+            let message = Message::Change {
+                version: self.version,
+                origin: *id,
+                change,
+            };
+            match write_message(host, &message) {
+                Ok(()) => *waiting = true,
+                Err(e) => log::warning!("colab", "erro ao enviar alteração: {}", e),
+            }
+        }
+    }
+}
+
+/// Send `message` to every guest but `except`
+fn broadcast(guests: &mut [(u32, TcpStream)], except: Option<u32>, message: &Message) {
+    for (id, stream) in guests.iter_mut() {
+        if Some(*id) != except {
+            // A guest that went away is dropped when its reader notices
+            let _ = write_message(stream, message);
+        }
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        match &self.role {
+            Role::Host {
+                address,
+                stop,
+                guests,
+                ..
+            } => {
+                for (_, stream) in guests {
+                    let _ = stream.shutdown(Shutdown::Both);
+                }
+                // Wake the accepting thread so it sees `stop` and frees the
+                // port: at the address it listens on, or this machine's when
+                // it listens on all of them
+                stop.store(true, Ordering::Relaxed);
+                let mut wake = *address;
+                if wake.ip().is_unspecified() {
+                    wake.set_ip(match wake {
+                        SocketAddr::V4(_) => Ipv4Addr::LOCALHOST.into(),
+                        SocketAddr::V6(_) => Ipv6Addr::LOCALHOST.into(),
+                    });
+                }
+                let _ = TcpStream::connect_timeout(&wake, CONNECT_TIMEOUT);
+            }
+            Role::Guest { host, .. } => {
+                let _ = host.shutdown(Shutdown::Both);
+            }
+        }
+    }
+}
+
+/// Where a guest's buffer "lives": the text comes from the host and is
+/// saved there, so reading or writing it here fails
+#[derive(Debug)]
+pub struct CollabFs;
+
+impl CollabFs {
+    fn refuse() -> io::Error {
+        io::Error::other("quem compartilhou o arquivo é quem salva")
+    }
+}
+
+impl Filesystem for CollabFs {
+    fn open(&self, _path: &Path) -> io::Result<Box<dyn Read + Send>> {
+        Err(CollabFs::refuse())
+    }
+
+    fn write(&self, _path: &Path, _bytes: &[u8]) -> io::Result<()> {
+        Err(CollabFs::refuse())
+    }
+
+    fn size(&self, _path: &Path) -> io::Result<u64> {
+        Err(CollabFs::refuse())
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<DirEntry>> {
+        LocalFs.read_dir(path)
+    }
+}
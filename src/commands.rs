@@ -0,0 +1,465 @@
+/// Commands reachable from the command palette (`Ctrl+P`)
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Command {
+    LineEndingLf,
+    LineEndingCrlf,
+    AddBom,
+    RemoveBom,
+    GotoLine,
+    SetLanguage,
+    Reload,
+    Undo,
+    Redo,
+    CancelJobs,
+    PerfHud,
+    OpenLog,
+    NewView,
+    Todos,
+    Blame,
+    HunkNext,
+    HunkPrev,
+    HunkStage,
+    HunkRevert,
+    OldFiles,
+    ReadOnly,
+    JsonFormat,
+    JsonMinify,
+    JsonValidate,
+    Transform,
+    OpenLink,
+    GotoFile,
+    GotoDefinition,
+    Tasks,
+    TaskOutput,
+    TaskStop,
+    RestoreTrash,
+    BulkRename,
+    Follow,
+    DiagnosticNext,
+    DiagnosticPrev,
+    Hover,
+    RenameSymbol,
+    Replace,
+    ProjectSearch,
+    ProjectReplace,
+    Marks,
+    CollabHost,
+    CollabJoin,
+    CollabLeave,
+    PasteHistory,
+    Snippet,
+    NewFile,
+    SidebarRefresh,
+    SidebarCollapse,
+    SidebarCompact,
+    SidebarJump,
+    Present,
+    InspectChar,
+    MakeExecutable,
+    ExtractFile,
+    TestFile,
+    IndentNext,
+    IndentPrev,
+    IndentStart,
+    IndentEnd,
+    TagMatch,
+    TagRename,
+    ApplyHunk,
+    Options,
+}
+
+impl Command {
+    /// Whether the command acts on an open file
+    pub fn needs_file(&self) -> bool {
+        !matches!(
+            self,
+            Command::CancelJobs
+                | Command::PerfHud
+                | Command::OpenLog
+                | Command::Todos
+                | Command::OldFiles
+                | Command::RestoreTrash
+                | Command::BulkRename
+                | Command::ProjectSearch
+                | Command::ProjectReplace
+                | Command::Marks
+                | Command::CollabJoin
+                | Command::CollabLeave
+                | Command::NewFile
+                | Command::SidebarRefresh
+                | Command::SidebarCollapse
+                | Command::SidebarCompact
+                | Command::SidebarJump
+                | Command::Tasks
+                | Command::TaskOutput
+                | Command::TaskStop
+                | Command::Options
+        )
+    }
+
+    /// Whether running the command edits the active buffer
+    pub fn modifies_buffer(&self) -> bool {
+        matches!(
+            self,
+            Command::LineEndingLf
+                | Command::LineEndingCrlf
+                | Command::AddBom
+                | Command::RemoveBom
+                | Command::Reload
+                | Command::Undo
+                | Command::Redo
+                | Command::HunkRevert
+                | Command::JsonFormat
+                | Command::JsonMinify
+                | Command::Transform
+                | Command::RenameSymbol
+                | Command::TagRename
+                | Command::PasteHistory
+                | Command::Snippet
+        )
+    }
+}
+
+/// The command called `name` in the palette
+pub fn by_name(name: &str) -> Option<Command> {
+    COMMANDS
+        .iter()
+        .find(|info| info.name == name)
+        .map(|info| info.command)
+}
+
+pub struct CommandInfo {
+    pub command: Command,
+    pub name: &'static str,
+    pub description: &'static str,
+}
+
+pub const COMMANDS: &[CommandInfo] = &[
+    CommandInfo {
+        command: Command::LineEndingLf,
+        name: "eol-lf",
+        description: "Converter finais de linha para LF",
+    },
+    CommandInfo {
+        command: Command::LineEndingCrlf,
+        name: "eol-crlf",
+        description: "Converter finais de linha para CRLF",
+    },
+    CommandInfo {
+        command: Command::AddBom,
+        name: "bom-add",
+        description: "Adicionar BOM UTF-8",
+    },
+    CommandInfo {
+        command: Command::RemoveBom,
+        name: "bom-remove",
+        description: "Remover BOM UTF-8",
+    },
+    CommandInfo {
+        command: Command::GotoLine,
+        name: "goto-line",
+        description: "Ir para linha (e coluna: linha:col)",
+    },
+    CommandInfo {
+        command: Command::SetLanguage,
+        name: "set-language",
+        description: "Escolher linguagem do destaque de sintaxe",
+    },
+    CommandInfo {
+        command: Command::Reload,
+        name: "reload",
+        description: "Recarregar arquivo do disco",
+    },
+    CommandInfo {
+        command: Command::Undo,
+        name: "undo",
+        description: "Desfazer (u)",
+    },
+    CommandInfo {
+        command: Command::Redo,
+        name: "redo",
+        description: "Refazer (Ctrl+R)",
+    },
+    CommandInfo {
+        command: Command::CancelJobs,
+        name: "cancel-jobs",
+        description: "Cancelar tarefas em segundo plano",
+    },
+    CommandInfo {
+        command: Command::PerfHud,
+        name: "perf-hud",
+        description: "Mostrar/ocultar painel de desempenho (F12)",
+    },
+    CommandInfo {
+        command: Command::OpenLog,
+        name: "open-log",
+        description: "Abrir o arquivo de log (--log)",
+    },
+    CommandInfo {
+        command: Command::NewView,
+        name: "new-view",
+        description: "Abrir o arquivo atual em outra aba, com cursor próprio",
+    },
+    CommandInfo {
+        command: Command::Todos,
+        name: "todos",
+        description: "Listar TODO/FIXME/HACK do projeto (ou das abas abertas)",
+    },
+    CommandInfo {
+        command: Command::Blame,
+        name: "blame",
+        description: "Mostrar/ocultar autor e commit da linha atual (git blame)",
+    },
+    CommandInfo {
+        command: Command::HunkNext,
+        name: "hunk-next",
+        description: "Ir para a próxima alteração (git diff)",
+    },
+    CommandInfo {
+        command: Command::HunkPrev,
+        name: "hunk-prev",
+        description: "Ir para a alteração anterior (git diff)",
+    },
+    CommandInfo {
+        command: Command::HunkStage,
+        name: "hunk-stage",
+        description: "Adicionar ao índice a alteração sob o cursor (git add)",
+    },
+    CommandInfo {
+        command: Command::HunkRevert,
+        name: "hunk-revert",
+        description: "Desfazer a alteração sob o cursor, voltando ao HEAD",
+    },
+    CommandInfo {
+        command: Command::OldFiles,
+        name: "oldfiles",
+        description: "Reabrir um arquivo do histórico (busca aproximada)",
+    },
+    CommandInfo {
+        command: Command::ReadOnly,
+        name: "read-only",
+        description: "Ativar/desativar somente leitura no arquivo atual",
+    },
+    CommandInfo {
+        command: Command::JsonFormat,
+        name: "json-format",
+        description: "Formatar o JSON do arquivo (recuo em [json] indent)",
+    },
+    CommandInfo {
+        command: Command::JsonMinify,
+        name: "json-minify",
+        description: "Compactar o JSON do arquivo em uma linha",
+    },
+    CommandInfo {
+        command: Command::JsonValidate,
+        name: "json-validate",
+        description: "Validar o JSON e ir para o erro",
+    },
+    CommandInfo {
+        command: Command::Transform,
+        name: "transform",
+        description: "Codificar/decodificar a seleção (Base64, URL, HTML, JSON, hex)",
+    },
+    CommandInfo {
+        command: Command::OpenLink,
+        name: "open-link",
+        description: "Abrir o link ou arquivo:linha sob o cursor (gx, Ctrl+Clique)",
+    },
+    CommandInfo {
+        command: Command::GotoFile,
+        name: "goto-file",
+        description: "Abrir o arquivo sob o cursor na linha:coluna indicada (gf)",
+    },
+    CommandInfo {
+        command: Command::GotoDefinition,
+        name: "goto-definition",
+        description:
+            "Ir para a definição do nome sob o cursor, pelo índice de símbolos do projeto (gd)",
+    },
+    CommandInfo {
+        command: Command::Tasks,
+        name: "tasks",
+        description: "Escolher e rodar uma tarefa do projeto (build, test... de [tasks])",
+    },
+    CommandInfo {
+        command: Command::TaskOutput,
+        name: "task-output",
+        description: "Mostrar/esconder o painel com a saída da última tarefa",
+    },
+    CommandInfo {
+        command: Command::TaskStop,
+        name: "task-stop",
+        description: "Interromper a tarefa em execução",
+    },
+    CommandInfo {
+        command: Command::RestoreTrash,
+        name: "restore-trash",
+        description: "Restaurar o último item movido para a lixeira pela sidebar",
+    },
+    CommandInfo {
+        command: Command::BulkRename,
+        name: "bulk-rename",
+        description: "Renomear os itens da pasta selecionada editando-os como texto",
+    },
+    CommandInfo {
+        command: Command::Follow,
+        name: "follow",
+        description: "Seguir o arquivo enquanto ele cresce, como tail -f (logs)",
+    },
+    CommandInfo {
+        command: Command::DiagnosticNext,
+        name: "diagnostic-next",
+        description: "Ir para o próximo erro ou aviso do arquivo (F8)",
+    },
+    CommandInfo {
+        command: Command::DiagnosticPrev,
+        name: "diagnostic-prev",
+        description: "Ir para o erro ou aviso anterior (Shift+F8)",
+    },
+    CommandInfo {
+        command: Command::Hover,
+        name: "hover",
+        description: "Mostrar erros e git blame da linha atual num popup (K)",
+    },
+    CommandInfo {
+        command: Command::RenameSymbol,
+        name: "rename-symbol",
+        description: "Renomear o identificador sob o cursor em todo o arquivo",
+    },
+    CommandInfo {
+        command: Command::Replace,
+        name: "replace",
+        description: "Substituir um texto no arquivo, uma ocorrência por vez ou todas (Ctrl+H)",
+    },
+    CommandInfo {
+        command: Command::ProjectSearch,
+        name: "project-search",
+        description: "Buscar um texto em todos os arquivos do projeto (Ctrl+Shift+F)",
+    },
+    CommandInfo {
+        command: Command::ProjectReplace,
+        name: "project-replace",
+        description: "Substituir um texto em todos os arquivos do projeto, revisando antes",
+    },
+    CommandInfo {
+        command: Command::Marks,
+        name: "marks",
+        description: "Listar as marcas globais (mA…mZ) e ir até uma delas ('A)",
+    },
+    CommandInfo {
+        command: Command::CollabHost,
+        name: "collab-host",
+        description: "Compartilhar o arquivo atual pela rede (experimental)",
+    },
+    CommandInfo {
+        command: Command::CollabJoin,
+        name: "collab-join",
+        description: "Entrar numa sessão compartilhada (código@host:porta)",
+    },
+    CommandInfo {
+        command: Command::CollabLeave,
+        name: "collab-leave",
+        description: "Encerrar ou sair da sessão compartilhada",
+    },
+    CommandInfo {
+        command: Command::PasteHistory,
+        name: "paste-history",
+        description: "Colar um dos últimos textos copiados (y) ou apagados (d)",
+    },
+    CommandInfo {
+        command: Command::Snippet,
+        name: "snippet",
+        description: "Inserir um trecho de [snippets] no cursor, com ${DATE}, ${FILENAME}...",
+    },
+    CommandInfo {
+        command: Command::NewFile,
+        name: "new-file",
+        description: "Criar um arquivo na pasta selecionada da barra lateral",
+    },
+    CommandInfo {
+        command: Command::SidebarRefresh,
+        name: "sidebar-refresh",
+        description: "Reler a árvore de arquivos do disco",
+    },
+    CommandInfo {
+        command: Command::SidebarCollapse,
+        name: "sidebar-collapse",
+        description: "Fechar todas as pastas da barra lateral",
+    },
+    CommandInfo {
+        command: Command::SidebarCompact,
+        name: "sidebar-compact",
+        description: "Alternar a barra lateral entre completa e só ícones",
+    },
+    CommandInfo {
+        command: Command::SidebarJump,
+        name: "sidebar-jump",
+        description: "Ir para uma pasta recente ou favorita ([bookmarks])",
+    },
+    CommandInfo {
+        command: Command::Present,
+        name: "present",
+        description: "Modo apresentação: a seleção (ou do cursor em diante) em letras grandes",
+    },
+    CommandInfo {
+        command: Command::InspectChar,
+        name: "inspect-char",
+        description: "Código, bytes UTF-8, nome e posição em bytes do caractere sob o cursor",
+    },
+    CommandInfo {
+        command: Command::MakeExecutable,
+        name: "make-executable",
+        description: "Tornar o arquivo atual executável (chmod +x), ou deixar de ser",
+    },
+    CommandInfo {
+        command: Command::ExtractFile,
+        name: "extract-file",
+        description: "Extrair de dentro do pacote (.zip, .tar.gz) o arquivo atual, para editá-lo",
+    },
+    CommandInfo {
+        command: Command::TestFile,
+        name: "test-file",
+        description:
+            "Ir para o teste do arquivo atual, criando-o se faltar (ou do teste para o código)",
+    },
+    CommandInfo {
+        command: Command::IndentNext,
+        name: "indent-next",
+        description: "Ir para a próxima linha com o mesmo recuo",
+    },
+    CommandInfo {
+        command: Command::IndentPrev,
+        name: "indent-prev",
+        description: "Ir para a linha anterior com o mesmo recuo",
+    },
+    CommandInfo {
+        command: Command::IndentStart,
+        name: "indent-start",
+        description: "Ir para o início do bloco com o recuo atual (Python, YAML)",
+    },
+    CommandInfo {
+        command: Command::IndentEnd,
+        name: "indent-end",
+        description: "Ir para o fim do bloco com o recuo atual (Python, YAML)",
+    },
+    CommandInfo {
+        command: Command::TagMatch,
+        name: "tag-match",
+        description: "Ir para a tag que abre ou fecha a tag sob o cursor (HTML, XML)",
+    },
+    CommandInfo {
+        command: Command::TagRename,
+        name: "tag-rename",
+        description: "Renomear a tag sob o cursor junto com a que a fecha (HTML, XML)",
+    },
+    CommandInfo {
+        command: Command::ApplyHunk,
+        name: "apply-hunk",
+        description: "Aplicar o trecho (@@) do diff sob o cursor ao arquivo e abri-lo na alteração",
+    },
+    CommandInfo {
+        command: Command::Options,
+        name: "options",
+        description: "Ver e mudar opções (números de linha, quebra, tabulação, tema...)",
+    },
+];
@@ -0,0 +1,160 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+/// Default size above which opening a file asks for confirmation (10 MB)
+const DEFAULT_LARGE_FILE_MB: u64 = 10;
+
+/// Default indent of `json-format`, in spaces
+const DEFAULT_JSON_INDENT: u64 = 2;
+
+/// User configuration read from `~/.config/reditor/config.toml`.
+///
+/// Only a small TOML subset is understood: `[section]` headers and
+/// `key = value` pairs, with optional double quotes around values and `#`
+/// comments. Keys outside any section live in the "" section.
+pub struct Config {
+    entries: Vec<(String, String, String)>,
+}
+
+impl Config {
+    pub fn load() -> Config {
+        match Config::path().and_then(|p| fs::read_to_string(p).ok()) {
+            Some(text) => Config::parse(&text),
+            None => Config { entries: vec![] },
+        }
+    }
+
+    pub fn path() -> Option<PathBuf> {
+        if let Ok(dir) = env::var("XDG_CONFIG_HOME") {
+            if !dir.is_empty() {
+                return Some(PathBuf::from(dir).join("reditor").join("config.toml"));
+            }
+        }
+        env::var("HOME")
+            .ok()
+            .map(|home| PathBuf::from(home).join(".config/reditor/config.toml"))
+    }
+
+    pub fn parse(text: &str) -> Config {
+        let mut entries = vec![];
+        let mut section = String::new();
+
+        for raw_line in text.lines() {
+            let line = strip_comment(raw_line).trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            if line.starts_with('[') && line.ends_with(']') {
+                section = line[1..line.len() - 1].trim().to_string();
+                continue;
+            }
+
+            if let Some((key, value)) = line.split_once('=') {
+                let key = unquote(key.trim());
+                let value = unquote(value.trim());
+                entries.push((section.clone(), key, value));
+            }
+        }
+
+        Config { entries }
+    }
+
+    /// Look up `key` in `section` ("" for top-level keys). Later entries win.
+    pub fn get(&self, section: &str, key: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .rev()
+            .find(|(s, k, _)| s == section && k == key)
+            .map(|(_, _, v)| v.as_str())
+    }
+
+    /// Set `key` of `section` for this session (`:set`); the file is left
+    /// as it is
+    pub fn set(&mut self, section: &str, key: &str, value: &str) {
+        self.entries
+            .push((section.to_string(), key.to_string(), value.to_string()));
+    }
+
+    /// Every `key = value` of `section`, in file order
+    pub fn section<'a>(&'a self, section: &'a str) -> impl Iterator<Item = (&'a str, &'a str)> {
+        self.entries
+            .iter()
+            .filter(move |(s, _, _)| s == section)
+            .map(|(_, k, v)| (k.as_str(), v.as_str()))
+    }
+
+    pub fn get_u64(&self, section: &str, key: &str, default: u64) -> u64 {
+        self.get(section, key)
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(default)
+    }
+
+    /// Size in bytes above which a file is considered "large"
+    pub fn large_file_threshold(&self) -> u64 {
+        self.get_u64("", "large_file_mb", DEFAULT_LARGE_FILE_MB) * 1024 * 1024
+    }
+
+    /// Spaces per level when formatting JSON (`[json] indent`)
+    pub fn json_indent(&self) -> usize {
+        self.get_u64("json", "indent", DEFAULT_JSON_INDENT) as usize
+    }
+
+    /// Whether diagnostic messages are written after their lines
+    /// (`[diagnostics] inline`); the gutter marks show either way
+    pub fn inline_diagnostics(&self) -> bool {
+        self.get("diagnostics", "inline") != Some("false")
+    }
+
+    /// Whether brackets and quotes typed in code get their closing char
+    /// (`[editor] auto_pairs`)
+    pub fn auto_pairs(&self) -> bool {
+        self.get("editor", "auto_pairs") != Some("false")
+    }
+
+    /// Whether typing the `>` of an opening HTML/XML tag adds its closing
+    /// tag (`[html] auto_close`)
+    pub fn auto_close_tags(&self) -> bool {
+        self.get("html", "auto_close") != Some("false")
+    }
+
+    /// Whether invisible and look-alike characters are marked in the text
+    /// (`[display] suspicious_chars`)
+    pub fn warn_suspicious_chars(&self) -> bool {
+        self.get("display", "suspicious_chars") != Some("false")
+    }
+}
+
+/// Where reditor keeps data between runs: `$XDG_STATE_HOME/reditor`, or
+/// `~/.local/state/reditor`
+pub fn state_dir() -> Option<PathBuf> {
+    if let Ok(dir) = env::var("XDG_STATE_HOME") {
+        if !dir.is_empty() {
+            return Some(PathBuf::from(dir).join("reditor"));
+        }
+    }
+    env::var("HOME")
+        .ok()
+        .map(|home| PathBuf::from(home).join(".local/state/reditor"))
+}
+
+fn strip_comment(line: &str) -> &str {
+    let mut in_string = false;
+    for (i, ch) in line.char_indices() {
+        match ch {
+            '"' => in_string = !in_string,
+            '#' if !in_string => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
+
+fn unquote(text: &str) -> String {
+    if text.len() >= 2 && text.starts_with('"') && text.ends_with('"') {
+        text[1..text.len() - 1].replace("\\\"", "\"")
+    } else {
+        text.to_string()
+    }
+}
@@ -0,0 +1,165 @@
+use crate::vfs::{self, DirEntry, Filesystem};
+use std::fmt;
+use std::io::{self, Cursor, Read, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::Arc;
+use std::thread;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Cipher {
+    /// `gpg --symmetric`; the passphrase comes from the editor's prompt
+    Gpg,
+    /// `age --passphrase`; age asks for the passphrase on the terminal itself
+    Age,
+}
+
+impl Cipher {
+    /// The cipher a file's extension names, if any
+    pub fn for_path(path: &str) -> Option<Cipher> {
+        match Path::new(path).extension()?.to_str()? {
+            "gpg" => Some(Cipher::Gpg),
+            "age" => Some(Cipher::Age),
+            _ => None,
+        }
+    }
+
+    fn program(&self) -> &'static str {
+        match self {
+            Cipher::Gpg => "gpg",
+            Cipher::Age => "age",
+        }
+    }
+}
+
+/// An encrypted file seen through its plain text. Decryption and encryption
+/// run through pipes, so the plain text only ever lives in memory; the
+/// encrypted bytes are read from and written to `inner`.
+pub struct CryptFs {
+    cipher: Cipher,
+    passphrase: Option<String>,
+    inner: Arc<dyn Filesystem>,
+}
+
+impl CryptFs {
+    pub fn gpg(path: &str, passphrase: String) -> Arc<dyn Filesystem> {
+        Arc::new(CryptFs {
+            cipher: Cipher::Gpg,
+            passphrase: Some(passphrase),
+            inner: vfs::for_path(path),
+        })
+    }
+
+    pub fn age(path: &str) -> Arc<dyn Filesystem> {
+        Arc::new(CryptFs {
+            cipher: Cipher::Age,
+            passphrase: None,
+            inner: vfs::for_path(path),
+        })
+    }
+
+    fn decrypt(&self, encrypted: Vec<u8>) -> io::Result<Vec<u8>> {
+        self.run(&["--decrypt"], encrypted)
+    }
+
+    fn encrypt(&self, plain: &[u8]) -> io::Result<Vec<u8>> {
+        match self.cipher {
+            Cipher::Gpg => self.run(&["--symmetric", "--output", "-"], plain.to_vec()),
+            Cipher::Age => self.run(&["--passphrase"], plain.to_vec()),
+        }
+    }
+
+    /// Run the cipher's program with `input` on stdin and return its stdout.
+    /// gpg reads the passphrase from the first line of stdin; age opens the
+    /// terminal, so stderr is left attached to it for the prompt.
+    fn run(&self, args: &[&str], input: Vec<u8>) -> io::Result<Vec<u8>> {
+        let mut command = Command::new(self.cipher.program());
+        if self.cipher == Cipher::Gpg {
+            command.args([
+                "--batch",
+                "--quiet",
+                "--yes",
+                "--pinentry-mode",
+                "loopback",
+                "--no-symkey-cache",
+                "--passphrase-fd",
+                "0",
+            ]);
+        }
+        let mut child = command
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(match self.cipher {
+                Cipher::Gpg => Stdio::piped(),
+                Cipher::Age => Stdio::inherit(),
+            })
+            .spawn()
+            .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", self.cipher.program(), e)))?;
+
+        // Fed from another thread so a full stdout pipe can't deadlock us
+        let mut stdin = child.stdin.take();
+        let passphrase = self.passphrase.clone();
+        let writer = thread::spawn(move || -> io::Result<()> {
+            if let Some(stdin) = &mut stdin {
+                if let Some(passphrase) = passphrase {
+                    writeln!(stdin, "{}", passphrase)?;
+                }
+                stdin.write_all(&input)?;
+            }
+            Ok(())
+        });
+        let output = child.wait_with_output()?;
+        let written = writer.join().unwrap_or(Ok(()));
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let reason = stderr
+                .lines()
+                .map(|line| line.trim().trim_start_matches("gpg: "))
+                .find(|line| !line.is_empty())
+                .unwrap_or("senha incorreta ou arquivo inválido");
+            return Err(io::Error::other(reason.to_string()));
+        }
+        written?;
+        Ok(output.stdout)
+    }
+}
+
+// Keeps the passphrase out of logs and panic messages
+impl fmt::Debug for CryptFs {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CryptFs")
+            .field("cipher", &self.cipher)
+            .field("inner", &self.inner)
+            .finish()
+    }
+}
+
+impl Filesystem for CryptFs {
+    fn open(&self, path: &Path) -> io::Result<Box<dyn Read + Send>> {
+        let plain = self.decrypt(self.inner.read(path)?)?;
+        Ok(Box::new(Cursor::new(plain)))
+    }
+
+    fn write(&self, path: &Path, bytes: &[u8]) -> io::Result<()> {
+        let encrypted = self.encrypt(bytes)?;
+        self.inner.write(path, &encrypted)
+    }
+
+    fn size(&self, path: &Path) -> io::Result<u64> {
+        self.inner.size(path)
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<DirEntry>> {
+        self.inner.read_dir(path)
+    }
+
+    fn needs_terminal(&self) -> bool {
+        self.cipher == Cipher::Age
+    }
+
+    fn is_private(&self) -> bool {
+        true
+    }
+}
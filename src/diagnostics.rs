@@ -0,0 +1,104 @@
+use crossterm::style::Color;
+
+/// Ordered from most to least severe
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+#[allow(dead_code)] // Info and Hint wait for checkers that report them
+pub enum Severity {
+    Error,
+    Warning,
+    Info,
+    Hint,
+}
+
+impl Severity {
+    /// Mark shown in the gutter and before inline messages
+    pub fn icon(&self) -> char {
+        match self {
+            Severity::Error => '✗',
+            Severity::Warning => '⚠',
+            Severity::Info => 'ℹ',
+            Severity::Hint => '·',
+        }
+    }
+
+    pub fn color(&self) -> Color {
+        match self {
+            Severity::Error => Color::Rgb {
+                r: 240,
+                g: 90,
+                b: 90,
+            },
+            Severity::Warning => Color::Rgb {
+                r: 230,
+                g: 190,
+                b: 80,
+            },
+            Severity::Info => Color::Rgb {
+                r: 100,
+                g: 170,
+                b: 240,
+            },
+            Severity::Hint => Color::Rgb {
+                r: 130,
+                g: 135,
+                b: 150,
+            },
+        }
+    }
+}
+
+/// A problem reported about a buffer by some checker (the JSON validator,
+/// and later linters, the spell checker or a language server)
+#[derive(Clone, Debug)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// First and last (row, col) of the range, 0-based
+    pub start: (usize, usize),
+    pub end: (usize, usize),
+    pub message: String,
+    /// Who reported it, e.g. "json"
+    pub source: String,
+    // Text of the start row when reported: once it changes the diagnostic
+    // no longer applies and is hidden
+    line: Vec<char>,
+}
+
+impl Diagnostic {
+    pub fn new(
+        severity: Severity,
+        start: (usize, usize),
+        end: (usize, usize),
+        message: &str,
+        source: &str,
+        lines: &[Vec<char>],
+    ) -> Diagnostic {
+        Diagnostic {
+            severity,
+            start,
+            end,
+            message: message.to_string(),
+            source: source.to_string(),
+            line: lines.get(start.0).cloned().unwrap_or_default(),
+        }
+    }
+
+    /// Whether the text it was reported on is still there
+    pub fn is_current(&self, lines: &[Vec<char>]) -> bool {
+        lines.get(self.start.0) == Some(&self.line)
+    }
+
+    /// Whether (row, col) falls inside the range
+    pub fn covers(&self, row: usize, col: usize) -> bool {
+        (row, col) >= self.start && (row, col) <= self.end
+    }
+
+    /// "✗ esperado ',' ou '}' [json]"
+    pub fn summary(&self) -> String {
+        format!(
+            "{} {} [{}]",
+            self.severity.icon(),
+            self.message,
+            self.source
+        )
+    }
+}
@@ -1,49 +1,150 @@
+use crate::diagnostics::Diagnostic;
+use crate::inspect;
+use crate::links;
+use crate::options::{Options, Theme};
+use crate::overlay::Overlay;
+use crate::present;
+use crate::screen::{self, LineSize, Screen, WIDE_TAIL};
 use crate::sidebar::Sidebar;
 use crate::syntax;
+use crate::tasks::ColoredLine;
 use crate::welcome::WelcomeScreen;
 use crossterm::style::Color;
-use crossterm::{cursor, execute, queue, style, terminal};
-use std::io;
-use std::io::{BufWriter, Write};
+use crossterm::terminal;
+
+/// Rows of the output panel, title included (at most half the text area)
+const OUTPUT_PANEL_ROWS: u16 = 12;
+
+/// Clickable regions of the status bar
+#[derive(Clone, Copy, PartialEq)]
+pub enum StatusSegment {
+    Encoding,
+    LineEnding,
+    Position,
+    Language,
+    Jobs,
+}
+
+/// Below this size only a "too small" placeholder is drawn
+const MIN_COLUMNS: u16 = 20;
+const MIN_ROWS: u16 = 4;
 
 pub struct Display {
-    pub file_matrix: Vec<Vec<char>>,
     pub columns: u16,
     pub rows: u16,
-    pub initial_row: u16,
-    pub initial_column: u16,
+    pub initial_row: usize,
+    pub initial_column: usize,
     mode: String,
     modified: bool,
-    cursor_line: u16,
-    cursor_column: u16,
+    cursor_row: usize,
+    cursor_col: usize,
     file_size: usize,
     filename: String,
+    // Path to the cursor in a JSON or YAML document, after the filename
+    breadcrumb: Option<String>,
+    syntax_ext: String,
+    // Dialog, input box or picker floating over the frame
+    overlay: Option<Overlay>,
+    // Box at the right edge (commit diffstat), drawn under the HUD
+    side_panel: Option<Overlay>,
+    // Popup anchored in the text (hover), under the panels
+    popup: Option<Overlay>,
+    // Performance panel, drawn under the overlay
+    hud: Option<Overlay>,
+    // Running background jobs, shown at the start of the status bar's right part
+    progress: String,
     sidebar_width: u16,
-    tab_names: Vec<(String, bool, bool)>,
+    tab_names: Vec<(String, bool, bool, bool)>,
     show_welcome: bool,
     show_cursor: bool,
+    // The sidebar has focus: the text is dimmed instead of the sidebar
+    sidebar_focused: bool,
+    // Presentation mode: how, and the rows of text shown big (end excluded)
+    presentation: Option<(present::Style, usize, usize)>,
+    read_only: bool,
+    message: String,
+    line_ending: &'static str,
+    has_bom: bool,
+    // Virtual text after the end of a file row (git blame)
+    annotation: Option<(usize, String)>,
+    // The buffer's current diagnostics, most severe first within a row,
+    // and whether messages follow their lines
+    diagnostics: Vec<Diagnostic>,
+    inline_diagnostics: bool,
+    // Output panel under the text: title and the lines in view
+    output_panel: Option<(String, Vec<ColoredLine>)>,
+    // Mark invisible and look-alike characters (`[display] suspicious_chars`)
+    warn_suspicious: bool,
+    // Text columns with a tinted background (commit message widths)
+    rulers: Vec<usize>,
+    // (row, start, end) ranges painted like search matches, end excluded
+    // (occurrences previewed by rename-symbol)
+    highlights: Vec<(usize, usize, usize)>,
+    // (row, col) of the other participants' cursors in a shared buffer
+    peer_cursors: Vec<(usize, usize)>,
+    // First and last selected (row, col), both included (Visual mode)
+    selection: Option<((usize, usize), (usize, usize))>,
+    // Search matches only show inside the selection (search in selection)
+    search_in_selection: bool,
+    // Highlight state at the first row drawn, kept so the next frame only
+    // carries it forward from there: (file, language, row, state)
+    highlight_start: Option<(String, String, usize, syntax::HighlightState)>,
+    // Options of the active buffer (line numbers, wrap, theme...)
+    options: Options,
+    // When wrapping, the (file row, first column) each content row shows
+    segments: Vec<(usize, usize)>,
+    screen: Screen,
 }
 
 impl Display {
     pub fn new() -> Display {
         let (columns, rows) = terminal::size().unwrap();
+        Display::with_size(columns, rows)
+    }
 
+    pub fn with_size(columns: u16, rows: u16) -> Display {
         Display {
-            file_matrix: vec![vec![]],
             columns,
             rows,
             initial_row: 0,
             initial_column: 0,
             mode: String::from("NORMAL"),
             modified: false,
-            cursor_line: 1,
-            cursor_column: 1,
+            cursor_row: 0,
+            cursor_col: 0,
             file_size: 1,
             filename: String::new(),
+            breadcrumb: None,
+            syntax_ext: String::new(),
+            overlay: None,
+            popup: None,
+            side_panel: None,
+            hud: None,
+            progress: String::new(),
             sidebar_width: 0,
             tab_names: vec![],
             show_welcome: false,
             show_cursor: true,
+            sidebar_focused: false,
+            presentation: None,
+            read_only: false,
+            message: String::new(),
+            line_ending: "LF",
+            has_bom: false,
+            annotation: None,
+            diagnostics: vec![],
+            inline_diagnostics: true,
+            warn_suspicious: true,
+            output_panel: None,
+            rulers: vec![],
+            highlights: vec![],
+            highlight_start: None,
+            peer_cursors: vec![],
+            selection: None,
+            search_in_selection: false,
+            options: Options::default(),
+            segments: vec![],
+            screen: Screen::new(columns, rows),
         }
     }
 
@@ -55,7 +156,7 @@ impl Display {
         self.sidebar_width = width;
     }
 
-    pub fn set_tab_names(&mut self, tabs: Vec<(String, bool, bool)>) {
+    pub fn set_tab_names(&mut self, tabs: Vec<(String, bool, bool, bool)>) {
         self.tab_names = tabs;
     }
 
@@ -63,10 +164,122 @@ impl Display {
         self.filename = name;
     }
 
+    pub fn set_breadcrumb(&mut self, path: Option<String>) {
+        self.breadcrumb = path;
+    }
+
+    /// Extension whose language drives highlighting (may differ from the filename's)
+    pub fn set_syntax_ext(&mut self, ext: String) {
+        self.syntax_ext = ext;
+    }
+
+    pub fn set_overlay(&mut self, overlay: Option<Overlay>) {
+        self.overlay = overlay;
+    }
+
+    pub fn set_side_panel(&mut self, panel: Option<Overlay>) {
+        self.side_panel = panel;
+    }
+
+    pub fn set_rulers(&mut self, rulers: Vec<usize>) {
+        self.rulers = rulers;
+    }
+
+    pub fn set_search_in_selection(&mut self, within: bool) {
+        self.search_in_selection = within;
+    }
+
+    pub fn set_selection(&mut self, selection: Option<((usize, usize), (usize, usize))>) {
+        self.selection = selection;
+    }
+
+    pub fn set_popup(&mut self, popup: Option<Overlay>) {
+        self.popup = popup;
+    }
+
+    pub fn set_highlights(&mut self, highlights: Vec<(usize, usize, usize)>) {
+        self.highlights = highlights;
+    }
+
+    pub fn set_peer_cursors(&mut self, cursors: Vec<(usize, usize)>) {
+        self.peer_cursors = cursors;
+    }
+
+    pub fn set_hud(&mut self, hud: Option<Overlay>) {
+        self.hud = hud;
+    }
+
+    pub fn set_progress(&mut self, progress: String) {
+        self.progress = progress;
+    }
+
     pub fn set_show_cursor(&mut self, show: bool) {
         self.show_cursor = show;
     }
 
+    pub fn set_sidebar_focused(&mut self, focused: bool) {
+        self.sidebar_focused = focused;
+    }
+
+    pub fn set_presentation(&mut self, presentation: Option<(present::Style, usize, usize)>) {
+        self.presentation = presentation;
+    }
+
+    pub fn set_read_only(&mut self, read_only: bool) {
+        self.read_only = read_only;
+    }
+
+    pub fn set_encoding_info(&mut self, line_ending: &'static str, has_bom: bool) {
+        self.line_ending = line_ending;
+        self.has_bom = has_bom;
+    }
+
+    /// Text drawn dimmed after the end of file row `row`, if it fits
+    pub fn set_annotation(&mut self, annotation: Option<(usize, String)>) {
+        self.annotation = annotation;
+    }
+
+    /// Diagnostics marked in the gutter and, when `inline`, written after
+    /// their lines
+    pub fn set_diagnostics(&mut self, diagnostics: Vec<Diagnostic>, inline: bool) {
+        self.diagnostics = diagnostics;
+        self.inline_diagnostics = inline;
+    }
+
+    /// Show the output panel with `title` over `lines`, or hide it
+    pub fn set_output_panel(&mut self, panel: Option<(String, Vec<ColoredLine>)>) {
+        self.output_panel = panel;
+    }
+
+    /// Rows the output panel takes, title included, when shown
+    fn output_panel_height(&self) -> u16 {
+        OUTPUT_PANEL_ROWS.min(self.rows.saturating_sub(2) / 2)
+    }
+
+    /// Output lines the panel has room for
+    pub fn output_panel_lines(&self) -> usize {
+        self.output_panel_height().saturating_sub(1) as usize
+    }
+
+    /// Whether screen `row` is on the output panel
+    pub fn in_output_panel(&self, row: u16) -> bool {
+        let top = self.content_top_row() + self.content_rows() as u16;
+        self.output_panel.is_some() && row >= top && row < self.rows.saturating_sub(1)
+    }
+
+    pub fn set_options(&mut self, options: Options) {
+        self.options = options;
+    }
+
+    pub fn set_warn_suspicious(&mut self, warn: bool) {
+        self.warn_suspicious = warn;
+    }
+
+    /// Show a transient message in the status bar (empty string clears it)
+    pub fn set_message(&mut self, message: String) {
+        self.message = message;
+    }
+
     fn content_start_col(&self) -> u16 {
         self.sidebar_width
     }
@@ -75,38 +288,194 @@ impl Display {
         self.columns.saturating_sub(self.sidebar_width)
     }
 
-    /// Write a full row span with a single color pair using queue! for performance.
-    fn write_span(
-        writer: &mut BufWriter<io::Stdout>,
-        col: u16,
-        row: u16,
-        fg: Color,
-        bg: Color,
-        text: &str,
+    /// Draw a span with a single color pair into the off-screen frame
+    fn write_span(screen: &mut Screen, col: u16, row: u16, fg: Color, bg: Color, text: &str) {
+        screen.put_str(col, row, fg, bg, text);
+    }
+
+    /// Draw a frame showing `lines`, the active buffer's text, which is
+    /// borrowed for the frame instead of copied on every edit.
+    pub fn show_display(
+        &mut self,
+        lines: &[Vec<char>],
+        sidebar: Option<&mut Sidebar>,
+        search_query: Option<&str>,
     ) {
-        queue!(
-            writer,
-            cursor::MoveTo(col, row),
-            style::SetForegroundColor(fg),
-            style::SetBackgroundColor(bg),
-            style::Print(text),
-        )
-        .unwrap();
+        self.compose(lines, sidebar, search_query);
+        self.screen.flush().unwrap();
+    }
+
+    /// Draw a frame into the off-screen grid without writing it out
+    pub fn compose(
+        &mut self,
+        lines: &[Vec<char>],
+        sidebar: Option<&mut Sidebar>,
+        search_query: Option<&str>,
+    ) {
+        let mut screen = std::mem::take(&mut self.screen);
+        if screen.size() != (self.columns, self.rows) {
+            screen.resize(self.columns, self.rows);
+        }
+        screen.clear();
+        if self.is_too_small() {
+            self.draw_too_small(&mut screen);
+            self.screen = screen;
+            return;
+        }
+        self.advance_highlight_start(lines);
+        self.segments = self.layout(lines);
+        self.draw(&mut screen, lines, sidebar, search_query);
+        self.dim_inactive_pane(&mut screen);
+        if let Some(popup) = self.popup.as_ref().filter(|_| !self.show_welcome) {
+            popup.draw(&mut screen, self.columns, self.rows);
+        }
+        if let Some(panel) = self.side_panel.as_ref().filter(|_| !self.show_welcome) {
+            panel.draw(&mut screen, self.columns, self.rows);
+        }
+        if let Some(hud) = &self.hud {
+            hud.draw(&mut screen, self.columns, self.rows);
+        }
+        if let Some(overlay) = &self.overlay {
+            // The caret of the overlay's input takes over the text cursor
+            let caret = overlay.draw(&mut screen, self.columns, self.rows);
+            screen.set_cursor(caret);
+        }
+        self.screen = screen;
+    }
+
+    /// Highlight state at the first visible row, carried forward from the
+    /// last frame's when the view didn't move up or change file. Edits
+    /// above the view are only caught by `rehighlight`.
+    fn advance_highlight_start(&mut self, lines: &[Vec<char>]) {
+        let start = self.initial_row.min(lines.len());
+        let (from, mut state) = match &self.highlight_start {
+            Some((filename, ext, row, state))
+                if *filename == self.filename && *ext == self.syntax_ext && *row <= start =>
+            {
+                (*row, *state)
+            }
+            _ => (0, syntax::HighlightState::new()),
+        };
+        for line in &lines[from..start] {
+            syntax::highlight_line(line, &self.syntax_ext, &mut state);
+        }
+        self.highlight_start = Some((self.filename.clone(), self.syntax_ext.clone(), start, state));
+    }
+
+    /// Highlight from the top of the file again on the next frame
+    pub fn rehighlight(&mut self) {
+        self.highlight_start = None;
+    }
+
+    /// With the sidebar open, fade whichever of it and the text doesn't
+    /// have focus (tab bar and status bar stay as they are)
+    fn dim_inactive_pane(&self, screen: &mut Screen) {
+        if self.sidebar_width == 0 {
+            return;
+        }
+        let rows = self.rows.saturating_sub(2);
+        if self.sidebar_focused {
+            screen.dim(self.content_start_col(), 1, self.content_width(), rows);
+        } else {
+            // The sidebar's header stays readable, like the tab bar
+            screen.dim(0, 1, self.sidebar_width, self.rows.saturating_sub(1));
+        }
     }
 
-    pub fn show_display(&self, sidebar: Option<&mut Sidebar>, search_query: Option<&str>) {
-        let (last_col, last_row) = cursor::position().unwrap();
-        let mut writer = BufWriter::with_capacity(64 * 1024, io::stdout());
+    pub fn is_too_small(&self) -> bool {
+        self.columns < MIN_COLUMNS || self.rows < MIN_ROWS
+    }
+
+    /// Placeholder shown until the terminal is big enough again
+    fn draw_too_small(&self, screen: &mut Screen) {
+        let message = ["Terminal muito pequeno", "Pequeno demais", "!"]
+            .into_iter()
+            .find(|text| text.chars().count() <= self.columns as usize)
+            .unwrap_or("");
+        let col = (self.columns as usize).saturating_sub(message.chars().count()) / 2;
+        Self::write_span(
+            screen,
+            col as u16,
+            self.rows / 2,
+            Color::Rgb {
+                r: 255,
+                g: 220,
+                b: 220,
+            },
+            Color::Reset,
+            message,
+        );
+        screen.set_cursor(None);
+    }
+
+    /// With wrapping, the (file row, first column) each content row shows,
+    /// from the first row in view down
+    fn layout(&self, lines: &[Vec<char>]) -> Vec<(usize, usize)> {
+        if !self.options.wrap {
+            return vec![];
+        }
+        let width = self.text_width().max(1);
+        let rows = self.content_rows();
+        let mut segments = vec![];
+        for (row, line) in lines.iter().enumerate().skip(self.initial_row) {
+            for part in 0..self.wrapped_rows(line.len()) {
+                if segments.len() == rows {
+                    return segments;
+                }
+                segments.push((row, part * width));
+            }
+        }
+        segments
+    }
+
+    /// Screen rows a line `len` chars long takes: with wrapping, enough
+    /// for the cursor after its last char too
+    pub fn wrapped_rows(&self, len: usize) -> usize {
+        if !self.options.wrap {
+            return 1;
+        }
+        len / self.text_width().max(1) + 1
+    }
+
+    /// File row and first column shown on content row `i`
+    fn shown_at(&self, i: usize) -> Option<(usize, usize)> {
+        if self.options.wrap {
+            self.segments.get(i).copied()
+        } else {
+            Some((self.initial_row + i, self.initial_column))
+        }
+    }
+
+    /// Content row and text column right after the end of file row `row`,
+    /// a line `len` chars long
+    fn line_end_cell(&self, row: usize, len: usize) -> Option<(usize, usize)> {
+        if self.options.wrap {
+            let i = self.segments.iter().rposition(|&(r, _)| r == row)?;
+            return Some((i, len - self.segments[i].1));
+        }
+        let i = row.checked_sub(self.initial_row)?;
+        Some((i, len.saturating_sub(self.initial_column)))
+    }
 
-        queue!(writer, cursor::Hide).unwrap();
+    /// Repaint every cell on the next render
+    pub fn invalidate(&mut self) {
+        self.screen.invalidate();
+    }
 
+    fn draw(
+        &self,
+        screen: &mut Screen,
+        lines: &[Vec<char>],
+        sidebar: Option<&mut Sidebar>,
+        search_query: Option<&str>,
+    ) {
         let content_start = self.content_start_col();
         let content_w = self.content_width();
 
         // --- Draw sidebar if visible ---
         if let Some(sidebar) = sidebar {
             if sidebar.visible {
-                self.render_sidebar(&mut writer, sidebar);
+                self.render_sidebar(screen, sidebar);
             }
         }
 
@@ -129,48 +498,38 @@ impl Display {
                         span.push(row[col_idx].character);
                         col_idx += 1;
                     }
-                    Self::write_span(&mut writer, screen_col, row_idx as u16, fg, bg, &span);
+                    Self::write_span(screen, screen_col, row_idx as u16, fg, bg, &span);
                 }
             }
-            queue!(writer, style::ResetColor).unwrap();
-            if self.show_cursor {
-                queue!(writer, cursor::Show).unwrap();
-            }
-            writer.flush().unwrap();
-            execute!(io::stdout(), cursor::MoveTo(last_col, last_row)).unwrap();
+            screen.set_cursor(None);
             return;
         }
 
         // --- Tab bar (row 0) ---
-        self.render_tab_bar(&mut writer, content_start, content_w);
+        self.render_tab_bar(screen, content_start, content_w);
 
-        // --- Content area (rows 1 to rows-2) ---
-        let content_rows = self.rows.saturating_sub(2);
-        let content_start_row: u16 = 1;
+        if let Some(presentation) = self.presentation {
+            self.draw_presentation(screen, lines, presentation);
+            self.render_status_bar(screen, content_start, content_w);
+            screen.set_cursor(None);
+            return;
+        }
 
-        let mut file_matrix_row_start = self.initial_row;
-        let mut file_matrix_row_end = file_matrix_row_start + content_rows;
+        // --- Content area (rows 1 to rows-2, less the output panel) ---
+        let content_rows = self.content_rows() as u16;
+        let content_start_row: u16 = 1;
 
-        if file_matrix_row_end as usize > self.file_matrix.len() {
-            let overflow = file_matrix_row_end as usize - self.file_matrix.len();
-            file_matrix_row_start = file_matrix_row_start.saturating_sub(overflow as u16);
-            file_matrix_row_end =
-                (file_matrix_row_start + content_rows).min(self.file_matrix.len() as u16);
-        }
+        let file_matrix_row_start = self.initial_row.min(lines.len());
+        let file_matrix_row_end = match self.segments.last() {
+            Some(&(row, _)) => row + 1,
+            None if self.options.wrap => file_matrix_row_start,
+            None => (file_matrix_row_start + content_rows as usize).min(lines.len()),
+        };
 
         let row_lines_length = self.offset_lines_number();
         let row_lines = self.offset_lines(&file_matrix_row_start, &file_matrix_row_end);
 
-        let bg_content = Color::Rgb {
-            r: 15,
-            g: 18,
-            b: 15,
-        };
-        let bg_line_nr = Color::Rgb {
-            r: 10,
-            g: 12,
-            b: 10,
-        };
+        let (bg_content, bg_line_nr, bg_ruler) = theme_colors(self.options.theme);
         let fg_line_nr = Color::Rgb {
             r: 100,
             g: 100,
@@ -194,23 +553,48 @@ impl Display {
             g: 60,
             b: 10,
         };
+        let bg_selection = Color::Rgb {
+            r: 40,
+            g: 60,
+            b: 90,
+        };
+        let bg_peer = Color::Rgb {
+            r: 150,
+            g: 70,
+            b: 160,
+        };
+        let fg_suspicious = Color::Rgb {
+            r: 255,
+            g: 220,
+            b: 200,
+        };
+        let bg_suspicious = Color::Rgb {
+            r: 140,
+            g: 50,
+            b: 30,
+        };
+        let fg_control = Color::Rgb {
+            r: 120,
+            g: 200,
+            b: 220,
+        };
 
         // --- Syntax highlighting ---
-        let ext = syntax::get_extension(&self.filename);
+        let ext = self.syntax_ext.as_str();
 
-        // Build highlight state from line 0 up to visible start (for block comments)
-        let mut hl_state = syntax::HighlightState::new();
-        for row_idx in 0..file_matrix_row_start as usize {
-            if row_idx < self.file_matrix.len() {
-                syntax::highlight_line(&self.file_matrix[row_idx], &ext, &mut hl_state);
-            }
-        }
+        // State at the visible start (for block comments), see
+        // `advance_highlight_start`
+        let mut hl_state = self
+            .highlight_start
+            .as_ref()
+            .map(|&(_, _, _, state)| state)
+            .unwrap_or_else(syntax::HighlightState::new);
 
         // Highlight visible lines
         let mut highlighted_lines: Vec<Vec<syntax::ColoredChar>> = Vec::new();
-        for row_idx in file_matrix_row_start as usize..file_matrix_row_end as usize {
-            if row_idx < self.file_matrix.len() {
-                let hl = syntax::highlight_line(&self.file_matrix[row_idx], &ext, &mut hl_state);
+        for row_idx in file_matrix_row_start..file_matrix_row_end {
+            if row_idx < lines.len() {
+                let hl = syntax::highlight_line(&lines[row_idx], ext, &mut hl_state);
                 highlighted_lines.push(hl);
             } else {
                 highlighted_lines.push(Vec::new());
@@ -219,47 +603,114 @@ impl Display {
 
         for i in 0..content_rows {
             let screen_row = content_start_row + i;
-            let file_row_idx = (file_matrix_row_start + i) as usize;
+            // Rows past the end of the file are left blank
+            let (file_row_idx, col_start) = self.shown_at(i as usize).unwrap_or((lines.len(), 0));
+            // The rest of a wrapped line has no number of its own
+            let continued = self.options.wrap && col_start > 0;
 
             // 1) Line number — single span
-            let line_nr_str: String = if (i as usize) < row_lines.len() {
-                row_lines[i as usize].iter().collect()
-            } else {
-                " ".repeat(row_lines_length)
+            let number = file_row_idx
+                .checked_sub(file_matrix_row_start)
+                .and_then(|i| row_lines.get(i))
+                .filter(|_| !continued);
+            let line_nr_str: String = match number {
+                Some(number) => number.iter().collect(),
+                None => " ".repeat(row_lines_length),
             };
             Self::write_span(
-                &mut writer,
+                screen,
                 content_start,
                 screen_row,
                 fg_line_nr,
                 bg_line_nr,
                 &line_nr_str,
             );
+            if let Some(severity) = self
+                .diagnostics
+                .iter()
+                .find(|d| d.start.0 == file_row_idx && row_lines_length > 0 && !continued)
+                .map(|d| d.severity)
+            {
+                Self::write_span(
+                    screen,
+                    content_start,
+                    screen_row,
+                    severity.color(),
+                    bg_line_nr,
+                    &severity.icon().to_string(),
+                );
+            }
 
             // 2) Content — syntax-colored spans
             let text_start_col = content_start + row_lines_length as u16;
             let text_width = content_w.saturating_sub(row_lines_length as u16) as usize;
-            let hl_idx = i as usize;
+            let hl_idx = file_row_idx.saturating_sub(file_matrix_row_start);
 
-            if file_row_idx < self.file_matrix.len() && hl_idx < highlighted_lines.len() {
-                let line = &self.file_matrix[file_row_idx];
+            if file_row_idx < lines.len() && hl_idx < highlighted_lines.len() {
+                let line = &lines[file_row_idx];
                 let hl_line = &highlighted_lines[hl_idx];
                 let mut col = 0;
 
-                while col < text_width {
-                    let file_col = self.initial_column as usize + col;
-                    let ch = line.get(file_col).copied().unwrap_or(' ');
-
-                    let is_match =
-                        search_len > 0 && self.is_search_match(line, file_col, &search_chars);
-
+                // A selected line break shows as one cell past the last char
+                let is_selected = |file_col: usize| {
+                    self.selection.is_some_and(|(start, end)| {
+                        (file_col < line.len() || (file_col == line.len() && file_row_idx < end.0))
+                            && (file_row_idx, file_col) >= start
+                            && (file_row_idx, file_col) <= end
+                    })
+                };
+                let suspicious = |file_col: usize| {
+                    self.warn_suspicious && inspect::is_suspicious(line, file_col)
+                };
+                // Invisible characters get a box to stand on
+                let char_at = |file_col: usize| match line.get(file_col) {
+                    Some(&ch) if suspicious(file_col) => inspect::shown(ch),
+                    Some(&ch) => ch,
+                    None => ' ',
+                };
+                let colors_at = |file_col: usize| {
                     let syntax_fg = hl_line.get(file_col).map(|c| c.fg).unwrap_or(fg_default);
-
-                    let (fg, bg) = if is_match {
+                    let swatch = hl_line.get(file_col).and_then(|c| c.bg);
+                    let highlighted = self.highlights.iter().any(|&(row, start, end)| {
+                        row == file_row_idx && file_col >= start && file_col < end
+                    });
+                    if self.peer_cursors.contains(&(file_row_idx, file_col)) {
+                        (fg_default, bg_peer)
+                    } else if highlighted
+                        || (search_len > 0
+                            && (!self.search_in_selection || is_selected(file_col))
+                            && self.is_search_match(line, file_col, &search_chars))
+                    {
                         (fg_match, bg_match)
+                    } else if is_selected(file_col) {
+                        // A swatch's fg is picked for its own bg
+                        let fg = if swatch.is_some() {
+                            fg_default
+                        } else {
+                            syntax_fg
+                        };
+                        (fg, bg_selection)
+                    } else if suspicious(file_col) {
+                        (fg_suspicious, bg_suspicious)
+                    } else if line
+                        .get(file_col)
+                        .is_some_and(|&c| c.is_control() && c != '\t')
+                    {
+                        // Drawn as their picture (`␍`), which mustn't pass for text
+                        (fg_control, bg_content)
+                    } else if let Some(swatch) = swatch {
+                        (syntax_fg, swatch)
+                    } else if self.rulers.contains(&file_col) {
+                        (syntax_fg, bg_ruler)
                     } else {
                         (syntax_fg, bg_content)
-                    };
+                    }
+                };
+
+                while col < text_width {
+                    let file_col = col_start + col;
+                    let ch = char_at(file_col);
+                    let (fg, bg) = colors_at(file_col);
 
                     // Accumulate consecutive chars with same color
                     let span_start = col;
@@ -268,21 +719,9 @@ impl Display {
                     col += 1;
 
                     while col < text_width {
-                        let next_file_col = self.initial_column as usize + col;
-                        let next_ch = line.get(next_file_col).copied().unwrap_or(' ');
-
-                        let next_match = search_len > 0
-                            && self.is_search_match(line, next_file_col, &search_chars);
-                        let next_syntax_fg = hl_line
-                            .get(next_file_col)
-                            .map(|c| c.fg)
-                            .unwrap_or(fg_default);
-
-                        let (next_fg, next_bg) = if next_match {
-                            (fg_match, bg_match)
-                        } else {
-                            (next_syntax_fg, bg_content)
-                        };
+                        let next_file_col = col_start + col;
+                        let next_ch = char_at(next_file_col);
+                        let (next_fg, next_bg) = colors_at(next_file_col);
 
                         if next_fg != fg || next_bg != bg {
                             break;
@@ -293,7 +732,7 @@ impl Display {
                     }
 
                     Self::write_span(
-                        &mut writer,
+                        screen,
                         text_start_col + span_start as u16,
                         screen_row,
                         fg,
@@ -301,11 +740,48 @@ impl Display {
                         &span,
                     );
                 }
+
+                // URLs and file:line references, for gx / Ctrl+Click
+                for link in links::find(line) {
+                    let start = link.start.max(col_start);
+                    let end = link.end.min(col_start + text_width);
+                    if start < end {
+                        screen.underline(
+                            text_start_col + (start - col_start) as u16,
+                            screen_row,
+                            (end - start) as u16,
+                        );
+                    }
+                }
+
+                // Range of each diagnostic on this row, up to the line end
+                // when it spans several
+                for diagnostic in self
+                    .diagnostics
+                    .iter()
+                    .filter(|d| d.start.0 == file_row_idx)
+                {
+                    let last = if diagnostic.end.0 == file_row_idx {
+                        diagnostic.end.1 + 1
+                    } else {
+                        line.len()
+                    };
+                    let start = diagnostic.start.1.max(col_start);
+                    let end = last.min(line.len().max(diagnostic.start.1 + 1));
+                    let end = end.min(col_start + text_width);
+                    if start < end {
+                        screen.underline(
+                            text_start_col + (start - col_start) as u16,
+                            screen_row,
+                            (end - start) as u16,
+                        );
+                    }
+                }
             } else {
                 // Empty row past end of file
                 let blank: String = " ".repeat(text_width);
                 Self::write_span(
-                    &mut writer,
+                    screen,
                     text_start_col,
                     screen_row,
                     fg_line_nr,
@@ -316,7 +792,10 @@ impl Display {
         }
 
         // Fill remaining content rows
-        let rendered_content_rows = file_matrix_row_end.saturating_sub(file_matrix_row_start);
+        let rendered_content_rows = match self.options.wrap {
+            true => self.segments.len() as u16,
+            false => (file_matrix_row_end - file_matrix_row_start) as u16,
+        };
         if rendered_content_rows < content_rows {
             let blank_line_nr: String = " ".repeat(row_lines_length);
             let blank_content: String =
@@ -324,7 +803,7 @@ impl Display {
             for i in rendered_content_rows..content_rows {
                 let screen_row = content_start_row + i;
                 Self::write_span(
-                    &mut writer,
+                    screen,
                     content_start,
                     screen_row,
                     fg_line_nr,
@@ -332,7 +811,7 @@ impl Display {
                     &blank_line_nr,
                 );
                 Self::write_span(
-                    &mut writer,
+                    screen,
                     content_start + row_lines_length as u16,
                     screen_row,
                     fg_line_nr,
@@ -342,14 +821,255 @@ impl Display {
             }
         }
 
+        // Virtual text after the line: the first diagnostic of each row,
+        // else the annotation
+        let text_width = content_w.saturating_sub(row_lines_length as u16) as usize;
+        let mut shown_rows = vec![];
+        for diagnostic in &self.diagnostics {
+            let (row, severity, message) = (
+                &diagnostic.start.0,
+                diagnostic.severity,
+                &diagnostic.message,
+            );
+            let visible = *row >= file_matrix_row_start && *row < file_matrix_row_end;
+            if !self.inline_diagnostics || !visible || shown_rows.contains(row) {
+                continue;
+            }
+            shown_rows.push(*row);
+            let Some((shown_row, line_end)) = self.line_end_cell(*row, lines[*row].len()) else {
+                continue;
+            };
+            let col = line_end + 3;
+            if col < text_width {
+                let text = format!("{} {}", severity.icon(), message);
+                let shown: String = text.chars().take(text_width - col).collect();
+                Self::write_span(
+                    screen,
+                    content_start + (row_lines_length + col) as u16,
+                    content_start_row + shown_row as u16,
+                    severity.color(),
+                    bg_content,
+                    &shown,
+                );
+            }
+        }
+
+        if let Some((row, text)) = &self.annotation {
+            let visible = *row >= file_matrix_row_start
+                && *row < file_matrix_row_end
+                && !shown_rows.contains(row);
+            let cell = self
+                .line_end_cell(*row, lines.get(*row).map_or(0, |line| line.len()))
+                .filter(|_| visible);
+            if let Some((shown_row, line_end)) = cell {
+                let col = line_end + 3;
+                if col < text_width {
+                    let shown: String = text.chars().take(text_width - col).collect();
+                    Self::write_span(
+                        screen,
+                        content_start + (row_lines_length + col) as u16,
+                        content_start_row + shown_row as u16,
+                        Color::Rgb {
+                            r: 110,
+                            g: 115,
+                            b: 130,
+                        },
+                        bg_content,
+                        &shown,
+                    );
+                }
+            }
+        }
+
+        if let Some((title, output)) = &self.output_panel {
+            self.render_output_panel(
+                screen,
+                content_start,
+                content_w,
+                content_start_row + content_rows,
+                title,
+                output,
+            );
+        }
+
         // --- Status bar ---
-        self.render_status_bar(&mut writer, content_start, content_w);
+        self.render_status_bar(screen, content_start, content_w);
+
+        screen.set_cursor(if self.show_cursor {
+            Some(self.cursor_screen_position())
+        } else {
+            None
+        });
+    }
+
+    /// Presentation mode: rows `start..end` of the text in big letters from
+    /// the top of the content area, long rows wrapped and, past the bottom,
+    /// left out
+    fn draw_presentation(
+        &self,
+        screen: &mut Screen,
+        lines: &[Vec<char>],
+        (style, start, end): (present::Style, usize, usize),
+    ) {
+        let (bg, _, _) = theme_colors(self.options.theme);
+        let left = self.content_start_col();
+        let width = self.content_width() as usize;
+        let bottom = self.rows.saturating_sub(1) as usize;
+        let blank = " ".repeat(width);
+        for row in 1..bottom {
+            Self::write_span(screen, left, row as u16, bg, bg, &blank);
+        }
+
+        let ext = self.syntax_ext.as_str();
+        let end = end.min(lines.len());
+        let start = start.min(end);
+        let mut hl_state = syntax::HighlightState::new();
+        for line in &lines[..start] {
+            syntax::highlight_line(line, ext, &mut hl_state);
+        }
+        let per_row = style.chars_per_row(width).max(1);
+        let mut row = 1;
+        'lines: for line in &lines[start..end] {
+            let text: Vec<(char, Color)> = syntax::highlight_line(line, ext, &mut hl_state)
+                .iter()
+                .flat_map(|colored| match colored.ch {
+                    '\t' => vec![(' ', colored.fg); self.options.tab_width],
+                    ch => vec![(ch, colored.fg)],
+                })
+                .collect();
+            // An empty line still takes its rows
+            let chunks: Vec<&[(char, Color)]> = if text.is_empty() {
+                vec![&[]]
+            } else {
+                text.chunks(per_row).collect()
+            };
+            for chunk in chunks {
+                if row + style.rows_per_line() > bottom {
+                    break 'lines;
+                }
+                match style {
+                    present::Style::Blocks => {
+                        for (i, cells) in present::block_rows(chunk).iter().enumerate() {
+                            Self::write_cells(screen, left, (row + i) as u16, bg, cells);
+                        }
+                    }
+                    present::Style::Double => {
+                        screen.set_line_size(row as u16, LineSize::DoubleTop);
+                        screen.set_line_size(row as u16 + 1, LineSize::DoubleBottom);
+                        Self::write_cells(screen, left, row as u16, bg, chunk);
+                        Self::write_cells(screen, left, row as u16 + 1, bg, chunk);
+                    }
+                }
+                row += style.rows_per_line();
+            }
+        }
+    }
+
+    /// The output of the last task below the text: a title row, then the
+    /// lines with the colors the task printed them in
+    fn render_output_panel(
+        &self,
+        screen: &mut Screen,
+        col: u16,
+        width: u16,
+        top: u16,
+        title: &str,
+        output: &[ColoredLine],
+    ) {
+        let fg = Color::Rgb {
+            r: 200,
+            g: 200,
+            b: 200,
+        };
+        let bg = Color::Rgb {
+            r: 12,
+            g: 14,
+            b: 18,
+        };
+        let bg_title = Color::Rgb {
+            r: 35,
+            g: 40,
+            b: 50,
+        };
+        let header = format!(" {} ", title);
+        let header = format!("{:<width$}", header, width = width as usize);
+        Self::write_span(screen, col, top, fg, bg_title, &header);
+
+        let blank = " ".repeat(width as usize);
+        for row in 0..self.output_panel_lines() {
+            let y = top + 1 + row as u16;
+            Self::write_span(screen, col, y, fg, bg, &blank);
+            if let Some(line) = output.get(row) {
+                let cells: Vec<(char, Color)> = line
+                    .iter()
+                    .map(|&(ch, color)| (ch, if color == Color::Reset { fg } else { color }))
+                    .collect();
+                Self::write_cells(screen, col, y, bg, &cells);
+            }
+        }
+    }
+
+    /// Draw chars each with its own color, a span per run of one color
+    fn write_cells(screen: &mut Screen, col: u16, row: u16, bg: Color, cells: &[(char, Color)]) {
+        let mut col = col;
+        for run in cells.chunk_by(|a, b| a.1 == b.1) {
+            let text: String = run.iter().map(|&(ch, _)| ch).collect();
+            col = screen.put_str(col, row, run[0].1, bg, &text);
+        }
+    }
+
+    /// Screen cell of the text cursor given the current scroll offsets
+    pub fn cursor_screen_position(&self) -> (u16, u16) {
+        let gutter = self.offset_lines_number() as u16;
+        // The part of a wrapped line the cursor is on
+        let wrapped = self
+            .segments
+            .iter()
+            .rposition(|&(row, start)| row == self.cursor_row && start <= self.cursor_col);
+        let (row, col) = match wrapped {
+            Some(i) => (i, self.cursor_col - self.segments[i].1),
+            None => (
+                self.cursor_row.saturating_sub(self.initial_row),
+                self.cursor_col.saturating_sub(self.initial_column),
+            ),
+        };
+        let x = self.sidebar_width + gutter + col as u16;
+        let y = self.content_top_row() + row as u16;
+        (
+            x.min(self.columns.saturating_sub(1)),
+            y.min(self.rows.saturating_sub(1)),
+        )
+    }
+
+    /// Buffer (row, col) under a screen cell of the text area, if any
+    pub fn buffer_position_at(&self, column: u16, row: u16) -> Option<(usize, usize)> {
+        let text_start = self.sidebar_width + self.offset_lines_number() as u16;
+        let top = self.content_top_row();
+        if column < text_start || row < top || row >= top + self.content_rows() as u16 {
+            return None;
+        }
+        let (file_row, start) = self.shown_at((row - top) as usize)?;
+        Some((file_row, start + (column - text_start) as usize))
+    }
 
-        queue!(writer, style::ResetColor).unwrap();
-        if self.show_cursor {
-            queue!(writer, cursor::Show, cursor::MoveTo(last_col, last_row)).unwrap();
+    /// Buffer row of a line number under a screen cell, if the cell is in
+    /// the gutter
+    pub fn gutter_row_at(&self, column: u16, row: u16) -> Option<usize> {
+        let gutter_end = self.sidebar_width + self.offset_lines_number() as u16;
+        if column < self.sidebar_width || column >= gutter_end {
+            return None;
         }
-        writer.flush().unwrap();
+        self.file_row_at(row)
+    }
+
+    /// Buffer row drawn on a screen row, if the row shows text
+    pub fn file_row_at(&self, row: u16) -> Option<usize> {
+        let top = self.content_top_row();
+        if row < top || row >= top + self.content_rows() as u16 {
+            return None;
+        }
+        self.shown_at((row - top) as usize)
+            .map(|(file_row, _)| file_row)
     }
 
     fn is_search_match(&self, line: &[char], col: usize, search_chars: &[char]) -> bool {
@@ -373,7 +1093,7 @@ impl Display {
         false
     }
 
-    fn render_tab_bar(&self, writer: &mut BufWriter<io::Stdout>, start_col: u16, width: u16) {
+    fn render_tab_bar(&self, screen: &mut Screen, start_col: u16, width: u16) {
         let bg_inactive = Color::Rgb {
             r: 20,
             g: 22,
@@ -395,29 +1115,41 @@ impl Display {
             b: 220,
         };
 
-        let mut tab_str = String::new();
+        let mut tab_chars: Vec<char> = vec![];
         let mut active_ranges: Vec<(usize, usize)> = vec![];
-        let mut pos = 0;
 
-        for (name, is_active, is_modified) in &self.tab_names {
+        for (name, is_active, is_modified, is_read_only) in &self.tab_names {
             let mod_indicator = if *is_modified { "● " } else { "" };
-            let tab_text = format!(" {}{} ", mod_indicator, name);
-            let tab_len = tab_text.chars().count();
+            let lock_indicator = if *is_read_only { "🔒 " } else { "" };
+            let tab_text = format!(" {}{}{} ", lock_indicator, mod_indicator, name);
+            let start = tab_chars.len();
+            tab_chars.extend(columns(&tab_text));
             if *is_active {
-                active_ranges.push((pos, pos + tab_len));
+                active_ranges.push((start, tab_chars.len()));
             }
-            tab_str.push_str(&tab_text);
-            tab_str.push('│');
-            pos += tab_len + 1;
+            tab_chars.push('│');
         }
-
-        let tab_chars: Vec<char> = tab_str.chars().collect();
         let total_len = width as usize;
 
+        // Scroll the tab strip so the active tab stays visible
+        if let Some(&(start, end)) = active_ranges.first() {
+            if end > total_len {
+                let shift = (end - total_len).min(start);
+                tab_chars.drain(..shift);
+                for range in active_ranges.iter_mut() {
+                    range.0 -= shift;
+                    range.1 -= shift;
+                }
+            }
+        }
+
         let mut col = 0;
         while col < total_len {
             let is_active = active_ranges.iter().any(|(s, e)| col >= *s && col < *e);
-            let (fg, bg) = if is_active {
+            let (fg, bg) = if is_active && self.sidebar_focused {
+                // Still marked, but the sidebar's header is the lit one
+                (fg_active, bg_inactive)
+            } else if is_active {
                 (fg_active, bg_active)
             } else {
                 (fg_inactive, bg_inactive)
@@ -430,41 +1162,138 @@ impl Display {
                 if next_active != is_active {
                     break;
                 }
-                let ch = tab_chars.get(col).copied().unwrap_or(' ');
-                span.push(ch);
+                match tab_chars.get(col).copied().unwrap_or(' ') {
+                    // Half of a wide character scrolled out of view
+                    WIDE_TAIL if col == span_start => span.push(' '),
+                    WIDE_TAIL => {}
+                    ch => span.push(ch),
+                }
                 col += 1;
             }
-            Self::write_span(writer, start_col + span_start as u16, 0, fg, bg, &span);
+            Self::write_span(screen, start_col + span_start as u16, 0, fg, bg, &span);
         }
     }
 
-    fn render_status_bar(&self, writer: &mut BufWriter<io::Stdout>, start_col: u16, width: u16) {
-        let status_row = self.rows - 1;
-
+    /// Status bar text padded to `width`, plus the column range of each
+    /// clickable segment (relative to the start of the bar).
+    fn status_layout(&self, width: u16) -> (String, Vec<(StatusSegment, usize, usize)>) {
         let modified_indicator = if self.modified { "[+] " } else { "" };
-        let left_part = format!(" {}{}", modified_indicator, self.filename);
-        let info_part = format!(
-            "Ln {}, Col {} | {} linhas",
-            self.cursor_line, self.cursor_column, self.file_size
-        );
-        let mode_text = format!(" -- {} -- ", self.mode);
-        let right_part = format!("{}  {}", info_part, mode_text);
+        let read_only_indicator = if self.read_only { "🔒 " } else { "" };
+        let breadcrumb = match &self.breadcrumb {
+            Some(path) => format!(" › {}", path),
+            None => String::new(),
+        };
+        let left_part = if self.message.is_empty() {
+            format!(
+                " {}{}{}{}",
+                modified_indicator, read_only_indicator, self.filename, breadcrumb
+            )
+        } else {
+            format!(
+                " {}{}{}{}  {}",
+                modified_indicator, read_only_indicator, self.filename, breadcrumb, self.message
+            )
+        };
+
+        let bom = if self.has_bom { " BOM" } else { "" };
+        let mut pieces: Vec<(Option<StatusSegment>, String)> = vec![
+            (Some(StatusSegment::Encoding), format!("UTF-8{}", bom)),
+            (
+                Some(StatusSegment::LineEnding),
+                self.line_ending.to_string(),
+            ),
+            (
+                Some(StatusSegment::Position),
+                format!("Ln {}, Col {}", self.cursor_row + 1, self.cursor_col + 1),
+            ),
+            (None, format!("{} linhas", self.file_size)),
+            (
+                Some(StatusSegment::Language),
+                syntax::language_name(&self.syntax_ext).to_string(),
+            ),
+        ];
+
+        if !self.progress.is_empty() {
+            pieces.insert(0, (Some(StatusSegment::Jobs), self.progress.clone()));
+        }
 
-        let padding = (width as usize).saturating_sub(left_part.len() + right_part.len());
-        let status_line = format!("{}{}{}", left_part, " ".repeat(padding), right_part);
+        let mut right_part = String::new();
+        let mut ranges: Vec<(StatusSegment, usize, usize)> = vec![];
+        for (i, (segment, text)) in pieces.iter().enumerate() {
+            if i > 0 {
+                right_part.push_str(" | ");
+            }
+            let start = right_part.chars().count();
+            right_part.push_str(text);
+            if let Some(segment) = segment {
+                ranges.push((*segment, start, start + text.chars().count()));
+            }
+        }
+        right_part.push_str(&format!("   -- {} -- ", self.mode));
+
+        // The right part holds the clickable segments, so the left part is
+        // the one cut short when both don't fit
+        let right_len = right_part.chars().count();
+        let left_room = (width as usize).saturating_sub(right_len + 1);
+        let mut left_columns = columns(&left_part);
+        left_columns.truncate(left_room);
+        if left_columns
+            .last()
+            .is_some_and(|&ch| screen::char_width(ch) == 2)
+        {
+            left_columns.pop();
+            left_columns.push(' ');
+        }
+        let left_len = left_columns.len();
+        let padding = (width as usize).saturating_sub(left_len + right_len);
+        let right_start = left_len + padding;
 
-        let status_chars: Vec<char> = status_line.chars().collect();
+        let mut status_chars = left_columns;
+        status_chars.extend(std::iter::repeat_n(' ', padding));
+        status_chars.extend(right_part.chars());
         let mut final_str = String::with_capacity(width as usize);
         for i in 0..width as usize {
-            final_str.push(status_chars.get(i).copied().unwrap_or(' '));
+            match status_chars.get(i).copied().unwrap_or(' ') {
+                WIDE_TAIL => {}
+                ch => final_str.push(ch),
+            }
         }
 
+        let segments = ranges
+            .into_iter()
+            .map(|(segment, start, end)| (segment, right_start + start, right_start + end))
+            .collect();
+        (final_str, segments)
+    }
+
+    /// Which status bar segment is drawn at screen column `column`, if any
+    pub fn status_segment_at(&self, column: u16) -> Option<StatusSegment> {
+        let start_col = self.content_start_col();
+        let relative = column.checked_sub(start_col)? as usize;
+        let (_, segments) = self.status_layout(self.content_width());
+        segments
+            .into_iter()
+            .find(|(_, start, end)| relative >= *start && relative < *end)
+            .map(|(segment, _, _)| segment)
+    }
+
+    fn render_status_bar(&self, screen: &mut Screen, start_col: u16, width: u16) {
+        let status_row = self.rows.saturating_sub(1);
+
+        let (final_str, _) = self.status_layout(width);
+
         let bg_color = if self.mode == "INSERT" {
             Color::Rgb {
                 r: 30,
                 g: 50,
                 b: 30,
             }
+        } else if self.mode == "VISUAL" {
+            Color::Rgb {
+                r: 30,
+                g: 38,
+                b: 60,
+            }
         } else {
             Color::Rgb {
                 r: 20,
@@ -479,11 +1308,11 @@ impl Display {
         };
 
         Self::write_span(
-            writer, start_col, status_row, fg_color, bg_color, &final_str,
+            screen, start_col, status_row, fg_color, bg_color, &final_str,
         );
     }
 
-    fn render_sidebar(&self, writer: &mut BufWriter<io::Stdout>, sidebar: &mut Sidebar) {
+    fn render_sidebar(&self, screen: &mut Screen, sidebar: &mut Sidebar) {
         let bg_sidebar = Color::Rgb {
             r: 18,
             g: 20,
@@ -499,6 +1328,18 @@ impl Display {
             g: 180,
             b: 180,
         };
+        // Chars of a name the search matched
+        let fg_match = Color::Rgb {
+            r: 230,
+            g: 200,
+            b: 90,
+        };
+        // The loading placeholder and "mais N itens" rows
+        let fg_note = Color::Rgb {
+            r: 110,
+            g: 120,
+            b: 110,
+        };
         let bg_selected = Color::Rgb {
             r: 40,
             g: 55,
@@ -524,66 +1365,168 @@ impl Display {
             g: 30,
             b: 25,
         };
+        let fg_header_focused = Color::Rgb {
+            r: 220,
+            g: 255,
+            b: 220,
+        };
+        let bg_header_focused = Color::Rgb {
+            r: 40,
+            g: 80,
+            b: 50,
+        };
 
-        let sw = sidebar.width as usize;
+        let sw = sidebar.sidebar_offset() as usize;
 
-        // Header row
+        // Header row: the folder's name, then the buttons at the right
+        let buttons = sidebar.header_buttons_text();
         let header_text = format!(
-            " {}",
+            " {}{}",
             sidebar
                 .root_path
                 .file_name()
                 .map(|n| n.to_string_lossy().to_string())
-                .unwrap_or_else(|| sidebar.root_path.to_string_lossy().to_string())
+                .unwrap_or_else(|| sidebar.root_path.to_string_lossy().to_string()),
+            if sidebar.is_changed_filter_on() {
+                " (alterados)"
+            } else {
+                ""
+            }
         );
-        let header_padded = Self::pad_to_width(&header_text, sw);
-        Self::write_span(writer, 0, 0, fg_header, bg_header, &header_padded);
+        let header_padded =
+            Self::pad_to_width(&header_text, sw.saturating_sub(buttons.chars().count())) + &buttons;
+        // A lit header marks the sidebar as the focused pane
+        let (fg_header, bg_header) = if self.sidebar_focused {
+            (fg_header_focused, bg_header_focused)
+        } else {
+            (fg_header, bg_header)
+        };
+        Self::write_span(screen, 0, 0, fg_header, bg_header, &header_padded);
 
         // Search bar at row 1 if active
-        let content_start_row: u16 = if sidebar.search_active { 2 } else { 1 };
+        let content_start_row = sidebar.list_top();
 
         if sidebar.search_active {
-            let search_display = format!(" / {}", sidebar.search_query);
-            let search_padded = Self::pad_to_width(&search_display, sw);
-            Self::write_span(writer, 0, 1, fg_search, bg_search, &search_padded);
+            // What the search found so far, against the right edge
+            let progress = sidebar
+                .search_progress()
+                .map(|progress| format!(" {} ", progress))
+                .filter(|progress| progress.chars().count() < sw)
+                .unwrap_or_default();
+            // `≈` marks fuzzy search (Tab)
+            let mode = if sidebar.fuzzy { '≈' } else { '/' };
+            let search_display = format!(" {} {}", mode, sidebar.search_query);
+            let search_padded =
+                Self::pad_to_width(&search_display, sw.saturating_sub(progress.chars().count()))
+                    + &progress;
+            Self::write_span(screen, 0, 1, fg_search, bg_search, &search_padded);
         }
 
-        // File entries
-        let entries = sidebar.flat_entries().to_vec();
-        let available_rows = (self.rows - content_start_row) as usize;
-
-        let scroll_offset = if sidebar.selected_index >= available_rows {
-            sidebar.selected_index - available_rows + 1
-        } else {
-            0
-        };
+        // File entries: only the rows on screen are copied, folders can
+        // list thousands
+        let available_rows = self.rows.saturating_sub(content_start_row) as usize;
+        sidebar.clamp_scroll(available_rows);
+        let scroll_offset = sidebar.scroll_offset;
+        let entries = sidebar.flat_entries();
+        let entries = entries[scroll_offset.min(entries.len())..]
+            .iter()
+            .take(available_rows)
+            .cloned()
+            .collect::<Vec<_>>();
+
+        if sidebar.loading {
+            let text = if sidebar.compact {
+                " …"
+            } else {
+                " Carregando…"
+            };
+            let text = Self::pad_to_width(text, sw);
+            Self::write_span(screen, 0, content_start_row, fg_note, bg_sidebar, &text);
+        }
 
         for row in 0..available_rows {
             let screen_row = content_start_row + row as u16;
             let entry_idx = scroll_offset + row;
 
-            if entry_idx < entries.len() {
-                let entry = &entries[entry_idx];
+            if let Some(entry) = entries.get(row) {
                 let is_selected = entry_idx == sidebar.selected_index;
 
                 let indent = "  ".repeat(entry.depth);
-                let line_text = if entry.is_dir {
-                    let dir_icon = if entry.expanded { "▼ " } else { "▶ " };
-                    format!(" {}{}{}", indent, dir_icon, entry.name)
+                // Links get an arrow after the name, as in `ls -l`, and
+                // programs and scripts that can be run a gear; archives open
+                // like folders, with a box after the name
+                let mark = match (entry.is_symlink, entry.is_executable) {
+                    _ if entry.is_archive => " 📦",
+                    (true, _) => " →",
+                    (false, true) => " ⚙",
+                    (false, false) => "",
+                };
+                let line_text = if sidebar.compact {
+                    // Just the icon, the name is in the tooltip
+                    let icon = if entry.more > 0 {
+                        "…"
+                    } else if !entry.is_dir {
+                        syntax::file_icon(&entry.name)
+                    } else if entry.is_loop {
+                        "↺"
+                    } else if entry.expanded {
+                        "▼"
+                    } else {
+                        "▶"
+                    };
+                    let text = format!(" {}", icon);
+                    let used: usize = text.chars().map(screen::char_width).sum();
+                    text + &" ".repeat(sw.saturating_sub(used))
+                } else if entry.more > 0 {
+                    format!(" {}{}", indent, entry.name)
+                } else if entry.is_dir {
+                    let dir_icon = match (entry.is_loop, entry.expanded) {
+                        (true, _) => "↺ ",
+                        (false, true) => "▼ ",
+                        (false, false) => "▶ ",
+                    };
+                    format!(" {}{}{}{}", indent, dir_icon, entry.name, mark)
                 } else {
                     let file_icon = syntax::file_icon(&entry.name);
-                    format!(" {}{} {}", indent, file_icon, entry.name)
+                    format!(" {}{} {}{}", indent, file_icon, entry.name, mark)
                 };
 
-                let padded = Self::pad_to_width(&line_text, sw);
+                // The compact line is padded by cells (wide icons)
+                let padded = if sidebar.compact {
+                    line_text
+                } else {
+                    Self::pad_to_width(&line_text, sw)
+                };
 
                 let bg = if is_selected { bg_selected } else { bg_sidebar };
-                let fg = if entry.is_dir { fg_dir } else { fg_file };
+                let fg = if entry.more > 0 {
+                    fg_note
+                } else if entry.is_dir {
+                    fg_dir
+                } else {
+                    fg_file
+                };
 
-                Self::write_span(writer, 0, screen_row, fg, bg, &padded);
-            } else {
+                Self::write_span(screen, 0, screen_row, fg, bg, &padded);
+
+                if !sidebar.compact && !entry.matched.is_empty() {
+                    let icon = syntax::file_icon(&entry.name);
+                    let prefix = format!(" {}{} ", indent, icon);
+                    let mut col: usize = prefix.chars().map(screen::char_width).sum();
+                    for (i, ch) in entry.name.chars().enumerate() {
+                        if col + screen::char_width(ch) > sw {
+                            break;
+                        }
+                        if entry.matched.contains(&i) {
+                            let text = ch.to_string();
+                            Self::write_span(screen, col as u16, screen_row, fg_match, bg, &text);
+                        }
+                        col += screen::char_width(ch);
+                    }
+                }
+            } else if !(sidebar.loading && row == 0) {
                 let blank = " ".repeat(sw);
-                Self::write_span(writer, 0, screen_row, fg_file, bg_sidebar, &blank);
+                Self::write_span(screen, 0, screen_row, fg_file, bg_sidebar, &blank);
             }
         }
     }
@@ -601,11 +1544,16 @@ impl Display {
     // --- Public API ---
 
     pub fn offset_lines_number(&self) -> usize {
-        let lines_length = self.file_matrix.len();
-        lines_length.to_string().chars().count() + 2
+        if !self.options.line_numbers {
+            return 0;
+        }
+        self.file_size.to_string().chars().count() + 2
     }
 
-    fn offset_lines(&self, row_start: &u16, row_end: &u16) -> Vec<Vec<char>> {
+    fn offset_lines(&self, row_start: &usize, row_end: &usize) -> Vec<Vec<char>> {
+        if !self.options.line_numbers {
+            return vec![];
+        }
         let row_lines_length = self.offset_lines_number();
         let rows_values = *row_start..*row_end;
         let mut rows: Vec<Vec<char>> = vec![];
@@ -621,32 +1569,19 @@ impl Display {
         rows
     }
 
-    pub fn next_row(&mut self) {
-        let content_rows = self.rows.saturating_sub(2);
-        if self.initial_row >= (self.file_matrix.len() as u16).saturating_sub(content_rows) {
-            return;
-        }
-        self.initial_row += 1;
-    }
-
-    pub fn previous_row(&mut self) {
-        if self.initial_row > 0 {
-            self.initial_row -= 1;
-        }
-    }
-
-    pub fn next_column(&mut self, column_position: u16) {
-        let content_w = self.content_width();
-        if column_position >= self.sidebar_width + content_w - 1 {
-            self.initial_column += 1;
-        }
+    /// Number of text rows between the tab bar and the status bar
+    pub fn content_rows(&self) -> usize {
+        let panel = match self.output_panel {
+            Some(_) => self.output_panel_height(),
+            None => 0,
+        };
+        self.rows.saturating_sub(2 + panel) as usize
     }
 
-    pub fn previous_column(&mut self, column_position: u16) {
-        let min_col = self.sidebar_width + self.offset_lines_number() as u16;
-        if column_position <= min_col && self.initial_column > 0 {
-            self.initial_column -= 1;
-        }
+    /// Number of text columns right of the line-number gutter
+    pub fn text_width(&self) -> usize {
+        self.content_width()
+            .saturating_sub(self.offset_lines_number() as u16) as usize
     }
 
     pub fn set_columns(&mut self, columns: u16) {
@@ -655,48 +1590,46 @@ impl Display {
     pub fn set_rows(&mut self, rows: u16) {
         self.rows = rows;
     }
-    pub fn set_file_matrix(&mut self, file_matrix: Vec<Vec<char>>) {
-        self.file_matrix = file_matrix;
-    }
     pub fn set_mode(&mut self, mode: &str) {
         self.mode = String::from(mode);
     }
     pub fn set_modified(&mut self, modified: bool) {
         self.modified = modified;
     }
-    pub fn set_cursor_info(&mut self, line: u16, column: u16) {
-        self.cursor_line = line;
-        self.cursor_column = column;
-    }
-    pub fn update_file_size(&mut self) {
-        self.file_size = self.file_matrix.len();
-    }
-    pub fn reset_column(&mut self) {
-        self.initial_column = 0;
-    }
-    pub fn reset_row(&mut self) {
-        self.initial_row = 0;
-    }
-
-    pub fn get_absolute_row(&self, screen_row: u16) -> u16 {
-        let content_row = screen_row.saturating_sub(1);
-        self.initial_row + content_row
+    pub fn set_cursor_info(&mut self, row: usize, col: usize) {
+        self.cursor_row = row;
+        self.cursor_col = col;
     }
-
-    pub fn set_initial_column(&mut self, column: u16) {
-        self.initial_column = column;
+    /// Line count of the active buffer (also sizes the line-number gutter)
+    pub fn set_file_size(&mut self, lines: usize) {
+        self.file_size = lines;
     }
-    pub fn set_initial_row(&mut self, row: u16) {
-        self.initial_row = row;
+    pub fn content_top_row(&self) -> u16 {
+        1
     }
+}
 
-    pub fn get_cursor_position(&self) -> u16 {
-        let column_position = cursor::position().unwrap().0;
-        let row_lines_length = self.offset_lines_number() as u16;
-        self.initial_column + column_position.saturating_sub(self.sidebar_width + row_lines_length)
-    }
+/// Backgrounds of the text, the line numbers and the rulers in `theme`
+fn theme_colors(theme: Theme) -> (Color, Color, Color) {
+    let rgb = |(r, g, b): (u8, u8, u8)| Color::Rgb { r, g, b };
+    let (content, line_nr, ruler) = match theme {
+        Theme::Dark => ((15, 18, 15), (10, 12, 10), (30, 34, 30)),
+        Theme::Black => ((0, 0, 0), (8, 8, 8), (26, 26, 26)),
+        Theme::Navy => ((1, 22, 39), (1, 16, 30), (18, 42, 64)),
+    };
+    (rgb(content), rgb(line_nr), rgb(ruler))
+}
 
-    pub fn content_top_row(&self) -> u16 {
-        1
+/// One entry per terminal column: a wide character is followed by
+/// `WIDE_TAIL`, which is dropped again when the text is drawn
+fn columns(text: &str) -> Vec<char> {
+    let mut columns = vec![];
+    for ch in text.chars() {
+        match screen::char_width(ch) {
+            0 => {}
+            2 => columns.extend([ch, WIDE_TAIL]),
+            _ => columns.push(ch),
+        }
     }
+    columns
 }
@@ -1,15 +1,97 @@
-use crate::display::Display;
-use crate::sidebar::Sidebar;
+use crate::archive;
+use crate::breadcrumb;
+use crate::buffer_file::{BufferFile, LineEnding};
+use crate::clipboard;
+use crate::collab::{self, CollabFs, Session};
+use crate::commands::{self, Command};
+use crate::config::Config;
+use crate::crypt::{Cipher, CryptFs};
+use crate::diagnostics::{Diagnostic, Severity};
+use crate::display::{Display, StatusSegment};
+use crate::ex::{self, Ex, Range, Substitute};
+use crate::expand;
+use crate::follow::{Follow, Growth};
+use crate::git;
+use crate::grapheme;
+use crate::history;
+use crate::idle::Idle;
+use crate::inspect;
+use crate::jobs::{JobId, JobMessage, Jobs};
+use crate::json;
+use crate::keymap::{Binding, Keymap, Step};
+use crate::killring::KillRing;
+use crate::links::{self, Target};
+use crate::loader::{self, FileLoad, LoadMessage};
+use crate::log;
+use crate::marks;
+use crate::motion::{self, CharFind};
+use crate::options::{self, Options};
+use crate::overlay::{Overlay, Placement};
+use crate::pairs::{self, Typed};
+use crate::patch;
+use crate::present;
+use crate::prompt::{Prompt, PromptEvent};
+use crate::remote::{self, RemotePath};
+use crate::rename::RenameFs;
+use crate::replace::{self, ReplaceFs};
+use crate::server::{Server, Waiter};
+use crate::sidebar::{HeaderButton, Sidebar};
+use crate::snippets;
+use crate::startup::StartupTiming;
+use crate::symbols::{self, Symbol};
+use crate::syntax::{self, Context};
+use crate::tags;
+use crate::tasks::{self, Task};
+use crate::testfile::{self, Counterpart};
+use crate::textobj::{self, Span};
+use crate::todos::{self, Todo};
+use crate::transform;
+use crate::trash;
+use crate::vfs;
+use crate::view::{EditorMode, ViewState};
 use crate::workspace::Workspace;
-use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+use crossterm::event::{
+    Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, KeyboardEnhancementFlags, MouseButton,
+    MouseEvent, MouseEventKind, PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+};
 use crossterm::{cursor, event, execute, style, terminal};
 use std::io;
-use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
 
-#[derive(PartialEq)]
-enum EditorMode {
-    Normal,
-    Insert,
+/// How often the event loop checks background jobs while idle
+const JOB_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// How often an otherwise idle event loop checks for `--remote` files
+const SERVER_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Lines moved per mouse wheel step
+const MOUSE_SCROLL_LINES: usize = 3;
+
+/// How a session ended, told to scripts wrapping the editor through the
+/// exit code
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Outcome {
+    Done,
+    /// A --quick session left without saving anything
+    Aborted,
+    /// Bad arguments, or the file given couldn't be opened
+    OpenFailed,
+    /// Quit discarding changes whose save failed
+    SaveFailed,
+}
+
+impl Outcome {
+    pub fn exit_code(self) -> ExitCode {
+        ExitCode::from(match self {
+            Outcome::Done => 0,
+            Outcome::Aborted => 1,
+            Outcome::OpenFailed => 2,
+            Outcome::SaveFailed => 3,
+        })
+    }
 }
 
 #[derive(PartialEq)]
@@ -18,26 +100,156 @@ enum Focus {
     Sidebar,
 }
 
+/// What the hover popup describes: a cell of the text (its diagnostics) or,
+/// without `col`, a whole line (diagnostics and blame)
+struct Hover {
+    row: usize,
+    col: Option<usize>,
+    // Screen cell the popup opens below
+    anchor: (u16, u16),
+}
+
+/// A project search running, its results listed as they arrive
+struct ProjectSearch {
+    job: JobId,
+    query: String,
+    root: PathBuf,
+    // Open files, searched in their buffers and skipped on disk
+    open: Vec<String>,
+    count: usize,
+}
+
+impl ProjectSearch {
+    fn title(&self, done: bool) -> String {
+        let count = match (done, self.count) {
+            (_, count) if count >= replace::MAX_RESULTS => format!("primeiras {}", count),
+            (true, count) => count.to_string(),
+            (false, count) => format!("{}, buscando…", count),
+        };
+        format!("'{}' no projeto ({})", self.query, count)
+    }
+}
+
 pub struct Editor {
     workspace: Workspace,
     display: Display,
     sidebar: Option<Sidebar>,
-    mode: EditorMode,
     focus: Focus,
     show_welcome: bool,
-    search_mode: bool,
     search_query: String,
-    // Search state for save/restore
-    search_saved_row: u16,
-    search_saved_col: u16,
-    search_saved_initial_row: u16,
-    search_saved_initial_col: u16,
+    // View of the searched buffer, restored when search is cancelled
+    search_saved: ViewState,
+    config: Config,
+    // File given on the command line, opened once the screen is ready
+    startup_file: Option<String>,
+    startup_more: Vec<String>,
+    // Files still to open, waiting on a prompt about one before them
+    open_queue: Vec<String>,
+    // Set when that file couldn't be opened
+    open_failed: bool,
+    // Open question and what to do with its answer; it takes every key
+    prompt: Option<(Prompt, PromptAction)>,
+    // Set once the user confirmed quitting
+    quit: bool,
+    // --quick: Ctrl+X saves and quits, and leaving without saving anything
+    // is an abort. `insert` starts the file in Insert mode.
+    quick: bool,
+    quick_insert: bool,
+    // A file was saved during the session, and saving all on quit failed
+    saved: bool,
+    save_failed: bool,
+    // Performance panel (F12) and what it reports about the last frame
+    perf_hud: bool,
+    frame_time: Duration,
+    frame_events: usize,
+    jobs: Jobs,
+    // Files still being read by background jobs
+    loads: Vec<FileLoad>,
+    // Project scan feeding the TODO panel
+    todo_scan: Option<JobId>,
+    // Reading the sidebar's root folder
+    tree_load: Option<JobId>,
+    // Looking for the sidebar's search query over the whole tree
+    sidebar_search: Option<JobId>,
+    // Blame of the cursor line shown after it, and the files being blamed
+    blame: bool,
+    blame_jobs: Vec<(JobId, String)>,
+    // `g` was typed in normal mode; the next key completes it (`gx`)
+    pending_g: bool,
+    // Alt and leader bindings, and whether the leader key was just pressed
+    keymap: Keymap,
+    pending_leader: bool,
+    // A `[macros]` entry is being played back
+    playing_macro: bool,
+    // Texts yanked and deleted, for `p` and paste-history
+    kill_ring: KillRing,
+    // Deferred refreshes, run once input pauses
+    idle: Idle,
+    // Last project task: its job while it runs, its output, and whether
+    // the output panel shows
+    task_job: Option<JobId>,
+    task_output: Option<tasks::Output>,
+    show_output: bool,
+    // Presentation mode: the rows shown big (end excluded), and how
+    presenting: Option<(usize, usize)>,
+    present_style: present::Style,
+    // `m` (set) or `'`/`` ` `` (jump) was typed; the next key names the mark
+    pending_mark: Option<char>,
+    // `f`, `F`, `t` or `T` was typed; the next key is the char to find.
+    // The last such search, for `;` and `,`
+    pending_find: Option<char>,
+    last_find: Option<CharFind>,
+    // `d`, `c` or `y` was typed, then maybe `i` (false) or `a` (true); the
+    // next key names the text object it acts on
+    pending_operator: Option<(char, Option<bool>)>,
+    // Digits typed in normal mode, repeating the motion after them (`3}`)
+    count: Option<usize>,
+    // Files moved to the trash from the sidebar, the latest last
+    trashed: Vec<trash::Trashed>,
+    // Bulk rename buffers by name, with the lists they rename through
+    renames: Vec<(String, Arc<RenameFs>)>,
+    project_search: Option<ProjectSearch>,
+    // Definitions indexed in the project, with the folder indexed
+    symbols: Option<(PathBuf, Vec<Symbol>)>,
+    // Symbol indexing: job, folder, and the name to go to once it's done
+    symbol_scan: Option<(JobId, PathBuf, String)>,
+    // Project search for a replace: job, text and replacement
+    replace_scan: Option<(JobId, String, String)>,
+    // Replace previews by buffer name, with the lists they apply through
+    replaces: Vec<(String, Arc<ReplaceFs>)>,
+    // Sockets other instances send files to (`--remote`, and launches on
+    // the open project), and the clients waiting for their file to close
+    servers: Vec<Server>,
+    waiters: Vec<(String, Waiter)>,
+    // Files whose appended lines flow into their buffers (follow)
+    follows: Vec<Follow>,
+    // Buffer shared with other instances (collab-host / collab-join)
+    collab: Option<Session>,
+    // Row where a line selection dragged over the line numbers started
+    gutter_drag: Option<usize>,
+    // Popup open by K or by resting the mouse on a diagnostic
+    hover: Option<Hover>,
+    // Lines run on the `:` command line, the latest last
+    ex_history: Vec<String>,
+    // Global options; buffers may override them
+    options: Options,
+    // Phases of starting up, until the first frame (--startup-timing)
+    startup_timing: Option<StartupTiming>,
 }
 
 impl Editor {
-    pub fn new(workspace: Workspace, sidebar: Option<Sidebar>) -> Editor {
+    pub fn new(workspace: Workspace, mut sidebar: Option<Sidebar>, config: Config) -> Editor {
         let show_welcome = !workspace.has_files();
-        let display = Display::new();
+        let keymap = Keymap::from_config(&config);
+        let kill_ring = KillRing::from_config(&config);
+        let idle = Idle::from_config(&config);
+        let present_style = present::Style::from_config(&config);
+        let options = Options::from_config(&config);
+        let mut display = Display::new();
+        display.set_warn_suspicious(config.warn_suspicious_chars());
+        if let Some(sidebar) = &mut sidebar {
+            sidebar.fit_width(display.columns);
+        }
         let initial_focus =
             if sidebar.as_ref().map(|s| s.visible).unwrap_or(false) && !workspace.has_files() {
                 Focus::Sidebar
@@ -48,29 +260,192 @@ impl Editor {
             workspace,
             display,
             sidebar,
-            mode: EditorMode::Normal,
             focus: initial_focus,
             show_welcome,
-            search_mode: false,
             search_query: String::new(),
-            search_saved_row: 0,
-            search_saved_col: 0,
-            search_saved_initial_row: 0,
-            search_saved_initial_col: 0,
+            search_saved: ViewState::default(),
+            config,
+            startup_file: None,
+            startup_more: vec![],
+            open_queue: vec![],
+            open_failed: false,
+            prompt: None,
+            quit: false,
+            quick: false,
+            quick_insert: false,
+            saved: false,
+            save_failed: false,
+            perf_hud: false,
+            frame_time: Duration::ZERO,
+            frame_events: 0,
+            jobs: Jobs::new(),
+            loads: vec![],
+            todo_scan: None,
+            tree_load: None,
+            sidebar_search: None,
+            blame: false,
+            blame_jobs: vec![],
+            pending_g: false,
+            keymap,
+            pending_leader: false,
+            playing_macro: false,
+            kill_ring,
+            idle,
+            task_job: None,
+            task_output: None,
+            show_output: false,
+            presenting: None,
+            present_style,
+            pending_mark: None,
+            pending_find: None,
+            last_find: None,
+            pending_operator: None,
+            count: None,
+            trashed: vec![],
+            renames: vec![],
+            project_search: None,
+            symbols: None,
+            symbol_scan: None,
+            replace_scan: None,
+            replaces: vec![],
+            servers: vec![],
+            waiters: vec![],
+            follows: vec![],
+            collab: None,
+            gutter_drag: None,
+            hover: None,
+            ex_history: vec![],
+            options,
+            startup_timing: None,
         }
     }
 
+    /// Go on timing the start in `run`: opening the file and the first
+    /// frames
+    pub fn time_startup(&mut self, timing: StartupTiming) {
+        self.startup_timing = Some(timing);
+    }
+
+    /// Time of each phase of starting up, with `--startup-timing`
+    pub fn startup_report(&self) -> Option<String> {
+        self.startup_timing.as_ref()?.report()
+    }
+
+    /// Record `phase` of starting up as having taken since `since`
+    fn startup_phase(&mut self, phase: &'static str, since: Instant) {
+        if let Some(timing) = &mut self.startup_timing {
+            timing.record(phase, since);
+        }
+    }
+
+    /// Open `path` as soon as the editor starts, then `more` files in tabs
+    /// after it, each going through the same large/binary file checks as
+    /// interactive opens
+    pub fn open_on_start(&mut self, path: String, more: Vec<String>) {
+        self.show_welcome = false;
+        self.focus = Focus::Editor;
+        self.startup_file = Some(path);
+        self.startup_more = more;
+    }
+
+    /// Tune the editor for a single edit from git, crontab and the like:
+    /// Ctrl+X saves and quits, and `insert` starts in Insert mode
+    pub fn quick(&mut self, insert: bool) {
+        self.quick = true;
+        self.quick_insert = insert;
+    }
+
+    /// How the session went, once `run` returned
+    pub fn outcome(&self) -> Outcome {
+        if self.open_failed {
+            Outcome::OpenFailed
+        } else if self.save_failed && self.workspace.is_any_modified() {
+            Outcome::SaveFailed
+        } else if self.quick && !self.saved {
+            // The calling tool should discard the edit
+            Outcome::Aborted
+        } else {
+            Outcome::Done
+        }
+    }
+
+    /// Open the files other processes send through `server`
+    pub fn serve(&mut self, server: Server) {
+        self.servers.push(server);
+    }
+
     pub fn run(&mut self) -> io::Result<()> {
-        execute!(io::stdout(), terminal::EnterAlternateScreen)?;
-        terminal::enable_raw_mode()?;
+        if let Some(sidebar) = &self.sidebar {
+            history::record_dir(&sidebar.root_path);
+            self.tree_load = Some(sidebar.start_load(&mut self.jobs));
+        }
+
+        // Asking the terminal what it supports can wait on its answer
+        let since = Instant::now();
+        Self::enter_terminal()?;
         style::force_color_output(true);
+        self.startup_phase("terminal", since);
 
+        let since = Instant::now();
         self.sync_display();
         self.render();
+        self.startup_phase("primeiro quadro", since);
 
-        self.position_cursor_at_start();
+        if let Some(path) = self.startup_file.clone() {
+            let since = Instant::now();
+            self.open_in_editor(&path)?;
+            self.startup_phase("abrir o arquivo", since);
+            if self.quick_insert && self.workspace.active().is_some_and(|b| !b.read_only) {
+                self.set_mode(EditorMode::Insert);
+            }
+            let more = std::mem::take(&mut self.startup_more);
+            if self.prompt.is_some() {
+                self.open_queue = more;
+            } else {
+                self.open_matches(more)?;
+            }
+            let since = Instant::now();
+            self.render();
+            self.startup_phase("quadro com o arquivo", since);
+        }
+        if let Some(timing) = &mut self.startup_timing {
+            timing.finish();
+            if let Some(summary) = timing.summary() {
+                self.display.set_message(summary);
+                self.render();
+            }
+        }
 
         loop {
+            let busy = self.jobs.is_busy() || !self.follows.is_empty() || self.collab.is_some();
+            let idle_wait = self.idle.time_left();
+            if busy || !self.servers.is_empty() || idle_wait.is_some() {
+                self.poll_jobs()?;
+                self.poll_follows();
+                self.poll_collab();
+                let served = self.poll_server()?;
+                // Wake up in time for whichever comes first, idle work included
+                let interval = [
+                    busy.then_some(JOB_POLL_INTERVAL),
+                    (!self.servers.is_empty()).then_some(SERVER_POLL_INTERVAL),
+                    idle_wait,
+                ]
+                .into_iter()
+                .flatten()
+                .min()
+                .unwrap_or(SERVER_POLL_INTERVAL);
+                if !event::poll(interval)? {
+                    let idle = self.idle.due();
+                    if idle {
+                        self.run_idle_tasks();
+                    }
+                    if busy || served || idle {
+                        self.render();
+                    }
+                    continue;
+                }
+            }
+
             // Wait for first event
             let ev = event::read()?;
 
@@ -78,140 +453,419 @@ impl Editor {
             let mut events = vec![ev];
 
             // Drain queued events (batching rapid key repeats)
-            while event::poll(std::time::Duration::ZERO)? {
+            while event::poll(Duration::ZERO)? {
                 events.push(event::read()?);
             }
+            self.frame_events = events.len();
+            self.idle.input();
 
-            let mut should_break = false;
-
-            for ev in events {
-                let (column_size, row_size) = terminal::size()?;
-                let (column_position, row_position) = cursor::position()?;
-
-                match ev {
-                    Event::Key(key) => {
-                        if self.search_mode {
-                            if self.handle_search_input(key)? {
-                                continue;
-                            }
-                        }
-
-                        // Global shortcuts
-                        if key.modifiers.contains(KeyModifiers::CONTROL) {
-                            match key.code {
-                                KeyCode::Char('q') => {
-                                    if self.handle_quit()? {
-                                        should_break = true;
-                                        break;
-                                    }
-                                    continue;
-                                }
-                                KeyCode::Char('s') => {
-                                    self.workspace.save_active()?;
-                                    self.sync_display();
-                                    self.render();
-                                    continue;
-                                }
-                                KeyCode::Char('t') => {
-                                    self.toggle_sidebar();
-                                    self.sync_display();
-                                    self.render();
-                                    self.position_cursor_at_start();
-                                    continue;
-                                }
-                                KeyCode::Char('o') => {
-                                    self.handle_open_file()?;
-                                    continue;
-                                }
-                                KeyCode::Char('w') => {
-                                    self.handle_close_tab()?;
-                                    continue;
-                                }
-                                KeyCode::Char('f') => {
-                                    if self.workspace.has_files() {
-                                        self.search_mode = true;
-                                        self.search_query.clear();
-                                        // Save current position
-                                        let (sc, sr) = cursor::position()?;
-                                        self.search_saved_col = sc;
-                                        self.search_saved_row = sr;
-                                        self.search_saved_initial_row = self.display.initial_row;
-                                        self.search_saved_initial_col = self.display.initial_column;
-                                    }
-                                    continue;
-                                }
-                                KeyCode::Tab | KeyCode::BackTab => {
-                                    self.handle_tab_switch(key)?;
-                                    continue;
-                                }
-                                _ => {}
-                            }
-                        }
+            let mut events = events.into_iter().peekable();
+            while let Some(ev) = events.next() {
+                if self.quit {
+                    break;
+                }
 
-                        if self.show_welcome && self.focus != Focus::Sidebar {
+                // A run of typed characters (key repeat, fast typing) is
+                // inserted as one edit
+                if let Some(c) = self.typed_char(&ev) {
+                    let mut text = String::from(c);
+                    while let Some(next) = events.peek() {
+                        if matches!(next, Event::Key(key) if key.kind == KeyEventKind::Release) {
+                            events.next();
                             continue;
                         }
+                        let Some(c) = self.typed_char(next) else {
+                            break;
+                        };
+                        text.push(c);
+                        events.next();
+                    }
+                    log::debug!(
+                        "evento",
+                        "{} caractere(s) digitado(s)",
+                        text.chars().count()
+                    );
+                    self.insert_typed(&text);
+                    continue;
+                }
 
-                        // Focus-specific handling
-                        match self.focus {
-                            Focus::Sidebar => {
-                                self.handle_sidebar_input(key)?;
-                            }
-                            Focus::Editor => {
-                                if !self.workspace.has_files() {
-                                    continue;
-                                }
-                                match self.mode {
-                                    EditorMode::Normal => {
-                                        self.handle_normal_mode(
-                                            key.code,
-                                            column_position,
-                                            row_position,
-                                            row_size,
-                                        )?;
-                                    }
-                                    EditorMode::Insert => {
-                                        self.handle_insert_mode(
-                                            key.code,
-                                            column_position,
-                                            row_position,
-                                            column_size,
-                                            row_size,
-                                        )?;
-                                    }
-                                }
-                            }
-                        }
+                match ev {
+                    // Only presses (and repeats) act; releases come with the
+                    // kitty protocol and on Windows
+                    Event::Key(key) if key.kind == KeyEventKind::Release => {}
+                    Event::Key(key) => self.handle_key(key)?,
+                    Event::Paste(text) => {
+                        self.handle_paste(&text)?;
+                    }
+                    // The open prompt keeps focus until it is answered
+                    Event::Mouse(_) if self.prompt.is_some() => {}
+                    Event::Mouse(mouse) => {
+                        self.handle_mouse(mouse)?;
                     }
                     Event::Resize(w, h) => {
-                        self.display.set_columns(w);
-                        self.display.set_rows(h);
+                        log::debug!("evento", "redimensionado para {}x{}", w, h);
+                        self.handle_resize(w, h)?;
                     }
                     _ => {}
                 }
             }
 
-            if should_break {
+            if self.quit {
                 break;
             }
 
-            self.update_status();
             self.render();
+        }
+
+        Self::leave_terminal()?;
+
+        Ok(())
+    }
+
+    /// Act on a key press: the open prompt takes it first, then the
+    /// global shortcuts, then the focused pane in its mode
+    fn handle_key(&mut self, key: KeyEvent) -> io::Result<()> {
+        log::debug!("evento", "tecla {:?} {:?}", key.code, key.modifiers);
+        self.display.set_message(String::new());
+        self.hover = None;
+
+        if self.prompt.is_some() {
+            self.handle_prompt_key(key)?;
+            return Ok(());
+        }
+
+        // The key after the leader picks from its popup;
+        // any other closes it
+        if std::mem::take(&mut self.pending_leader) {
+            if let KeyCode::Char(c) = key.code {
+                if let Some(binding) = self.keymap.leader(c) {
+                    self.run_binding(binding)?;
+                }
+            }
+            return Ok(());
+        }
+
+        if self.presenting.is_some() && self.handle_presentation_key(key.code) {
+            return Ok(());
+        }
+
+        let alt = key.modifiers - KeyModifiers::SHIFT == KeyModifiers::ALT;
+        if let (true, KeyCode::Char(c)) = (alt, key.code) {
+            if let Some(binding) = self.keymap.alt(c) {
+                self.run_binding(binding)?;
+                return Ok(());
+            }
+        }
+
+        // Global shortcuts
+        if key.modifiers.contains(KeyModifiers::CONTROL) {
+            match key.code {
+                KeyCode::Char('q') => {
+                    self.handle_quit();
+                    return Ok(());
+                }
+                KeyCode::Char('x') if self.quick => {
+                    // A failed save keeps the editor open
+                    // with the error shown
+                    let read_only = self.workspace.active().is_some_and(|b| b.read_only);
+                    if read_only || self.save_active() {
+                        self.handle_quit();
+                    }
+                    self.sync_display();
+                    return Ok(());
+                }
+                KeyCode::Char('c' | 'x')
+                    if self.focus == Focus::Editor && self.workspace.has_files() =>
+                {
+                    self.copy_to_clipboard(key.code == KeyCode::Char('x'));
+                    return Ok(());
+                }
+                KeyCode::Char('v') if self.focus == Focus::Editor && self.workspace.has_files() => {
+                    self.paste_from_clipboard()?;
+                    return Ok(());
+                }
+                KeyCode::Char(' ')
+                    if self.focus == Focus::Editor
+                        && self.workspace.has_files()
+                        && self.mode() == EditorMode::Insert =>
+                {
+                    self.complete_path()?;
+                    return Ok(());
+                }
+                KeyCode::Char('s') => {
+                    self.save_unless_read_only();
+                    self.sync_display();
+                    self.render();
+                    return Ok(());
+                }
+                KeyCode::Char('t') => {
+                    self.toggle_sidebar();
+                    self.sync_display();
+                    self.render();
+                    return Ok(());
+                }
+                KeyCode::Char('o') => {
+                    self.handle_open_file();
+                    return Ok(());
+                }
+                KeyCode::Char('p') => {
+                    self.handle_command_palette();
+                    return Ok(());
+                }
+                KeyCode::Char('l') => {
+                    self.full_redraw()?;
+                    return Ok(());
+                }
+                KeyCode::Char('z') => {
+                    self.suspend()?;
+                    return Ok(());
+                }
+                KeyCode::Char('w') => {
+                    self.handle_close_tab();
+                    return Ok(());
+                }
+                KeyCode::Char('r') => {
+                    if self.focus == Focus::Editor
+                        && self.mode() == EditorMode::Normal
+                        && !self.active_read_only()
+                    {
+                        self.undo_redo(false);
+                    }
+                    return Ok(());
+                }
+                KeyCode::Char('h') if self.focus == Focus::Editor && self.workspace.has_files() => {
+                    self.start_replace();
+                    return Ok(());
+                }
+                // Ctrl+Shift+F, where the terminal tells it from Ctrl+F
+                KeyCode::Char('F') => {
+                    self.open_project_search();
+                    return Ok(());
+                }
+                KeyCode::Char('f') if key.modifiers.contains(KeyModifiers::SHIFT) => {
+                    self.open_project_search();
+                    return Ok(());
+                }
+                KeyCode::Char('f') => {
+                    self.start_search();
+                    return Ok(());
+                }
+                KeyCode::Up | KeyCode::Down
+                    if self.focus == Focus::Editor && self.workspace.has_files() =>
+                {
+                    self.jump_paragraph(key.code == KeyCode::Down, 1)?;
+                    return Ok(());
+                }
+                // Ctrl+PageDown/PageUp reach the editor even
+                // where Ctrl+Tab arrives as a plain Tab
+                KeyCode::Tab | KeyCode::BackTab | KeyCode::PageDown | KeyCode::PageUp => {
+                    self.handle_tab_switch(key)?;
+                    return Ok(());
+                }
+                _ => {}
+            }
+        }
+
+        if key.code == KeyCode::F(12) {
+            self.perf_hud = !self.perf_hud;
+            return Ok(());
+        }
+
+        if key.code == KeyCode::F(8) && self.workspace.has_files() {
+            let forward = !key.modifiers.contains(KeyModifiers::SHIFT);
+            self.goto_diagnostic(forward)?;
+            return Ok(());
+        }
+
+        if self.show_welcome && self.focus != Focus::Sidebar {
+            return Ok(());
+        }
+
+        // Focus-specific handling
+        match self.focus {
+            Focus::Sidebar => {
+                self.handle_sidebar_input(key)?;
+            }
+            Focus::Editor => {
+                if !self.workspace.has_files() {
+                    return Ok(());
+                }
+                match self.mode() {
+                    EditorMode::Normal => {
+                        self.handle_normal_mode(key.code)?;
+                    }
+                    EditorMode::Insert => {
+                        self.handle_insert_mode(key.code)?;
+                    }
+                    EditorMode::Visual => {
+                        self.handle_visual_mode(key.code);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Run what a leader or Alt binding stands for
+    fn run_binding(&mut self, binding: Binding) -> io::Result<()> {
+        match binding {
+            Binding::Command(command) => self.execute_command(command),
+            Binding::Macro(index) => self.play_macro(index),
+        }
+    }
 
-            // Draw search bar on top of status bar when in search mode
-            if self.search_mode {
-                self.render_search_bar().ok();
+    /// Play the keys and commands of a `[macros]` entry as if typed. A
+    /// macro bound to a key another one presses is skipped there, so none
+    /// can call itself forever.
+    fn play_macro(&mut self, index: usize) -> io::Result<()> {
+        let Some((name, steps)) = self.keymap.macro_steps(index) else {
+            return Ok(());
+        };
+        let (name, steps) = (name.to_string(), steps.to_vec());
+        if self.playing_macro {
+            log::warning!("macro", "'{}' chamada de dentro de outra macro", name);
+            return Ok(());
+        }
+        log::info!("macro", "'{}': {} passo(s)", name, steps.len());
+        self.playing_macro = true;
+        let mut played = Ok(());
+        for step in steps {
+            if self.quit || played.is_err() {
+                break;
             }
+            played = match step {
+                Step::Key(key) => match self.typed_char(&Event::Key(key)) {
+                    Some(c) => {
+                        self.insert_typed(&c.to_string());
+                        Ok(())
+                    }
+                    None => self.handle_key(key),
+                },
+                Step::Command(command) => self.execute_command(command),
+            };
         }
+        self.playing_macro = false;
+        played
+    }
+
+    fn enter_terminal() -> io::Result<()> {
+        execute!(
+            io::stdout(),
+            terminal::EnterAlternateScreen,
+            event::EnableBracketedPaste,
+            event::EnableMouseCapture
+        )?;
+        terminal::enable_raw_mode()?;
+        if Self::keyboard_enhanced() {
+            execute!(
+                io::stdout(),
+                PushKeyboardEnhancementFlags(
+                    KeyboardEnhancementFlags::DISAMBIGUATE_ESCAPE_CODES
+                        | KeyboardEnhancementFlags::REPORT_EVENT_TYPES
+                )
+            )?;
+        }
+        Ok(())
+    }
 
+    fn leave_terminal() -> io::Result<()> {
+        if Self::keyboard_enhanced() {
+            execute!(io::stdout(), PopKeyboardEnhancementFlags)?;
+        }
         terminal::disable_raw_mode()?;
         execute!(
             io::stdout(),
+            event::DisableBracketedPaste,
+            event::DisableMouseCapture,
             cursor::Show,
             terminal::Clear(terminal::ClearType::All),
             terminal::LeaveAlternateScreen
-        )?;
+        )
+    }
+
+    /// Whether the terminal speaks the kitty keyboard protocol, which tells
+    /// Ctrl+Tab from Tab and Ctrl+Shift combinations apart. Asked once, in
+    /// raw mode; other terminals keep the legacy key codes.
+    fn keyboard_enhanced() -> bool {
+        static SUPPORTED: OnceLock<bool> = OnceLock::new();
+        *SUPPORTED.get_or_init(|| {
+            let supported = terminal::supports_keyboard_enhancement().unwrap_or(false);
+            log::info!(
+                "terminal",
+                "protocolo de teclado do kitty: {}",
+                if supported {
+                    "ativado"
+                } else {
+                    "indisponível"
+                }
+            );
+            supported
+        })
+    }
+
+    /// Hand the terminal back to the shell while `f` runs (suspend, external
+    /// commands), then take it over again and repaint from scratch.
+    fn with_terminal_released<T>(&mut self, f: impl FnOnce(&mut Self) -> T) -> io::Result<T> {
+        Self::leave_terminal()?;
+        let result = f(self);
+        Self::enter_terminal()?;
+        self.full_redraw()?;
+        Ok(result)
+    }
+
+    /// Run `f`, releasing the terminal first when `needed`: age asks for its
+    /// passphrase there
+    fn with_terminal_if<T>(
+        &mut self,
+        needed: bool,
+        f: impl FnOnce(&mut Self) -> T,
+    ) -> io::Result<T> {
+        if needed {
+            self.with_terminal_released(f)
+        } else {
+            Ok(f(self))
+        }
+    }
+
+    /// Clear the whole screen and paint every region again. Recovers from
+    /// output written over the editor by other programs or resize glitches.
+    fn full_redraw(&mut self) -> io::Result<()> {
+        let (columns, rows) = terminal::size()?;
+        self.display.set_columns(columns);
+        self.display.set_rows(rows);
+
+        self.display.invalidate();
+        self.sync_display();
+        self.render();
+        Ok(())
+    }
+
+    /// Apply a new terminal size: every piece of viewport state is clamped
+    /// against it on the next render, and the screen is repainted at once so
+    /// no stale cells from the old layout survive.
+    fn handle_resize(&mut self, columns: u16, rows: u16) -> io::Result<()> {
+        self.display.set_columns(columns);
+        self.display.set_rows(rows);
+        if let Some(sidebar) = &mut self.sidebar {
+            sidebar.fit_width(columns);
+        }
+        self.full_redraw()
+    }
+
+    /// Stop the process like a shell job (Ctrl+Z); `fg` resumes it.
+    #[cfg(unix)]
+    fn suspend(&mut self) -> io::Result<()> {
+        self.with_terminal_released(|_| {
+            // SAFETY: raising a signal on our own process has no memory-safety
+            // preconditions; execution continues here after SIGCONT.
+            unsafe {
+                libc::raise(libc::SIGTSTP);
+            }
+        })
+    }
 
+    #[cfg(not(unix))]
+    fn suspend(&mut self) -> io::Result<()> {
         Ok(())
     }
 
@@ -221,258 +875,4205 @@ impl Editor {
             .as_ref()
             .map(|s| s.sidebar_offset())
             .unwrap_or(0);
+        // Double-width rows span the whole terminal, so no sidebar beside them
+        let sidebar_w = if self.presenting.is_some() {
+            0
+        } else {
+            sidebar_w
+        };
         self.display.set_sidebar_width(sidebar_w);
         self.display.set_welcome(self.show_welcome);
 
+        self.display.set_rulers(vec![]);
+        self.display.set_side_panel(None);
         if let Some(buf) = self.workspace.active() {
-            self.display.set_file_matrix(buf.file_matrix.clone());
             self.display.set_filename(buf.filename.clone());
-            self.display.set_modified(buf.modified);
-            self.display.set_initial_row(buf.initial_row);
-            self.display.initial_column = buf.initial_column;
+            self.display.set_syntax_ext(buf.syntax_ext());
+            self.display.set_read_only(buf.read_only);
+            let line_ending = if buf.has_mixed_endings() {
+                "LF+CRLF"
+            } else {
+                buf.line_ending.as_str()
+            };
+            self.display.set_encoding_info(line_ending, buf.has_bom);
+            if buf.syntax_ext() == "gitcommit" {
+                // Subject up to 50 columns, body up to 72
+                self.display.set_rulers(vec![50, 72]);
+                let panel = commit_panel(&buf.file_matrix, self.display.text_width());
+                self.display.set_side_panel(panel);
+            }
         }
 
         self.display.set_tab_names(self.workspace.tab_names());
-        self.display.set_mode(if self.mode == EditorMode::Insert {
-            "INSERT"
-        } else {
-            "NORMAL"
+        self.display.set_mode(match self.mode() {
+            EditorMode::Normal => "NORMAL",
+            EditorMode::Insert => "INSERT",
+            EditorMode::Visual => "VISUAL",
         });
         self.display
             .set_show_cursor(self.focus == Focus::Editor && self.workspace.has_files());
     }
 
     fn render(&mut self) {
+        self.refresh_blame();
+        self.update_view();
+        self.display
+            .set_sidebar_focused(self.focus == Focus::Sidebar);
+        let lines = self.display.output_panel_lines();
+        self.display.set_output_panel(
+            self.task_output
+                .as_ref()
+                .filter(|_| self.show_output && self.presenting.is_none())
+                .map(|output| (output.title(), output.visible(lines))),
+        );
+        self.display.set_presentation(
+            self.presenting
+                .map(|(start, end)| (self.present_style, start, end)),
+        );
+        self.display
+            .set_overlay(self.prompt.as_ref().map(|(prompt, _)| prompt.overlay()));
+        self.display
+            .set_hud(self.perf_hud.then(|| self.perf_overlay()));
+        let popup = if self.pending_leader {
+            Some(self.leader_overlay())
+        } else {
+            self.hover_overlay().or_else(|| self.sidebar_tip())
+        };
+        self.display.set_popup(popup);
+        // Occurrences a rename would change, while its name is typed
+        let highlights = match &self.prompt {
+            Some((_, PromptAction::RenameSymbol(word, found))) => found
+                .iter()
+                .map(|&(row, col)| (row, col, col + word.len()))
+                .collect(),
+            _ => self.tag_highlights(),
+        };
+        self.display.set_highlights(highlights);
+        let peers = match (&self.collab, self.workspace.active()) {
+            (Some(session), Some(buf)) if session.buffer == buf.filename => session
+                .cursors
+                .iter()
+                .map(|&(_, row, col)| (row, col))
+                .collect(),
+            _ => vec![],
+        };
+        self.display.set_peer_cursors(peers);
         let search_q = if !self.search_query.is_empty() {
             Some(self.search_query.as_str())
         } else {
             None
         };
-        self.display.show_display(self.sidebar.as_mut(), search_q);
+        let lines = self
+            .workspace
+            .active()
+            .map(|buf| buf.file_matrix.as_slice())
+            .unwrap_or_default();
+        let started = Instant::now();
+        let sidebar = match self.presenting {
+            Some(_) => None,
+            None => self.sidebar.as_mut(),
+        };
+        self.display.show_display(lines, sidebar, search_q);
+        self.frame_time = started.elapsed();
     }
 
-    fn update_status(&mut self) {
-        if !self.workspace.has_files() {
-            return;
-        }
+    /// Stats about the last frame and the open buffers
+    fn perf_overlay(&self) -> Overlay {
+        let lines: usize = self
+            .workspace
+            .buffers
+            .iter()
+            .map(|buf| buf.file_matrix.len())
+            .sum();
+        let memory: usize = self
+            .workspace
+            .buffers
+            .iter()
+            .map(|buf| buf.memory_estimate())
+            .sum();
+        Overlay::dialog(
+            "Desempenho",
+            vec![
+                format!(
+                    "Frame:     {:.2} ms",
+                    self.frame_time.as_secs_f64() * 1000.0
+                ),
+                format!("Eventos:   {} no último frame", self.frame_events),
+                format!(
+                    "Buffers:   {} ({} linhas)",
+                    self.workspace.buffers.len(),
+                    lines
+                ),
+                format!("Memória:   ~{}", format_size(memory as u64)),
+            ],
+        )
+        .at(Placement::At(u16::MAX, 1))
+    }
 
-        let (_col_pos, row_pos) = cursor::position().unwrap_or((0, 0));
-        let absolute_row = self.display.get_absolute_row(row_pos);
-        let cursor_col = self.display.get_cursor_position();
+    /// What the leader key can be followed by, in the bottom-right corner
+    fn leader_overlay(&self) -> Overlay {
+        Overlay::dialog(
+            &format!("Líder ({})", self.keymap.leader_name()),
+            self.keymap.leader_rows(),
+        )
+        .at(Placement::At(u16::MAX, u16::MAX))
+    }
 
-        if let Some(buf) = self.workspace.active() {
-            self.display.set_modified(buf.modified);
+    /// Popup for the hovered line or cell: its diagnostics and, for lines,
+    /// the blame once it arrives. Nothing to say closes it.
+    fn hover_overlay(&self) -> Option<Overlay> {
+        let hover = self.hover.as_ref()?;
+        let buf = self.workspace.active()?;
+        let mut lines: Vec<String> = buf
+            .current_diagnostics()
+            .filter(|d| match hover.col {
+                Some(col) => d.covers(hover.row, col),
+                None => d.start.0 == hover.row,
+            })
+            .map(|d| d.summary())
+            .collect();
+        if hover.col.is_none() {
+            let blame = if buf.modified {
+                Some(String::from("git blame: salve o arquivo para ver"))
+            } else {
+                match &buf.blame {
+                    Some(blame) => blame
+                        .get(hover.row)
+                        .map(|line| format!("git blame: {}", line.summary())),
+                    None => Some(String::from("git blame: carregando…")),
+                }
+            };
+            lines.extend(blame);
         }
-        self.display
-            .set_cursor_info(absolute_row + 1, cursor_col + 1);
-        self.display.update_file_size();
+        if lines.is_empty() {
+            return None;
+        }
+        let (col, row) = hover.anchor;
+        Some(
+            Overlay::dialog(&format!("Linha {}", hover.row + 1), lines)
+                .at(Placement::At(col, row + 1)),
+        )
     }
 
-    fn position_cursor_at_start(&self) {
-        let sidebar_w = self
+    /// Tooltip with the name of the compact sidebar's entry under the mouse,
+    /// or of the selected one while the sidebar has focus
+    fn sidebar_tip(&mut self) -> Option<Overlay> {
+        let focused = self.focus == Focus::Sidebar;
+        let sidebar = self
             .sidebar
-            .as_ref()
-            .map(|s| s.sidebar_offset())
-            .unwrap_or(0);
-        let offset = self.display.offset_lines_number() as u16;
-        let col = sidebar_w + offset;
-        let row = self.display.content_top_row();
-        execute!(io::stdout(), cursor::MoveTo(col, row)).unwrap();
+            .as_mut()
+            .filter(|sidebar| sidebar.visible && sidebar.compact)?;
+        let index = sidebar
+            .hovered
+            .or(focused.then_some(sidebar.selected_index))?;
+        let row = sidebar.list_top() as usize + index.checked_sub(sidebar.scroll_offset)?;
+        let entry = sidebar.flat_entries().get(index)?.clone();
+        let relative = entry
+            .path
+            .strip_prefix(&sidebar.root_path)
+            .unwrap_or(&entry.path);
+        Some(
+            Overlay::dialog(&entry.name, vec![relative.display().to_string()])
+                .at(Placement::At(sidebar.sidebar_offset(), row as u16)),
+        )
     }
 
-    fn toggle_sidebar(&mut self) {
-        if let Some(sidebar) = &mut self.sidebar {
-            if sidebar.visible && self.focus == Focus::Editor {
-                // Sidebar already open — just switch focus to it
-                self.focus = Focus::Sidebar;
-            } else if sidebar.visible && self.focus == Focus::Sidebar {
-                // Close sidebar
-                sidebar.toggle_visible();
-                self.focus = Focus::Editor;
-            } else {
-                // Open sidebar
-                sidebar.toggle_visible();
-                self.focus = Focus::Sidebar;
-            }
-        }
+    /// Open the hover popup for the cursor line
+    fn hover_cursor_line(&mut self) {
+        let Some(buf) = self.workspace.active() else {
+            return;
+        };
+        self.hover = Some(Hover {
+            row: buf.view.cursor_row,
+            col: None,
+            anchor: self.display.cursor_screen_position(),
+        });
     }
 
-    // --- Quit ---
-    fn handle_quit(&mut self) -> io::Result<bool> {
+    /// Clamp the active buffer's cursor and scroll offsets to the current
+    /// screen size and hand them to the display.
+    fn update_view(&mut self) {
+        let options = self.options();
+        self.display.set_options(options);
+        let content_rows = self.display.content_rows().max(1);
+        let text_width = self.display.text_width().max(1);
+        // Lines kept in view around the cursor, at most half the screen
+        let margin = options.scrolloff.min(content_rows.saturating_sub(1) / 2);
+
+        self.workspace.keep_modified_previews();
+        self.display.set_annotation(None);
+        self.display.set_selection(None);
+        self.display.set_search_in_selection(false);
+        self.display.set_diagnostics(vec![], false);
+        self.display.set_breadcrumb(None);
+        if let Some(buf) = self.workspace.active_mut() {
+            self.display.set_file_size(buf.file_matrix.len());
+            buf.clamp_cursor();
+
+            // Never scroll past the last screenful of lines
+            let max_initial_row = buf.file_matrix.len().saturating_sub(content_rows);
+            buf.view.initial_row = buf.view.initial_row.min(max_initial_row);
+
+            // Keep the cursor inside the viewport, `margin` lines from its
+            // edges
+            if buf.view.cursor_row < buf.view.initial_row + margin {
+                buf.view.initial_row = buf.view.cursor_row.saturating_sub(margin);
+            } else if buf.view.cursor_row + margin >= buf.view.initial_row + content_rows {
+                buf.view.initial_row =
+                    (buf.view.cursor_row + margin + 1 - content_rows).min(max_initial_row);
+            }
+            if options.wrap {
+                // Lines above the cursor's take more than one row when long
+                let rows = |row: usize| self.display.wrapped_rows(buf.file_matrix[row].len());
+                let cursor_part = buf.view.cursor_col / text_width;
+                let mut needed: usize = (buf.view.initial_row..buf.view.cursor_row)
+                    .map(rows)
+                    .sum::<usize>()
+                    + cursor_part
+                    + 1
+                    + margin;
+                while needed > content_rows && buf.view.initial_row < buf.view.cursor_row {
+                    needed -= rows(buf.view.initial_row);
+                    buf.view.initial_row += 1;
+                }
+                buf.view.initial_column = 0;
+            } else if buf.view.cursor_col < buf.view.initial_column {
+                buf.view.initial_column = buf.view.cursor_col;
+            } else if buf.view.cursor_col >= buf.view.initial_column + text_width {
+                buf.view.initial_column = buf.view.cursor_col + 1 - text_width;
+            }
+
+            self.display.initial_row = buf.view.initial_row;
+            self.display.initial_column = buf.view.initial_column;
+            self.display.set_modified(buf.modified);
+            self.display
+                .set_cursor_info(buf.view.cursor_row, buf.view.cursor_col);
+            let cursor = (buf.view.cursor_row, buf.view.cursor_col);
+            self.display.set_breadcrumb(breadcrumb::at(
+                &buf.syntax_ext(),
+                &buf.file_matrix,
+                cursor,
+            ));
+            self.display.set_selection(buf.view.selection());
+            self.display
+                .set_search_in_selection(buf.view.scope.is_some());
+
+            // Line numbers of the blame only match the text until it's edited
+            if let Some(lines) = buf.blame.as_ref().filter(|_| self.blame && !buf.modified) {
+                let row = buf.view.cursor_row;
+                self.display
+                    .set_annotation(lines.get(row).map(|line| (row, line.summary())));
+            }
+            let mut diagnostics: Vec<Diagnostic> = buf.current_diagnostics().cloned().collect();
+            diagnostics.sort_by_key(|d| (d.start.0, d.severity));
+            self.display
+                .set_diagnostics(diagnostics, self.config.inline_diagnostics());
+        }
+    }
+
+    /// Mode of the active buffer (Normal when no file is open)
+    fn mode(&self) -> EditorMode {
+        self.workspace
+            .active()
+            .map(|buf| buf.view.mode)
+            .unwrap_or_default()
+    }
+
+    fn set_mode(&mut self, mode: EditorMode) {
+        if let Some(buf) = self.workspace.active_mut() {
+            buf.view.mode = mode;
+            if mode != EditorMode::Visual {
+                buf.view.anchor = None;
+                buf.view.scope = None;
+            }
+        }
+        self.sync_display();
+    }
+
+    fn toggle_sidebar(&mut self) {
+        if let Some(sidebar) = &mut self.sidebar {
+            if sidebar.visible && self.focus == Focus::Editor {
+                // Sidebar already open — just switch focus to it
+                self.focus = Focus::Sidebar;
+            } else if sidebar.visible && self.focus == Focus::Sidebar {
+                // Close sidebar
+                sidebar.toggle_visible();
+                self.focus = Focus::Editor;
+            } else {
+                // Open sidebar
+                sidebar.toggle_visible();
+                self.focus = Focus::Sidebar;
+            }
+        }
+    }
+
+    // --- Quit ---
+    fn handle_quit(&mut self) {
+        let empty_commit = self.workspace.buffers.iter().any(|buf| {
+            buf.syntax_ext() == "gitcommit" && git::commit_message_is_empty(&buf.file_matrix)
+        });
         if self.workspace.is_any_modified() {
-            match self.confirm_quit()? {
-                QuitAction::Save => {
-                    // Save all modified
-                    for buf in &mut self.workspace.buffers {
+            self.open_prompt(unsaved_changes_prompt("Sair"), PromptAction::Quit);
+        } else if empty_commit {
+            self.open_prompt(
+                Prompt::confirm(
+                    "Mensagem de commit vazia",
+                    vec![
+                        String::from("O git vai cancelar o commit."),
+                        String::from("(s)air mesmo assim, (c)ontinuar editando"),
+                    ],
+                    &['s', 'c'],
+                )
+                .warning(),
+                PromptAction::Quit,
+            );
+        } else {
+            self.quit = true;
+        }
+    }
+
+    // --- Prompts ---
+    fn open_prompt(&mut self, prompt: Prompt, action: PromptAction) {
+        self.prompt = Some((prompt, action));
+    }
+
+    /// Feed a key to the open prompt and act on its answer once it has one
+    fn handle_prompt_key(&mut self, key: KeyEvent) -> io::Result<()> {
+        let (prompt, action) = match &mut self.prompt {
+            Some(open) => open,
+            None => return Ok(()),
+        };
+        let event = prompt.handle_key(key);
+        // Matches are highlighted while the query is typed
+        if let PromptAction::Search | PromptAction::FindReplace = action {
+            self.search_query = prompt.text().to_string();
+        }
+        if let PromptEvent::Pending = event {
+            return Ok(());
+        }
+
+        if let Some((_, action)) = self.prompt.take() {
+            self.finish_prompt(action, event)?;
+        }
+        if self.prompt.is_none() && !self.open_queue.is_empty() {
+            let queued = std::mem::take(&mut self.open_queue);
+            self.open_all(queued)?;
+        }
+        self.sync_display();
+        Ok(())
+    }
+
+    fn finish_prompt(&mut self, action: PromptAction, event: PromptEvent) -> io::Result<()> {
+        match (action, event) {
+            (PromptAction::Search, PromptEvent::Text(_)) => {
+                // Keep search_query for highlighting
+                self.navigate_to_next_match()?;
+            }
+            (PromptAction::Search, _) => self.cancel_search(),
+            (PromptAction::FindReplace, PromptEvent::Text(query)) if !query.is_empty() => {
+                self.open_prompt(
+                    Prompt::input(&format!("Substituir '{}' por", query))
+                        .at(Placement::At(u16::MAX, 1)),
+                    PromptAction::FindReplaceWith(query),
+                );
+            }
+            (PromptAction::FindReplaceWith(query), PromptEvent::Text(new)) => {
+                self.replace_next(query, new, 0, None)?
+            }
+            (PromptAction::FindReplace | PromptAction::FindReplaceWith(_), _) => {
+                self.cancel_search()
+            }
+            (PromptAction::FindReplaceNext(query, new, count), event) => {
+                self.answer_replace(query, new, count, event)?
+            }
+            (PromptAction::OpenLarge(path, size), event) => {
+                let choice = match event {
+                    PromptEvent::Choice(c) => c,
+                    _ => 'c',
+                };
+                self.open_confirmed(&path, size, choice)?;
+            }
+            (_, PromptEvent::Cancelled) => {}
+            (PromptAction::Quit, PromptEvent::Choice('s')) => {
+                let needs_terminal = self
+                    .workspace
+                    .buffers
+                    .iter()
+                    .any(|buf| buf.modified && buf.fs.needs_terminal());
+                let failed = self.with_terminal_if(needs_terminal, |editor| {
+                    let mut failed = None;
+                    for buf in &mut editor.workspace.buffers {
                         if buf.modified {
-                            buf.save()?;
+                            if let Err(e) = buf.save() {
+                                failed = Some((buf.filename.clone(), e));
+                            }
                         }
                     }
-                    return Ok(true);
+                    failed
+                })?;
+                match failed {
+                    Some((path, e)) => {
+                        self.save_failed = true;
+                        self.report_save_error(&path, &e)
+                    }
+                    None => {
+                        self.saved = true;
+                        self.quit = true;
+                    }
+                }
+            }
+            (PromptAction::Quit, PromptEvent::Choice('n')) => self.quit = true,
+            // A failed save keeps the tab open, with the error shown
+            (PromptAction::CloseTab, PromptEvent::Choice('s')) if self.save_active() => {
+                self.close_active_tab()
+            }
+            (PromptAction::CloseTab, PromptEvent::Choice('n')) => self.close_active_tab(),
+            (PromptAction::OpenFile, PromptEvent::Text(path)) => {
+                let path = expand::expand(path.trim());
+                if expand::has_glob(&path) && !Path::new(&path).exists() {
+                    let found: Vec<String> = expand::glob(&path)
+                        .iter()
+                        .map(|file| file.to_string_lossy().to_string())
+                        .collect();
+                    if found.is_empty() {
+                        self.display
+                            .set_message(format!("Nenhum arquivo casa com '{}'", path));
+                    }
+                    self.open_matches(found)?;
+                } else if !path.is_empty()
+                    && (Path::new(&path).exists() || remote::is_remote(&path))
+                {
+                    self.open_in_editor(&path)?;
+                }
+            }
+            (PromptAction::OpenMany(files), PromptEvent::Choice('s')) => self.open_all(files)?,
+            (PromptAction::SudoSave, PromptEvent::Choice('s')) => self.sudo_save()?,
+            (PromptAction::Trash(path), PromptEvent::Choice('s')) => self.move_to_trash(&path),
+            (PromptAction::ProjectSearch, PromptEvent::Text(query)) if !query.is_empty() => {
+                self.start_project_search(query)
+            }
+            (
+                PromptAction::Locations(targets) | PromptAction::SearchResults(targets),
+                PromptEvent::Picked(index),
+            ) => {
+                let (path, line, col) = &targets[index];
+                self.open_at(path, *line, *col)?;
+            }
+            (PromptAction::ReplaceQuery, PromptEvent::Text(query)) if !query.is_empty() => {
+                self.open_prompt(
+                    Prompt::input(&format!("Substituir '{}' por", query)),
+                    PromptAction::ReplaceWith(query),
+                );
+            }
+            (PromptAction::ReplaceWith(query), PromptEvent::Text(replacement)) => {
+                self.start_project_replace(query, replacement)
+            }
+            (PromptAction::RenameSymbol(word, found), PromptEvent::Text(name)) => {
+                self.rename_symbol(&word, &found, name.trim())
+            }
+            (PromptAction::Transform, PromptEvent::Picked(index)) => {
+                self.transform_selection(&transform::TRANSFORMS[index])
+            }
+            (PromptAction::Decrypt(path), PromptEvent::Text(passphrase)) => {
+                let loaded =
+                    BufferFile::open_in(CryptFs::gpg(&path, passphrase), &path).map(|buf| {
+                        self.workspace.open_buffer(buf);
+                    });
+                self.finish_open(&path, loaded)?;
+            }
+            (PromptAction::DroppedPath(path, _), PromptEvent::Choice('s')) => {
+                self.open_in_editor(&path)?;
+            }
+            (PromptAction::DroppedPath(_, text), PromptEvent::Choice(_)) => {
+                self.paste_text(&text)?;
+            }
+            (PromptAction::Reload, PromptEvent::Choice('s')) => self.reload_active(),
+            (PromptAction::Convert, PromptEvent::Choice(c)) => {
+                let command = match c {
+                    'l' => Command::LineEndingLf,
+                    'c' => Command::LineEndingCrlf,
+                    'a' => Command::AddBom,
+                    _ => Command::RemoveBom,
+                };
+                self.execute_command(command)?;
+            }
+            (PromptAction::GotoLine, PromptEvent::Text(input)) => self.goto_line(&input)?,
+            (PromptAction::CollabHost, PromptEvent::Text(port)) => self.collab_host(port.trim()),
+            (PromptAction::CollabJoin, PromptEvent::Text(address)) => {
+                self.collab_join(address.trim())
+            }
+            (PromptAction::CollabShared(join), PromptEvent::Choice('c')) => {
+                let message = match clipboard::copy(&join) {
+                    Ok(_) => format!("'{}' copiado para a área de transferência", join),
+                    Err(e) => format!("Erro ao copiar: {}", e),
+                };
+                self.display.set_message(message);
+            }
+            (PromptAction::NewFile(dir), PromptEvent::Text(name)) => {
+                self.create_file(&dir, name.trim())?
+            }
+            (PromptAction::SetLanguage, PromptEvent::Picked(index)) => {
+                let (name, ext) = syntax::LANGUAGES[index];
+                if let Some(buf) = self.workspace.active_mut() {
+                    buf.language = Some(ext.to_string());
+                }
+                self.display.set_message(format!("Linguagem: {}", name));
+            }
+            (PromptAction::Todos(targets), PromptEvent::Picked(index)) => {
+                let (path, line) = &targets[index];
+                self.open_in_editor(path)?;
+                if self
+                    .workspace
+                    .active()
+                    .is_some_and(|buf| &buf.filename == path)
+                {
+                    self.jump_to_position(*line, 0)?;
+                }
+            }
+            (PromptAction::CompletePath(names, typed), PromptEvent::Picked(index)) => {
+                self.insert_completion(&names[index], typed)?;
+            }
+            (PromptAction::PasteHistory, PromptEvent::Picked(index)) => {
+                if let Some(text) = self.kill_ring.entries().get(index).cloned() {
+                    let private = self.kill_ring.is_private(&text);
+                    self.kill_ring.push(text.clone(), private);
+                    self.paste_after_cursor(&text)?;
+                }
+            }
+            (PromptAction::Snippet(texts), PromptEvent::Picked(index)) => {
+                let filename = self
+                    .workspace
+                    .active()
+                    .map(|buf| buf.filename.clone())
+                    .unwrap_or_default();
+                let text = snippets::expand(&texts[index], &filename);
+                self.insert_completion(&text, 0)?;
+            }
+            (PromptAction::Marks(names), PromptEvent::Picked(index)) => {
+                self.jump_to_mark(names[index])?;
+            }
+            (PromptAction::OldFiles(paths), PromptEvent::Picked(index)) => {
+                self.open_in_editor(&paths[index])?;
+            }
+            (PromptAction::SidebarJump(dirs), PromptEvent::Picked(index)) => {
+                self.jump_to_dir(&dirs[index]);
+            }
+            (PromptAction::RunTask(found), PromptEvent::Picked(index)) => {
+                self.run_task(&found[index]);
+            }
+            (PromptAction::RunCommand, PromptEvent::Picked(index)) => {
+                self.execute_command(commands::COMMANDS[index].command)?;
+            }
+            // On/off options flip, the others ask for their value
+            (PromptAction::Options, PromptEvent::Picked(index)) => {
+                let (name, _) = options::OPTIONS[index];
+                if Options::is_flag(name) {
+                    self.set_runtime_option(name, None, true, false);
+                } else {
+                    let value = self.options().get(name).unwrap_or_default();
+                    self.open_prompt(
+                        Prompt::input(name).with_input(&value),
+                        PromptAction::SetOption(name),
+                    );
+                }
+            }
+            (PromptAction::SetOption(name), PromptEvent::Text(value)) => {
+                self.set_runtime_option(name, Some(value.trim()), false, false)
+            }
+            (PromptAction::Ex(selection), PromptEvent::Text(line)) => {
+                self.run_ex(&line, selection)?
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Open the `:` command line, on the rows of the selection in Visual mode
+    fn open_ex(&mut self) {
+        let selection = self
+            .workspace
+            .active()
+            .filter(|_| self.mode() == EditorMode::Visual)
+            .and_then(|buf| buf.view.selection())
+            .map(|(start, end)| (start.0, end.0));
+        let mut prompt = Prompt::input(":")
+            .with_history(self.ex_history.clone())
+            .with_completion(ex::complete);
+        if selection.is_some() {
+            prompt = prompt.with_input("'<,'>");
+            self.set_mode(EditorMode::Normal);
+        }
+        self.open_prompt(prompt, PromptAction::Ex(selection));
+    }
+
+    /// Run a line typed on the `:` command line
+    fn run_ex(&mut self, line: &str, selection: Option<(usize, usize)>) -> io::Result<()> {
+        let line = line.trim();
+        if !line.is_empty() {
+            self.ex_history.retain(|entry| entry != line);
+            self.ex_history.push(line.to_string());
+            if self.ex_history.len() > ex::HISTORY_SIZE {
+                self.ex_history.remove(0);
+            }
+        }
+        let ex = match ex::parse(line) {
+            Ok(ex) => ex,
+            Err(message) => {
+                if !message.is_empty() {
+                    self.display.set_message(message);
+                }
+                return Ok(());
+            }
+        };
+        log::info!("ex", ":{}", line);
+        let needs_file = !matches!(
+            ex,
+            Ex::Quit { .. } | Ex::Edit(_) | Ex::Set { .. } | Ex::Command(_)
+        );
+        if needs_file && !self.workspace.has_files() {
+            self.display
+                .set_message(String::from("Nenhum arquivo aberto"));
+            return Ok(());
+        }
+        match ex {
+            Ex::Write => self.save_unless_read_only(),
+            Ex::Quit { force: false } => self.handle_quit(),
+            Ex::Quit { force: true } => self.quit = true,
+            // A failed save keeps the editor open with the error shown
+            Ex::WriteQuit => {
+                if self.active_read_only() || self.save_active() {
+                    self.handle_quit();
+                }
+            }
+            Ex::Edit(path) => self.open_in_editor(&expand::expand(&path))?,
+            Ex::Goto(line) => self.jump_to_position(line - 1, 0)?,
+            Ex::Command(command) => self.execute_command(command)?,
+            Ex::Substitute(substitute) => self.substitute(&substitute, selection),
+            Ex::Set {
+                option,
+                value,
+                local,
+            } => self.set_option(&option, value.as_deref(), local),
+        }
+        Ok(())
+    }
+
+    /// `:s`: replace the text in the lines of its range, as one undo step
+    fn substitute(&mut self, substitute: &Substitute, selection: Option<(usize, usize)>) {
+        if self.active_read_only() {
+            return;
+        }
+        let Some(buf) = self.workspace.active_mut() else {
+            return;
+        };
+        let last_row = buf.file_matrix.len().saturating_sub(1);
+        let (first, last) = match substitute.range {
+            Range::Line => (buf.view.cursor_row, buf.view.cursor_row),
+            Range::All => (0, last_row),
+            Range::Lines(first, last) => (first - 1, last.saturating_sub(1).min(last_row)),
+            Range::Selection => match selection {
+                Some(rows) => rows,
+                None => {
+                    self.display
+                        .set_message(String::from("Nenhuma seleção para '<,'>"));
+                    return;
+                }
+            },
+        };
+        if first > last {
+            self.display
+                .set_message(String::from("Intervalo de linhas inválido"));
+            return;
+        }
+        let changed: Vec<(usize, Vec<char>)> = (first..=last)
+            .filter_map(|row| {
+                substitute
+                    .apply(&buf.file_matrix[row])
+                    .map(|line| (row, line))
+            })
+            .collect();
+        let (Some(&(top, _)), Some(&(bottom, _))) = (changed.first(), changed.last()) else {
+            let old: String = substitute.old.iter().collect();
+            self.display
+                .set_message(format!("Nenhuma ocorrência de '{}'", old));
+            return;
+        };
+        let mut lines = buf.file_matrix[top..=bottom].to_vec();
+        for (row, line) in &changed {
+            lines[row - top] = line.clone();
+        }
+        buf.replace_block(top, lines.len(), lines);
+        buf.view.cursor_row = bottom;
+        buf.view.cursor_col = 0;
+        buf.clamp_cursor();
+        self.display
+            .set_message(format!("Texto substituído em {} linha(s)", changed.len()));
+    }
+
+    /// `:set`: show an option, or change it for this session (for the
+    /// active buffer alone when `local`)
+    fn set_option(&mut self, option: &str, value: Option<&str>, local: bool) {
+        let (option, flip) = match option.strip_suffix('!') {
+            Some(option) => (option, true),
+            None => (option, false),
+        };
+        let runtime = option.strip_prefix("editor.").unwrap_or(option);
+        if Options::is_option(runtime) {
+            self.set_runtime_option(runtime, value, flip, local);
+            return;
+        }
+        if local || flip {
+            self.display.set_message(format!(
+                "{} só pode ser definida com :set nome=valor",
+                option
+            ));
+            return;
+        }
+        let Some((section, key)) = ex::config_option(option) else {
+            self.display
+                .set_message(format!("Opção desconhecida: {}", option));
+            return;
+        };
+        let Some(value) = value else {
+            let current = self.config.get(&section, &key).unwrap_or("(padrão)");
+            self.display
+                .set_message(format!("{} = {}", option, current));
+            return;
+        };
+        self.config.set(&section, &key, value);
+        self.display
+            .set_warn_suspicious(self.config.warn_suspicious_chars());
+        self.keymap = Keymap::from_config(&self.config);
+        self.display.set_message(format!("{} = {}", option, value));
+    }
+
+    /// Show or change one of the runtime `options`, taking effect on the
+    /// next frame; `flip` turns an on/off one around
+    fn set_runtime_option(&mut self, name: &str, value: Option<&str>, flip: bool, local: bool) {
+        let current = self.options();
+        let value = match value {
+            Some(value) => value.to_string(),
+            None if flip && Options::is_flag(name) => {
+                (current.get(name).as_deref() != Some("true")).to_string()
+            }
+            None if flip => {
+                self.display
+                    .set_message(format!("{} não é uma opção liga/desliga", name));
+                return;
+            }
+            None => {
+                let overridden = self
+                    .workspace
+                    .active()
+                    .is_some_and(|buf| buf.local_options.iter().any(|(n, _)| n == name));
+                let scope = if overridden { " (neste buffer)" } else { "" };
+                self.display.set_message(format!(
+                    "{} = {}{}",
+                    name,
+                    current.get(name).unwrap_or_default(),
+                    scope
+                ));
+                return;
+            }
+        };
+        let set = if local {
+            match self.workspace.active_mut() {
+                Some(buf) => {
+                    let mut options = self.options.with(&buf.local_options);
+                    options.set(name, &value).map(|()| {
+                        buf.local_options.retain(|(n, _)| n != name);
+                        buf.local_options.push((name.to_string(), value.clone()));
+                    })
+                }
+                None => Err(String::from("Nenhum arquivo aberto")),
+            }
+        } else {
+            self.options.set(name, &value)
+        };
+        match set {
+            Ok(()) => {
+                let scope = if local { " (local)" } else { "" };
+                log::info!("opções", "{} = {}{}", name, value, scope);
+                self.display.set_message(format!("{} = {}", name, value));
+            }
+            Err(e) => self.display.set_message(e),
+        }
+    }
+
+    /// Options of the active buffer: the global ones with its overrides.
+    /// While Tab's width is the default one, the buffer's language picks it
+    /// (2 for YAML and JSON).
+    fn options(&self) -> Options {
+        let Some(buf) = self.workspace.active() else {
+            return self.options;
+        };
+        let mut options = self.options;
+        if options.tab_width == Options::default().tab_width {
+            if let Some(width) = syntax::indent_width(&buf.syntax_ext()) {
+                options.tab_width = width;
+            }
+        }
+        options.with(&buf.local_options)
+    }
+
+    /// Options picker: a row per option with its value
+    fn handle_options(&mut self) {
+        let current = self.options();
+        let rows = options::OPTIONS
+            .iter()
+            .map(|(name, description)| {
+                let value = current.get(name).unwrap_or_default();
+                format!("{:<13} {:<6} {}", name, value, description)
+            })
+            .collect();
+        self.open_prompt(Prompt::list("Opções", rows), PromptAction::Options);
+    }
+
+    /// Open the files a glob matched, asking first when there are many
+    fn open_matches(&mut self, files: Vec<String>) -> io::Result<()> {
+        if files.len() <= expand::MANY_FILES {
+            return self.open_all(files);
+        }
+        let mut lines: Vec<String> = files.iter().take(5).cloned().collect();
+        lines.push(format!("... e mais {}", files.len() - 5));
+        lines.push(String::from("Abrir todos? (s/n)"));
+        self.open_prompt(
+            Prompt::confirm(&format!("{} arquivos", files.len()), lines, &['s', 'n']),
+            PromptAction::OpenMany(files),
+        );
+        Ok(())
+    }
+
+    /// Open `files` in tabs, in order. One that asks how to open it (large
+    /// or binary) leaves the rest for after the answer.
+    fn open_all(&mut self, files: Vec<String>) -> io::Result<()> {
+        let mut files = files.into_iter();
+        while let Some(path) = files.next() {
+            self.open_in_editor(&path)?;
+            if self.prompt.is_some() {
+                self.open_queue = files.collect();
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Open `path` in a tab and move focus to the editor. Large or binary
+    /// files ask first whether to open normally, read-only or as hex.
+    fn open_in_editor(&mut self, path: &str) -> io::Result<()> {
+        let already_open = self.workspace.find_same_file(path);
+
+        let loaded = if let Some(index) = already_open {
+            let name = self.workspace.buffers[index].filename.clone();
+            if name != path {
+                self.display
+                    .set_message(format!("Mesmo arquivo que '{}', já aberto", name));
+            }
+            self.workspace.open_file(path).map(|_| ())
+        } else if let Some(cipher) = Cipher::for_path(path) {
+            if cipher == Cipher::Gpg {
+                let name = std::path::Path::new(path)
+                    .file_name()
+                    .map(|n| n.to_string_lossy().to_string())
+                    .unwrap_or_default();
+                self.open_prompt(
+                    Prompt::input(&format!("Senha de {}", name)).secret(),
+                    PromptAction::Decrypt(path.to_string()),
+                );
+                return Ok(());
+            }
+            self.with_terminal_released(|_| BufferFile::open_in(CryptFs::age(path), path))?
+                .map(|buf| {
+                    self.workspace.open_buffer(buf);
+                })
+        } else if archive::is_inside(path) {
+            // Read-only: the archive itself isn't rewritten
+            let opened = match BufferFile::probe(path) {
+                Ok((_, true)) => BufferFile::new_hex_view(path),
+                Ok(_) => BufferFile::new(path).map(|mut buf| {
+                    buf.read_only = true;
+                    buf
+                }),
+                Err(e) => Err(e),
+            };
+            opened.map(|buf| {
+                self.workspace.open_buffer(buf);
+                self.display.set_message(String::from(
+                    "Dentro de um pacote: somente leitura (extract-file extrai para editar)",
+                ));
+            })
+        } else if let Some(remote) = RemotePath::parse(path) {
+            // The copy blocks, so say what we're waiting on first
+            self.display
+                .set_message(format!("Conectando a {}…", remote.host));
+            self.render();
+            let loaded = BufferFile::new(path).map(|buf| {
+                self.workspace.open_buffer(buf);
+            });
+            self.display.set_message(String::new());
+            loaded
+        } else {
+            match BufferFile::probe(path) {
+                Ok((size, is_binary)) => {
+                    let is_large = size > self.config.large_file_threshold();
+                    if is_large || is_binary {
+                        let kind = match (is_large, is_binary) {
+                            (true, true) => "grande e binário",
+                            (true, false) => "grande",
+                            _ => "binário",
+                        };
+                        let title = format!("Arquivo {} ({})", kind, format_size(size));
+                        self.open_prompt(
+                            Prompt::confirm(
+                                &title,
+                                vec![String::from(
+                                    "(a)brir, somente (l)eitura, (h)ex, (c)ancelar",
+                                )],
+                                &['a', 'l', 'h', 'c'],
+                            ),
+                            PromptAction::OpenLarge(path.to_string(), size),
+                        );
+                        return Ok(());
+                    }
+                    self.load_file(path, size, false)
+                }
+                Err(e) => Err(e),
+            }
+        };
+        self.finish_open(path, loaded)
+    }
+
+    /// Save the active buffer (Ctrl+S, `:w`), unless it is read-only
+    fn save_unless_read_only(&mut self) {
+        if self.active_read_only() {
+            log::warning!("arquivo", "salvar ignorado: somente leitura");
+            self.display
+                .set_message(String::from("Buffer somente leitura — não salvo"));
+        } else {
+            self.save_active();
+        }
+    }
+
+    /// Save the active buffer, showing failures (e.g. a dropped SSH
+    /// connection) in the status bar. Returns whether it was saved.
+    fn save_active(&mut self) -> bool {
+        let needs_terminal = self
+            .workspace
+            .active()
+            .is_some_and(|buf| buf.fs.needs_terminal());
+        let saved = self
+            .with_terminal_if(needs_terminal, |editor| editor.workspace.save_active())
+            .and_then(|saved| saved);
+        match saved {
+            Ok(()) => {
+                self.saved = true;
+                self.follow_renames();
+                self.apply_replace();
+                self.reindex_active();
+                self.restart_follow();
+                let empty_commit = self.workspace.active().is_some_and(|buf| {
+                    buf.syntax_ext() == "gitcommit"
+                        && git::commit_message_is_empty(&buf.file_matrix)
+                });
+                if empty_commit {
+                    self.display.set_message(String::from(
+                        "Mensagem de commit vazia: o git vai cancelar o commit",
+                    ));
+                } else {
+                    self.warn_saved_links();
+                }
+                true
+            }
+            Err(e) => {
+                let path = self
+                    .workspace
+                    .active()
+                    .map(|buf| buf.filename.clone())
+                    .unwrap_or_default();
+                self.report_save_error(&path, &e);
+                // Plain local files can go through sudo instead; encrypted
+                // ones would need their cipher too
+                let local = !remote::is_remote(&path) && Cipher::for_path(&path).is_none();
+                if e.kind() == io::ErrorKind::PermissionDenied && local {
+                    self.open_prompt(
+                        Prompt::confirm(
+                            "Sem permissão para gravar",
+                            vec![path.clone(), String::from("Salvar com sudo? (s/n)")],
+                            &['s', 'n'],
+                        )
+                        .warning(),
+                        PromptAction::SudoSave,
+                    );
+                }
+                false
+            }
+        }
+    }
+
+    /// Say which file a save wrote when the buffer's path is a symlink (the
+    /// file it points to, the link stays), or when the file has other names
+    /// (hard links) that now show the new contents too
+    fn warn_saved_links(&mut self) {
+        let Some(path) = self.workspace.active().map(|buf| buf.filename.clone()) else {
+            return;
+        };
+        if remote::is_remote(&path) {
+            return;
+        }
+        if let Some(target) = vfs::link_target(&path) {
+            self.display.set_message(format!(
+                "Salvo em '{}': '{}' é um link para ele",
+                target.display(),
+                path
+            ));
+        } else if vfs::hard_links(&path) > 1 {
+            self.display.set_message(format!(
+                "Salvo; o arquivo tem {} nomes (hard links) e todos veem a alteração",
+                vfs::hard_links(&path)
+            ));
+        }
+    }
+
+    /// Save the active buffer through `sudo tee`, handing the terminal over
+    /// for the password. Later saves of the buffer go through sudo too.
+    fn sudo_save(&mut self) -> io::Result<()> {
+        let sudo = vfs::sudo();
+        let saved = self.with_terminal_released(|editor| match editor.workspace.active_mut() {
+            Some(buf) => buf.save_to(sudo.as_ref()).map(|_| buf.fs = sudo.clone()),
+            None => Ok(()),
+        })?;
+        let path = self
+            .workspace
+            .active()
+            .map(|buf| buf.filename.clone())
+            .unwrap_or_default();
+        match saved {
+            Ok(()) => {
+                self.saved = true;
+                self.display
+                    .set_message(format!("Salvo com sudo: {}", path))
+            }
+            Err(e) => self.report_save_error(&path, &e),
+        }
+        self.sync_display();
+        Ok(())
+    }
+
+    fn report_save_error(&mut self, path: &str, e: &io::Error) {
+        log::error!("arquivo", "erro ao salvar '{}': {}", path, e);
+        self.display
+            .set_message(format!("Erro ao salvar '{}': {}", path, e));
+    }
+
+    /// Open a large or binary file the way the user chose: (a)s text,
+    /// read-on(l)y, (h)ex, or (c)ancel
+    fn open_confirmed(&mut self, path: &str, size: u64, choice: char) -> io::Result<()> {
+        let loaded = match choice {
+            'a' => self.load_file(path, size, false),
+            'l' => self.load_file(path, size, true),
+            'h' => BufferFile::new_hex_view(path).map(|buf| {
+                self.workspace.open_buffer(buf);
+            }),
+            _ => {
+                self.show_welcome = !self.workspace.has_files();
+                self.sync_display();
+                return Ok(());
+            }
+        };
+        self.finish_open(path, loaded)
+    }
+
+    fn finish_open(&mut self, path: &str, loaded: io::Result<()>) -> io::Result<()> {
+        if let Err(e) = loaded {
+            log::error!("arquivo", "erro ao abrir '{}': {}", path, e);
+            if self.startup_file.as_deref() == Some(path) {
+                self.open_failed = true;
+            }
+            self.display
+                .set_message(format!("Erro ao abrir '{}': {}", path, e));
+            self.show_welcome = !self.workspace.has_files();
+            self.sync_display();
+            self.render();
+            return Ok(());
+        }
+
+        history::record(path);
+        self.show_welcome = false;
+        self.focus = Focus::Editor;
+        self.sync_display();
+        self.render();
+        Ok(())
+    }
+
+    /// Open `path` as a new tab. Big files are read on a worker thread and
+    /// stay read-only until the last line arrives.
+    fn load_file(&mut self, path: &str, size: u64, read_only: bool) -> io::Result<()> {
+        if size < loader::ASYNC_LOAD_MIN_BYTES {
+            let mut buf = BufferFile::new(path)?;
+            buf.read_only = read_only;
+            self.workspace.open_buffer(buf);
+            return Ok(());
+        }
+
+        let mut buf = BufferFile::new_empty(path);
+        buf.read_only = true;
+        self.workspace.open_buffer(buf);
+        if let Some(index) = self.loads.iter().position(|load| load.path == path) {
+            self.jobs.cancel(self.loads.remove(index).job);
+        }
+        let load = FileLoad::start(&mut self.jobs, path, size, read_only);
+        self.loads.push(load);
+        Ok(())
+    }
+
+    /// Dispatch messages from background jobs and refresh the status bar spinner
+    fn poll_jobs(&mut self) -> io::Result<()> {
+        for (id, message) in self.jobs.poll() {
+            if let JobMessage::Blame(result) = message {
+                if let Some(index) = self.blame_jobs.iter().position(|(job, _)| *job == id) {
+                    let (_, path) = self.blame_jobs.remove(index);
+                    self.finish_blame(&path, result);
+                }
+                continue;
+            }
+            if let JobMessage::Finished(_) = message {
+                // A cancelled blame is fetched again on the next frame
+                self.blame_jobs.retain(|(job, _)| *job != id);
+            }
+            if let JobMessage::Symbols(found) = message {
+                if let Some((_, root, name)) = self.symbol_scan.take_if(|(job, _, _)| *job == id) {
+                    log::info!(
+                        "símbolos",
+                        "{} definições em {}",
+                        found.len(),
+                        root.display()
+                    );
+                    self.symbols = Some((root, found));
+                    self.show_definitions(&name)?;
+                }
+                continue;
+            }
+            if self
+                .project_search
+                .as_ref()
+                .is_some_and(|search| search.job == id)
+            {
+                match message {
+                    JobMessage::Matches(found) => self.add_search_results(found),
+                    JobMessage::Finished(_) => self.finish_project_search(),
+                    _ => {}
+                }
+                continue;
+            }
+            if let JobMessage::Matches(found) = message {
+                if let Some((_, query, replacement)) =
+                    self.replace_scan.take_if(|(job, _, _)| *job == id)
+                {
+                    self.show_replace_preview(found, &query, &replacement);
+                }
+                continue;
+            }
+            if self.task_job == Some(id) {
+                self.task_message(message);
+                continue;
+            }
+            if self.tree_load == Some(id) {
+                if let Some(sidebar) = &mut self.sidebar {
+                    match message {
+                        JobMessage::Tree(entries) => sidebar.finish_load(entries),
+                        // Cancelled: an empty tree, read again with F5
+                        JobMessage::Finished(_) => {
+                            self.tree_load = None;
+                            sidebar.loading = false;
+                        }
+                        _ => {}
+                    }
+                }
+                continue;
+            }
+            if self.sidebar_search == Some(id) {
+                if let Some(sidebar) = &mut self.sidebar {
+                    match message {
+                        JobMessage::Names(scanned, found) => sidebar.add_found(scanned, found),
+                        JobMessage::Finished(_) => {
+                            self.sidebar_search = None;
+                            sidebar.scanning = false;
+                        }
+                        _ => {}
+                    }
+                }
+                continue;
+            }
+            if let JobMessage::Todos(found) = message {
+                if self.todo_scan == Some(id) {
+                    self.todo_scan = None;
+                    self.show_todos(found);
+                }
+                continue;
+            }
+            let load_index = match self.loads.iter().position(|load| load.job == id) {
+                Some(index) => index,
+                None => continue,
+            };
+            let buf_index = match self.workspace.find_buffer(&self.loads[load_index].path) {
+                Some(index) => index,
+                None => {
+                    // Tab closed while loading
+                    self.jobs.cancel(id);
+                    self.loads.remove(load_index);
+                    continue;
+                }
+            };
+            let load = &mut self.loads[load_index];
+            let buf = &mut self.workspace.buffers[buf_index];
+
+            match message {
+                JobMessage::Load(LoadMessage::Lines(lines)) => {
+                    if !load.started {
+                        buf.file_matrix.clear();
+                        load.started = true;
+                    }
+                    buf.file_matrix.extend(lines);
+                }
+                JobMessage::Load(LoadMessage::Encoding(line_ending, has_bom)) => {
+                    buf.line_ending = line_ending;
+                    buf.has_bom = has_bom;
+                }
+                JobMessage::Load(LoadMessage::Failed(e)) => {
+                    load.failed = true;
+                    if self.startup_file.as_deref() == Some(load.path.as_str()) {
+                        self.open_failed = true;
+                    }
+                    log::error!("arquivo", "erro ao ler '{}': {}", load.path, e);
+                    self.display
+                        .set_message(format!("Erro ao ler '{}': {}", load.path, e));
+                }
+                JobMessage::Progress(_)
+                | JobMessage::Todos(_)
+                | JobMessage::Matches(_)
+                | JobMessage::Symbols(_)
+                | JobMessage::Tree(_)
+                | JobMessage::Names(..)
+                | JobMessage::Blame(_)
+                | JobMessage::TaskOutput(_)
+                | JobMessage::TaskExit(_) => {}
+                JobMessage::Finished(cancelled) => {
+                    if buf.file_matrix.is_empty() {
+                        buf.file_matrix.push(vec![]);
+                    }
+                    buf.flag_mixed_endings();
+                    // A partial buffer stays read-only so it can't be saved over the file
+                    if cancelled {
+                        log::warning!("arquivo", "carregamento de '{}' cancelado", load.path);
+                        self.display
+                            .set_message(format!("Carregamento de '{}' cancelado", load.path));
+                    } else if !load.failed {
+                        buf.read_only = load.read_only;
+                    }
+                    self.loads.remove(load_index);
+                }
+            }
+        }
+
+        self.display.set_progress(self.jobs.status_text());
+        self.sync_display();
+        Ok(())
+    }
+
+    /// Output and end of the running project task
+    fn task_message(&mut self, message: JobMessage) {
+        let Some(output) = &mut self.task_output else {
+            return;
+        };
+        let status = match message {
+            JobMessage::TaskOutput(bytes) => {
+                output.push(&bytes);
+                return;
+            }
+            JobMessage::TaskExit(code) => tasks::Status::Exited(code),
+            JobMessage::Finished(true) => tasks::Status::Exited(None),
+            _ => return,
+        };
+        output.status = status;
+        self.task_job = None;
+        log::info!("tarefa", "{}", output.title());
+        self.display.set_message(output.title());
+    }
+
+    /// Pick a task of the project to run
+    fn handle_tasks(&mut self) {
+        let found = tasks::load(&self.project_root(), &self.config);
+        if found.is_empty() {
+            self.display.set_message(String::from(
+                "Nenhuma tarefa: defina [tasks] no .reditor.toml do projeto",
+            ));
+            return;
+        }
+        let rows = found
+            .iter()
+            .map(|task| format!("{}  {}", task.name, task.command))
+            .collect();
+        self.open_prompt(
+            Prompt::list("Tarefas", rows).fuzzy(),
+            PromptAction::RunTask(found),
+        );
+    }
+
+    /// Run `task` in the project, stopping the one still running, and show
+    /// its output in the panel
+    fn run_task(&mut self, task: &Task) {
+        if let Some(id) = self.task_job.take() {
+            self.jobs.cancel(id);
+        }
+        log::info!("tarefa", "{}: {}", task.name, task.command);
+        let root = self.project_root();
+        self.task_job = Some(tasks::start(&mut self.jobs, &root, task));
+        self.task_output = Some(tasks::Output::new(&task.name));
+        self.show_output = true;
+        self.sync_display();
+    }
+
+    /// Hunk commands work on the saved file, so the diff's rows match the
+    /// buffer. Reverting edits the buffer (undoable); staging goes to the index.
+    fn handle_hunk(&mut self, command: Command) -> io::Result<()> {
+        let (path, row) = match self.workspace.active() {
+            Some(buf) if buf.modified => {
+                self.display.set_message(String::from(
+                    "Salve o arquivo antes de usar os comandos de hunk",
+                ));
+                return Ok(());
+            }
+            Some(buf) => (buf.filename.clone(), buf.view.cursor_row),
+            None => return Ok(()),
+        };
+        let diff = match git::diff(&path, command == Command::HunkRevert) {
+            Ok(diff) => diff,
+            Err(e) => {
+                log::warning!("git", "diff de '{}' falhou: {}", path, e);
+                self.display.set_message(format!("git: {}", e));
+                return Ok(());
+            }
+        };
+
+        let target = match command {
+            Command::HunkNext => diff.hunks.iter().find(|hunk| hunk.row > row),
+            Command::HunkPrev => diff
+                .hunks
+                .iter()
+                .rev()
+                .find(|hunk| hunk.row < row && !hunk.contains(row)),
+            _ => diff.hunks.iter().find(|hunk| hunk.contains(row)),
+        };
+        let hunk = match target {
+            Some(hunk) => hunk,
+            None => {
+                self.display.set_message(String::from(match command {
+                    Command::HunkNext => "Nenhuma alteração abaixo",
+                    Command::HunkPrev => "Nenhuma alteração acima",
+                    _ => "Nenhuma alteração sob o cursor",
+                }));
+                return Ok(());
+            }
+        };
+
+        match command {
+            Command::HunkStage => {
+                let message = match git::stage_hunk(&diff, hunk) {
+                    Ok(()) => format!("Alteração na linha {} adicionada ao índice", hunk.row + 1),
+                    Err(e) => {
+                        log::warning!("git", "stage de '{}' falhou: {}", path, e);
+                        format!("git apply: {}", e)
+                    }
+                };
+                self.display.set_message(message);
+            }
+            Command::HunkRevert => {
+                let lines = hunk
+                    .old_lines
+                    .iter()
+                    .map(|line| line.chars().collect())
+                    .collect();
+                if let Some(buf) = self.workspace.active_mut() {
+                    buf.replace_block(hunk.row, hunk.count, lines);
+                }
+                self.display
+                    .set_message(String::from("Alteração revertida para o HEAD (u desfaz)"));
+            }
+            _ => {
+                let row = hunk.row;
+                self.jump_to_position(row, 0)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Start `git blame` for the active buffer the first time it's shown
+    /// with blame on. The result stays on the buffer until it's saved.
+    fn refresh_blame(&mut self) {
+        let line_hover = self.hover.as_ref().is_some_and(|hover| hover.col.is_none());
+        if !self.blame && !line_hover {
+            return;
+        }
+        let path = match self.workspace.active() {
+            Some(buf) if buf.blame.is_none() => buf.filename.clone(),
+            _ => return,
+        };
+        if self.blame_jobs.iter().any(|(_, pending)| *pending == path) {
+            return;
+        }
+        let id = git::start_blame(&mut self.jobs, &path);
+        self.blame_jobs.push((id, path));
+    }
+
+    fn finish_blame(&mut self, path: &str, result: io::Result<Vec<git::BlameLine>>) {
+        let lines = result.unwrap_or_else(|e| {
+            log::warning!("git", "blame de '{}' falhou: {}", path, e);
+            // Hovers ask for blame on their own, outside git repos too
+            if self.blame {
+                self.display.set_message(format!("git blame: {}", e));
+            }
+            vec![]
+        });
+        if let Some(index) = self.workspace.find_buffer(path) {
+            self.workspace.buffers[index].blame = Some(lines);
+        }
+    }
+
+    fn handle_cancel_jobs(&mut self) {
+        let count = self.jobs.cancel_all();
+        self.display.set_message(if count == 0 {
+            String::from("Nenhuma tarefa em andamento")
+        } else {
+            format!("{} tarefa(s) cancelada(s)", count)
+        });
+    }
+
+    /// Show `path` in the transient preview tab while the sidebar keeps focus.
+    /// Binary files preview as hex; large files must be opened explicitly.
+    fn preview_file(&mut self, path: &str) -> io::Result<()> {
+        if Cipher::for_path(path).is_some() {
+            self.display
+                .set_message(String::from("Arquivo criptografado — use Enter para abrir"));
+            return Ok(());
+        }
+        let loaded = BufferFile::probe(path).and_then(|(size, is_binary)| {
+            if size > self.config.large_file_threshold() {
+                Ok(None)
+            } else if is_binary {
+                BufferFile::new_hex_view(path).map(Some)
+            } else {
+                BufferFile::new(path).map(Some)
+            }
+        });
+
+        match loaded {
+            Ok(Some(buf)) => {
+                self.workspace.open_preview(buf);
+                self.show_welcome = false;
+            }
+            Ok(None) => self
+                .display
+                .set_message(String::from("Arquivo grande — use Enter para abrir")),
+            Err(e) => {
+                log::error!("arquivo", "erro ao abrir '{}': {}", path, e);
+                self.display
+                    .set_message(format!("Erro ao abrir '{}': {}", path, e));
+            }
+        }
+        self.sync_display();
+        Ok(())
+    }
+
+    /// True (and a status message is shown) when the active buffer can't be edited
+    fn active_read_only(&mut self) -> bool {
+        let read_only = self.workspace.active().is_some_and(|b| b.read_only);
+        if read_only {
+            self.display
+                .set_message(String::from("Buffer somente leitura"));
+        }
+        read_only
+    }
+
+    /// Parse the active buffer as JSON, then rewrite it formatted or
+    /// minified as one undoable step. A syntax error moves the cursor to
+    /// where it was found and is shown after that line; validating also
+    /// warns about repeated keys.
+    fn handle_json(&mut self, command: Command) -> io::Result<()> {
+        let indent = self.config.json_indent();
+        let Some(buf) = self.workspace.active_mut() else {
+            return Ok(());
+        };
+        let text: String = buf
+            .file_matrix
+            .iter()
+            .map(|line| line.iter().collect::<String>())
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        let (value, duplicates) = match json::parse(&text) {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                let position = (e.line, e.column);
+                let found = Diagnostic::new(
+                    Severity::Error,
+                    position,
+                    position,
+                    &e.message,
+                    "json",
+                    &buf.file_matrix,
+                );
+                buf.set_diagnostics("json", vec![found]);
+                self.display.set_message(format!(
+                    "JSON inválido (linha {}, coluna {}): {}",
+                    e.line + 1,
+                    e.column + 1,
+                    e.message
+                ));
+                return self.jump_to_position(e.line, e.column);
+            }
+        };
+        let output = match command {
+            Command::JsonFormat => json::format(&value, indent),
+            Command::JsonMinify => json::minify(&value),
+            _ => {
+                let warnings: Vec<Diagnostic> = duplicates
+                    .iter()
+                    .map(|d| {
+                        Diagnostic::new(
+                            Severity::Warning,
+                            (d.line, d.column),
+                            (d.line, d.column + d.key.chars().count() + 1),
+                            &format!("chave \"{}\" repetida", d.key),
+                            "json",
+                            &buf.file_matrix,
+                        )
+                    })
+                    .collect();
+                let message = match warnings.len() {
+                    0 => String::from("JSON válido"),
+                    n => format!("JSON válido, {} chave(s) repetida(s)", n),
+                };
+                buf.set_diagnostics("json", warnings);
+                self.display.set_message(message);
+                self.sync_display();
+                return Ok(());
+            }
+        };
+        buf.set_diagnostics("json", vec![]);
+        let lines: Vec<Vec<char>> = output.lines().map(|line| line.chars().collect()).collect();
+        if lines != buf.file_matrix {
+            let count = buf.file_matrix.len();
+            buf.replace_block(0, count, lines);
+        }
+        self.display
+            .set_message(String::from(if command == Command::JsonFormat {
+                "JSON formatado"
+            } else {
+                "JSON compactado"
+            }));
+        self.sync_display();
+        Ok(())
+    }
+
+    /// Replace each occurrence of `word` found when the rename started with
+    /// `name`, as one undoable edit over the rows between the first and
+    /// last of them. The cursor stays on the same identifier.
+    fn rename_symbol(&mut self, word: &[char], found: &[(usize, usize)], name: &str) {
+        let name: Vec<char> = name.chars().collect();
+        if name.is_empty() || name == word {
+            return;
+        }
+        let Some(buf) = self.workspace.active_mut() else {
+            return;
+        };
+        // A followed file can be reloaded while the prompt is open, so the
+        // occurrences are checked again
+        let found: Vec<(usize, usize)> = found
+            .iter()
+            .copied()
+            .filter(|&(row, col)| {
+                buf.file_matrix
+                    .get(row)
+                    .and_then(|line| line.get(col..col + word.len()))
+                    == Some(word)
+            })
+            .collect();
+        let (Some(&(first, _)), Some(&(last, _))) = (found.first(), found.last()) else {
+            return;
+        };
+
+        let mut lines = buf.file_matrix[first..=last].to_vec();
+        let (cursor_row, cursor_col) = (buf.view.cursor_row, buf.view.cursor_col);
+        let mut new_cursor_col = cursor_col;
+        // Right to left, so earlier columns stay valid
+        for &(row, col) in found.iter().rev() {
+            lines[row - first].splice(col..col + word.len(), name.iter().copied());
+            if row == cursor_row && col + word.len() <= cursor_col {
+                new_cursor_col = new_cursor_col + name.len() - word.len();
+            } else if row == cursor_row && col <= cursor_col {
+                new_cursor_col = col;
+            }
+        }
+        buf.replace_block(first, last - first + 1, lines);
+        buf.view.cursor_row = cursor_row;
+        buf.view.cursor_col = new_cursor_col;
+        buf.clamp_cursor();
+        buf.view.preferred_col = buf.view.cursor_col;
+        self.display.set_message(format!(
+            "{} ocorrência(s) de '{}' renomeada(s) para '{}'",
+            found.len(),
+            word.iter().collect::<String>(),
+            name.iter().collect::<String>()
+        ));
+    }
+
+    /// Move to the next (or previous) current diagnostic, wrapping around,
+    /// and show its message in the status bar
+    fn goto_diagnostic(&mut self, forward: bool) -> io::Result<()> {
+        let Some(buf) = self.workspace.active() else {
+            return Ok(());
+        };
+        let cursor = (buf.view.cursor_row, buf.view.cursor_col);
+        let found: Vec<&Diagnostic> = buf.current_diagnostics().collect();
+        let target = if forward {
+            found
+                .iter()
+                .find(|d| d.start > cursor)
+                .or_else(|| found.first())
+        } else {
+            found
+                .iter()
+                .rev()
+                .find(|d| d.start < cursor)
+                .or_else(|| found.last())
+        };
+        let Some(diagnostic) = target else {
+            self.display
+                .set_message(String::from("Nenhum erro ou aviso no arquivo"));
+            return Ok(());
+        };
+        let (start, summary) = (diagnostic.start, diagnostic.summary());
+        self.jump_to_position(start.0, start.1)?;
+        self.display.set_message(summary);
+        Ok(())
+    }
+
+    /// Replace the selection with `transform` applied to it, leaving Visual
+    /// mode with the cursor at its start
+    fn transform_selection(&mut self, transform: &transform::Transform) {
+        let Some(buf) = self.workspace.active_mut() else {
+            return;
+        };
+        let Some((start, end)) = buf.view.selection() else {
+            return;
+        };
+        let result = (transform.apply)(&buf.selected_text(start, end));
+        match result {
+            Ok(text) => {
+                buf.replace_selection(start, end, &text);
+                buf.view.cursor_row = start.0;
+                buf.view.cursor_col = start.1;
+                buf.clamp_cursor();
+                buf.view.preferred_col = buf.view.cursor_col;
+                self.set_mode(EditorMode::Normal);
+                self.display
+                    .set_message(format!("{} aplicado", transform.name));
+            }
+            Err(e) => self
+                .display
+                .set_message(format!("{}: {}", transform.name, e)),
+        }
+    }
+
+    /// Lock or unlock the active buffer against edits. Hex views and files
+    /// still loading stay locked.
+    fn toggle_read_only(&mut self) -> String {
+        let loading = self
+            .workspace
+            .active()
+            .is_some_and(|buf| self.loads.iter().any(|load| load.path == buf.filename));
+        let Some(buf) = self.workspace.active_mut() else {
+            return String::new();
+        };
+        if buf.hex_view {
+            return String::from("A visualização hex é sempre somente leitura");
+        }
+        if loading {
+            return String::from("Arquivo ainda carregando");
+        }
+        buf.read_only = !buf.read_only;
+        String::from(if buf.read_only {
+            "Somente leitura ativado"
+        } else {
+            "Somente leitura desativado"
+        })
+    }
+
+    /// Start or stop following the active file: lines appended on disk show
+    /// up at the end of the buffer, scrolling along while the cursor is on
+    /// the last line
+    fn toggle_follow(&mut self) -> String {
+        let Some(buf) = self.workspace.active_mut() else {
+            return String::new();
+        };
+        if let Some(index) = self.follows.iter().position(|f| f.path == buf.filename) {
+            self.follows.remove(index);
+            return String::from("Seguir arquivo desativado");
+        }
+        if buf.hex_view || remote::is_remote(&buf.filename) {
+            return String::from("Só arquivos locais de texto podem ser seguidos");
+        }
+        if self.loads.iter().any(|load| load.path == buf.filename) {
+            return String::from("Arquivo ainda carregando");
+        }
+        match Follow::start(&buf.filename) {
+            Ok(follow) => {
+                buf.view.cursor_row = buf.file_matrix.len().saturating_sub(1);
+                buf.view.cursor_col = 0;
+                buf.view.preferred_col = 0;
+                self.follows.push(follow);
+                String::from("Seguindo o arquivo (follow de novo para parar)")
+            }
+            Err(e) => format!("Erro ao seguir '{}': {}", buf.filename, e),
+        }
+    }
+
+    /// Bring appended lines of followed files into their buffers
+    fn poll_follows(&mut self) {
+        let active = self.workspace.active().map(|buf| buf.filename.clone());
+        let mut index = 0;
+        while index < self.follows.len() {
+            let follow = &mut self.follows[index];
+            let Some(buf_index) = self.workspace.find_buffer(&follow.path) else {
+                // Tab closed
+                self.follows.remove(index);
+                continue;
+            };
+            let buf = &mut self.workspace.buffers[buf_index];
+            let at_bottom = buf.view.cursor_row + 1 >= buf.file_matrix.len();
+            match follow.poll() {
+                Ok(None) => {}
+                Ok(Some(Growth::Lines { lines, continues })) => {
+                    let mut lines = lines.into_iter();
+                    if continues {
+                        if let (Some(last), Some(first)) =
+                            (buf.file_matrix.last_mut(), lines.next())
+                        {
+                            last.extend(first);
+                        }
+                    }
+                    buf.file_matrix.extend(lines);
+                }
+                Ok(Some(Growth::Truncated)) => {
+                    if let Err(e) = buf.reload() {
+                        log::error!("arquivo", "erro ao reler '{}': {}", follow.path, e);
+                    }
+                }
+                Err(e) => {
+                    log::error!("arquivo", "parou de seguir '{}': {}", follow.path, e);
+                    self.display
+                        .set_message(format!("Parou de seguir '{}': {}", follow.path, e));
+                    self.follows.remove(index);
+                    continue;
+                }
+            }
+            // Scrolled up to read: leave the view alone
+            if at_bottom && active.as_ref() == Some(&buf.filename) {
+                buf.view.cursor_row = buf.file_matrix.len().saturating_sub(1);
+                buf.clamp_cursor();
+            }
+            index += 1;
+        }
+    }
+
+    /// A followed buffer was just saved: continue from the file's new end
+    fn restart_follow(&mut self) {
+        let Some(buf) = self.workspace.active() else {
+            return;
+        };
+        if let Some(follow) = self.follows.iter_mut().find(|f| f.path == buf.filename) {
+            if let Ok(fresh) = Follow::start(&buf.filename) {
+                *follow = fresh;
+            }
+        }
+    }
+
+    // --- Paste / drag-and-drop ---
+    fn handle_paste(&mut self, text: &str) -> io::Result<()> {
+        if let Some((prompt, _)) = &mut self.prompt {
+            prompt.paste(text);
+            return Ok(());
+        }
+
+        // Terminals deliver dropped files as a pasted path
+        if let Some(path) = parse_dropped_path(text) {
+            if path.is_file() {
+                let path = path.to_string_lossy().to_string();
+                let question = format!("Abrir '{}'? (s)im, (n)ão, colar como texto", path);
+                self.open_prompt(
+                    Prompt::confirm("Arquivo arrastado", vec![question], &['s', 'n']),
+                    PromptAction::DroppedPath(path, text.to_string()),
+                );
+                return Ok(());
+            }
+        }
+        self.paste_text(text)
+    }
+
+    fn paste_text(&mut self, text: &str) -> io::Result<()> {
+        if self.focus != Focus::Editor
+            || self.mode() != EditorMode::Insert
+            || !self.workspace.has_files()
+            || self.active_read_only()
+        {
+            return Ok(());
+        }
+
+        let end = if let Some(buf) = self.workspace.active_mut() {
+            let end = buf.insert_text(text, buf.view.cursor_col, buf.view.cursor_row);
+            buf.commit_edits();
+            end
+        } else {
+            return Ok(());
+        };
+        self.jump_to_position(end.1, end.0)
+    }
+
+    // --- Command palette ---
+    fn handle_command_palette(&mut self) {
+        let items = commands::COMMANDS
+            .iter()
+            .map(|info| format!("{:<14} {}", info.name, info.description))
+            .collect();
+        self.open_prompt(Prompt::list("Comandos", items), PromptAction::RunCommand);
+    }
+
+    fn execute_command(&mut self, command: Command) -> io::Result<()> {
+        if !self.workspace.has_files() && command.needs_file() {
+            self.display
+                .set_message(String::from("Nenhum arquivo aberto"));
+            return Ok(());
+        }
+        log::info!("comando", "{:?}", command);
+        if command.modifies_buffer() && self.active_read_only() {
+            return Ok(());
+        }
+
+        let message = match command {
+            Command::LineEndingLf | Command::LineEndingCrlf => {
+                let target = if command == Command::LineEndingLf {
+                    LineEnding::Lf
+                } else {
+                    LineEnding::Crlf
+                };
+                let changed = self
+                    .workspace
+                    .active_mut()
+                    .is_some_and(|buf| buf.set_line_ending(target));
+                if changed {
+                    format!("Finais de linha convertidos para {}", target.as_str())
+                } else {
+                    format!("Finais de linha já são {}", target.as_str())
+                }
+            }
+            Command::AddBom | Command::RemoveBom => {
+                let add = command == Command::AddBom;
+                let changed = self
+                    .workspace
+                    .active_mut()
+                    .is_some_and(|buf| buf.set_bom(add));
+                String::from(match (add, changed) {
+                    (true, true) => "BOM adicionado",
+                    (true, false) => "Arquivo já possui BOM",
+                    (false, true) => "BOM removido",
+                    (false, false) => "Arquivo não possui BOM",
+                })
+            }
+            Command::Reload => {
+                self.handle_reload();
+                return Ok(());
+            }
+            Command::Undo | Command::Redo => {
+                self.undo_redo(command == Command::Undo);
+                return Ok(());
+            }
+            Command::CancelJobs => {
+                self.handle_cancel_jobs();
+                return Ok(());
+            }
+            Command::PerfHud => {
+                self.perf_hud = !self.perf_hud;
+                return Ok(());
+            }
+            Command::NewView => {
+                self.workspace.duplicate_view();
+                self.sync_display();
+                return Ok(());
+            }
+            Command::ReadOnly => self.toggle_read_only(),
+            Command::Follow => self.toggle_follow(),
+            Command::Hover => {
+                self.hover_cursor_line();
+                return Ok(());
+            }
+            Command::DiagnosticNext | Command::DiagnosticPrev => {
+                self.goto_diagnostic(command == Command::DiagnosticNext)?;
+                return Ok(());
+            }
+            Command::OpenLink => {
+                self.open_link_at_cursor()?;
+                return Ok(());
+            }
+            Command::Tasks => {
+                self.handle_tasks();
+                return Ok(());
+            }
+            Command::TaskOutput => {
+                if self.task_output.is_none() {
+                    String::from("Nenhuma tarefa rodou ainda")
+                } else {
+                    self.show_output = !self.show_output;
+                    self.sync_display();
+                    return Ok(());
+                }
+            }
+            Command::TaskStop => match self.task_job {
+                Some(id) => {
+                    self.jobs.cancel(id);
+                    String::from("Interrompendo a tarefa")
+                }
+                None => String::from("Nenhuma tarefa rodando"),
+            },
+            Command::GotoFile => {
+                self.goto_file_at_cursor()?;
+                return Ok(());
+            }
+            Command::GotoDefinition => {
+                self.goto_definition()?;
+                return Ok(());
+            }
+            Command::IndentNext | Command::IndentPrev => {
+                let Some(buf) = self.workspace.active() else {
+                    return Ok(());
+                };
+                let forward = command == Command::IndentNext;
+                match motion::same_indent(&buf.file_matrix, buf.view.cursor_row, forward) {
+                    Some(row) => return self.jump_to_indented(row),
+                    None => String::from("Nenhuma linha com o mesmo recuo"),
+                }
+            }
+            Command::IndentStart | Command::IndentEnd => {
+                let Some(buf) = self.workspace.active() else {
+                    return Ok(());
+                };
+                let forward = command == Command::IndentEnd;
+                let row = motion::indent_block_edge(&buf.file_matrix, buf.view.cursor_row, forward);
+                return self.jump_to_indented(row);
+            }
+            Command::ApplyHunk => {
+                self.apply_hunk()?;
+                return Ok(());
+            }
+            Command::Options => {
+                self.handle_options();
+                return Ok(());
+            }
+            Command::TagMatch => {
+                let Some(buf) = self.workspace.active() else {
+                    return Ok(());
+                };
+                let cursor = (buf.view.cursor_row, buf.view.cursor_col);
+                match tags::pair_at(&buf.file_matrix, &buf.syntax_ext(), cursor) {
+                    Some((_, Some(partner))) => {
+                        return self.jump_to_position(partner.start.0, partner.name_col);
+                    }
+                    Some((tag, None)) => format!("A tag '{}' não tem par", tag.name),
+                    None => String::from("Nenhuma tag sob o cursor"),
+                }
+            }
+            Command::TagRename => {
+                let Some(buf) = self.workspace.active() else {
+                    return Ok(());
+                };
+                let cursor = (buf.view.cursor_row, buf.view.cursor_col);
+                match tags::pair_at(&buf.file_matrix, &buf.syntax_ext(), cursor) {
+                    Some((tag, partner)) => {
+                        // Both names go through the same rename as identifiers
+                        let mut found: Vec<(usize, usize)> = std::iter::once(&tag)
+                            .chain(partner.as_ref())
+                            .map(|tag| (tag.start.0, tag.name_col))
+                            .collect();
+                        found.sort();
+                        let (x, y) = self.display.cursor_screen_position();
+                        self.open_prompt(
+                            Prompt::input(&format!("Renomear a tag '{}'", tag.name))
+                                .with_input(&tag.name)
+                                .at(Placement::At(x, y + 1)),
+                            PromptAction::RenameSymbol(tag.name.chars().collect(), found),
+                        );
+                        return Ok(());
+                    }
+                    None => String::from("Nenhuma tag sob o cursor"),
+                }
+            }
+            Command::Marks => {
+                self.handle_marks();
+                return Ok(());
+            }
+            Command::PasteHistory => {
+                self.handle_paste_history();
+                return Ok(());
+            }
+            Command::Snippet => {
+                self.handle_snippet();
+                return Ok(());
+            }
+            Command::CollabHost => {
+                if self.collab.is_some() {
+                    String::from("Já existe uma sessão compartilhada (collab-leave para sair)")
+                } else {
+                    self.open_prompt(
+                        Prompt::input("Compartilhar na porta (ou endereço:porta)")
+                            .with_input(&collab::DEFAULT_PORT.to_string()),
+                        PromptAction::CollabHost,
+                    );
+                    return Ok(());
+                }
+            }
+            Command::CollabJoin => {
+                if self.collab.is_some() {
+                    String::from("Já existe uma sessão compartilhada (collab-leave para sair)")
+                } else {
+                    self.open_prompt(
+                        Prompt::input("Entrar na sessão (código@host:porta)"),
+                        PromptAction::CollabJoin,
+                    );
+                    return Ok(());
+                }
+            }
+            Command::CollabLeave => match self.collab.take() {
+                Some(session) if session.is_host() => String::from("Compartilhamento encerrado"),
+                Some(_) => String::from("Saiu da sessão compartilhada"),
+                None => String::from("Nenhuma sessão compartilhada"),
+            },
+            Command::Replace => {
+                self.start_replace();
+                return Ok(());
+            }
+            Command::ProjectSearch => {
+                self.open_project_search();
+                return Ok(());
+            }
+            Command::ProjectReplace => {
+                self.open_prompt(
+                    Prompt::input("Substituir no projeto: buscar"),
+                    PromptAction::ReplaceQuery,
+                );
+                return Ok(());
+            }
+            Command::RenameSymbol => {
+                let Some(buf) = self.workspace.active() else {
+                    return Ok(());
+                };
+                let (row, col) = (buf.view.cursor_row, buf.view.cursor_col);
+                match buf.word_at(row, col) {
+                    Some((start, end)) => {
+                        let word = buf.file_matrix[row][start..end].to_vec();
+                        let found = buf.find_word(&word);
+                        let name: String = word.iter().collect();
+                        // Below the cursor, clear of the occurrences above it
+                        let (x, y) = self.display.cursor_screen_position();
+                        self.open_prompt(
+                            Prompt::input(&format!(
+                                "Renomear '{}' ({} ocorrência(s))",
+                                name,
+                                found.len()
+                            ))
+                            .with_input(&name)
+                            .at(Placement::At(x, y + 1)),
+                            PromptAction::RenameSymbol(word, found),
+                        );
+                        return Ok(());
+                    }
+                    None => String::from("Nenhum identificador sob o cursor"),
+                }
+            }
+            Command::Transform => {
+                if self
+                    .workspace
+                    .active()
+                    .and_then(|buf| buf.view.selection())
+                    .is_none()
+                {
+                    String::from("Nenhuma seleção (use v para selecionar)")
+                } else {
+                    let names = transform::TRANSFORMS
+                        .iter()
+                        .map(|t| t.name.to_string())
+                        .collect();
+                    self.open_prompt(
+                        Prompt::list("Transformar seleção", names),
+                        PromptAction::Transform,
+                    );
+                    return Ok(());
+                }
+            }
+            Command::JsonFormat | Command::JsonMinify | Command::JsonValidate => {
+                self.handle_json(command)?;
+                return Ok(());
+            }
+            Command::Blame => {
+                self.blame = !self.blame;
+                String::from(if self.blame {
+                    "Blame ativado"
+                } else {
+                    "Blame desativado"
+                })
+            }
+            Command::HunkNext | Command::HunkPrev | Command::HunkStage | Command::HunkRevert => {
+                self.handle_hunk(command)?;
+                return Ok(());
+            }
+            Command::Todos => {
+                self.handle_todos();
+                return Ok(());
+            }
+            Command::OldFiles => {
+                self.handle_old_files();
+                return Ok(());
+            }
+            Command::SidebarJump => {
+                self.handle_sidebar_jump();
+                return Ok(());
+            }
+            Command::RestoreTrash => self.restore_from_trash(),
+            Command::BulkRename => {
+                let dir = match &mut self.sidebar {
+                    Some(sidebar) => Self::sidebar_dir(sidebar),
+                    None => std::env::current_dir().unwrap_or_default(),
+                };
+                self.start_bulk_rename(&dir);
+                return Ok(());
+            }
+            Command::OpenLog => {
+                match log::current_path() {
+                    Some(path) => self.open_in_editor(&path.to_string_lossy())?,
+                    None => self
+                        .display
+                        .set_message(String::from("Log desativado (inicie com --log debug)")),
+                }
+                return Ok(());
+            }
+            Command::GotoLine => {
+                self.open_prompt(Prompt::input("Ir para linha"), PromptAction::GotoLine);
+                return Ok(());
+            }
+            Command::SetLanguage => {
+                self.handle_set_language();
+                return Ok(());
+            }
+            Command::InspectChar => self.inspect_char(),
+            Command::MakeExecutable => self.toggle_executable(),
+            Command::ExtractFile => {
+                self.extract_file()?;
+                return Ok(());
+            }
+            Command::TestFile => {
+                self.test_file()?;
+                return Ok(());
+            }
+            Command::Present => {
+                if self.presenting.take().is_some() {
+                    String::from("Apresentação encerrada")
+                } else {
+                    self.start_presentation();
+                    String::from("Apresentação: ↑/↓ rolam, Esc sai")
+                }
+            }
+            Command::NewFile => {
+                let (dir, shown) = match &mut self.sidebar {
+                    Some(sidebar) => {
+                        let dir = Self::sidebar_dir(sidebar);
+                        let shown = match dir.strip_prefix(&sidebar.root_path) {
+                            Ok(relative) if relative.as_os_str().is_empty() => String::from("."),
+                            Ok(relative) => relative.display().to_string(),
+                            Err(_) => dir.display().to_string(),
+                        };
+                        (dir, shown)
+                    }
+                    None => (
+                        std::env::current_dir().unwrap_or_default(),
+                        String::from("."),
+                    ),
+                };
+                self.open_prompt(
+                    Prompt::input(&format!("Novo arquivo em {}", shown)),
+                    PromptAction::NewFile(dir),
+                );
+                return Ok(());
+            }
+            Command::SidebarRefresh | Command::SidebarCollapse | Command::SidebarCompact => {
+                let Some(sidebar) = &mut self.sidebar else {
+                    self.display
+                        .set_message(String::from("Nenhuma pasta aberta na barra lateral"));
+                    return Ok(());
+                };
+                let message = match command {
+                    Command::SidebarRefresh => {
+                        sidebar.refresh();
+                        String::from("Árvore de arquivos relida")
+                    }
+                    Command::SidebarCollapse => {
+                        sidebar.collapse_all();
+                        String::from("Pastas fechadas")
+                    }
+                    _ => {
+                        sidebar.toggle_compact();
+                        sidebar.visible = true;
+                        String::from(if sidebar.compact {
+                            "Barra lateral só com ícones"
+                        } else {
+                            "Barra lateral completa"
+                        })
+                    }
+                };
+                self.sync_display();
+                message
+            }
+        };
+        self.display.set_message(message);
+        Ok(())
+    }
+
+    /// Follow the link under the cursor: URLs go to the browser, file
+    /// references open in a tab at their line
+    fn open_link_at_cursor(&mut self) -> io::Result<()> {
+        let (link, base) = match self.workspace.active() {
+            Some(buf) => (
+                buf.file_matrix
+                    .get(buf.view.cursor_row)
+                    .and_then(|line| links::at(line, buf.view.cursor_col)),
+                Path::new(&buf.filename)
+                    .parent()
+                    .map(Path::to_path_buf)
+                    .unwrap_or_default(),
+            ),
+            None => return Ok(()),
+        };
+        match link.map(|link| link.target) {
+            None => self
+                .display
+                .set_message(String::from("Nenhum link sob o cursor")),
+            Some(Target::Url(url)) => match links::open_url(&url) {
+                Ok(()) => self.display.set_message(format!("Abrindo {}", url)),
+                Err(e) => {
+                    log::error!("link", "erro ao abrir '{}': {}", url, e);
+                    self.display
+                        .set_message(format!("Erro ao abrir '{}': {}", url, e));
+                }
+            },
+            Some(Target::File { path, line, column }) => {
+                self.open_file_reference(&path, line, column, &base)?;
+            }
+        }
+        self.sync_display();
+        Ok(())
+    }
+
+    /// `gf`: open the file named under the cursor, at the line and column
+    /// that follow it (`src/main.rs:12:5`, `a.ts(12,5)`...)
+    fn goto_file_at_cursor(&mut self) -> io::Result<()> {
+        let (link, base) = match self.workspace.active() {
+            Some(buf) => (
+                buf.file_matrix
+                    .get(buf.view.cursor_row)
+                    .and_then(|line| links::file_at(line, buf.view.cursor_col)),
+                Path::new(&buf.filename)
+                    .parent()
+                    .map(Path::to_path_buf)
+                    .unwrap_or_default(),
+            ),
+            None => return Ok(()),
+        };
+        match link.map(|link| link.target) {
+            Some(Target::File { path, line, column }) => {
+                self.open_file_reference(&path, line, column, &base)?;
+            }
+            _ => self
+                .display
+                .set_message(String::from("Nenhum arquivo sob o cursor")),
+        }
+        self.sync_display();
+        Ok(())
+    }
+
+    /// Open `path` at a 1-based line and column, looking for it next to the
+    /// current file (in `base`), then in the project, then in the working
+    /// directory
+    fn open_file_reference(
+        &mut self,
+        path: &str,
+        line: Option<usize>,
+        column: Option<usize>,
+        base: &Path,
+    ) -> io::Result<()> {
+        let expanded = match (path.strip_prefix("~/"), std::env::var_os("HOME")) {
+            (Some(rest), Some(home)) => PathBuf::from(home).join(rest),
+            _ => PathBuf::from(path),
+        };
+        let found = [
+            base.join(&expanded),
+            self.project_root().join(&expanded),
+            expanded,
+        ]
+        .into_iter()
+        .find(|candidate| candidate.is_file());
+        let file = match found {
+            Some(file) => file.to_string_lossy().to_string(),
+            None => {
+                self.display
+                    .set_message(format!("Arquivo não encontrado: {}", path));
+                return Ok(());
+            }
+        };
+        self.open_in_editor(&file)?;
+        if let (Some(line), true) = (
+            line,
+            self.workspace
+                .active()
+                .is_some_and(|buf| buf.filename == file),
+        ) {
+            let column = column.unwrap_or(1);
+            self.jump_to_position(line.saturating_sub(1), column.saturating_sub(1))?;
+        }
+        Ok(())
+    }
+
+    /// Apply the diff hunk under the cursor to the file it names, looked
+    /// for as `gf` does, and show the change there. The file's buffer takes
+    /// it unsaved, undone with `u`.
+    fn apply_hunk(&mut self) -> io::Result<()> {
+        let (hunk, base) = match self.workspace.active() {
+            Some(buf) => (
+                patch::hunk_at(&buf.file_matrix, buf.view.cursor_row),
+                Path::new(&buf.filename)
+                    .parent()
+                    .map(Path::to_path_buf)
+                    .unwrap_or_default(),
+            ),
+            None => return Ok(()),
+        };
+        let Some(hunk) = hunk else {
+            self.display
+                .set_message(String::from("Nenhum trecho (@@) de diff sob o cursor"));
+            return Ok(());
+        };
+        let root = self.project_root();
+        let found = [&hunk.path, &hunk.raw_path]
+            .into_iter()
+            .flat_map(|name| [base.join(name), root.join(name), PathBuf::from(name)])
+            .find(|candidate| candidate.is_file());
+        let Some(file) = found.map(|file| file.to_string_lossy().to_string()) else {
+            self.display
+                .set_message(format!("Arquivo do diff não encontrado: {}", hunk.path));
+            return Ok(());
+        };
+        self.open_in_editor(&file)?;
+        // Large and binary files ask before opening
+        let Some(buf) = self
+            .workspace
+            .active_mut()
+            .filter(|buf| buf.filename == file)
+        else {
+            return Ok(());
+        };
+        if buf.read_only {
+            self.display
+                .set_message(String::from("Buffer somente leitura"));
+            return Ok(());
+        }
+        let message = match patch::locate(&buf.file_matrix, &hunk) {
+            Ok(row) => {
+                buf.replace_block(row, hunk.old.len(), hunk.new.clone());
+                self.jump_to_position(row + hunk.leading, 0)?;
+                format!("Trecho aplicado a '{}' (salve para gravar)", hunk.path)
+            }
+            Err(true) => format!("O trecho já está aplicado em '{}'", hunk.path),
+            Err(false) => format!("O trecho não casa com o texto de '{}'", hunk.path),
+        };
+        self.display.set_message(message);
+        Ok(())
+    }
+
+    /// The folder the sidebar selection is in, or is
+    fn sidebar_dir(sidebar: &mut Sidebar) -> PathBuf {
+        let selected = sidebar.get_selected_path();
+        match selected {
+            Some(path) if sidebar.is_selected_dir() => path,
+            Some(path) => path
+                .parent()
+                .map(Path::to_path_buf)
+                .unwrap_or_else(|| sidebar.root_path.clone()),
+            None => sidebar.root_path.clone(),
+        }
+    }
+
+    /// Give the active file the execute bit (for scripts), or take it away
+    /// if it has it
+    fn toggle_executable(&mut self) -> String {
+        let Some(path) = self.workspace.active().map(|buf| buf.filename.clone()) else {
+            return String::new();
+        };
+        if remote::is_remote(&path) || !Path::new(&path).is_file() {
+            return String::from("Só arquivos locais já salvos podem ser executáveis");
+        }
+        let executable = !vfs::is_executable(Path::new(&path));
+        if let Err(e) = vfs::set_executable(Path::new(&path), executable) {
+            log::error!("arquivo", "chmod de '{}' falhou: {}", path, e);
+            return format!("Erro ao mudar permissões de '{}': {}", path, e);
+        }
+        if let Some(sidebar) = &mut self.sidebar {
+            sidebar.refresh();
+        }
+        if executable {
+            format!("'{}' agora é executável", path)
+        } else {
+            format!("'{}' não é mais executável", path)
+        }
+    }
+
+    /// Copy the active file out of the archive it was opened from, beside
+    /// the archive, and open the copy to be edited. A copy extracted before
+    /// is opened as it is.
+    fn extract_file(&mut self) -> io::Result<()> {
+        let Some(path) = self.workspace.active().map(|buf| buf.filename.clone()) else {
+            return Ok(());
+        };
+        let Some((archive, inner)) =
+            archive::split(Path::new(&path)).filter(|(_, inner)| !inner.is_empty())
+        else {
+            self.display
+                .set_message(String::from("O arquivo atual não está dentro de um pacote"));
+            return Ok(());
+        };
+        let Some(target) = archive::extract_path(&archive, &inner) else {
+            self.display
+                .set_message(format!("'{}' sairia da pasta do pacote", inner));
+            return Ok(());
+        };
+        let extracted = target.exists()
+            || archive::read(&archive, &inner)
+                .and_then(|bytes| {
+                    if let Some(dir) = target.parent() {
+                        std::fs::create_dir_all(dir)?;
+                    }
+                    std::fs::write(&target, bytes)
+                })
+                .inspect_err(|e| {
+                    log::error!("pacote", "erro ao extrair '{}': {}", path, e);
+                    self.display
+                        .set_message(format!("Erro ao extrair '{}': {}", inner, e));
+                })
+                .is_ok();
+        if extracted {
+            let target = target.to_string_lossy().to_string();
+            self.open_in_editor(&target)?;
+            self.display
+                .set_message(format!("Extraído para {}", target));
+        }
+        Ok(())
+    }
+
+    /// Go to the test of the current file by its language's convention,
+    /// creating it (from the `[templates]` one and a first test) when
+    /// missing, or from a test back to its source
+    fn test_file(&mut self) -> io::Result<()> {
+        let Some(buf) = self.workspace.active() else {
+            return Ok(());
+        };
+        let path = Path::new(&buf.filename);
+        let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        let (path, created) = match testfile::counterpart(&path, &buf.file_matrix) {
+            None => {
+                self.display.set_message(String::from(
+                    "Sem convenção de testes conhecida para este arquivo",
+                ));
+                return Ok(());
+            }
+            Some(Counterpart::InFile(row)) => {
+                self.jump_to_position(row, 0)?;
+                return Ok(());
+            }
+            Some(Counterpart::Source(path)) if !path.is_file() => {
+                self.display
+                    .set_message(format!("'{}' não existe", path.display()));
+                return Ok(());
+            }
+            Some(Counterpart::Source(path)) => (path, false),
+            Some(Counterpart::Test(path, _)) if path.is_file() => (path, false),
+            Some(Counterpart::Test(path, skeleton)) => {
+                let name = path.to_string_lossy().to_string();
+                let header = snippets::template(&self.config, &path)
+                    .map(|template| snippets::expand(&template, &name))
+                    .unwrap_or_default();
+                let written = path
+                    .parent()
+                    .map_or(Ok(()), std::fs::create_dir_all)
+                    .and_then(|()| {
+                        std::fs::OpenOptions::new()
+                            .write(true)
+                            .create_new(true)
+                            .open(&path)
+                    })
+                    .and_then(|mut file| {
+                        io::Write::write_all(&mut file, (header + &skeleton).as_bytes())
+                    });
+                if let Err(e) = written {
+                    self.display
+                        .set_message(format!("Erro ao criar '{}': {}", path.display(), e));
+                    return Ok(());
+                }
+                log::info!("arquivo", "teste criado {}", path.display());
+                if let Some(sidebar) = &mut self.sidebar {
+                    sidebar.refresh();
+                }
+                (path, true)
+            }
+        };
+        let name = path.to_string_lossy().to_string();
+        self.open_in_editor(&name)?;
+        if created {
+            self.display.set_message(format!("Criado {}", name));
+        }
+        Ok(())
+    }
+
+    /// Report on the character under the cursor, with the combining marks
+    /// and joined parts the cursor steps over along with it
+    fn inspect_char(&self) -> String {
+        let Some(buf) = self.workspace.active() else {
+            return String::new();
+        };
+        if buf.hex_view {
+            return String::from("Na visualização hex os bytes já estão à vista");
+        }
+        let (row, col) = (buf.view.cursor_row, buf.view.cursor_col);
+        let offset = buf.byte_offset(row, col);
+        let Some(line) = buf.file_matrix.get(row) else {
+            return inspect::describe_line_end(None, offset);
+        };
+        if col >= line.len() {
+            let last = row + 1 >= buf.file_matrix.len();
+            return inspect::describe_line_end((!last).then_some(buf.line_ending), offset);
+        }
+        let end = grapheme::next_boundary(line, col);
+        inspect::describe(&line[col..end], offset)
+    }
+
+    /// Show the selected lines big, or without a selection the lines from
+    /// the cursor on. The text stays editable underneath.
+    fn start_presentation(&mut self) {
+        let Some(buf) = self.workspace.active() else {
+            return;
+        };
+        let rows = match buf.view.selection() {
+            Some(((start, _), (end, _))) => (start, end + 1),
+            None => (buf.view.cursor_row, buf.file_matrix.len()),
+        };
+        self.presenting = Some(rows);
+        if self.mode() == EditorMode::Visual {
+            self.set_mode(EditorMode::Normal);
+        }
+        self.sync_display();
+    }
+
+    /// Keys of presentation mode: arrows scroll the lines shown, Esc leaves.
+    /// Returns whether the key was taken.
+    fn handle_presentation_key(&mut self, code: KeyCode) -> bool {
+        let (Some((start, end)), Some(buf)) = (self.presenting, self.workspace.active()) else {
+            return false;
+        };
+        let len = buf.file_matrix.len();
+        self.presenting = match code {
+            KeyCode::Esc => {
+                self.display
+                    .set_message(String::from("Apresentação encerrada"));
+                None
+            }
+            KeyCode::Up if start > 0 => Some((start - 1, end - 1)),
+            KeyCode::Down if end < len => Some((start + 1, end + 1)),
+            KeyCode::Up | KeyCode::Down => Some((start, end)),
+            _ => return false,
+        };
+        self.sync_display();
+        true
+    }
+
+    /// Create the file `name` (a path under `dir`, folders included) empty
+    /// on disk and open it
+    fn create_file(&mut self, dir: &Path, name: &str) -> io::Result<()> {
+        if name.is_empty() {
+            return Ok(());
+        }
+        let path = dir.join(name);
+        let created = path
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|()| {
+                std::fs::OpenOptions::new()
+                    .write(true)
+                    .create_new(true)
+                    .open(&path)
+            });
+        match created {
+            Ok(_) => {
+                log::info!("arquivo", "criado {}", path.display());
+                if let Some(sidebar) = &mut self.sidebar {
+                    sidebar.refresh();
+                }
+                self.open_in_editor(&path.to_string_lossy())?;
+                self.focus = Focus::Editor;
+                if let Some(template) = snippets::template(&self.config, &path) {
+                    let text = snippets::expand(&template, &path.to_string_lossy());
+                    if let Some(buf) = self.workspace.active_mut() {
+                        buf.insert_text(&text, 0, 0);
+                        buf.commit_edits();
+                    }
+                }
+            }
+            Err(e) => {
+                self.display
+                    .set_message(format!("Erro ao criar '{}': {}", path.display(), e))
+            }
+        }
+        Ok(())
+    }
+
+    /// Open the entries of `dir` as lines of a buffer; saving it renames
+    /// the entries whose lines were edited
+    fn start_bulk_rename(&mut self, dir: &Path) {
+        let name = RenameFs::buffer_name(dir);
+        let opened = RenameFs::new(dir).map(Arc::new).and_then(|fs| {
+            let buf = BufferFile::open_in(fs.clone(), &name)?;
+            Ok((fs, buf))
+        });
+        match opened {
+            Ok((fs, buf)) => {
+                self.workspace.open_buffer(buf);
+                self.renames.retain(|(open, _)| *open != name);
+                self.renames.push((name, fs));
+                self.show_welcome = false;
+                self.focus = Focus::Editor;
+                self.display.set_message(String::from(
+                    "Edite os nomes e salve (Ctrl+S) para renomear",
+                ));
+            }
+            Err(e) => {
+                self.display
+                    .set_message(format!("Erro ao listar '{}': {}", dir.display(), e))
+            }
+        }
+        self.sync_display();
+    }
+
+    /// After a bulk rename is saved, refresh the sidebar and point open
+    /// buffers at the new names
+    fn follow_renames(&mut self) {
+        let applied = match self
+            .workspace
+            .active()
+            .and_then(|buf| self.renames.iter().find(|(name, _)| *name == buf.filename))
+        {
+            Some((_, fs)) => fs.take_applied(),
+            None => return,
+        };
+        if applied.is_empty() {
+            return;
+        }
+        for buf in &mut self.workspace.buffers {
+            let Ok(path) = std::path::absolute(&buf.filename) else {
+                continue;
+            };
+            if let Some((from, to)) = applied.iter().find(|(from, _)| path.starts_with(from)) {
+                let rest = path.strip_prefix(from).unwrap_or(Path::new(""));
+                let renamed = if rest.as_os_str().is_empty() {
+                    to.clone()
+                } else {
+                    to.join(rest)
+                };
+                buf.filename = renamed.to_string_lossy().to_string();
+            }
+        }
+        if let Some(sidebar) = &mut self.sidebar {
+            sidebar.refresh();
+        }
+        log::info!("arquivo", "{} item(ns) renomeado(s)", applied.len());
+        self.display
+            .set_message(format!("{} item(ns) renomeado(s)", applied.len()));
+    }
+
+    /// Folder a project-wide search covers: the sidebar's, else the
+    /// working directory
+    fn project_root(&self) -> PathBuf {
+        match &self.sidebar {
+            Some(sidebar) => sidebar.root_path.clone(),
+            None => std::env::current_dir().unwrap_or_else(|_| PathBuf::from(".")),
+        }
+    }
+
+    /// Open `path` (in its tab, if open under another name) at `line`, `col`
+    fn open_at(&mut self, path: &str, line: usize, col: usize) -> io::Result<()> {
+        let file = self
+            .buffer_for_path(path)
+            .unwrap_or_else(|| path.to_string());
+        self.open_in_editor(&file)?;
+        if self
+            .workspace
+            .active()
+            .is_some_and(|buf| buf.filename == file)
+        {
+            self.jump_to_position(line, col)?;
+        }
+        Ok(())
+    }
+
+    /// Go to the definition of the name under the cursor, from the
+    /// project's symbol index. The index is built in the background the
+    /// first time (and for another project folder); saves keep it current.
+    fn goto_definition(&mut self) -> io::Result<()> {
+        let Some(buf) = self.workspace.active() else {
+            return Ok(());
+        };
+        let (row, col) = (buf.view.cursor_row, buf.view.cursor_col);
+        let Some((start, end)) = buf.word_at(row, col) else {
+            self.display
+                .set_message(String::from("Nenhum nome sob o cursor"));
+            return Ok(());
+        };
+        let name: String = buf.file_matrix[row][start..end].iter().collect();
+        let root = std::path::absolute(self.project_root()).unwrap_or_default();
+        if self
+            .symbols
+            .as_ref()
+            .is_some_and(|(indexed, _)| *indexed == root)
+        {
+            return self.show_definitions(&name);
+        }
+        if let Some((id, _, _)) = self.symbol_scan.take() {
+            self.jobs.cancel(id);
+        }
+        let id = symbols::start(&mut self.jobs, &root);
+        self.symbol_scan = Some((id, root, name));
+        Ok(())
+    }
+
+    /// Go to the definition of `name`, or list them when there are several.
+    /// The active file counts as it is in the buffer, unsaved edits included.
+    fn show_definitions(&mut self, name: &str) -> io::Result<()> {
+        self.reindex_active();
+        let Some((root, index)) = &self.symbols else {
+            return Ok(());
+        };
+        let mut found: Vec<&Symbol> = index.iter().filter(|symbol| symbol.name == name).collect();
+        found.sort_by(|a, b| a.path.cmp(&b.path).then(a.line.cmp(&b.line)));
+        let targets: Vec<(String, usize, usize)> = found
+            .iter()
+            .map(|symbol| (symbol.path.clone(), symbol.line, symbol.col))
+            .collect();
+        let rows: Vec<String> = found
+            .iter()
+            .map(|symbol| {
+                let shown = Path::new(&symbol.path)
+                    .strip_prefix(root)
+                    .map(|relative| relative.to_string_lossy().to_string())
+                    .unwrap_or_else(|_| symbol.path.clone());
+                format!("{}:{}  {}", shown, symbol.line + 1, symbol.kind)
+            })
+            .collect();
+        match targets.as_slice() {
+            [] => self
+                .display
+                .set_message(format!("Definição de '{}' não encontrada no projeto", name)),
+            [(path, line, col)] => {
+                let (path, line, col) = (path.clone(), *line, *col);
+                self.open_at(&path, line, col)?;
+            }
+            _ => self.open_prompt(
+                Prompt::list(&format!("Definições de '{}' ({})", name, rows.len()), rows),
+                PromptAction::Locations(targets),
+            ),
+        }
+        Ok(())
+    }
+
+    /// Index the active file again, as it is in the buffer
+    fn reindex_active(&mut self) {
+        let (Some(buf), Some((root, index))) = (self.workspace.active(), &mut self.symbols) else {
+            return;
+        };
+        let Ok(path) = std::path::absolute(&buf.filename) else {
+            return;
+        };
+        if !path.starts_with(&*root) {
+            return;
+        }
+        let path = path.to_string_lossy().to_string();
+        index.retain(|symbol| symbol.path != path);
+        let lines: Vec<String> = buf
+            .file_matrix
+            .iter()
+            .map(|line| line.iter().collect())
+            .collect();
+        index.extend(symbols::scan_lines(&path, lines.iter().map(String::as_str)));
+    }
+
+    /// Ask for the text to search the project for
+    fn open_project_search(&mut self) {
+        self.open_prompt(
+            Prompt::input("Buscar no projeto"),
+            PromptAction::ProjectSearch,
+        );
+    }
+
+    /// Search the project for `query` in the background. The results list
+    /// opens right away, with the open files (searched as they are in their
+    /// buffers), and fills in as the other files are searched.
+    fn start_project_search(&mut self, query: String) {
+        if let Some(search) = self.project_search.take() {
+            self.jobs.cancel(search.job);
+        }
+        let root = std::path::absolute(self.project_root()).unwrap_or_default();
+        let (open, found) = self.search_open_buffers(&root, &query);
+        let (rows, targets) = replace::panel_rows(&found, &root);
+        let search = ProjectSearch {
+            job: replace::start_streaming(&mut self.jobs, &root, &query),
+            query,
+            root,
+            open,
+            count: found.len(),
+        };
+        self.open_prompt(
+            Prompt::list(&search.title(false), rows),
+            PromptAction::SearchResults(targets),
+        );
+        self.project_search = Some(search);
+    }
+
+    /// Add occurrences found by the project search to its list; closing the
+    /// list stops the search
+    fn add_search_results(&mut self, mut found: Vec<replace::Match>) {
+        let Some(search) = &mut self.project_search else {
+            return;
+        };
+        let Some((prompt, PromptAction::SearchResults(targets))) = &mut self.prompt else {
+            self.jobs.cancel(search.job);
+            self.project_search = None;
+            return;
+        };
+        found.retain(|m| !search.open.contains(&m.path));
+        search.count += found.len();
+        let (rows, more) = replace::panel_rows(&found, &search.root);
+        targets.extend(more);
+        prompt.extend(rows);
+        prompt.set_title(&search.title(false));
+    }
+
+    /// The project search is over: its list shows the total, or closes if
+    /// nothing was found
+    fn finish_project_search(&mut self) {
+        let Some(search) = self.project_search.take() else {
+            return;
+        };
+        let Some((prompt, PromptAction::SearchResults(_))) = &mut self.prompt else {
+            return;
+        };
+        if search.count == 0 {
+            self.prompt = None;
+            self.display
+                .set_message(format!("Nenhuma ocorrência de '{}'", search.query));
+        } else {
+            prompt.set_title(&search.title(true));
+        }
+    }
+
+    /// The open buffers under `root`, and the occurrences of `query` in
+    /// them as they are in the editor, unsaved edits included
+    fn search_open_buffers(&self, root: &Path, query: &str) -> (Vec<String>, Vec<replace::Match>) {
+        let mut open = vec![];
+        let mut found = vec![];
+        for buf in &self.workspace.buffers {
+            let Ok(path) = std::path::absolute(&buf.filename) else {
+                continue;
+            };
+            if !path.starts_with(root) || !path.is_file() {
+                continue;
+            }
+            let path = path.to_string_lossy().to_string();
+            let lines: Vec<String> = buf
+                .file_matrix
+                .iter()
+                .map(|line| line.iter().collect())
+                .collect();
+            found.extend(replace::find_in_lines(
+                &path,
+                lines.iter().map(String::as_str),
+                query,
+            ));
+            open.push(path);
+        }
+        replace::sort(&mut found);
+        (open, found)
+    }
+
+    /// Search the project for `query` in the background; the occurrences
+    /// open as a list to review before replacing
+    fn start_project_replace(&mut self, query: String, replacement: String) {
+        if let Some((id, _, _)) = self.replace_scan.take() {
+            self.jobs.cancel(id);
+        }
+        let root = self.project_root();
+        let id = replace::start(&mut self.jobs, &root, &query);
+        self.replace_scan = Some((id, query, replacement));
+    }
+
+    /// Open the occurrences of a replace as an editable list. Open buffers
+    /// are searched as they are in the editor, unsaved edits included.
+    fn show_replace_preview(
+        &mut self,
+        mut found: Vec<replace::Match>,
+        query: &str,
+        replacement: &str,
+    ) {
+        let root = std::path::absolute(self.project_root()).unwrap_or_default();
+        let (open, in_buffers) = self.search_open_buffers(&root, query);
+        found.retain(|m| !open.contains(&m.path));
+        found.extend(in_buffers);
+        replace::sort(&mut found);
+        if found.is_empty() {
+            self.display
+                .set_message(format!("Nenhuma ocorrência de '{}'", query));
+            return;
+        }
+
+        let fs = Arc::new(ReplaceFs::new(&root, query, replacement, found));
+        let name = fs.buffer_name();
+        match BufferFile::open_in(fs.clone(), &name) {
+            Ok(buf) => {
+                // A list left from an earlier replace of the same text is
+                // out of date
+                if let Some(index) = self.workspace.find_buffer(&name) {
+                    self.workspace.buffers[index] = buf;
+                } else {
+                    self.workspace.open_buffer(buf);
+                }
+                self.replaces.retain(|(open, _)| *open != name);
+                self.replaces.push((name, fs));
+                self.show_welcome = false;
+                self.focus = Focus::Editor;
+                self.display.set_message(String::from(
+                    "Revise as ocorrências e salve (Ctrl+S) para substituir",
+                ));
+            }
+            Err(e) => self.display.set_message(format!("Erro: {}", e)),
+        }
+        self.sync_display();
+    }
+
+    /// After a replace list is saved, replace the occurrences it kept: in
+    /// the open buffer when the file is open (as an undoable edit, left
+    /// unsaved), on disk otherwise
+    fn apply_replace(&mut self) {
+        let Some(fs) = self
+            .workspace
+            .active()
+            .and_then(|buf| self.replaces.iter().find(|(name, _)| *name == buf.filename))
+            .map(|(_, fs)| fs.clone())
+        else {
+            return;
+        };
+        let Some(picked) = fs.take_picked() else {
+            return;
+        };
+
+        let mut replaced = 0;
+        let mut files = 0;
+        let mut errors = vec![];
+        for (i, m) in picked.iter().enumerate() {
+            if i > 0 && picked[i - 1].path == m.path {
+                continue;
+            }
+            let in_file: Vec<replace::Match> = picked[i..]
+                .iter()
+                .take_while(|other| other.path == m.path)
+                .cloned()
+                .collect();
+            let open = self.workspace.buffers.iter().position(|buf| {
+                std::path::absolute(&buf.filename).is_ok_and(|path| path == Path::new(&m.path))
+            });
+            let count = match open {
+                Some(index) => {
+                    let buf = &mut self.workspace.buffers[index];
+                    let mut lines = buf.file_matrix.clone();
+                    let count =
+                        replace::apply_to_lines(&mut lines, &in_file, &fs.query, &fs.replacement);
+                    if count > 0 {
+                        let (first, last) = (in_file[0].line, in_file[in_file.len() - 1].line);
+                        let last = last.min(lines.len().saturating_sub(1));
+                        buf.replace_block(first, last + 1 - first, lines[first..=last].to_vec());
+                    }
+                    count
+                }
+                None => match replace_on_disk(&m.path, &in_file, &fs.query, &fs.replacement) {
+                    Ok(count) => count,
+                    Err(e) => {
+                        log::error!("arquivo", "substituir em '{}' falhou: {}", m.path, e);
+                        errors.push(m.path.clone());
+                        0
+                    }
+                },
+            };
+            if count > 0 {
+                replaced += count;
+                files += 1;
+            }
+        }
+        log::info!(
+            "arquivo",
+            "{} ocorrência(s) substituída(s) em {} arquivo(s)",
+            replaced,
+            files
+        );
+        let mut message = format!(
+            "{} ocorrência(s) substituída(s) em {} arquivo(s)",
+            replaced, files
+        );
+        if !errors.is_empty() {
+            message.push_str(&format!("; falhou em {}", errors.join(", ")));
+        }
+        self.display.set_message(message);
+    }
+
+    /// Move a sidebar entry to the system trash; `restore-trash` brings it back
+    fn move_to_trash(&mut self, path: &Path) {
+        match trash::trash(path) {
+            Ok(trashed) => {
+                log::info!("lixeira", "'{}' movido para a lixeira", path.display());
+                self.display.set_message(format!(
+                    "Movido para a lixeira: {} (restore-trash desfaz)",
+                    path.display()
+                ));
+                self.trashed.push(trashed);
+                if let Some(sidebar) = &mut self.sidebar {
+                    sidebar.refresh();
+                }
+            }
+            Err(e) => {
+                log::error!("lixeira", "erro ao mover '{}': {}", path.display(), e);
+                self.display
+                    .set_message(format!("Erro ao mover para a lixeira: {}", e));
+            }
+        }
+    }
+
+    /// Put the last file moved to the trash back in place
+    fn restore_from_trash(&mut self) -> String {
+        let trashed = match self.trashed.pop() {
+            Some(trashed) => trashed,
+            None => return String::from("Nada foi movido para a lixeira nesta sessão"),
+        };
+        match trash::restore(&trashed) {
+            Ok(()) => {
+                if let Some(sidebar) = &mut self.sidebar {
+                    sidebar.refresh();
+                }
+                format!("Restaurado: {}", trashed.original.display())
+            }
+            Err(e) => {
+                let message = format!("Erro ao restaurar '{}': {}", trashed.original.display(), e);
+                // Kept so the restore can be retried after moving the
+                // file in the way
+                if e.kind() == io::ErrorKind::AlreadyExists {
+                    self.trashed.push(trashed);
+                }
+                message
+            }
+        }
+    }
+
+    /// Scan the sidebar's project for TODOs in the background, or the open
+    /// buffers when there is no project
+    fn handle_todos(&mut self) {
+        if let Some(sidebar) = &self.sidebar {
+            if let Some(id) = self.todo_scan.take() {
+                self.jobs.cancel(id);
+            }
+            self.todo_scan = Some(todos::start(&mut self.jobs, &sidebar.root_path));
+            return;
+        }
+
+        let mut found = vec![];
+        for buf in &self.workspace.buffers {
+            let lines: Vec<String> = buf
+                .file_matrix
+                .iter()
+                .map(|line| line.iter().collect())
+                .collect();
+            found.extend(todos::scan_lines(
+                &buf.filename,
+                lines.iter().map(String::as_str),
+            ));
+        }
+        found.sort_by(|a, b| a.path.cmp(&b.path).then(a.line.cmp(&b.line)));
+        self.show_todos(found);
+    }
+
+    fn show_todos(&mut self, found: Vec<Todo>) {
+        if found.is_empty() {
+            self.display
+                .set_message(String::from("Nenhum TODO/FIXME/HACK encontrado"));
+            return;
+        }
+        let root = self
+            .sidebar
+            .as_ref()
+            .map(|sidebar| sidebar.root_path.as_path());
+        let (rows, targets) = todos::panel_rows(&found, root);
+        self.open_prompt(
+            Prompt::list(&format!("TODOs ({})", found.len()), rows),
+            PromptAction::Todos(targets),
+        );
+    }
+
+    /// Open files sent by other processes and tell the ones waiting when
+    /// theirs was closed. Returns whether anything arrived.
+    fn poll_server(&mut self) -> io::Result<bool> {
+        let requests: Vec<_> = self.servers.iter().flat_map(Server::poll).collect();
+        let arrived = !requests.is_empty();
+        for request in requests {
+            log::info!("servidor", "recebido '{}'", request.path);
+            let file = self
+                .buffer_for_path(&request.path)
+                .unwrap_or(request.path.clone());
+            self.show_welcome = false;
+            self.focus = Focus::Editor;
+            self.open_in_editor(&file)?;
+            match request.waiter {
+                Some(waiter) if self.workspace.find_buffer(&file).is_some() => {
+                    self.waiters.push((file, waiter));
+                    self.display.set_message(format!(
+                        "{} aberto por outro processo: feche a aba (Ctrl+W) para devolver",
+                        request.path
+                    ));
+                }
+                // Couldn't be opened: nothing to wait for
+                Some(waiter) => waiter.done(),
+                None => {}
+            }
+        }
+
+        let (open, closed): (Vec<_>, Vec<_>) = std::mem::take(&mut self.waiters)
+            .into_iter()
+            .partition(|(file, _)| self.workspace.find_buffer(file).is_some());
+        self.waiters = open;
+        for (_, waiter) in closed {
+            waiter.done();
+        }
+        Ok(arrived)
+    }
+
+    /// Share the active buffer on `port` (0 picks a free one), on this
+    /// machine only unless an address comes first (`0.0.0.0:7878`)
+    fn collab_host(&mut self, input: &str) {
+        let (address, port) = input
+            .rsplit_once(':')
+            .unwrap_or((collab::DEFAULT_ADDRESS, input));
+        // "[::1]:7878", as IPv6 addresses are written with a port
+        let address = address.trim_start_matches('[').trim_end_matches(']');
+        let Ok(port) = port.parse::<u16>() else {
+            self.display
+                .set_message(format!("Porta inválida: {}", port));
+            return;
+        };
+        let Some(buf) = self.workspace.active() else {
+            return;
+        };
+        match Session::host(address, port, &buf.filename, &buf.file_matrix) {
+            Ok(session) => {
+                let join = format!(
+                    "{}@{}",
+                    session.token().unwrap_or_default(),
+                    session.join_address().unwrap_or_default()
+                );
+                let lines = vec![
+                    format!("'{}' compartilhado (experimental)", buf.short_name()),
+                    format!("Para entrar: collab-join {}", join),
+                    String::from("Copiar? (c)"),
+                ];
+                self.collab = Some(session);
+                self.open_prompt(
+                    Prompt::confirm("Sessão compartilhada", lines, &['c']),
+                    PromptAction::CollabShared(join),
+                );
+            }
+            Err(e) => self
+                .display
+                .set_message(format!("Erro ao compartilhar na porta {}: {}", port, e)),
+        }
+    }
+
+    /// Join the session at `input` (`código@host:porta`), opening its text
+    /// in a new tab. Without a port, the default one is used.
+    fn collab_join(&mut self, input: &str) {
+        if input.is_empty() {
+            return;
+        }
+        let Some((token, address)) = input.split_once('@') else {
+            self.display
+                .set_message(String::from("Falta o código da sessão (código@host:porta)"));
+            return;
+        };
+        let address = if address.contains(':') && !address.ends_with(']') {
+            address.to_string()
+        } else {
+            format!("{}:{}", address, collab::DEFAULT_PORT)
+        };
+        match Session::join(&address, token) {
+            Ok((session, lines)) => {
+                let buf = BufferFile::with_lines(Arc::new(CollabFs), &session.buffer, lines);
+                self.display
+                    .set_message(format!("Editando '{}' em conjunto", buf.filename));
+                self.workspace.open_buffer(buf);
+                self.collab = Some(session);
+                self.show_welcome = false;
+                self.focus = Focus::Editor;
+                self.sync_display();
+            }
+            Err(e) => self
+                .display
+                .set_message(format!("Erro ao entrar em {}: {}", address, e)),
+        }
+    }
+
+    /// Trade edits and cursors with the other participants of the shared
+    /// buffer
+    fn poll_collab(&mut self) {
+        let Some(session) = &mut self.collab else {
+            return;
+        };
+        let Some(index) = self.workspace.find_buffer(&session.buffer) else {
+            // Tab closed
+            self.collab = None;
+            self.display
+                .set_message(String::from("Sessão compartilhada encerrada"));
+            return;
+        };
+        let buf = &mut self.workspace.buffers[index];
+        let sync = session.sync(&buf.file_matrix, (buf.view.cursor_row, buf.view.cursor_col));
+        for change in sync.changes {
+            buf.replace_external(change.row, change.removed, change.lines);
+        }
+        if !session.is_host() {
+            // The host is the one who saves
+            buf.modified = false;
+        }
+
+        let message = if sync.ended {
+            self.collab = None;
+            Some(String::from("O anfitrião encerrou a sessão compartilhada"))
+        } else if sync.conflict {
+            Some(String::from(
+                "Alteração descartada: outra pessoa editou as mesmas linhas",
+            ))
+        } else if sync.joined > 0 {
+            Some(String::from("Alguém entrou na sessão compartilhada"))
+        } else if sync.left > 0 {
+            Some(String::from("Alguém saiu da sessão compartilhada"))
+        } else {
+            None
+        };
+        if let Some(message) = message {
+            self.display.set_message(message);
+        }
+        if sync.received {
+            self.sync_display();
+        }
+    }
+
+    /// Work deferred until input pauses: highlight the whole file again
+    /// (the frame only carries the state forward from where it last
+    /// started), refresh git's changed files and reread sidebar folders
+    /// that changed on disk
+    fn run_idle_tasks(&mut self) {
+        let started = Instant::now();
+        self.display.rehighlight();
+        if let Some(sidebar) = &mut self.sidebar {
+            if sidebar.is_changed_filter_on() {
+                match git::changed_files(&sidebar.root_path) {
+                    Ok(files) => sidebar.refresh_changed_files(files),
+                    Err(e) => log::debug!("ocioso", "git status falhou: {}", e),
+                }
+            }
+            sidebar.refresh_if_changed();
+        }
+        self.autosave();
+        log::debug!("ocioso", "tarefas em {:?}", started.elapsed());
+    }
+
+    /// Save the modified files whose options have `autosave` on. Files
+    /// saved through the terminal (sudo, age) or whose saving does more
+    /// than write them (bulk rename, project replace, followed logs) are
+    /// left to Ctrl+S.
+    fn autosave(&mut self) {
+        let mut failed = None;
+        for buf in &mut self.workspace.buffers {
+            let name = buf.filename.as_str();
+            let special = self.renames.iter().any(|(n, _)| n == name)
+                || self.replaces.iter().any(|(n, _)| n == name)
+                || self.follows.iter().any(|f| f.path == name);
+            let wanted = buf.modified
+                && !buf.read_only
+                && !special
+                && !buf.fs.needs_terminal()
+                && Path::new(name).is_file()
+                && self.options.with(&buf.local_options).autosave;
+            if !wanted {
+                continue;
+            }
+            match buf.save() {
+                Ok(()) => self.saved = true,
+                Err(e) => failed = Some((buf.filename.clone(), e)),
+            }
+        }
+        if let Some((path, e)) = failed {
+            self.report_save_error(&path, &e);
+        }
+    }
+
+    /// Name of the open buffer showing the file at `path`, even when it was
+    /// opened through another path
+    fn buffer_for_path(&self, path: &str) -> Option<String> {
+        let wanted = std::fs::canonicalize(path).ok()?;
+        self.workspace
+            .buffers
+            .iter()
+            .find(|buf| std::fs::canonicalize(&buf.filename).is_ok_and(|p| p == wanted))
+            .map(|buf| buf.filename.clone())
+    }
+
+    /// Save the cursor position of the active file as global mark `name`
+    fn set_mark(&mut self, name: char) {
+        if !marks::is_global(name) {
+            self.display
+                .set_message(String::from("Use uma letra maiúscula (mA…mZ) para a marca"));
+            return;
+        }
+        let Some(buf) = self.workspace.active() else {
+            return;
+        };
+        let (row, col) = (buf.view.cursor_row, buf.view.cursor_col);
+        let message = match marks::set(name, &buf.filename, row, col) {
+            Ok(()) => format!("Marca {} em {}:{}", name, buf.short_name(), row + 1),
+            Err(e) => {
+                log::warning!("marcas", "erro ao gravar a marca {}: {}", name, e);
+                format!("Erro ao gravar a marca {}: {}", name, e)
+            }
+        };
+        self.display.set_message(message);
+    }
+
+    /// Open the file of global mark `name` at its position. An open buffer
+    /// of the same file is reused even if it was opened by another path.
+    fn jump_to_mark(&mut self, name: char) -> io::Result<()> {
+        let Some(mark) = marks::get(name) else {
+            self.display
+                .set_message(format!("Marca {} não definida", name));
+            return Ok(());
+        };
+        let file = self
+            .buffer_for_path(&mark.path)
+            .unwrap_or(mark.path.clone());
+        self.open_in_editor(&file)?;
+        if self
+            .workspace
+            .active()
+            .is_some_and(|buf| buf.filename == file)
+        {
+            self.jump_to_position(mark.row, mark.col)?;
+        }
+        Ok(())
+    }
+
+    fn handle_marks(&mut self) {
+        let saved = marks::load();
+        if saved.is_empty() {
+            self.display
+                .set_message(String::from("Nenhuma marca global (use mA…mZ)"));
+            return;
+        }
+        let home = std::env::var("HOME").unwrap_or_default();
+        let rows = saved
+            .iter()
+            .map(|mark| {
+                let shown = match mark.path.strip_prefix(&home) {
+                    Some(rest) if !home.is_empty() && rest.starts_with('/') => format!("~{}", rest),
+                    _ => mark.path.clone(),
+                };
+                format!("{}  {}:{}", mark.name, shown, mark.row + 1)
+            })
+            .collect();
+        self.open_prompt(
+            Prompt::list("Marcas", rows),
+            PromptAction::Marks(saved.iter().map(|mark| mark.name).collect()),
+        );
+    }
+
+    /// Pick one of the last yanked or deleted texts to paste
+    fn handle_paste_history(&mut self) {
+        if self.kill_ring.entries().is_empty() {
+            self.display
+                .set_message(String::from("Nada copiado ainda (y ou d no modo Visual)"));
+            return;
+        }
+        let rows = self
+            .kill_ring
+            .entries()
+            .iter()
+            .map(|text| {
+                let first = text.lines().next().unwrap_or_default();
+                match text.lines().count() {
+                    0 | 1 => first.to_string(),
+                    lines => format!("{}  (+{} linhas)", first, lines - 1),
                 }
-                QuitAction::Discard => return Ok(true),
-                QuitAction::Cancel => {
-                    self.sync_display();
-                    self.render();
-                    return Ok(false);
+            })
+            .collect();
+        self.open_prompt(
+            Prompt::list("Histórico de cópias", rows),
+            PromptAction::PasteHistory,
+        );
+    }
+
+    /// Pick one of the `[snippets]` to insert at the cursor
+    fn handle_snippet(&mut self) {
+        let snippets = snippets::snippets(&self.config);
+        if snippets.is_empty() {
+            self.display
+                .set_message(String::from("Nenhum trecho em [snippets] no config.toml"));
+            return;
+        }
+        let rows = snippets
+            .iter()
+            .map(|(name, text)| format!("{:<12} {}", name, text.lines().next().unwrap_or_default()))
+            .collect();
+        let texts = snippets.into_iter().map(|(_, text)| text).collect();
+        self.open_prompt(
+            Prompt::list("Trechos", rows).fuzzy(),
+            PromptAction::Snippet(texts),
+        );
+    }
+
+    /// Copy the selection to the kill ring (`y`) or cut it there (`d`)
+    fn yank_selection(&mut self, cut: bool) {
+        if cut && self.active_read_only() {
+            return;
+        }
+        let Some(buf) = self.workspace.active_mut() else {
+            return;
+        };
+        let private = buf.fs.is_private();
+        let Some((start, end)) = buf.view.selection() else {
+            return;
+        };
+        let text = buf.selected_text(start, end);
+        if cut {
+            buf.replace_selection(start, end, "");
+        }
+        (buf.view.cursor_row, buf.view.cursor_col) = start;
+        buf.clamp_cursor();
+        buf.view.preferred_col = buf.view.cursor_col;
+        let count = text.chars().count();
+        self.kill_ring.push(text, private);
+        self.set_mode(EditorMode::Normal);
+        self.display.set_message(format!(
+            "{} caractere(s) {}",
+            count,
+            if cut { "recortado(s)" } else { "copiado(s)" }
+        ));
+    }
+
+    /// Copy the selection, or the cursor's line without one, to the system
+    /// clipboard and the kill ring (`Ctrl+C`), or cut it there (`Ctrl+X`)
+    fn copy_to_clipboard(&mut self, cut: bool) {
+        if cut && self.active_read_only() {
+            return;
+        }
+        let Some(buf) = self.workspace.active_mut() else {
+            return;
+        };
+        let private = buf.fs.is_private();
+        let (text, what) = match buf.view.selection() {
+            Some((start, end)) => {
+                let text = buf.selected_text(start, end);
+                if cut {
+                    buf.replace_selection(start, end, "");
+                    (buf.view.cursor_row, buf.view.cursor_col) = start;
+                }
+                let what = format!("{} caractere(s)", text.chars().count());
+                (text, what)
+            }
+            None => {
+                let row = buf.view.cursor_row;
+                let text = format!("{}\n", buf.file_matrix[row].iter().collect::<String>());
+                if cut {
+                    buf.replace_block(row, 1, vec![]);
+                    buf.view.cursor_col = 0;
                 }
+                (text, String::from("1 linha"))
+            }
+        };
+        buf.clamp_cursor();
+        buf.view.preferred_col = buf.view.cursor_col;
+        self.kill_ring.push(text.clone(), private);
+        if self.mode() == EditorMode::Visual {
+            self.set_mode(EditorMode::Normal);
+        }
+        let done = if cut { "recortado(s)" } else { "copiado(s)" };
+        match clipboard::copy(&text) {
+            Ok(via) => {
+                log::info!("clipboard", "{} {} via {}", what, done, via);
+                self.display
+                    .set_message(format!("{} {} para a área de transferência", what, done));
+            }
+            Err(e) => {
+                log::warning!("clipboard", "erro ao copiar: {}", e);
+                self.display.set_message(format!(
+                    "{} {} só no editor (área de transferência: {})",
+                    what, done, e
+                ));
             }
         }
-        Ok(true)
     }
 
-    fn confirm_quit(&self) -> io::Result<QuitAction> {
-        let (_columns, rows) = terminal::size()?;
-        let prompt = " Arquivos modificados! (s)alvar, (n)ão salvar, (c)ancelar: ";
+    /// Insert the system clipboard at the cursor (`Ctrl+V`), over the
+    /// selection if there's one; without a clipboard, the last text yanked
+    fn paste_from_clipboard(&mut self) -> io::Result<()> {
+        if self.active_read_only() {
+            return Ok(());
+        }
+        let text = match clipboard::paste() {
+            Ok(text) => text,
+            Err(e) => {
+                log::warning!("clipboard", "erro ao colar: {}", e);
+                match self.kill_ring.latest() {
+                    Some(text) => text.to_string(),
+                    None => {
+                        self.display
+                            .set_message(format!("Nada para colar (área de transferência: {})", e));
+                        return Ok(());
+                    }
+                }
+            }
+        };
+        if text.is_empty() {
+            return Ok(());
+        }
+        let Some(buf) = self.workspace.active_mut() else {
+            return Ok(());
+        };
+        if let Some((start, end)) = buf.view.selection() {
+            buf.replace_selection(start, end, "");
+            (buf.view.cursor_row, buf.view.cursor_col) = start;
+        }
+        let (end_col, end_row) = buf.insert_text(&text, buf.view.cursor_col, buf.view.cursor_row);
+        buf.commit_edits();
+        match self.mode() {
+            EditorMode::Insert => self.jump_to_position(end_row, end_col),
+            EditorMode::Visual => {
+                self.set_mode(EditorMode::Normal);
+                self.jump_to_position(end_row, end_col.saturating_sub(1))
+            }
+            EditorMode::Normal => self.jump_to_position(end_row, end_col.saturating_sub(1)),
+        }
+    }
 
-        execute!(
-            io::stdout(),
-            cursor::MoveTo(0, rows - 1),
-            style::SetBackgroundColor(style::Color::Rgb {
-                r: 80,
-                g: 30,
-                b: 30,
-            }),
-            style::SetForegroundColor(style::Color::Rgb {
-                r: 255,
-                g: 220,
-                b: 220,
-            }),
-        )?;
+    /// Complete the path typed in the string the cursor is in (Ctrl+Space
+    /// in Insert mode): relative ones from the file's folder, or the
+    /// current one. A single name, or the start all of them share, goes in
+    /// right away; otherwise they're listed to pick from.
+    fn complete_path(&mut self) -> io::Result<()> {
+        if self.active_read_only() {
+            return Ok(());
+        }
+        let Some(buf) = self.workspace.active() else {
+            return Ok(());
+        };
+        let (row, col) = (buf.view.cursor_row, buf.view.cursor_col);
+        let line = &buf.file_matrix[row];
+        if syntax::context_at(&buf.file_matrix, &buf.syntax_ext(), (row, col)) != Context::String {
+            self.display.set_message(String::from(
+                "Ctrl+Espaço completa caminhos dentro de strings",
+            ));
+            return Ok(());
+        }
+        let quote = line[..col.min(line.len())]
+            .iter()
+            .rposition(|c| matches!(c, '"' | '\'' | '`'))
+            .map_or(0, |i| i + 1);
+        let typed: String = line[quote..col.min(line.len())].iter().collect();
+        if !(typed.contains('/') || typed.starts_with(['.', '~'])) {
+            self.display
+                .set_message(format!("'{}' não parece um caminho", typed));
+            return Ok(());
+        }
+        let (dir, start) = match typed.rfind('/') {
+            Some(slash) => (&typed[..=slash], &typed[slash + 1..]),
+            None => ("", typed.as_str()),
+        };
+        let expanded = PathBuf::from(expand::expand(dir));
+        let folder = if expanded.is_absolute() {
+            expanded
+        } else {
+            let beside = Path::new(&buf.filename)
+                .parent()
+                .unwrap_or(Path::new(""))
+                .join(&expanded);
+            if beside.is_dir() {
+                beside
+            } else {
+                expanded
+            }
+        };
+        let folder = if folder.as_os_str().is_empty() {
+            PathBuf::from(".")
+        } else {
+            folder
+        };
+        let names = expand::names_starting(&folder, start);
+        let typed_len = start.chars().count();
 
-        for _ in 0.._columns {
-            write!(io::stdout(), " ")?;
+        let shared = names
+            .iter()
+            .skip(1)
+            .fold(names.first().map(|name| name.as_str()), |shared, name| {
+                let shared = shared?;
+                let len: usize = shared
+                    .chars()
+                    .zip(name.chars())
+                    .take_while(|(a, b)| a == b)
+                    .map(|(a, _)| a.len_utf8())
+                    .sum();
+                Some(&shared[..len])
+            })
+            .unwrap_or_default();
+        match names.len() {
+            0 => self
+                .display
+                .set_message(format!("Nenhum caminho começa com '{}'", typed)),
+            1 => self.insert_completion(&names[0], typed_len)?,
+            _ if shared.chars().count() > typed_len => {
+                let shared = shared.to_string();
+                self.insert_completion(&shared, typed_len)?
+            }
+            _ => {
+                let title = format!("Caminhos em {}", if dir.is_empty() { "." } else { dir });
+                self.open_prompt(
+                    Prompt::list(&title, names.clone()).fuzzy(),
+                    PromptAction::CompletePath(names, typed_len),
+                );
+            }
         }
+        Ok(())
+    }
 
-        execute!(io::stdout(), cursor::MoveTo(0, rows - 1))?;
-        write!(io::stdout(), "{}", prompt)?;
-        io::stdout().flush()?;
-        execute!(io::stdout(), style::ResetColor)?;
+    /// Type the rest of `name` at the cursor, past the `typed` chars of it
+    /// already there
+    fn insert_completion(&mut self, name: &str, typed: usize) -> io::Result<()> {
+        let rest: String = name.chars().skip(typed).collect();
+        let Some(buf) = self.workspace.active_mut() else {
+            return Ok(());
+        };
+        let (end_col, end_row) = buf.insert_text(&rest, buf.view.cursor_col, buf.view.cursor_row);
+        buf.commit_edits();
+        self.jump_to_position(end_row, end_col)
+    }
 
-        loop {
-            if let Event::Key(key) = event::read()? {
-                match key.code {
-                    KeyCode::Char('s') | KeyCode::Char('S') => return Ok(QuitAction::Save),
-                    KeyCode::Char('n') | KeyCode::Char('N') => return Ok(QuitAction::Discard),
-                    KeyCode::Char('c') | KeyCode::Char('C') | KeyCode::Esc => {
-                        return Ok(QuitAction::Cancel)
-                    }
-                    _ => {}
+    /// Shift the lines of the selection right by `tab_width` spaces (`>`),
+    /// or left by as many as they have up to that, or a tab (`<`). Blank
+    /// lines stay empty.
+    fn indent_selection(&mut self, outdent: bool) {
+        if self.active_read_only() {
+            return;
+        }
+        let width = self.options().tab_width;
+        let Some(buf) = self.workspace.active_mut() else {
+            return;
+        };
+        let Some((start, end)) = buf.view.selection() else {
+            return;
+        };
+        let lines: Vec<Vec<char>> = buf.file_matrix[start.0..=end.0]
+            .iter()
+            .map(|line| {
+                if line.iter().all(|c| c.is_whitespace()) {
+                    line.clone()
+                } else if !outdent {
+                    std::iter::repeat_n(' ', width)
+                        .chain(line.iter().copied())
+                        .collect()
+                } else if line.first() == Some(&'\t') {
+                    line[1..].to_vec()
+                } else {
+                    let spaces = line.iter().take(width).take_while(|&&c| c == ' ').count();
+                    line[spaces..].to_vec()
                 }
+            })
+            .collect();
+        let count = lines.len();
+        buf.replace_block(start.0, count, lines);
+        buf.view.cursor_row = start.0;
+        buf.view.cursor_col = buf.file_matrix[start.0]
+            .iter()
+            .take_while(|c| c.is_whitespace())
+            .count();
+        buf.clamp_cursor();
+        buf.view.preferred_col = buf.view.cursor_col;
+        self.set_mode(EditorMode::Normal);
+        self.display.set_message(format!(
+            "{} linha(s) {}",
+            count,
+            if outdent {
+                "desindentada(s)"
+            } else {
+                "indentada(s)"
             }
+        ));
+    }
+
+    /// Insert `text` just after the cursor, leaving the cursor on its last
+    /// char (`p`)
+    fn paste_after_cursor(&mut self, text: &str) -> io::Result<()> {
+        if self.active_read_only() || text.is_empty() {
+            return Ok(());
         }
+        let Some(buf) = self.workspace.active_mut() else {
+            return Ok(());
+        };
+        let row = buf.view.cursor_row;
+        let col = (buf.view.cursor_col + 1).min(buf.get_line_length(row));
+        let (end_col, end_row) = buf.insert_text(text, col, row);
+        buf.commit_edits();
+        self.jump_to_position(end_row, end_col.saturating_sub(1))
     }
 
-    // --- Open file prompt ---
-    fn handle_open_file(&mut self) -> io::Result<()> {
-        let (_columns, rows) = terminal::size()?;
-        let prompt = " Abrir arquivo: ";
+    /// Pick a file from the ones opened in this and earlier sessions
+    fn handle_old_files(&mut self) {
+        let old_files = history::load();
+        if old_files.is_empty() {
+            self.display
+                .set_message(String::from("Histórico de arquivos vazio"));
+            return;
+        }
+        let home = std::env::var("HOME").unwrap_or_default();
+        let shown: Vec<String> = old_files
+            .iter()
+            .map(|old| match old.path.strip_prefix(&home) {
+                Some(rest) if !home.is_empty() && rest.starts_with('/') => format!("~{}", rest),
+                _ => old.path.clone(),
+            })
+            .collect();
+        let width = shown
+            .iter()
+            .map(|path| path.chars().count())
+            .max()
+            .unwrap_or(0);
+        let rows = shown
+            .iter()
+            .zip(&old_files)
+            .map(|(path, old)| format!("{:<width$}  {}", path, git::age(old.time)))
+            .collect();
+        let paths = old_files.into_iter().map(|old| old.path).collect();
+        self.open_prompt(
+            Prompt::list("Arquivos recentes", rows).fuzzy(),
+            PromptAction::OldFiles(paths),
+        );
+    }
 
-        execute!(
-            io::stdout(),
-            cursor::MoveTo(0, rows - 1),
-            style::SetBackgroundColor(style::Color::Rgb {
-                r: 25,
-                g: 35,
-                b: 50,
-            }),
-            style::SetForegroundColor(style::Color::Rgb {
-                r: 200,
-                g: 220,
-                b: 255,
-            }),
-        )?;
+    /// Pick a folder for the sidebar: the bookmarks of `[bookmarks]`, then
+    /// the folders opened most often
+    fn handle_sidebar_jump(&mut self) {
+        let home = std::env::var("HOME").unwrap_or_default();
+        let shown = |path: &Path| {
+            let path = path.to_string_lossy().to_string();
+            match path.strip_prefix(&home) {
+                Some(rest) if !home.is_empty() && rest.starts_with('/') => format!("~{}", rest),
+                _ => path,
+            }
+        };
+        let mut rows = vec![];
+        let mut dirs = vec![];
+        for (name, path) in self.config.section("bookmarks") {
+            let path = PathBuf::from(expand::expand(path));
+            rows.push(format!("★ {}  {}", name, shown(&path)));
+            dirs.push(path);
+        }
+        for recent in history::load_dirs() {
+            if !dirs.contains(&recent.path) {
+                rows.push(format!(
+                    "{}  {}",
+                    shown(&recent.path),
+                    git::age(recent.time)
+                ));
+                dirs.push(recent.path);
+            }
+        }
+        if dirs.is_empty() {
+            self.display.set_message(String::from(
+                "Nenhuma pasta recente nem favorita ([bookmarks] no config.toml)",
+            ));
+            return;
+        }
+        self.open_prompt(
+            Prompt::list("Ir para a pasta", rows).fuzzy(),
+            PromptAction::SidebarJump(dirs),
+        );
+    }
+
+    /// Show `dir` in the sidebar: opened in the tree when it's inside the
+    /// root, as the new root otherwise
+    fn jump_to_dir(&mut self, dir: &Path) {
+        let dir = &std::fs::canonicalize(dir).unwrap_or_else(|_| dir.to_path_buf());
+        if !dir.is_dir() {
+            self.display
+                .set_message(format!("Pasta não encontrada: {}", dir.display()));
+            return;
+        }
+        if let Some(id) = self.sidebar_search.take() {
+            self.jobs.cancel(id);
+        }
+        history::record_dir(dir);
+        match &mut self.sidebar {
+            Some(sidebar) if dir.starts_with(&sidebar.root_path) && !sidebar.loading => {
+                sidebar.reveal(dir);
+                sidebar.visible = true;
+            }
+            _ => {
+                let mut sidebar = Sidebar::new(dir.to_path_buf());
+                if let Some(old) = &self.sidebar {
+                    sidebar.width = old.width;
+                    sidebar.compact = old.compact;
+                } else {
+                    sidebar.fit_width(self.display.columns);
+                }
+                if let Some(id) = self.tree_load.take() {
+                    self.jobs.cancel(id);
+                }
+                self.tree_load = Some(sidebar.start_load(&mut self.jobs));
+                self.sidebar = Some(sidebar);
+            }
+        }
+        self.focus = Focus::Sidebar;
+        self.sync_display();
+    }
 
-        for _ in 0.._columns {
-            write!(io::stdout(), " ")?;
+    fn undo_redo(&mut self, undo: bool) {
+        let done =
+            self.workspace
+                .active_mut()
+                .is_some_and(|buf| if undo { buf.undo() } else { buf.redo() });
+        if !done {
+            self.display.set_message(String::from(if undo {
+                "Nada para desfazer"
+            } else {
+                "Nada para refazer"
+            }));
         }
+        self.sync_display();
+    }
 
-        execute!(io::stdout(), cursor::MoveTo(0, rows - 1))?;
-        write!(io::stdout(), "{}", prompt)?;
-        io::stdout().flush()?;
+    /// Re-read the active file from disk, keeping undo history and cursor
+    fn handle_reload(&mut self) {
+        if self.workspace.active().is_some_and(|b| b.modified) {
+            self.open_prompt(
+                Prompt::confirm(
+                    "Recarregar",
+                    vec![String::from(
+                        "Descartar alterações e recarregar? (s)im, (n)ão",
+                    )],
+                    &['s', 'n'],
+                ),
+                PromptAction::Reload,
+            );
+        } else {
+            self.reload_active();
+        }
+    }
 
-        let mut input = String::new();
-        loop {
-            if let Event::Key(key) = event::read()? {
-                match key.code {
-                    KeyCode::Enter => {
-                        execute!(io::stdout(), style::ResetColor)?;
-                        let path = input.trim().to_string();
-                        if !path.is_empty() && std::path::Path::new(&path).exists() {
-                            self.workspace.open_file(&path);
-                            self.show_welcome = false;
-                            self.mode = EditorMode::Normal;
-                            self.focus = Focus::Editor;
+    fn reload_active(&mut self) {
+        let needs_terminal = match self.workspace.active() {
+            Some(buf) => buf.fs.needs_terminal(),
+            None => return,
+        };
+        let result = self
+            .with_terminal_if(needs_terminal, |editor| {
+                match editor.workspace.active_mut() {
+                    Some(buf) => buf.reload(),
+                    None => Ok(false),
+                }
+            })
+            .and_then(|result| result);
+        self.display.set_message(match result {
+            Ok(true) => String::from("Arquivo recarregado"),
+            Ok(false) => String::from("Arquivo sem alterações no disco"),
+            Err(e) => {
+                log::error!("arquivo", "erro ao recarregar: {}", e);
+                format!("Erro ao recarregar: {}", e)
+            }
+        });
+        self.sync_display();
+    }
+
+    /// Jump to "linha" or "linha:coluna", both 1-based
+    fn goto_line(&mut self, input: &str) -> io::Result<()> {
+        let mut parts = input.trim().splitn(2, ':');
+        let row = parts.next().and_then(|r| r.trim().parse::<usize>().ok());
+        let col = parts
+            .next()
+            .map(|c| c.trim().parse::<usize>().ok())
+            .unwrap_or(Some(1));
+
+        match (row, col) {
+            (Some(row), Some(col)) if row > 0 => {
+                self.jump_to_position(row - 1, col.saturating_sub(1))
+            }
+            _ => {
+                self.display
+                    .set_message(format!("Linha inválida: '{}'", input.trim()));
+                Ok(())
+            }
+        }
+    }
+
+    fn handle_set_language(&mut self) {
+        let names = syntax::LANGUAGES
+            .iter()
+            .map(|(name, _)| name.to_string())
+            .collect();
+        self.open_prompt(Prompt::list("Linguagem", names), PromptAction::SetLanguage);
+    }
+
+    /// Offer line ending and BOM conversions (status bar encoding click)
+    fn handle_encoding_options(&mut self) {
+        self.open_prompt(
+            Prompt::confirm(
+                "Converter",
+                vec![String::from("(l)f, (c)rlf, (a)dicionar BOM, (r)emover BOM")],
+                &['l', 'c', 'a', 'r'],
+            ),
+            PromptAction::Convert,
+        );
+    }
+
+    // --- Mouse ---
+    fn handle_mouse(&mut self, mouse: MouseEvent) -> io::Result<()> {
+        match mouse.kind {
+            MouseEventKind::Down(MouseButton::Left) => {
+                self.display.set_message(String::new());
+
+                if mouse.row == self.display.rows.saturating_sub(1) {
+                    let action = match self.display.status_segment_at(mouse.column) {
+                        Some(StatusSegment::Position) => Command::GotoLine,
+                        Some(StatusSegment::Language) => Command::SetLanguage,
+                        Some(StatusSegment::Jobs) => Command::CancelJobs,
+                        Some(StatusSegment::Encoding) | Some(StatusSegment::LineEnding) => {
+                            if self.workspace.has_files() {
+                                self.handle_encoding_options();
+                            }
                             self.sync_display();
-                            self.render();
-                            self.position_cursor_at_start();
+                            return Ok(());
+                        }
+                        None => return Ok(()),
+                    };
+                    self.execute_command(action)?;
+                    self.sync_display();
+                    return Ok(());
+                }
+
+                if let Some(sidebar) = self
+                    .sidebar
+                    .as_mut()
+                    .filter(|sidebar| mouse.column < sidebar.sidebar_offset())
+                {
+                    self.focus = Focus::Sidebar;
+                    if mouse.row == 0 {
+                        if let Some(button) = sidebar.header_button_at(mouse.column) {
+                            self.execute_command(header_command(button))?;
+                        }
+                    } else if let Some(index) = sidebar.entry_at_row(mouse.row) {
+                        // Like Enter: folders open and close, files open
+                        sidebar.selected_index = index;
+                        if sidebar.is_selected_dir() {
+                            if let Some(dir) = sidebar.toggle_selected_dir() {
+                                history::record_dir(&dir);
+                            }
+                        } else if let Some(path) = sidebar.get_selected_path() {
+                            self.open_in_editor(&path.to_string_lossy())?;
                         } else {
-                            self.sync_display();
-                            self.render();
+                            sidebar.show_more();
                         }
-                        return Ok(());
                     }
-                    KeyCode::Esc => {
-                        execute!(io::stdout(), style::ResetColor)?;
+                    self.sync_display();
+                    return Ok(());
+                }
+                if self.show_welcome {
+                    return Ok(());
+                }
+                if let Some(row) = self.display.gutter_row_at(mouse.column, mouse.row) {
+                    if self.workspace.has_files() {
+                        self.focus = Focus::Editor;
+                        self.gutter_drag = Some(row);
+                        self.select_lines(row, row);
+                    }
+                    return Ok(());
+                }
+                if let Some((row, col)) = self.display.buffer_position_at(mouse.column, mouse.row) {
+                    if let Some(buf) = self.workspace.active_mut() {
+                        buf.view.cursor_row = row;
+                        buf.view.cursor_col = col;
+                        buf.clamp_cursor();
+                        buf.view.preferred_col = buf.view.cursor_col;
+                        self.focus = Focus::Editor;
                         self.sync_display();
-                        self.render();
-                        return Ok(());
                     }
-                    KeyCode::Char(c) => {
-                        input.push(c);
-                        write!(io::stdout(), "{}", c)?;
-                        io::stdout().flush()?;
-                    }
-                    KeyCode::Backspace => {
-                        if !input.is_empty() {
-                            input.pop();
-                            execute!(io::stdout(), cursor::MoveLeft(1))?;
-                            write!(io::stdout(), " ")?;
-                            execute!(io::stdout(), cursor::MoveLeft(1))?;
-                            io::stdout().flush()?;
-                        }
+                    if mouse.modifiers.contains(KeyModifiers::CONTROL) {
+                        self.open_link_at_cursor()?;
                     }
-                    _ => {}
                 }
             }
+            MouseEventKind::Drag(MouseButton::Left) => {
+                if let (Some(anchor), Some(row)) =
+                    (self.gutter_drag, self.display.file_row_at(mouse.row))
+                {
+                    self.select_lines(anchor, row);
+                }
+            }
+            MouseEventKind::Up(MouseButton::Left) => self.gutter_drag = None,
+            MouseEventKind::Moved => self.hover_mouse(mouse.column, mouse.row),
+            MouseEventKind::ScrollUp | MouseEventKind::ScrollDown => {
+                if self.show_welcome {
+                    return Ok(());
+                }
+                let down = mouse.kind == MouseEventKind::ScrollDown;
+                if self.display.in_output_panel(mouse.row) {
+                    let lines = self.display.output_panel_lines();
+                    if let Some(output) = &mut self.task_output {
+                        let delta = MOUSE_SCROLL_LINES as isize;
+                        output.scroll_by(if down { -delta } else { delta }, lines);
+                    }
+                    return Ok(());
+                }
+                if let Some(buf) = self.workspace.active_mut() {
+                    let last_row = buf.file_matrix.len().saturating_sub(1);
+                    if down {
+                        buf.view.cursor_row =
+                            (buf.view.cursor_row + MOUSE_SCROLL_LINES).min(last_row);
+                        buf.view.initial_row += MOUSE_SCROLL_LINES;
+                    } else {
+                        buf.view.cursor_row =
+                            buf.view.cursor_row.saturating_sub(MOUSE_SCROLL_LINES);
+                        buf.view.initial_row =
+                            buf.view.initial_row.saturating_sub(MOUSE_SCROLL_LINES);
+                    }
+                    buf.view.cursor_col = buf.view.preferred_col;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Follow the mouse: over the line numbers the popup shows the line,
+    /// over text only a diagnostic under the pointer opens it
+    fn hover_mouse(&mut self, column: u16, row: u16) {
+        self.hover = None;
+        if let Some(sidebar) = &mut self.sidebar {
+            sidebar.hovered = if sidebar.compact && column < sidebar.sidebar_offset() {
+                sidebar.entry_at_row(row)
+            } else {
+                None
+            };
+        }
+        if self.show_welcome || self.prompt.is_some() {
+            return;
+        }
+        let Some(buf) = self.workspace.active() else {
+            return;
+        };
+        let anchor = (column, row);
+        if let Some(file_row) = self.display.gutter_row_at(column, row) {
+            if file_row < buf.file_matrix.len() {
+                self.hover = Some(Hover {
+                    row: file_row,
+                    col: None,
+                    anchor,
+                });
+            }
+        } else if let Some((file_row, col)) = self.display.buffer_position_at(column, row) {
+            if buf.current_diagnostics().any(|d| d.covers(file_row, col)) {
+                self.hover = Some(Hover {
+                    row: file_row,
+                    col: Some(col),
+                    anchor,
+                });
+            }
+        }
+    }
+
+    /// Select whole lines from `anchor` to `row` in Visual mode, the cursor
+    /// on the `row` end
+    fn select_lines(&mut self, anchor: usize, row: usize) {
+        if let Some(buf) = self.workspace.active_mut() {
+            let last = buf.file_matrix.len().saturating_sub(1);
+            let (anchor, row) = (anchor.min(last), row.min(last));
+            let line_end = |row: usize| (row, buf.get_line_length(row));
+            let (from, to) = if row >= anchor {
+                ((anchor, 0), line_end(row))
+            } else {
+                (line_end(anchor), (row, 0))
+            };
+            buf.view.mode = EditorMode::Visual;
+            buf.view.anchor = Some(from);
+            buf.view.scope = None;
+            (buf.view.cursor_row, buf.view.cursor_col) = to;
+            buf.view.preferred_col = buf.view.cursor_col;
         }
+        self.sync_display();
+    }
+
+    // --- Open file prompt ---
+    fn handle_open_file(&mut self) {
+        self.open_prompt(Prompt::input("Abrir arquivo"), PromptAction::OpenFile);
     }
 
     // --- Close tab ---
-    fn handle_close_tab(&mut self) -> io::Result<()> {
+    fn handle_close_tab(&mut self) {
         if !self.workspace.has_files() {
-            return Ok(());
+            return;
         }
-
-        // Check if active buffer is modified
-        if let Some(buf) = self.workspace.active() {
-            if buf.modified {
-                match self.confirm_quit()? {
-                    QuitAction::Save => {
-                        self.workspace.save_active()?;
-                    }
-                    QuitAction::Discard => {}
-                    QuitAction::Cancel => {
-                        self.sync_display();
-                        self.render();
-                        return Ok(());
-                    }
-                }
-            }
+        // Changes are only lost with the last tab showing the buffer
+        if self.workspace.active().is_some_and(|buf| buf.modified)
+            && !self.workspace.active_is_shared()
+        {
+            self.open_prompt(unsaved_changes_prompt("Fechar aba"), PromptAction::CloseTab);
+        } else {
+            self.close_active_tab();
         }
+    }
 
+    fn close_active_tab(&mut self) {
         let was_empty = self.workspace.close_active();
         if was_empty || !self.workspace.has_files() {
             self.show_welcome = true;
         }
-
-        self.display.reset_column();
-        self.display.reset_row();
         self.sync_display();
-        self.render();
-        self.position_cursor_at_start();
-
-        Ok(())
     }
 
     // --- Tab switching ---
@@ -481,10 +5082,9 @@ impl Editor {
             return Ok(());
         }
 
-        // Save current cursor state
-        self.save_cursor_state();
-
+        // Cursor and scroll live in each buffer, so switching restores them
         if key.code == KeyCode::BackTab
+            || key.code == KeyCode::PageUp
             || (key.code == KeyCode::Tab && key.modifiers.contains(KeyModifiers::SHIFT))
         {
             self.workspace.prev_tab();
@@ -492,208 +5092,189 @@ impl Editor {
             self.workspace.next_tab();
         }
 
-        // Restore cursor state for new active buffer
-        self.restore_cursor_state();
         self.sync_display();
         self.render();
 
-        // Move cursor to saved position
-        if let Some(buf) = self.workspace.active() {
-            let sidebar_w = self
-                .sidebar
-                .as_ref()
-                .map(|s| s.sidebar_offset())
-                .unwrap_or(0);
-            let offset = self.display.offset_lines_number() as u16;
-            let col = sidebar_w + offset + buf.cursor_col;
-            let row = self.display.content_top_row() + buf.cursor_row;
-            execute!(io::stdout(), cursor::MoveTo(col, row))?;
-        }
-
         Ok(())
     }
 
-    fn save_cursor_state(&mut self) {
-        let (_col_pos, row_pos) = cursor::position().unwrap_or((0, 0));
-        let _abs_row = self.display.get_absolute_row(row_pos);
-        let cursor_col = self.display.get_cursor_position();
-
-        if let Some(buf) = self.workspace.active_mut() {
-            buf.cursor_row = row_pos.saturating_sub(self.display.content_top_row());
-            buf.cursor_col = cursor_col;
-            buf.initial_row = self.display.initial_row;
-            buf.initial_column = self.display.initial_column;
-        }
-    }
-
-    fn restore_cursor_state(&mut self) {
-        if let Some(buf) = self.workspace.active() {
-            self.display.set_initial_row(buf.initial_row);
-            self.display.initial_column = buf.initial_column;
-        }
-    }
-
     // --- Search ---
-    fn handle_search_input(&mut self, key: KeyEvent) -> io::Result<bool> {
-        match key.code {
-            KeyCode::Esc => {
-                // Restore original position
-                self.search_mode = false;
-                self.search_query.clear();
-                self.display.set_initial_row(self.search_saved_initial_row);
-                self.display
-                    .set_initial_column(self.search_saved_initial_col);
-                self.sync_display();
-                self.render();
-                execute!(
-                    io::stdout(),
-                    cursor::MoveTo(self.search_saved_col, self.search_saved_row)
-                )?;
-                return Ok(true);
-            }
-            KeyCode::Enter => {
-                // Navigate to next match
-                if !self.search_query.is_empty() {
-                    self.navigate_to_next_match()?;
-                }
-                self.search_mode = false;
-                // Keep search_query for highlighting
-                return Ok(true);
-            }
-            KeyCode::Char(c) => {
-                self.search_query.push(c);
-                return Ok(true);
-            }
-            KeyCode::Backspace => {
-                self.search_query.pop();
-                return Ok(true);
-            }
-            _ => {}
+    /// Search the active buffer; in Visual mode, only inside the selection,
+    /// which stays put while the cursor goes from match to match
+    fn start_search(&mut self) {
+        if let Some(buf) = self.workspace.active_mut() {
+            self.search_query.clear();
+            self.search_saved = buf.view;
+            buf.view.scope = buf.view.selection();
+            let title = if buf.view.scope.is_some() {
+                "Buscar na seleção"
+            } else {
+                "Buscar"
+            };
+            self.open_prompt(
+                Prompt::input(title).at(Placement::At(u16::MAX, 1)),
+                PromptAction::Search,
+            );
         }
-        Ok(true)
     }
 
-    fn navigate_to_next_match(&mut self) -> io::Result<()> {
-        let query: Vec<char> = self.search_query.to_lowercase().chars().collect();
-        if query.is_empty() {
-            return Ok(());
+    /// Find and replace in the active buffer (Ctrl+H): asks for the text,
+    /// highlighting it as it's typed, then for what replaces it
+    fn start_replace(&mut self) {
+        if self.active_read_only() {
+            self.display
+                .set_message(String::from("Arquivo somente leitura"));
+            return;
         }
+        if let Some(buf) = self.workspace.active() {
+            self.search_query.clear();
+            self.search_saved = buf.view;
+            self.open_prompt(
+                Prompt::input("Substituir: buscar").at(Placement::At(u16::MAX, 1)),
+                PromptAction::FindReplace,
+            );
+        }
+    }
 
-        let buf = match self.workspace.active() {
-            Some(b) => b,
-            None => return Ok(()),
+    /// Go to the next occurrence of `query` from `from` (the cursor when
+    /// `None`) and ask what to do with it; `count` were replaced so far
+    fn replace_next(
+        &mut self,
+        query: String,
+        new: String,
+        count: usize,
+        from: Option<(usize, usize)>,
+    ) -> io::Result<()> {
+        let found = self.workspace.active().and_then(|buf| {
+            let (row, col) = from.unwrap_or((buf.view.cursor_row, buf.view.cursor_col));
+            buf.find_next(&query, row, col)
+        });
+        let Some((row, col)) = found else {
+            self.search_query.clear();
+            self.display.set_message(match count {
+                0 => format!("Nenhuma ocorrência de '{}'", query),
+                _ => format!("{} ocorrência(s) substituída(s)", count),
+            });
+            return Ok(());
         };
+        self.search_query = query.clone();
+        self.jump_to_position(row, col)?;
+        let question = format!("Trocar por '{}'? (s)im, (n)ão, (t)odas, (q) parar", new);
+        self.open_prompt(
+            Prompt::confirm("Substituir", vec![question], &['s', 'n', 't', 'q'])
+                .at(Placement::At(u16::MAX, 1)),
+            PromptAction::FindReplaceNext(query, new, count),
+        );
+        Ok(())
+    }
 
-        // Current position
-        let (_cur_col_pos, cur_row_pos) = cursor::position()?;
-        let current_row = self.display.get_absolute_row(cur_row_pos) as usize;
-        let current_col = self.display.get_cursor_position() as usize;
-
-        // Search from current position forward, wrap around
-        let total_lines = buf.file_matrix.len();
-        let search_col = current_col + 1; // start after current position
-
-        for offset in 0..total_lines {
-            let row_idx = (current_row + offset) % total_lines;
-            let line = &buf.file_matrix[row_idx];
-            let line_lower: Vec<char> = line.iter().flat_map(|c| c.to_lowercase()).collect();
+    /// What was answered for the occurrence of `query` at the cursor
+    fn answer_replace(
+        &mut self,
+        query: String,
+        new: String,
+        count: usize,
+        event: PromptEvent,
+    ) -> io::Result<()> {
+        let Some(buf) = self.workspace.active_mut() else {
+            return Ok(());
+        };
+        let (row, col) = (buf.view.cursor_row, buf.view.cursor_col);
+        match event {
+            PromptEvent::Choice('s') => {
+                let end = (row, col + query.chars().count());
+                buf.replace_between((row, col), end, &new);
+                let after = (row, col + new.chars().count());
+                self.replace_next(query, new, count + 1, Some(after))
+            }
+            PromptEvent::Choice('n') => self.replace_next(query, new, count, Some((row, col + 1))),
+            PromptEvent::Choice('t') => {
+                self.search_query.clear();
+                let substitute = Substitute {
+                    range: Range::All,
+                    old: query.chars().collect(),
+                    new: new.chars().collect(),
+                    global: true,
+                    ignore_case: true,
+                };
+                self.substitute(&substitute, None);
+                Ok(())
+            }
+            _ => {
+                self.search_query.clear();
+                self.display
+                    .set_message(format!("{} ocorrência(s) substituída(s)", count));
+                Ok(())
+            }
+        }
+    }
 
-            let start_col = if offset == 0 { search_col } else { 0 };
+    /// Restore the position saved when the search started
+    fn cancel_search(&mut self) {
+        self.search_query.clear();
+        if let Some(buf) = self.workspace.active_mut() {
+            buf.view = self.search_saved;
+        }
+    }
 
-            // Search within this line
-            let qlen = query.len();
-            if line_lower.len() >= qlen {
-                for col in start_col..=line_lower.len().saturating_sub(qlen) {
-                    let matches = (0..qlen).all(|k| line_lower[col + k] == query[k]);
-                    if matches {
-                        // Found match at (row_idx, col)
-                        self.jump_to_position(row_idx as u16, col as u16)?;
-                        return Ok(());
-                    }
-                }
+    fn navigate_to_next_match(&mut self) -> io::Result<()> {
+        let found = self.workspace.active().and_then(|buf| {
+            let (row, col) = (buf.view.cursor_row, buf.view.cursor_col + 1);
+            match buf.view.scope {
+                Some(scope) => buf.find_next_within(&self.search_query, row, col, scope),
+                None => buf.find_next(&self.search_query, row, col),
             }
+        });
+        if let Some((row, col)) = found {
+            self.jump_to_position(row, col)?;
         }
-
         Ok(())
     }
 
-    fn jump_to_position(&mut self, file_row: u16, file_col: u16) -> io::Result<()> {
-        let content_rows = self.display.rows.saturating_sub(2);
-        let sidebar_w = self
-            .sidebar
-            .as_ref()
-            .map(|s| s.sidebar_offset())
-            .unwrap_or(0);
-        let line_nr_w = self.display.offset_lines_number() as u16;
-        let text_offset = sidebar_w + line_nr_w;
-        let content_w = self.display.content_width().saturating_sub(line_nr_w);
+    fn jump_to_position(&mut self, file_row: usize, file_col: usize) -> io::Result<()> {
+        let content_rows = self.display.content_rows();
+        let text_width = self.display.text_width();
 
-        // Set initial_row so the target line is visible
-        if file_row < self.display.initial_row
-            || file_row >= self.display.initial_row + content_rows
-        {
-            // Center the target row
-            let half = content_rows / 2;
-            self.display.set_initial_row(file_row.saturating_sub(half));
-        }
+        if let Some(buf) = self.workspace.active_mut() {
+            buf.view.cursor_row = file_row;
+            buf.view.cursor_col = file_col;
+            buf.clamp_cursor();
+            buf.view.preferred_col = buf.view.cursor_col;
 
-        // Set initial_column so the target column is visible
-        if file_col < self.display.initial_column
-            || file_col >= self.display.initial_column + content_w
-        {
-            self.display.set_initial_column(file_col.saturating_sub(5));
-        }
+            // Center the target row when it is off-screen
+            if buf.view.cursor_row < buf.view.initial_row
+                || buf.view.cursor_row >= buf.view.initial_row + content_rows
+            {
+                buf.view.initial_row = buf.view.cursor_row.saturating_sub(content_rows / 2);
+            }
 
-        // Calculate screen position
-        let screen_row = 1 + file_row.saturating_sub(self.display.initial_row);
-        let screen_col = text_offset + file_col.saturating_sub(self.display.initial_column);
+            // Set initial_column so the target column is visible
+            if buf.view.cursor_col < buf.view.initial_column
+                || buf.view.cursor_col >= buf.view.initial_column + text_width
+            {
+                buf.view.initial_column = buf.view.cursor_col.saturating_sub(5);
+            }
+        }
 
         self.sync_display();
         self.render();
-        execute!(io::stdout(), cursor::MoveTo(screen_col, screen_row))?;
 
         Ok(())
     }
 
-    fn render_search_bar(&self) -> io::Result<()> {
-        let (columns, rows) = terminal::size()?;
-        let sidebar_w = self
-            .sidebar
-            .as_ref()
-            .map(|s| if s.visible { s.width } else { 0 })
-            .unwrap_or(0);
-        let start_col = sidebar_w;
-        let width = columns.saturating_sub(sidebar_w) as usize;
-        let prompt = format!(" Buscar: {}█", self.search_query);
-
-        let bg = style::Color::Rgb {
-            r: 25,
-            g: 35,
-            b: 50,
-        };
-        let fg = style::Color::Rgb {
-            r: 200,
-            g: 220,
-            b: 255,
+    /// Look for the sidebar's search query over the whole tree, stopping
+    /// the search for what was typed before
+    fn start_sidebar_search(&mut self) {
+        if let Some(id) = self.sidebar_search.take() {
+            self.jobs.cancel(id);
+        }
+        let Some(sidebar) = &mut self.sidebar else {
+            return;
         };
-
-        // Pad to width
-        let prompt_chars: Vec<char> = prompt.chars().collect();
-        let mut padded = String::with_capacity(width);
-        for i in 0..width {
-            padded.push(prompt_chars.get(i).copied().unwrap_or(' '));
+        // The changed files are filtered as they are
+        if !sidebar.search_query.is_empty() && !sidebar.is_changed_filter_on() {
+            self.sidebar_search = Some(sidebar.start_search(&mut self.jobs));
         }
-
-        execute!(
-            io::stdout(),
-            cursor::MoveTo(start_col, rows - 1),
-            style::SetBackgroundColor(bg),
-            style::SetForegroundColor(fg),
-            style::Print(&padded),
-            style::ResetColor,
-        )?;
-
-        Ok(())
     }
 
     // --- Sidebar input ---
@@ -708,9 +5289,20 @@ impl Editor {
 
         if sidebar.search_active {
             match key.code {
+                // A search still running stops first, keeping what it found
+                KeyCode::Esc if sidebar.scanning => {
+                    sidebar.scanning = false;
+                    if let Some(id) = self.sidebar_search.take() {
+                        self.jobs.cancel(id);
+                    }
+                }
                 KeyCode::Esc => {
                     sidebar.clear_search();
                 }
+                KeyCode::Tab => {
+                    sidebar.toggle_fuzzy();
+                    self.start_sidebar_search();
+                }
                 KeyCode::Enter => {
                     sidebar.search_active = false;
                     // Keep search results visible
@@ -719,12 +5311,14 @@ impl Editor {
                     let mut q = sidebar.search_query.clone();
                     q.push(c);
                     sidebar.set_search_query(q);
+                    self.start_sidebar_search();
                     return Ok(());
                 }
                 KeyCode::Backspace => {
                     let mut q = sidebar.search_query.clone();
                     q.pop();
                     sidebar.set_search_query(q);
+                    self.start_sidebar_search();
                     return Ok(());
                 }
                 _ => {}
@@ -737,44 +5331,85 @@ impl Editor {
             KeyCode::Down => sidebar.select_next(),
             KeyCode::Enter => {
                 if sidebar.is_selected_dir() {
-                    sidebar.toggle_selected_dir();
+                    if let Some(dir) = sidebar.toggle_selected_dir() {
+                        history::record_dir(&dir);
+                    }
                 } else if let Some(path) = sidebar.get_selected_path() {
-                    let path_str = path.to_string_lossy().to_string();
-                    self.workspace.open_file(&path_str);
-                    self.show_welcome = false;
-                    self.focus = Focus::Editor;
-                    self.mode = EditorMode::Normal;
-                    self.sync_display();
-                    self.render();
-                    self.position_cursor_at_start();
-                    return Ok(());
+                    return self.open_in_editor(&path.to_string_lossy());
+                } else {
+                    sidebar.show_more();
+                }
+            }
+            KeyCode::Char(' ') if !sidebar.is_selected_dir() => {
+                if let Some(path) = sidebar.get_selected_path() {
+                    return self.preview_file(&path.to_string_lossy());
                 }
             }
             KeyCode::Right => {
                 // Switch focus to editor
                 self.focus = Focus::Editor;
-                if self.workspace.has_files() {
-                    self.position_cursor_at_start();
-                }
                 return Ok(());
             }
-            KeyCode::Left => {
+            KeyCode::Left if sidebar.is_selected_dir() => {
                 // Collapse selected dir
-                if sidebar.is_selected_dir() {
-                    sidebar.toggle_selected_dir();
-                }
+                sidebar.toggle_selected_dir();
             }
             KeyCode::Esc => {
                 self.focus = Focus::Editor;
-                if self.workspace.has_files() {
-                    self.position_cursor_at_start();
-                }
                 return Ok(());
             }
             KeyCode::Char('/') => {
                 sidebar.search_active = true;
                 sidebar.search_query.clear();
             }
+            KeyCode::Char('r') => {
+                let dir = Self::sidebar_dir(sidebar);
+                self.start_bulk_rename(&dir);
+            }
+            KeyCode::Char('n') => return self.execute_command(Command::NewFile),
+            KeyCode::Char('c') => return self.execute_command(Command::SidebarCollapse),
+            KeyCode::Char('z') => return self.execute_command(Command::SidebarCompact),
+            KeyCode::Char('j') => return self.execute_command(Command::SidebarJump),
+            KeyCode::F(5) => return self.execute_command(Command::SidebarRefresh),
+            KeyCode::Delete => {
+                if let Some(path) = sidebar.get_selected_path() {
+                    let kind = if sidebar.is_selected_dir() {
+                        "pasta"
+                    } else {
+                        "arquivo"
+                    };
+                    self.open_prompt(
+                        Prompt::confirm(
+                            &format!("Mover {} para a lixeira", kind),
+                            vec![
+                                path.to_string_lossy().to_string(),
+                                String::from("Confirmar? (s/n)"),
+                            ],
+                            &['s', 'n'],
+                        )
+                        .warning(),
+                        PromptAction::Trash(path),
+                    );
+                }
+            }
+            KeyCode::Char('g') => {
+                if sidebar.is_changed_filter_on() {
+                    sidebar.set_changed_files(None);
+                    self.display
+                        .set_message(String::from("Mostrando todos os arquivos"));
+                    self.start_sidebar_search();
+                    return Ok(());
+                }
+                let message = match git::changed_files(&sidebar.root_path) {
+                    Ok(files) => {
+                        let message = format!("{} arquivo(s) alterado(s) no git", files.len());
+                        sidebar.set_changed_files(Some(files));
+                        message
+                    }
+                    Err(e) => format!("git: {}", e),
+                };
+                self.display.set_message(message);
+            }
             _ => {}
         }
 
@@ -782,110 +5417,144 @@ impl Editor {
     }
 
     // --- Navigation (shared) ---
-    fn handle_navigation(
-        &mut self,
-        key_code: &KeyCode,
-        column_position: u16,
-        row_position: u16,
-        row_size: u16,
-    ) -> io::Result<bool> {
-        let content_top = self.display.content_top_row();
-        let content_bottom = row_size.saturating_sub(2); // status bar
+    fn handle_navigation(&mut self, key_code: &KeyCode) -> bool {
+        let buf = match self.workspace.active_mut() {
+            Some(b) => b,
+            None => return false,
+        };
 
         match key_code {
             KeyCode::Up => {
-                if row_position > content_top {
-                    execute!(io::stdout(), cursor::MoveUp(1))?;
-                } else {
-                    self.display.previous_row();
+                if buf.view.cursor_row > 0 {
+                    buf.view.cursor_row -= 1;
+                    let line = &buf.file_matrix[buf.view.cursor_row];
+                    buf.view.cursor_col = grapheme::snap(line, buf.view.preferred_col);
                 }
-                Ok(true)
             }
             KeyCode::Down => {
-                if row_position < content_bottom {
-                    execute!(io::stdout(), cursor::MoveDown(1))?;
-                } else {
-                    self.display.next_row();
+                if buf.view.cursor_row + 1 < buf.file_matrix.len() {
+                    buf.view.cursor_row += 1;
+                    let line = &buf.file_matrix[buf.view.cursor_row];
+                    buf.view.cursor_col = grapheme::snap(line, buf.view.preferred_col);
                 }
-                Ok(true)
             }
             KeyCode::Right => {
-                self.display.next_column(column_position);
-                execute!(io::stdout(), cursor::MoveRight(1))?;
-                Ok(true)
+                if let Some(line) = buf.file_matrix.get(buf.view.cursor_row) {
+                    buf.view.cursor_col = grapheme::next_boundary(line, buf.view.cursor_col);
+                }
+                buf.view.preferred_col = buf.view.cursor_col;
             }
             KeyCode::Left => {
-                let sidebar_w = self
-                    .sidebar
-                    .as_ref()
-                    .map(|s| s.sidebar_offset())
-                    .unwrap_or(0);
-                let min_col = sidebar_w + self.display.offset_lines_number() as u16;
-                if column_position > min_col {
-                    execute!(io::stdout(), cursor::MoveLeft(1))?;
-                } else {
-                    self.display.previous_column(column_position);
+                if let Some(line) = buf.file_matrix.get(buf.view.cursor_row) {
+                    buf.view.cursor_col = grapheme::prev_boundary(line, buf.view.cursor_col);
                 }
-                Ok(true)
+                buf.view.preferred_col = buf.view.cursor_col;
             }
             KeyCode::Home => {
-                let sidebar_w = self
-                    .sidebar
-                    .as_ref()
-                    .map(|s| s.sidebar_offset())
-                    .unwrap_or(0);
-                let offset = sidebar_w + self.display.offset_lines_number() as u16;
-                self.display.reset_column();
-                execute!(io::stdout(), cursor::MoveTo(offset, row_position))?;
-                Ok(true)
+                buf.view.cursor_col = 0;
+                buf.view.preferred_col = 0;
             }
             KeyCode::End => {
-                let absolute_row = self.display.get_absolute_row(row_position);
-                if let Some(buf) = self.workspace.active() {
-                    let line_len = buf.get_line_length(absolute_row);
-                    let sidebar_w = self
-                        .sidebar
-                        .as_ref()
-                        .map(|s| s.sidebar_offset())
-                        .unwrap_or(0);
-                    let offset = sidebar_w + self.display.offset_lines_number() as u16;
-                    let (col_size, _) = terminal::size()?;
-                    let visible_area = col_size.saturating_sub(offset);
-
-                    if line_len <= visible_area {
-                        self.display.reset_column();
-                        execute!(
-                            io::stdout(),
-                            cursor::MoveTo(offset + line_len, row_position)
-                        )?;
-                    } else {
-                        self.display
-                            .set_initial_column(line_len.saturating_sub(visible_area));
-                        execute!(io::stdout(), cursor::MoveTo(col_size - 1, row_position))?;
-                    }
-                }
-                Ok(true)
+                buf.view.cursor_col = buf.get_line_length(buf.view.cursor_row);
+                buf.view.preferred_col = buf.view.cursor_col;
             }
-            _ => Ok(false),
+            _ => return false,
         }
+        true
     }
 
     // --- Normal mode ---
-    fn handle_normal_mode(
-        &mut self,
-        key_code: KeyCode,
-        column_position: u16,
-        row_position: u16,
-        row_size: u16,
-    ) -> io::Result<()> {
-        if self.handle_navigation(&key_code, column_position, row_position, row_size)? {
+    fn handle_normal_mode(&mut self, key_code: KeyCode) -> io::Result<()> {
+        let count = self.count.take();
+        if let Some(action) = self.pending_mark.take() {
+            if let KeyCode::Char(name) = key_code {
+                if action == 'm' {
+                    self.set_mark(name);
+                } else {
+                    self.jump_to_mark(name)?;
+                }
+            }
+            return Ok(());
+        }
+        if let Some(key) = self.pending_find.take() {
+            if let KeyCode::Char(ch) = key_code {
+                let find = CharFind::new(key, ch);
+                self.last_find = find;
+                if let Some(find) = find {
+                    self.find_char(find, false);
+                }
+            }
+            return Ok(());
+        }
+        if let Some((operator, around)) = self.pending_operator.take() {
+            match (around, key_code) {
+                (None, KeyCode::Char('i')) => self.pending_operator = Some((operator, Some(false))),
+                (None, KeyCode::Char('a')) => self.pending_operator = Some((operator, Some(true))),
+                (Some(around), KeyCode::Char(object)) => {
+                    self.apply_operator(operator, around, object)
+                }
+                _ => {}
+            }
+            return Ok(());
+        }
+        if std::mem::take(&mut self.pending_g) {
+            match key_code {
+                KeyCode::Char('x') => self.open_link_at_cursor()?,
+                KeyCode::Char('f') => self.goto_file_at_cursor()?,
+                KeyCode::Char('d') => self.goto_definition()?,
+                KeyCode::Char('a') => {
+                    let report = self.inspect_char();
+                    self.display.set_message(report);
+                }
+                _ => {}
+            }
+            return Ok(());
+        }
+        if self.handle_navigation(&key_code) {
             return Ok(());
         }
-
         match key_code {
+            KeyCode::Char(c) if Some(c) == self.keymap.leader => self.pending_leader = true,
+            KeyCode::Char(':') => self.open_ex(),
             KeyCode::Char('i') => {
-                self.mode = EditorMode::Insert;
-                self.display.set_mode("INSERT");
+                if self.active_read_only() {
+                    return Ok(());
+                }
+                self.set_mode(EditorMode::Insert);
+            }
+            KeyCode::Char('u') if !self.active_read_only() => {
+                self.undo_redo(true);
+            }
+            KeyCode::Char('v') => {
+                if let Some(buf) = self.workspace.active_mut() {
+                    buf.view.anchor = Some((buf.view.cursor_row, buf.view.cursor_col));
+                }
+                self.set_mode(EditorMode::Visual);
+            }
+            KeyCode::Char('g') => self.pending_g = true,
+            KeyCode::Char('K') => self.hover_cursor_line(),
+            KeyCode::Char('p') => {
+                if let Some(text) = self.kill_ring.latest().map(str::to_string) {
+                    self.paste_after_cursor(&text)?;
+                }
+            }
+            KeyCode::Char(c @ ('m' | '\'' | '`')) => self.pending_mark = Some(c),
+            KeyCode::Char(c @ ('f' | 'F' | 't' | 'T')) => self.pending_find = Some(c),
+            KeyCode::Char(c @ ('d' | 'c' | 'y')) => self.pending_operator = Some((c, None)),
+            KeyCode::Char(digit @ '0'..='9') if digit != '0' || count.is_some() => {
+                let digit = digit.to_digit(10).unwrap_or(0) as usize;
+                self.count = Some(count.unwrap_or(0).saturating_mul(10).saturating_add(digit));
+            }
+            KeyCode::Char(c @ ('{' | '}')) => self.jump_paragraph(c == '}', count.unwrap_or(1))?,
+            KeyCode::Char(';') => {
+                if let Some(find) = self.last_find {
+                    self.find_char(find, true);
+                }
+            }
+            KeyCode::Char(',') => {
+                if let Some(find) = self.last_find {
+                    self.find_char(find.reversed(), true);
+                }
             }
             _ => {}
         }
@@ -893,113 +5562,477 @@ impl Editor {
         Ok(())
     }
 
+    /// Run `operator` (`d` cuts, `c` cuts and starts Insert mode, `y`
+    /// copies) on the text object at the cursor: `di(`, `ciw`, `yap`...
+    fn apply_operator(&mut self, operator: char, around: bool, object: char) {
+        if operator != 'y' && self.active_read_only() {
+            return;
+        }
+        let Some(buf) = self.workspace.active_mut() else {
+            return;
+        };
+        let private = buf.fs.is_private();
+        let cursor = (buf.view.cursor_row, buf.view.cursor_col);
+        let Some(span) = textobj::find(&buf.file_matrix, cursor, around, object) else {
+            let variant = if around { 'a' } else { 'i' };
+            self.display
+                .set_message(format!("Nenhum '{}{}' sob o cursor", variant, object));
+            return;
+        };
+        let (text, start) = match span {
+            Span::Chars(start, end) => (buf.text_between(start, end), start),
+            Span::Lines(first, last) => {
+                let text = buf.text_between((first, 0), (last, usize::MAX));
+                (text + "\n", (first, 0))
+            }
+        };
+        if operator != 'y' {
+            match span {
+                Span::Chars(..) if text.is_empty() => {}
+                Span::Chars(start, end) => buf.replace_between(start, end, ""),
+                // Changed lines leave an empty one to type on
+                Span::Lines(first, last) => {
+                    let lines = if operator == 'c' {
+                        vec![vec![]]
+                    } else {
+                        vec![]
+                    };
+                    buf.replace_block(first, last + 1 - first, lines);
+                }
+            }
+        }
+        (buf.view.cursor_row, buf.view.cursor_col) = start;
+        buf.clamp_cursor();
+        buf.view.preferred_col = buf.view.cursor_col;
+        let count = text.chars().count();
+        if !text.is_empty() {
+            self.kill_ring.push(text, private);
+        }
+        if operator == 'c' {
+            self.set_mode(EditorMode::Insert);
+            return;
+        }
+        self.display.set_message(format!(
+            "{} caractere(s) {}",
+            count,
+            if operator == 'd' {
+                "recortado(s)"
+            } else {
+                "copiado(s)"
+            }
+        ));
+    }
+
+    /// Move to the blank line after (or before) the paragraph at the cursor,
+    /// `count` times over (`}` / `{`, Ctrl+Down / Ctrl+Up)
+    fn jump_paragraph(&mut self, forward: bool, count: usize) -> io::Result<()> {
+        let Some(buf) = self.workspace.active() else {
+            return Ok(());
+        };
+        let mut row = buf.view.cursor_row;
+        for _ in 0..count {
+            row = motion::paragraph_edge(&buf.file_matrix, row, forward);
+        }
+        self.jump_to_position(row, 0)
+    }
+
+    /// Move to the first non-blank char of `row`
+    fn jump_to_indented(&mut self, row: usize) -> io::Result<()> {
+        let col = self
+            .workspace
+            .active()
+            .and_then(|buf| buf.file_matrix.get(row))
+            .map_or(0, |line| {
+                line.iter()
+                    .position(|c| !c.is_whitespace())
+                    .unwrap_or(line.len())
+            });
+        self.jump_to_position(row, col)
+    }
+
+    /// Names of the HTML/XML tag under the cursor and of its pair, marked
+    /// while the cursor is on either
+    fn tag_highlights(&self) -> Vec<(usize, usize, usize)> {
+        let Some(buf) = self.workspace.active() else {
+            return vec![];
+        };
+        let cursor = (buf.view.cursor_row, buf.view.cursor_col);
+        match tags::pair_at(&buf.file_matrix, &buf.syntax_ext(), cursor) {
+            Some((tag, Some(partner))) => [tag, partner]
+                .iter()
+                .map(|tag| {
+                    let len = tag.name.chars().count();
+                    (tag.start.0, tag.name_col, tag.name_col + len)
+                })
+                .collect(),
+            _ => vec![],
+        }
+    }
+
+    /// Move the cursor to the char `find` looks for in its line
+    fn find_char(&mut self, find: CharFind, repeat: bool) {
+        let Some(buf) = self.workspace.active_mut() else {
+            return;
+        };
+        let line = &buf.file_matrix[buf.view.cursor_row];
+        match find.target(line, buf.view.cursor_col, repeat) {
+            Some(col) => {
+                buf.view.cursor_col = grapheme::snap(line, col);
+                buf.view.preferred_col = buf.view.cursor_col;
+            }
+            None => self
+                .display
+                .set_message(format!("'{}' não encontrado na linha", find.ch)),
+        }
+    }
+
+    // --- Visual mode ---
+    /// Movement extends the selection; Esc or `v` drops it
+    fn handle_visual_mode(&mut self, key_code: KeyCode) {
+        if self.handle_navigation(&key_code) {
+            // Moving on from a search in the selection: it follows the
+            // cursor again
+            if let Some(buf) = self.workspace.active_mut() {
+                buf.view.scope = None;
+            }
+            return;
+        }
+        match key_code {
+            KeyCode::Esc | KeyCode::Char('v') => self.set_mode(EditorMode::Normal),
+            KeyCode::Char('y') => self.yank_selection(false),
+            KeyCode::Char('d' | 'x') => self.yank_selection(true),
+            KeyCode::Char(c @ ('>' | '<')) => self.indent_selection(c == '<'),
+            KeyCode::Char(':') => self.open_ex(),
+            _ => {}
+        }
+    }
+
     // --- Insert mode ---
-    fn handle_insert_mode(
-        &mut self,
-        key_code: KeyCode,
-        column_position: u16,
-        row_position: u16,
-        _column_size: u16,
-        row_size: u16,
-    ) -> io::Result<()> {
+    /// The character `event` types into the buffer, if it is a plain key
+    /// press in insert mode with the editor focused
+    /// The char a key types into the buffer as plain text, batched with the
+    /// ones after it. Chars that auto-pairs acts on, and the `>` that may
+    /// close a tag, aren't: they go through `handle_insert_mode` one by one.
+    fn typed_char(&self, event: &Event) -> Option<char> {
+        let key = match event {
+            Event::Key(key) => key,
+            _ => return None,
+        };
+        let typing = self.prompt.is_none()
+            && key.kind != KeyEventKind::Release
+            && self.focus == Focus::Editor
+            && self.mode() == EditorMode::Insert
+            && !self.show_welcome
+            && (key.modifiers - KeyModifiers::SHIFT).is_empty();
+        match key.code {
+            KeyCode::Char(c) if typing => {
+                let special = (pairs::acts_on(c) && self.config.auto_pairs())
+                    || (c == '>' && self.config.auto_close_tags());
+                Some(c).filter(|_| !special)
+            }
+            _ => None,
+        }
+    }
+
+    fn insert_typed(&mut self, text: &str) {
+        self.display.set_message(String::new());
+        if self.active_read_only() {
+            return;
+        }
+        if let Some(buf) = self.workspace.active_mut() {
+            let (mut col, row) = (buf.view.cursor_col, buf.view.cursor_row);
+            // An accent sent apart from its letter joins the char before
+            let before = col
+                .checked_sub(1)
+                .and_then(|c| buf.file_matrix.get(row)?.get(c).copied());
+            let (took, text) = grapheme::compose(before, text);
+            if took {
+                buf.remove_char(col, row);
+                col -= 1;
+            }
+            let (col, row) = buf.insert_text(&text, col, row);
+            buf.view.cursor_row = row;
+            buf.view.cursor_col = col;
+            buf.view.preferred_col = col;
+        }
+    }
+
+    fn handle_insert_mode(&mut self, key_code: KeyCode) -> io::Result<()> {
         if key_code == KeyCode::Esc {
-            self.mode = EditorMode::Normal;
-            self.display.set_mode("NORMAL");
+            self.set_mode(EditorMode::Normal);
+            // Everything typed in one insert session is undone together
+            if let Some(buf) = self.workspace.active_mut() {
+                buf.commit_edits();
+            }
+            return Ok(());
+        }
+
+        if self.handle_navigation(&key_code) {
             return Ok(());
         }
 
-        if self.handle_navigation(&key_code, column_position, row_position, row_size)? {
+        if self.active_read_only() {
             return Ok(());
         }
 
-        let absolute_row = self.display.get_absolute_row(row_position);
-        let content_top = self.display.content_top_row();
+        let auto_close = self.config.auto_close_tags();
+        let auto_pairs = self.config.auto_pairs();
+        let tab_width = self.options().tab_width;
+        let buf = match self.workspace.active_mut() {
+            Some(b) => b,
+            None => return Ok(()),
+        };
+        let row = buf.view.cursor_row;
+        let col = buf.view.cursor_col;
 
         match key_code {
             KeyCode::Char(c) => {
-                let cursor_col = self.display.get_cursor_position();
-                if let Some(buf) = self.workspace.active_mut() {
-                    buf.add_char(c, cursor_col, absolute_row);
-                    self.display.set_file_matrix(buf.file_matrix.clone());
-                }
-                self.display.next_column(column_position);
-                execute!(io::stdout(), cursor::MoveRight(1))?;
-            }
-            KeyCode::Backspace => {
-                let cursor_col = self.display.get_cursor_position();
-                let merged = if let Some(buf) = self.workspace.active_mut() {
-                    let m = buf.remove_char(cursor_col, absolute_row);
-                    self.display.set_file_matrix(buf.file_matrix.clone());
-                    m
+                let typed = if auto_pairs {
+                    pairs::on_type(&buf.file_matrix, &buf.syntax_ext(), (row, col), c)
                 } else {
-                    false
+                    Typed::Plain
                 };
-
-                if merged {
-                    if row_position > content_top {
-                        execute!(io::stdout(), cursor::MoveUp(1))?;
-                    } else {
-                        self.display.previous_row();
+                match typed {
+                    Typed::Plain => buf.add_char(c, col, row),
+                    Typed::Pair(close) => {
+                        buf.add_char(c, col, row);
+                        buf.add_char(close, col + 1, row);
                     }
-                } else if cursor_col > 0 {
-                    self.display.previous_column(column_position);
-                    let sidebar_w = self
-                        .sidebar
-                        .as_ref()
-                        .map(|s| s.sidebar_offset())
-                        .unwrap_or(0);
-                    let min_col = sidebar_w + self.display.offset_lines_number() as u16;
-                    if column_position > min_col {
-                        execute!(io::stdout(), cursor::MoveLeft(1))?;
+                    Typed::StepOver => {}
+                }
+                buf.view.cursor_col += 1;
+                // The closing tag goes after the cursor, which stays inside
+                let closing = Some(c).filter(|&c| c == '>' && auto_close).and_then(|_| {
+                    tags::closing_for(&buf.file_matrix, &buf.syntax_ext(), (row, col))
+                });
+                for (i, ch) in closing.unwrap_or_default().chars().enumerate() {
+                    buf.add_char(ch, col + 1 + i, row);
+                }
+            }
+            KeyCode::Backspace => {
+                if auto_pairs && pairs::deletes_pair(&buf.file_matrix[row], col) {
+                    buf.remove_char(col + 1, row);
+                    buf.remove_char(col, row);
+                    buf.view.cursor_col -= 1;
+                } else if col > 0 {
+                    // The whole character, accents and emoji sequences included
+                    let start = grapheme::prev_boundary(&buf.file_matrix[row], col);
+                    for c in (start + 1..=col).rev() {
+                        buf.remove_char(c, row);
                     }
+                    buf.view.cursor_col = start;
+                } else if row > 0 {
+                    let previous_len = buf.get_line_length(row - 1);
+                    buf.remove_char(col, row);
+                    buf.view.cursor_row -= 1;
+                    buf.view.cursor_col = previous_len;
                 }
             }
             KeyCode::Enter => {
-                let cursor_col = self.display.get_cursor_position();
-                if let Some(buf) = self.workspace.active_mut() {
-                    buf.split_line(cursor_col, absolute_row);
-                    self.display.set_file_matrix(buf.file_matrix.clone());
-                }
-
-                let sidebar_w = self
-                    .sidebar
-                    .as_ref()
-                    .map(|s| s.sidebar_offset())
-                    .unwrap_or(0);
-                let offset = sidebar_w + self.display.offset_lines_number() as u16;
-                let content_bottom = row_size.saturating_sub(2);
-
-                self.display.reset_column();
-
-                if row_position < content_bottom {
-                    execute!(io::stdout(), cursor::MoveTo(offset, row_position + 1))?;
-                } else {
-                    self.display.next_row();
-                    execute!(io::stdout(), cursor::MoveTo(offset, row_position))?;
-                }
+                buf.split_line(col, row);
+                buf.view.cursor_row += 1;
+                buf.view.cursor_col = 0;
             }
             KeyCode::Tab => {
-                let cursor_col = self.display.get_cursor_position();
-                if let Some(buf) = self.workspace.active_mut() {
-                    for i in 0..4 {
-                        buf.add_char(' ', cursor_col + i, absolute_row);
-                    }
-                    self.display.set_file_matrix(buf.file_matrix.clone());
-                }
-                for j in 0..4 {
-                    self.display.next_column(column_position + j);
+                for i in 0..tab_width {
+                    buf.add_char(' ', col + i, row);
                 }
-                execute!(io::stdout(), cursor::MoveRight(4))?;
+                buf.view.cursor_col += tab_width;
             }
-            _ => {}
+            _ => return Ok(()),
         }
+        buf.view.preferred_col = buf.view.cursor_col;
 
         Ok(())
     }
 }
 
-enum QuitAction {
-    Save,
-    Discard,
-    Cancel,
+/// What to do with the answer of the open prompt
+enum PromptAction {
+    Quit,
+    CloseTab,
+    OpenFile,
+    /// Large or binary file (path, size) waiting for how to open it
+    OpenLarge(String, u64),
+    /// Path dropped on the terminal, with the pasted text
+    DroppedPath(String, String),
+    Reload,
+    Convert,
+    GotoLine,
+    SetLanguage,
+    RunCommand,
+    Search,
+    /// TODO panel, with the (path, line) each row jumps to
+    Todos(Vec<(String, usize)>),
+    /// Retry a save refused by permissions through sudo
+    SudoSave,
+    /// Encoding picked for the selection
+    Transform,
+    /// Passphrase for the gpg file at the path
+    Decrypt(String),
+    /// File history picker, with the path of each row
+    OldFiles(Vec<String>),
+    /// Folders for the sidebar, bookmarks first
+    SidebarJump(Vec<PathBuf>),
+    /// Sidebar entry waiting for confirmation to go to the trash
+    Trash(PathBuf),
+    /// New name for an identifier, with where it occurs in the buffer
+    RenameSymbol(Vec<char>, Vec<(usize, usize)>),
+    /// Global mark picker, with the name of each row's mark
+    Marks(Vec<char>),
+    /// Text to find and replace in the buffer
+    FindReplace,
+    /// What replaces it
+    FindReplaceWith(String),
+    /// Occurrence at the cursor waiting for what to do with it, with the
+    /// text, its replacement and how many were replaced so far
+    FindReplaceNext(String, String, usize),
+    /// Kill ring picker, a row per entry
+    PasteHistory,
+    /// Snippet picker, with the text of each row
+    Snippet(Vec<String>),
+    /// Names the path in a string can go on with, and the chars of the
+    /// last one typed already
+    CompletePath(Vec<String>, usize),
+    /// Text to search the project for
+    ProjectSearch,
+    /// Definitions, with the (path, line, column) each row opens at
+    Locations(Vec<(String, usize, usize)>),
+    /// Project search results so far, the same way
+    SearchResults(Vec<(String, usize, usize)>),
+    /// Text to replace across the project
+    ReplaceQuery,
+    /// What replaces the text
+    ReplaceWith(String),
+    /// Port to share the active buffer on
+    CollabHost,
+    /// Address of the session to join
+    CollabJoin,
+    /// Session just shared, with what guests join it with
+    CollabShared(String),
+    /// Name of a file to create in the folder
+    NewFile(PathBuf),
+    /// Project task picker, a row per task
+    RunTask(Vec<Task>),
+    /// Files a glob matched, waiting for confirmation to open them all
+    OpenMany(Vec<String>),
+    /// `:` command line, with the rows of the selection it was opened on
+    Ex(Option<(usize, usize)>),
+    /// Options picker, a row per option
+    Options,
+    /// New value of the option
+    SetOption(&'static str),
+}
+
+/// Palette command behind a sidebar header button
+fn header_command(button: HeaderButton) -> Command {
+    match button {
+        HeaderButton::Refresh => Command::SidebarRefresh,
+        HeaderButton::CollapseAll => Command::SidebarCollapse,
+        HeaderButton::NewFile => Command::NewFile,
+        HeaderButton::Compact => Command::SidebarCompact,
+    }
+}
+
+/// Files of the commit being written, at the right edge, when the text
+/// area has room for it beside the 72-column body
+fn commit_panel(lines: &[Vec<char>], text_width: usize) -> Option<Overlay> {
+    let rows = git::commit_diffstat(lines);
+    let widest = rows.iter().map(|row| row.chars().count()).max()?;
+    let box_width = widest.max(30) + 4;
+    if text_width < 74 + box_width {
+        return None;
+    }
+    Some(Overlay::dialog("Arquivos do commit", rows).at(Placement::At(u16::MAX, 1)))
+}
+
+/// Replace `matches` in the file at `path` on disk, keeping its line endings
+fn replace_on_disk(
+    path: &str,
+    matches: &[replace::Match],
+    query: &str,
+    replacement: &str,
+) -> io::Result<usize> {
+    let text = std::fs::read_to_string(path)?;
+    // Split on '\n' only: a '\r' stays at the end of its line, past every match
+    let mut lines: Vec<Vec<char>> = text
+        .split('\n')
+        .map(|line| line.chars().collect())
+        .collect();
+    let count = replace::apply_to_lines(&mut lines, matches, query, replacement);
+    if count > 0 {
+        let text: Vec<String> = lines.iter().map(|line| line.iter().collect()).collect();
+        std::fs::write(path, text.join("\n"))?;
+    }
+    Ok(count)
+}
+
+/// Dialog shown before closing buffers with unsaved changes
+fn unsaved_changes_prompt(title: &str) -> Prompt {
+    Prompt::confirm(
+        title,
+        vec![
+            String::from("Arquivos modificados!"),
+            String::from("(s)alvar, (n)ão salvar, (c)ancelar"),
+        ],
+        &['s', 'n', 'c'],
+    )
+    .warning()
+}
+
+/// Human readable file size, e.g. "12.3 MB"
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+/// Interpret pasted text as a dropped file path. Handles the forms terminals
+/// use for drag-and-drop: plain paths, quoted paths, backslash-escaped spaces
+/// and `file://` URIs.
+fn parse_dropped_path(text: &str) -> Option<PathBuf> {
+    let mut raw = text.trim();
+    if raw.is_empty() || raw.contains('\n') {
+        return None;
+    }
+
+    if raw.len() >= 2
+        && ((raw.starts_with('\'') && raw.ends_with('\''))
+            || (raw.starts_with('"') && raw.ends_with('"')))
+    {
+        raw = &raw[1..raw.len() - 1];
+    }
+
+    let path = if let Some(uri) = raw.strip_prefix("file://") {
+        transform::url_decode(uri)?
+    } else {
+        let mut unescaped = String::with_capacity(raw.len());
+        let mut chars = raw.chars();
+        while let Some(c) = chars.next() {
+            if c == '\\' {
+                if let Some(next) = chars.next() {
+                    unescaped.push(next);
+                }
+            } else {
+                unescaped.push(c);
+            }
+        }
+        unescaped
+    };
+
+    let path = PathBuf::from(path);
+    if path.is_absolute() {
+        Some(path)
+    } else {
+        None
+    }
 }
@@ -0,0 +1,243 @@
+use std::path::Path;
+
+use crate::commands::{self, Command};
+use crate::expand;
+use crate::options;
+
+/// Entries the `:` line remembers
+pub const HISTORY_SIZE: usize = 100;
+
+/// Settings read from config.toml when used, which `:set` knows by a short
+/// name besides the runtime options; any other one is written as
+/// `section.key`
+pub const CONFIG_OPTIONS: &[(&str, &str, &str)] = &[
+    ("auto_pairs", "editor", "auto_pairs"),
+    ("auto_close", "html", "auto_close"),
+    ("json_indent", "json", "indent"),
+    ("inline_diagnostics", "diagnostics", "inline"),
+    ("suspicious_chars", "display", "suspicious_chars"),
+    ("large_file_mb", "", "large_file_mb"),
+];
+
+/// Commands of the `:` line besides the palette's
+const BUILTINS: &[&str] = &["w", "q", "q!", "wq", "x", "e", "set", "setlocal", "s", "%s"];
+
+/// A command typed after `:`
+pub enum Ex {
+    /// `:w`
+    Write,
+    /// `:q`, or `:q!` to leave unsaved changes behind
+    Quit { force: bool },
+    /// `:wq` or `:x`
+    WriteQuit,
+    /// `:e path`
+    Edit(String),
+    /// `:[range]s/old/new/[gi]`
+    Substitute(Substitute),
+    /// `:set name=value`, `:set name` to see it, `:set name!` to flip it;
+    /// `:setlocal` for the active buffer alone
+    Set {
+        option: String,
+        value: Option<String>,
+        local: bool,
+    },
+    /// `:N`
+    Goto(usize),
+    /// Any palette command by its name (`:json-format`)
+    Command(Command),
+}
+
+/// Lines a `:s` works on
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Range {
+    /// No range: the cursor's line
+    Line,
+    /// `%`
+    All,
+    /// `N,M` (1-based; `$` is the last line)
+    Lines(usize, usize),
+    /// `'<,'>`, the lines of the selection the `:` was typed in
+    Selection,
+}
+
+/// A literal text replacement (`old` isn't a regular expression)
+pub struct Substitute {
+    pub range: Range,
+    pub old: Vec<char>,
+    pub new: Vec<char>,
+    /// Every occurrence in a line, not just the first (`g`)
+    pub global: bool,
+    /// `i`
+    pub ignore_case: bool,
+}
+
+impl Substitute {
+    /// `line` with `old` replaced, or `None` when it doesn't occur
+    pub fn apply(&self, line: &[char]) -> Option<Vec<char>> {
+        let same = |a: &char, b: &char| {
+            a == b || (self.ignore_case && a.to_lowercase().eq(b.to_lowercase()))
+        };
+        let mut out = vec![];
+        let mut found = false;
+        let mut i = 0;
+        while i < line.len() {
+            let matches = (!found || self.global)
+                && line.len() - i >= self.old.len()
+                && line[i..i + self.old.len()]
+                    .iter()
+                    .zip(&self.old)
+                    .all(|(a, b)| same(a, b));
+            if matches {
+                out.extend(&self.new);
+                i += self.old.len();
+                found = true;
+            } else {
+                out.push(line[i]);
+                i += 1;
+            }
+        }
+        found.then_some(out)
+    }
+}
+
+/// Read a `:` line (without the `:`)
+pub fn parse(input: &str) -> Result<Ex, String> {
+    let input = input.trim();
+    let (name, arg) = match input.split_once(char::is_whitespace) {
+        Some((name, arg)) => (name, arg.trim()),
+        None => (input, ""),
+    };
+    if let Ok(line) = input.parse::<usize>() {
+        return Ok(Ex::Goto(line.max(1)));
+    }
+    match name {
+        "" => Err(String::new()),
+        "w" | "write" => Ok(Ex::Write),
+        "q" | "quit" => Ok(Ex::Quit { force: false }),
+        "q!" | "quit!" => Ok(Ex::Quit { force: true }),
+        "wq" | "x" => Ok(Ex::WriteQuit),
+        "e" | "edit" if arg.is_empty() => Err(String::from(":e espera um arquivo")),
+        "e" | "edit" => Ok(Ex::Edit(arg.to_string())),
+        "set" | "setlocal" | "setl" if arg.is_empty() => Err(format!(":{} espera uma opção", name)),
+        "set" | "setlocal" | "setl" => {
+            let (option, value) = match arg.split_once('=') {
+                Some((option, value)) => (option.trim(), Some(value.trim().to_string())),
+                None => (arg, None),
+            };
+            Ok(Ex::Set {
+                option: option.to_string(),
+                value,
+                local: name != "set",
+            })
+        }
+        _ => match commands::by_name(input) {
+            Some(command) => Ok(Ex::Command(command)),
+            None => substitute(input)
+                .map(Ex::Substitute)
+                .ok_or_else(|| format!("Comando desconhecido: :{}", input)),
+        },
+    }
+}
+
+/// `[range]s<sep>old<sep>new[<sep>flags]`, any punctuation as `<sep>`
+fn substitute(input: &str) -> Option<Substitute> {
+    let (range, rest) = if let Some(rest) = input.strip_prefix('%') {
+        (Range::All, rest)
+    } else if let Some(rest) = input.strip_prefix("'<,'>") {
+        (Range::Selection, rest)
+    } else {
+        let digits = input.find(|c: char| !c.is_ascii_digit() && c != ',' && c != '$');
+        match digits.map(|end| input.split_at(end)) {
+            Some(("", rest)) => (Range::Line, rest),
+            Some((range, rest)) => {
+                let (first, last) = range.split_once(',').unwrap_or((range, range));
+                let line = |n: &str| match n {
+                    "$" => Some(usize::MAX),
+                    _ => n.parse::<usize>().ok().filter(|&n| n > 0),
+                };
+                (Range::Lines(line(first)?, line(last)?), rest)
+            }
+            None => return None,
+        }
+    };
+    let mut chars = rest.strip_prefix('s')?.chars();
+    let sep = chars.next().filter(|c| c.is_ascii_punctuation())?;
+
+    // Fields split at unescaped separators; `\<sep>` and `\\` are literal
+    let mut fields: Vec<Vec<char>> = vec![vec![]];
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some(next) if next == sep || next == '\\' => fields.last_mut()?.push(next),
+                Some(next) => fields.last_mut()?.extend(['\\', next]),
+                None => fields.last_mut()?.push('\\'),
+            },
+            _ if c == sep && fields.len() < 3 => fields.push(vec![]),
+            _ => fields.last_mut()?.push(c),
+        }
+    }
+    let old = fields.first().filter(|old| !old.is_empty())?.clone();
+    let new = fields.get(1).cloned().unwrap_or_default();
+    let flags = fields.get(2).cloned().unwrap_or_default();
+    if flags.iter().any(|flag| !matches!(flag, 'g' | 'i')) {
+        return None;
+    }
+    Some(Substitute {
+        range,
+        old,
+        new,
+        global: flags.contains(&'g'),
+        ignore_case: flags.contains(&'i'),
+    })
+}
+
+/// Where `:set` keeps a config.toml `option`: a name of `CONFIG_OPTIONS`,
+/// or `section.key`
+pub fn config_option(option: &str) -> Option<(String, String)> {
+    let known = CONFIG_OPTIONS.iter().find(|(name, _, _)| *name == option);
+    if let Some(&(_, section, key)) = known {
+        return Some((section.to_string(), key.to_string()));
+    }
+    let (section, key) = option.split_once('.')?;
+    Some((section.to_string(), key.to_string())).filter(|_| !key.is_empty())
+}
+
+/// Lines Tab can turn `input` into: command names, `:set` options and,
+/// after `:e`, the files and folders the path so far leads to
+pub fn complete(input: &str) -> Vec<String> {
+    let Some((name, arg)) = input.split_once(' ') else {
+        let palette = commands::COMMANDS.iter().map(|info| info.name);
+        return BUILTINS
+            .iter()
+            .copied()
+            .chain(palette)
+            .filter(|name| name.starts_with(input))
+            .map(str::to_string)
+            .collect();
+    };
+    let arg = arg.trim_start();
+    match name {
+        "set" | "setlocal" | "setl" => {
+            let runtime = options::OPTIONS.iter().map(|(option, _)| *option);
+            let config = CONFIG_OPTIONS.iter().map(|(option, _, _)| *option);
+            runtime
+                .chain(config)
+                .filter(|option| option.starts_with(arg))
+                .map(|option| format!("{} {}", name, option))
+                .collect()
+        }
+        "e" | "edit" => {
+            // The folder typed so far, and the start of a name in it
+            let (dir, start) = match arg.rfind('/') {
+                Some(slash) => (&arg[..=slash], &arg[slash + 1..]),
+                None => ("", arg),
+            };
+            let listed = expand::expand(if dir.is_empty() { "." } else { dir });
+            expand::names_starting(Path::new(&listed), start)
+                .into_iter()
+                .map(|file| format!("{} {}{}", name, dir, file))
+                .collect()
+        }
+        _ => vec![],
+    }
+}
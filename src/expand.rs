@@ -0,0 +1,179 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Matches above which opening them all asks first
+pub const MANY_FILES: usize = 20;
+
+/// `~`, `$VAR` and `${VAR}` replaced as a shell would, for paths typed in
+/// the open prompt or quoted on the command line. Unset variables stay as
+/// written.
+pub fn expand(input: &str) -> String {
+    let home = env::var("HOME").ok();
+    let input = match (input.strip_prefix('~'), &home) {
+        (Some(rest), Some(home)) if rest.is_empty() || rest.starts_with('/') => {
+            format!("{}{}", home, rest)
+        }
+        _ => input.to_string(),
+    };
+
+    let mut out = String::new();
+    let mut rest = input.as_str();
+    while let Some(dollar) = rest.find('$') {
+        out.push_str(&rest[..dollar]);
+        let after = &rest[dollar + 1..];
+        let (name, consumed) = match after.strip_prefix('{') {
+            Some(braced) => match braced.find('}') {
+                Some(end) => (&braced[..end], end + 2),
+                None => ("", 0),
+            },
+            None => {
+                let end = after
+                    .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                    .unwrap_or(after.len());
+                (&after[..end], end)
+            }
+        };
+        match env::var(name).ok().filter(|_| !name.is_empty()) {
+            Some(value) => out.push_str(&value),
+            None => out.push_str(&rest[dollar..dollar + 1 + consumed]),
+        }
+        rest = &after[consumed..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Names in folder `dir` that start with `start`, sorted, with a `/` after
+/// the folders. Hidden ones only when `start` begins with `.` itself.
+pub fn names_starting(dir: &Path, start: &str) -> Vec<String> {
+    let mut names: Vec<String> = fs::read_dir(dir)
+        .map(|read| {
+            read.flatten()
+                .filter_map(|entry| {
+                    let name = entry.file_name().to_string_lossy().to_string();
+                    let hidden = name.starts_with('.') && !start.starts_with('.');
+                    if hidden || !name.starts_with(start) {
+                        return None;
+                    }
+                    let slash = if entry.path().is_dir() { "/" } else { "" };
+                    Some(format!("{}{}", name, slash))
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    names.sort();
+    names
+}
+
+/// Whether `path` has wildcards to expand (`*`, `?`, `[...]`)
+pub fn has_glob(path: &str) -> bool {
+    path.contains(['*', '?', '['])
+}
+
+/// Files matching `pattern`, sorted. `*` and `?` stay within a name, `[a-z]`
+/// picks one char of a set and `**` spans any number of folders. Hidden
+/// entries only match a part that starts with `.` itself.
+pub fn glob(pattern: &str) -> Vec<PathBuf> {
+    let (base, rest) = match pattern.strip_prefix('/') {
+        Some(rest) => (PathBuf::from("/"), rest),
+        None => (PathBuf::from("."), pattern),
+    };
+    let parts: Vec<&str> = rest.split('/').filter(|part| !part.is_empty()).collect();
+    let mut found = vec![];
+    walk(&base, &parts, &mut found);
+    found.sort();
+    found.dedup();
+    // Relative patterns give relative paths, as a shell would
+    found
+        .into_iter()
+        .map(|path| {
+            path.strip_prefix("./")
+                .map(Path::to_path_buf)
+                .unwrap_or(path)
+        })
+        .collect()
+}
+
+fn walk(dir: &Path, parts: &[&str], found: &mut Vec<PathBuf>) {
+    let Some((&part, rest)) = parts.split_first() else {
+        if dir.is_file() {
+            found.push(dir.to_path_buf());
+        }
+        return;
+    };
+    if part == "**" {
+        // Zero folders, then one more level down with `**` still pending
+        walk(dir, rest, found);
+        // Hidden folders (.git) and links (which may loop) are left out
+        let folders = entries(dir).into_iter().filter(|path| {
+            !is_hidden(path) && fs::symlink_metadata(path).is_ok_and(|meta| meta.is_dir())
+        });
+        for entry in folders {
+            walk(&entry, parts, found);
+        }
+        return;
+    }
+    if !has_glob(part) {
+        walk(&dir.join(part), rest, found);
+        return;
+    }
+    let pattern: Vec<char> = part.chars().collect();
+    for entry in entries(dir) {
+        let name: Vec<char> = entry
+            .file_name()
+            .map(|name| name.to_string_lossy().chars().collect())
+            .unwrap_or_default();
+        if is_hidden(&entry) && pattern.first() != Some(&'.') {
+            continue;
+        }
+        if matches(&pattern, &name) {
+            walk(&entry, rest, found);
+        }
+    }
+}
+
+fn is_hidden(path: &Path) -> bool {
+    path.file_name()
+        .is_some_and(|name| name.to_string_lossy().starts_with('.'))
+}
+
+/// Entries of `dir`, hidden ones included (`walk` filters them)
+fn entries(dir: &Path) -> Vec<PathBuf> {
+    fs::read_dir(dir)
+        .map(|read| read.flatten().map(|entry| entry.path()).collect())
+        .unwrap_or_default()
+}
+
+/// Whether `name` matches the wildcard `pattern`
+fn matches(pattern: &[char], name: &[char]) -> bool {
+    match pattern.first() {
+        None => name.is_empty(),
+        Some('*') => (0..=name.len()).any(|skip| matches(&pattern[1..], &name[skip..])),
+        Some('?') => !name.is_empty() && matches(&pattern[1..], &name[1..]),
+        Some('[') => match (pattern.iter().position(|&c| c == ']'), name.first()) {
+            (Some(close), Some(&ch)) if close > 1 => {
+                let set = &pattern[1..close];
+                let (negated, set) = match set.first() {
+                    Some('!') | Some('^') => (true, &set[1..]),
+                    _ => (false, set),
+                };
+                let mut hit = false;
+                let mut i = 0;
+                while i < set.len() {
+                    if set.get(i + 1) == Some(&'-') && i + 2 < set.len() {
+                        hit |= (set[i]..=set[i + 2]).contains(&ch);
+                        i += 3;
+                    } else {
+                        hit |= set[i] == ch;
+                        i += 1;
+                    }
+                }
+                hit != negated && matches(&pattern[close + 1..], &name[1..])
+            }
+            // No closing bracket: a literal '['
+            _ => name.first() == Some(&'[') && matches(&pattern[1..], &name[1..]),
+        },
+        Some(&c) => name.first() == Some(&c) && matches(&pattern[1..], &name[1..]),
+    }
+}
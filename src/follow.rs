@@ -0,0 +1,78 @@
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+
+/// A buffer following its file as it grows, like `tail -f`
+pub struct Follow {
+    pub path: String,
+    // Bytes of the file already in the buffer
+    offset: u64,
+    // Start of a line still being written, held back until its newline
+    partial: Vec<u8>,
+    // The buffer's last line was cut short by the end of the file, so the
+    // next complete line continues it
+    continues_line: bool,
+}
+
+/// What changed in a followed file since the last check
+pub enum Growth {
+    /// Complete new lines; the first one continues the buffer's last line
+    /// when `continues` is set
+    Lines {
+        lines: Vec<Vec<char>>,
+        continues: bool,
+    },
+    /// The file shrank (truncated or rotated) and has to be read again
+    Truncated,
+}
+
+impl Follow {
+    /// Follow `path` from its current end
+    pub fn start(path: &str) -> io::Result<Follow> {
+        let mut file = File::open(path)?;
+        let size = file.metadata()?.len();
+        let mut last = [b'\n'];
+        if size > 0 {
+            file.seek(SeekFrom::Start(size - 1))?;
+            file.read_exact(&mut last)?;
+        }
+        Ok(Follow {
+            path: path.to_string(),
+            offset: size,
+            partial: vec![],
+            // An empty file shows one empty line, which the first line fills
+            continues_line: size == 0 || last[0] != b'\n',
+        })
+    }
+
+    /// Read what was appended since the last call
+    pub fn poll(&mut self) -> io::Result<Option<Growth>> {
+        let mut file = File::open(&self.path)?;
+        let size = file.metadata()?.len();
+        if size < self.offset {
+            *self = Follow::start(&self.path)?;
+            return Ok(Some(Growth::Truncated));
+        }
+        if size == self.offset {
+            return Ok(None);
+        }
+        file.seek(SeekFrom::Start(self.offset))?;
+        let mut bytes = std::mem::take(&mut self.partial);
+        let read = file.take(size - self.offset).read_to_end(&mut bytes)?;
+        self.offset += read as u64;
+
+        let complete = match bytes.iter().rposition(|&b| b == b'\n') {
+            Some(end) => end + 1,
+            None => {
+                self.partial = bytes;
+                return Ok(None);
+            }
+        };
+        self.partial = bytes.split_off(complete);
+        let lines = String::from_utf8_lossy(&bytes)
+            .lines()
+            .map(|line| line.chars().collect())
+            .collect();
+        let continues = std::mem::replace(&mut self.continues_line, false);
+        Ok(Some(Growth::Lines { lines, continues }))
+    }
+}
@@ -0,0 +1,335 @@
+use crate::jobs::{JobId, JobMessage, Jobs};
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Who last changed one line of a file
+#[derive(Debug, Clone)]
+pub struct BlameLine {
+    /// Abbreviated commit hash; all zeros for uncommitted lines
+    pub hash: String,
+    pub author: String,
+    /// Author time, in seconds since the epoch
+    pub time: u64,
+}
+
+impl BlameLine {
+    fn is_committed(&self) -> bool {
+        self.hash.chars().any(|c| c != '0')
+    }
+
+    /// End-of-line annotation, e.g. "Ana Souza, há 3 dias • 1a2b3c4d"
+    pub fn summary(&self) -> String {
+        if !self.is_committed() {
+            return String::from("Não commitado");
+        }
+        format!("{}, {} • {}", self.author, age(self.time), self.hash)
+    }
+}
+
+/// Run `git blame` for `path` on a worker thread. The result arrives as
+/// `JobMessage::Blame`.
+pub fn start_blame(jobs: &mut Jobs, path: &str) -> JobId {
+    let thread_path = path.to_string();
+    let label = format!(
+        "git blame {}",
+        Path::new(path)
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default()
+    );
+    jobs.spawn(label, move |context| {
+        context.send(JobMessage::Blame(blame(&thread_path)));
+    })
+}
+
+fn blame(path: &str) -> io::Result<Vec<BlameLine>> {
+    let dir = Path::new(path).parent().unwrap_or(Path::new("."));
+    let output = run_git(dir, &["blame", "--porcelain", "--", path], None)?;
+    Ok(parse_porcelain(&output))
+}
+
+/// One entry per line of the file. A commit's author fields only follow its
+/// first header, so they are remembered by hash.
+fn parse_porcelain(text: &str) -> Vec<BlameLine> {
+    let mut commits: HashMap<&str, (String, u64)> = HashMap::new();
+    let mut lines = vec![];
+    let mut current = "";
+    for line in text.lines() {
+        if line.starts_with('\t') {
+            let (author, time) = commits.get(current).cloned().unwrap_or_default();
+            lines.push(BlameLine {
+                hash: current.chars().take(8).collect(),
+                author,
+                time,
+            });
+        } else if let Some(author) = line.strip_prefix("author ") {
+            commits.entry(current).or_default().0 = author.to_string();
+        } else if let Some(time) = line.strip_prefix("author-time ") {
+            commits.entry(current).or_default().1 = time.trim().parse().unwrap_or(0);
+        } else {
+            let first = line.split(' ').next().unwrap_or("");
+            if first.len() == 40 && first.chars().all(|c| c.is_ascii_hexdigit()) {
+                current = first;
+            }
+        }
+    }
+    lines
+}
+
+/// "há 5 min", "há 3 dias"... relative to now
+pub fn age(time: u64) -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let seconds = now.saturating_sub(time);
+    let (count, unit, plural) = match seconds {
+        0..=59 => return String::from("agora"),
+        60..=3_599 => (seconds / 60, "min", "min"),
+        3_600..=86_399 => (seconds / 3_600, "hora", "horas"),
+        86_400..=2_591_999 => (seconds / 86_400, "dia", "dias"),
+        2_592_000..=31_535_999 => (seconds / 2_592_000, "mês", "meses"),
+        _ => (seconds / 31_536_000, "ano", "anos"),
+    };
+    format!("há {} {}", count, if count == 1 { unit } else { plural })
+}
+
+/// A block of changed lines from `git diff -U0`
+pub struct Hunk {
+    /// The `@@ -a,b +c,d @@` line
+    header: String,
+    /// Lines of the diff after the header ('-', '+' and '\' lines)
+    body: Vec<String>,
+    /// 0-based row in the working file where the hunk starts
+    pub row: usize,
+    /// Lines of the working file the hunk covers (0 for a pure deletion)
+    pub count: usize,
+    /// Text the hunk replaced
+    pub old_lines: Vec<String>,
+}
+
+impl Hunk {
+    /// Whether `row` is inside the hunk. A deletion sits on the line that
+    /// followed the removed block.
+    pub fn contains(&self, row: usize) -> bool {
+        row >= self.row && row < self.row + self.count.max(1)
+    }
+}
+
+/// The changes of one file, with the diff header `git apply` needs
+pub struct FileDiff {
+    toplevel: String,
+    header: Vec<String>,
+    pub hunks: Vec<Hunk>,
+}
+
+/// Hunks of the saved `path` against the index, or against HEAD when
+/// `against_head` is set
+pub fn diff(path: &str, against_head: bool) -> io::Result<FileDiff> {
+    let dir = Path::new(path).parent().unwrap_or(Path::new("."));
+    let toplevel = run_git(dir, &["rev-parse", "--show-toplevel"], None)?
+        .trim()
+        .to_string();
+    let mut args = vec!["diff", "-U0", "--no-color", "--no-ext-diff"];
+    if against_head {
+        args.push("HEAD");
+    }
+    args.extend(["--", path]);
+    let output = run_git(Path::new(&toplevel), &args, None)?;
+
+    let mut header = vec![];
+    let mut hunks: Vec<Hunk> = vec![];
+    for line in output.lines() {
+        if line.starts_with("@@") {
+            let (row, count) = parse_new_range(line).unwrap_or((0, 0));
+            hunks.push(Hunk {
+                header: line.to_string(),
+                body: vec![],
+                row,
+                count,
+                old_lines: vec![],
+            });
+        } else if let Some(hunk) = hunks.last_mut() {
+            if let Some(old) = line.strip_prefix('-') {
+                hunk.old_lines.push(old.to_string());
+            }
+            hunk.body.push(line.to_string());
+        } else {
+            header.push(line.to_string());
+        }
+    }
+    Ok(FileDiff {
+        toplevel,
+        header,
+        hunks,
+    })
+}
+
+/// 0-based start row and line count of the "+c,d" part of a hunk header
+fn parse_new_range(header: &str) -> Option<(usize, usize)> {
+    let range = header.split(' ').find(|part| part.starts_with('+'))?;
+    let mut parts = range[1..].splitn(2, ',');
+    let start: usize = parts.next()?.parse().ok()?;
+    let count: usize = match parts.next() {
+        Some(count) => count.parse().ok()?,
+        None => 1,
+    };
+    // For a deletion git gives the line before the removed block
+    let row = if count == 0 {
+        start
+    } else {
+        start.saturating_sub(1)
+    };
+    Some((row, count))
+}
+
+/// Add one hunk of `diff` to the index
+pub fn stage_hunk(diff: &FileDiff, hunk: &Hunk) -> io::Result<()> {
+    let mut patch = diff.header.join("\n");
+    patch.push('\n');
+    patch.push_str(&hunk.header);
+    patch.push('\n');
+    for line in &hunk.body {
+        patch.push_str(line);
+        patch.push('\n');
+    }
+    run_git(
+        Path::new(&diff.toplevel),
+        &["apply", "--cached", "--unidiff-zero", "-"],
+        Some(&patch),
+    )
+    .map(|_| ())
+}
+
+/// Run git in `dir`, feeding `input` on stdin. Errors carry git's message.
+fn run_git(dir: &Path, args: &[&str], input: Option<&str>) -> io::Result<String> {
+    let mut child = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .stdin(if input.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    if let (Some(input), Some(mut stdin)) = (input, child.stdin.take()) {
+        stdin.write_all(input.as_bytes())?;
+    }
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let reason = stderr.lines().next().unwrap_or("git falhou").trim();
+        return Err(io::Error::other(reason.to_string()));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// Line above which `git commit -v` puts the diff being committed
+const SCISSORS: &str = "------------------------ >8 ------------------------";
+
+/// Whether a commit message has nothing besides comments and blank lines,
+/// which makes git abort the commit
+pub fn commit_message_is_empty(lines: &[Vec<char>]) -> bool {
+    for line in lines {
+        let text: String = line.iter().collect();
+        if text.contains(SCISSORS) {
+            break;
+        }
+        if !text.starts_with('#') && !text.trim().is_empty() {
+            return false;
+        }
+    }
+    true
+}
+
+/// Files being committed, read from the message's comments: "+a -d" per
+/// file when `git commit -v` included the diff, the status otherwise.
+/// Empty when the comments list nothing.
+pub fn commit_diffstat(lines: &[Vec<char>]) -> Vec<String> {
+    // The first "#\t" block lists the staged files, whatever the locale;
+    // later ones are unstaged and untracked files
+    let mut staged: Vec<(String, String)> = vec![];
+    let mut staged_done = false;
+    let mut counts: Vec<(String, usize, usize)> = vec![];
+    let mut in_diff = false;
+    for line in lines {
+        let text: String = line.iter().collect();
+        if in_diff {
+            if let Some(names) = text.strip_prefix("diff --git ") {
+                let name = names.rsplit(" b/").next().unwrap_or(names);
+                counts.push((name.to_string(), 0, 0));
+            } else if let Some(file) = counts.last_mut() {
+                if text.starts_with('+') && !text.starts_with("+++") {
+                    file.1 += 1;
+                } else if text.starts_with('-') && !text.starts_with("---") {
+                    file.2 += 1;
+                }
+            }
+        } else if text.contains(SCISSORS) {
+            in_diff = true;
+        } else if let Some(entry) = text.strip_prefix("#\t") {
+            if let (false, Some((status, path))) = (staged_done, entry.split_once(':')) {
+                let path = path.trim();
+                let path = path.rsplit(" -> ").next().unwrap_or(path);
+                staged.push((status.trim().to_string(), path.to_string()));
+            }
+        } else if !staged.is_empty() && text.trim() != "#" {
+            staged_done = true;
+        }
+    }
+
+    if !counts.is_empty() {
+        let added: usize = counts.iter().map(|file| file.1).sum();
+        let removed: usize = counts.iter().map(|file| file.2).sum();
+        let mut rows: Vec<String> = counts
+            .iter()
+            .map(|(name, a, d)| format!("{}  +{} -{}", name, a, d))
+            .collect();
+        rows.push(format!(
+            "{} arquivo(s), +{} -{}",
+            counts.len(),
+            added,
+            removed
+        ));
+        return rows;
+    }
+    staged
+        .iter()
+        .map(|(status, path)| format!("{}: {}", status, path))
+        .collect()
+}
+
+/// Modified, added and untracked files under `dir` (absolute paths), as
+/// `git status` reports them. Deleted files are left out.
+pub fn changed_files(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let toplevel = run_git(dir, &["rev-parse", "--show-toplevel"], None)?;
+    let toplevel = PathBuf::from(toplevel.trim());
+    let output = run_git(
+        dir,
+        &["status", "--porcelain", "-z", "--untracked-files=all"],
+        None,
+    )?;
+    let mut files = vec![];
+    let mut entries = output.split('\0');
+    while let Some(entry) = entries.next() {
+        if entry.len() < 4 {
+            continue;
+        }
+        let (status, path) = entry.split_at(3);
+        // Renames are followed by the old name
+        if status.starts_with('R') || status.starts_with('C') {
+            entries.next();
+        }
+        let path = toplevel.join(path);
+        if path.starts_with(dir) && path.exists() {
+            files.push(path);
+        }
+    }
+    Ok(files)
+}
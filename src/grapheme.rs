@@ -0,0 +1,156 @@
+/// Whether `ch` extends the character before it instead of starting a new
+/// one: combining accents, variation selectors, skin tones, emoji tags
+pub fn is_extend(ch: char) -> bool {
+    matches!(
+        ch as u32,
+        0x0300..=0x036F
+            | 0x1AB0..=0x1AFF
+            | 0x1DC0..=0x1DFF
+            | 0x200C..=0x200D
+            | 0x20D0..=0x20FF
+            | 0xFE00..=0xFE0F
+            | 0xFE20..=0xFE2F
+            | 0x1F3FB..=0x1F3FF
+            | 0xE0020..=0xE007F
+    )
+}
+
+const ZWJ: char = '\u{200D}';
+
+fn is_regional_indicator(ch: char) -> bool {
+    matches!(ch as u32, 0x1F1E6..=0x1F1FF)
+}
+
+/// Column where the character the user sees at `col` ends: past its
+/// accents, and past the rest of an emoji sequence or flag
+pub fn next_boundary(line: &[char], col: usize) -> usize {
+    if col >= line.len() {
+        return line.len();
+    }
+    let mut end = col + 1;
+    if is_regional_indicator(line[col]) && line.get(end).copied().is_some_and(is_regional_indicator)
+    {
+        end += 1;
+    }
+    while end < line.len() {
+        if line[end - 1] == ZWJ {
+            // Joins the next character into the same emoji
+            end += 1;
+        } else if is_extend(line[end]) {
+            end += 1;
+        } else {
+            break;
+        }
+    }
+    end.min(line.len())
+}
+
+/// Column where the character the user sees before `col` starts
+pub fn prev_boundary(line: &[char], col: usize) -> usize {
+    let col = col.min(line.len());
+    let mut start = 0;
+    // Boundaries depend on what came before (flags pair up from the start
+    // of a run), so walk from the line start
+    while start < col {
+        let next = next_boundary(line, start);
+        if next >= col {
+            return start;
+        }
+        start = next;
+    }
+    0
+}
+
+/// The precomposed form of `base` followed by the combining `mark`, for
+/// the Latin letters Portuguese (and its neighbours) write with accents
+fn compose_pair(base: char, mark: char) -> Option<char> {
+    let composed = match (mark, base) {
+        // Grave
+        ('\u{0300}', 'a') => 'à',
+        ('\u{0300}', 'e') => 'è',
+        ('\u{0300}', 'i') => 'ì',
+        ('\u{0300}', 'o') => 'ò',
+        ('\u{0300}', 'u') => 'ù',
+        ('\u{0300}', 'A') => 'À',
+        ('\u{0300}', 'E') => 'È',
+        ('\u{0300}', 'I') => 'Ì',
+        ('\u{0300}', 'O') => 'Ò',
+        ('\u{0300}', 'U') => 'Ù',
+        // Acute
+        ('\u{0301}', 'a') => 'á',
+        ('\u{0301}', 'e') => 'é',
+        ('\u{0301}', 'i') => 'í',
+        ('\u{0301}', 'o') => 'ó',
+        ('\u{0301}', 'u') => 'ú',
+        ('\u{0301}', 'y') => 'ý',
+        ('\u{0301}', 'A') => 'Á',
+        ('\u{0301}', 'E') => 'É',
+        ('\u{0301}', 'I') => 'Í',
+        ('\u{0301}', 'O') => 'Ó',
+        ('\u{0301}', 'U') => 'Ú',
+        ('\u{0301}', 'Y') => 'Ý',
+        // Circumflex
+        ('\u{0302}', 'a') => 'â',
+        ('\u{0302}', 'e') => 'ê',
+        ('\u{0302}', 'i') => 'î',
+        ('\u{0302}', 'o') => 'ô',
+        ('\u{0302}', 'u') => 'û',
+        ('\u{0302}', 'A') => 'Â',
+        ('\u{0302}', 'E') => 'Ê',
+        ('\u{0302}', 'I') => 'Î',
+        ('\u{0302}', 'O') => 'Ô',
+        ('\u{0302}', 'U') => 'Û',
+        // Tilde
+        ('\u{0303}', 'a') => 'ã',
+        ('\u{0303}', 'o') => 'õ',
+        ('\u{0303}', 'n') => 'ñ',
+        ('\u{0303}', 'A') => 'Ã',
+        ('\u{0303}', 'O') => 'Õ',
+        ('\u{0303}', 'N') => 'Ñ',
+        // Diaeresis
+        ('\u{0308}', 'a') => 'ä',
+        ('\u{0308}', 'e') => 'ë',
+        ('\u{0308}', 'i') => 'ï',
+        ('\u{0308}', 'o') => 'ö',
+        ('\u{0308}', 'u') => 'ü',
+        ('\u{0308}', 'y') => 'ÿ',
+        ('\u{0308}', 'A') => 'Ä',
+        ('\u{0308}', 'E') => 'Ë',
+        ('\u{0308}', 'I') => 'Ï',
+        ('\u{0308}', 'O') => 'Ö',
+        ('\u{0308}', 'U') => 'Ü',
+        // Cedilla
+        ('\u{0327}', 'c') => 'ç',
+        ('\u{0327}', 'C') => 'Ç',
+        _ => return None,
+    };
+    Some(composed)
+}
+
+/// `text` with each letter + combining accent (as dead keys and IMEs may
+/// send them) folded into one precomposed char, which the screen draws in
+/// a single cell. `before`, the char preceding the text, can take the
+/// first accent too; returns whether it did.
+pub fn compose(before: Option<char>, text: &str) -> (bool, String) {
+    let mut out: Vec<char> = before.into_iter().collect();
+    for ch in text.chars() {
+        if let Some(last) = out.last_mut() {
+            if let Some(composed) = compose_pair(*last, ch) {
+                *last = composed;
+                continue;
+            }
+        }
+        out.push(ch);
+    }
+    let took = before.is_some_and(|before| out.first() != Some(&before));
+    let skip = usize::from(before.is_some() && !took);
+    (took, out.into_iter().skip(skip).collect())
+}
+
+/// `col` moved back to the start of the character it falls inside of
+pub fn snap(line: &[char], col: usize) -> usize {
+    if col >= line.len() {
+        return line.len();
+    }
+    prev_boundary(line, col + 1)
+}
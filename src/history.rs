@@ -0,0 +1,161 @@
+use crate::config;
+use crate::log;
+use crate::remote;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Entries kept; older files fall off the end
+const MAX_ENTRIES: usize = 1000;
+
+/// Folders kept in the jump list; the least used fall off
+const MAX_DIRS: usize = 200;
+
+/// A file opened in this or an earlier session
+pub struct OldFile {
+    pub path: String,
+    /// When it was last opened, in seconds since the epoch
+    pub time: u64,
+}
+
+/// A folder opened in the sidebar, in this or an earlier session
+pub struct RecentDir {
+    pub path: PathBuf,
+    /// Times it was opened
+    pub count: u64,
+    /// When it was last opened, in seconds since the epoch
+    pub time: u64,
+}
+
+/// `history` in the state directory: one "time\tpath" line per file,
+/// most recent first
+fn path() -> Option<PathBuf> {
+    config::state_dir().map(|dir| dir.join("history"))
+}
+
+/// Every file in the history, most recent first. Missing local files are
+/// left out.
+pub fn load() -> Vec<OldFile> {
+    let text = match path().and_then(|p| fs::read_to_string(p).ok()) {
+        Some(text) => text,
+        None => return vec![],
+    };
+    text.lines()
+        .filter_map(|line| {
+            let (time, path) = line.split_once('\t')?;
+            Some(OldFile {
+                path: path.to_string(),
+                time: time.parse().ok()?,
+            })
+        })
+        .filter(|old| remote::is_remote(&old.path) || Path::new(&old.path).exists())
+        .collect()
+}
+
+/// Move `file` to the top of the history. Local paths are stored absolute
+/// so they reopen from any directory.
+pub fn record(file: &str) {
+    let file = if remote::is_remote(file) {
+        file.to_string()
+    } else {
+        match fs::canonicalize(file) {
+            Ok(absolute) => absolute.to_string_lossy().to_string(),
+            Err(_) => return,
+        }
+    };
+    if let Err(e) = save(&file) {
+        log::warning!("histórico", "erro ao gravar o histórico: {}", e);
+    }
+}
+
+fn save(file: &str) -> io::Result<()> {
+    let path = path().ok_or_else(|| io::Error::other("HOME não definido"))?;
+    let now = now();
+    let previous = fs::read_to_string(&path).unwrap_or_default();
+
+    let mut text = format!("{}\t{}\n", now, file);
+    for line in previous
+        .lines()
+        .filter(|line| line.split_once('\t').is_some_and(|(_, old)| old != file))
+        .take(MAX_ENTRIES - 1)
+    {
+        text.push_str(line);
+        text.push('\n');
+    }
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(&path, text)
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// `dirs` in the state directory: one "count\ttime\tpath" line per folder
+fn dirs_path() -> Option<PathBuf> {
+    config::state_dir().map(|dir| dir.join("dirs"))
+}
+
+/// Folders opened in the sidebar, the most used first (the most recent
+/// among equals). Folders gone since are left out.
+pub fn load_dirs() -> Vec<RecentDir> {
+    let text = dirs_path()
+        .and_then(|p| fs::read_to_string(p).ok())
+        .unwrap_or_default();
+    let mut dirs: Vec<RecentDir> = text
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.splitn(3, '\t');
+            Some(RecentDir {
+                count: fields.next()?.parse().ok()?,
+                time: fields.next()?.parse().ok()?,
+                path: PathBuf::from(fields.next()?),
+            })
+        })
+        .collect();
+    dirs.sort_by(|a, b| b.count.cmp(&a.count).then(b.time.cmp(&a.time)));
+    dirs.retain(|dir| dir.path.is_dir());
+    dirs
+}
+
+/// Count one more opening of folder `dir` (expanded in the sidebar or
+/// opened as its root)
+pub fn record_dir(dir: &Path) {
+    let Ok(dir) = fs::canonicalize(dir) else {
+        return;
+    };
+    if let Err(e) = save_dir(dir) {
+        log::warning!("histórico", "erro ao gravar as pastas recentes: {}", e);
+    }
+}
+
+fn save_dir(dir: PathBuf) -> io::Result<()> {
+    let path = dirs_path().ok_or_else(|| io::Error::other("HOME não definido"))?;
+    let mut dirs = load_dirs();
+    match dirs.iter_mut().find(|recent| recent.path == dir) {
+        Some(recent) => {
+            recent.count += 1;
+            recent.time = now();
+        }
+        None => dirs.push(RecentDir {
+            path: dir,
+            count: 1,
+            time: now(),
+        }),
+    }
+    dirs.sort_by(|a, b| b.count.cmp(&a.count).then(b.time.cmp(&a.time)));
+    let text: String = dirs
+        .iter()
+        .take(MAX_DIRS)
+        .map(|dir| format!("{}\t{}\t{}\n", dir.count, dir.time, dir.path.display()))
+        .collect();
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&path, text)
+}
@@ -0,0 +1,49 @@
+use crate::config::Config;
+use std::time::{Duration, Instant};
+
+/// Pause after the last input when `[idle] delay_ms` isn't set
+const DEFAULT_DELAY_MS: u64 = 500;
+
+/// Tells when the user has stopped typing for a while, so work that can
+/// wait (highlighting the whole file again, git status, the sidebar's
+/// folders) runs in the pause instead of between keystrokes. Fires once
+/// per pause.
+pub struct Idle {
+    delay: Duration,
+    last_input: Instant,
+    done: bool,
+}
+
+impl Idle {
+    pub fn from_config(config: &Config) -> Idle {
+        Idle {
+            delay: Duration::from_millis(config.get_u64("idle", "delay_ms", DEFAULT_DELAY_MS)),
+            last_input: Instant::now(),
+            done: false,
+        }
+    }
+
+    /// The user pressed a key, clicked or resized: the pause starts over
+    pub fn input(&mut self) {
+        self.last_input = Instant::now();
+        self.done = false;
+    }
+
+    /// How long until the pause is long enough, or `None` when this pause's
+    /// work already ran
+    pub fn time_left(&self) -> Option<Duration> {
+        if self.done {
+            return None;
+        }
+        Some(self.delay.saturating_sub(self.last_input.elapsed()))
+    }
+
+    /// Whether the deferred work should run now; true once per pause
+    pub fn due(&mut self) -> bool {
+        if self.time_left() != Some(Duration::ZERO) {
+            return false;
+        }
+        self.done = true;
+        true
+    }
+}
@@ -0,0 +1,223 @@
+use crate::buffer_file::LineEnding;
+use crate::grapheme;
+use crate::screen;
+
+/// Names of the characters worth telling apart when they can't be seen:
+/// controls, unusual spaces, joiners, direction marks and look-alikes
+const NAMES: &[(char, &str)] = &[
+    ('\u{00}', "NULL"),
+    ('\u{07}', "BELL"),
+    ('\u{08}', "BACKSPACE"),
+    ('\u{09}', "CHARACTER TABULATION"),
+    ('\u{0A}', "LINE FEED"),
+    ('\u{0B}', "LINE TABULATION"),
+    ('\u{0C}', "FORM FEED"),
+    ('\u{0D}', "CARRIAGE RETURN"),
+    ('\u{1B}', "ESCAPE"),
+    ('\u{20}', "SPACE"),
+    ('\u{7F}', "DELETE"),
+    ('\u{85}', "NEXT LINE"),
+    ('\u{A0}', "NO-BREAK SPACE"),
+    ('\u{AD}', "SOFT HYPHEN"),
+    ('\u{34F}', "COMBINING GRAPHEME JOINER"),
+    ('\u{61C}', "ARABIC LETTER MARK"),
+    ('\u{115F}', "HANGUL CHOSEONG FILLER"),
+    ('\u{1160}', "HANGUL JUNGSEONG FILLER"),
+    ('\u{180E}', "MONGOLIAN VOWEL SEPARATOR"),
+    ('\u{2000}', "EN QUAD"),
+    ('\u{2001}', "EM QUAD"),
+    ('\u{2002}', "EN SPACE"),
+    ('\u{2003}', "EM SPACE"),
+    ('\u{2004}', "THREE-PER-EM SPACE"),
+    ('\u{2005}', "FOUR-PER-EM SPACE"),
+    ('\u{2006}', "SIX-PER-EM SPACE"),
+    ('\u{2007}', "FIGURE SPACE"),
+    ('\u{2008}', "PUNCTUATION SPACE"),
+    ('\u{2009}', "THIN SPACE"),
+    ('\u{200A}', "HAIR SPACE"),
+    ('\u{200B}', "ZERO WIDTH SPACE"),
+    ('\u{200C}', "ZERO WIDTH NON-JOINER"),
+    ('\u{200D}', "ZERO WIDTH JOINER"),
+    ('\u{200E}', "LEFT-TO-RIGHT MARK"),
+    ('\u{200F}', "RIGHT-TO-LEFT MARK"),
+    ('\u{2010}', "HYPHEN"),
+    ('\u{2011}', "NON-BREAKING HYPHEN"),
+    ('\u{2012}', "FIGURE DASH"),
+    ('\u{2013}', "EN DASH"),
+    ('\u{2014}', "EM DASH"),
+    ('\u{2018}', "LEFT SINGLE QUOTATION MARK"),
+    ('\u{2019}', "RIGHT SINGLE QUOTATION MARK"),
+    ('\u{201C}', "LEFT DOUBLE QUOTATION MARK"),
+    ('\u{201D}', "RIGHT DOUBLE QUOTATION MARK"),
+    ('\u{2026}', "HORIZONTAL ELLIPSIS"),
+    ('\u{2028}', "LINE SEPARATOR"),
+    ('\u{2029}', "PARAGRAPH SEPARATOR"),
+    ('\u{202A}', "LEFT-TO-RIGHT EMBEDDING"),
+    ('\u{202B}', "RIGHT-TO-LEFT EMBEDDING"),
+    ('\u{202C}', "POP DIRECTIONAL FORMATTING"),
+    ('\u{202D}', "LEFT-TO-RIGHT OVERRIDE"),
+    ('\u{202E}', "RIGHT-TO-LEFT OVERRIDE"),
+    ('\u{202F}', "NARROW NO-BREAK SPACE"),
+    ('\u{205F}', "MEDIUM MATHEMATICAL SPACE"),
+    ('\u{2060}', "WORD JOINER"),
+    ('\u{2066}', "LEFT-TO-RIGHT ISOLATE"),
+    ('\u{2067}', "RIGHT-TO-LEFT ISOLATE"),
+    ('\u{2068}', "FIRST STRONG ISOLATE"),
+    ('\u{2069}', "POP DIRECTIONAL ISOLATE"),
+    ('\u{2212}', "MINUS SIGN"),
+    ('\u{3000}', "IDEOGRAPHIC SPACE"),
+    ('\u{3164}', "HANGUL FILLER"),
+    ('\u{FE0E}', "VARIATION SELECTOR-15"),
+    ('\u{FE0F}', "VARIATION SELECTOR-16"),
+    ('\u{FEFF}', "ZERO WIDTH NO-BREAK SPACE (BOM)"),
+    ('\u{FFFC}', "OBJECT REPLACEMENT CHARACTER"),
+    ('\u{FFFD}', "REPLACEMENT CHARACTER"),
+];
+
+fn name(ch: char) -> Option<&'static str> {
+    NAMES.iter().find(|&&(c, _)| c == ch).map(|&(_, name)| name)
+}
+
+/// Invisible characters that only steer layout (joiners, direction marks)
+fn is_format(ch: char) -> bool {
+    matches!(
+        ch as u32,
+        0xAD | 0x61C | 0x180E | 0x200B..=0x200F | 0x2028..=0x202E | 0x2060..=0x206F | 0xFEFF
+    )
+}
+
+/// Rough Unicode category of `ch`, in words
+fn category(ch: char) -> &'static str {
+    match ch {
+        _ if ch.is_control() => "controle",
+        _ if is_format(ch) => "formatação invisível",
+        _ if grapheme::is_extend(ch) => "marca combinante",
+        ' ' => "espaço",
+        _ if ch.is_whitespace() => "espaço não-ASCII",
+        _ if ch.is_uppercase() => "letra maiúscula",
+        _ if ch.is_lowercase() => "letra minúscula",
+        _ if ch.is_alphabetic() => "letra",
+        _ if ch.is_ascii_digit() => "dígito",
+        _ if ch.is_numeric() => "número",
+        _ if ch.is_ascii_punctuation() => "pontuação",
+        '\u{E000}'..='\u{F8FF}' | '\u{F0000}'.. => "uso privado",
+        '\u{2600}'..='\u{27BF}' | '\u{1F300}'..='\u{1FAFF}' => "símbolo ou emoji",
+        _ => "símbolo ou pontuação",
+    }
+}
+
+fn hex_bytes(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|b| format!("{:02X}", b))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Status-bar report on the character at `offset` (in bytes from the start
+/// of the file): what it looks like, its code points with name or category,
+/// and its UTF-8 bytes. `cluster` is the character and the marks or joined
+/// parts drawn with it, so none of them hides.
+pub fn describe(cluster: &[char], offset: usize) -> String {
+    let shown: String = cluster
+        .iter()
+        .map(|ch| match ch {
+            _ if ch.is_control() || is_format(*ch) => ch.escape_unicode().to_string(),
+            _ => ch.to_string(),
+        })
+        .collect();
+    let points: Vec<String> = cluster
+        .iter()
+        .map(|&ch| {
+            format!(
+                "U+{:04X} {}",
+                ch as u32,
+                name(ch).unwrap_or_else(|| category(ch))
+            )
+        })
+        .collect();
+    let text: String = cluster.iter().collect();
+    format!(
+        "'{}' {} · UTF-8: {} · byte {} (0x{:X})",
+        shown,
+        points.join(" + "),
+        hex_bytes(text.as_bytes()),
+        offset,
+        offset
+    )
+}
+
+/// Report for the cursor sitting past the end of a line, on its line break
+/// (or on nothing, after the last line)
+pub fn describe_line_end(ending: Option<LineEnding>, offset: usize) -> String {
+    match ending {
+        Some(LineEnding::Lf) => format!(
+            "Fim de linha (LF) · UTF-8: 0A · byte {} (0x{:X})",
+            offset, offset
+        ),
+        Some(LineEnding::Crlf) => format!(
+            "Fim de linha (CRLF) · UTF-8: 0D 0A · byte {} (0x{:X})",
+            offset, offset
+        ),
+        None => format!("Fim do arquivo · byte {} (0x{:X})", offset, offset),
+    }
+}
+
+/// Letters of scripts with look-alikes of Latin ones (Cyrillic "а",
+/// Greek "ο"...)
+fn is_confusable_script(ch: char) -> bool {
+    matches!(ch as u32, 0x0370..=0x04FF) && ch.is_alphabetic()
+}
+
+/// Characters that can't be seen, or pass for others, and so routinely
+/// break configs and slip through reviews: control chars, zero-width and
+/// direction marks, unusual spaces, fullwidth ASCII, and Cyrillic or Greek
+/// letters inside a word written in Latin letters.
+pub fn is_suspicious(line: &[char], col: usize) -> bool {
+    let Some(&ch) = line.get(col) else {
+        return false;
+    };
+    let near_ascii = || {
+        let before = col.checked_sub(1).and_then(|i| line.get(i));
+        let after = line.get(col + 1);
+        [before, after]
+            .into_iter()
+            .flatten()
+            .any(|c| c.is_ascii_graphic())
+    };
+    match ch as u32 {
+        0x09 => false,
+        // Joiners are part of emoji and of several scripts; in code they
+        // only hide
+        0x200C | 0x200D => near_ascii(),
+        0x115F | 0x1160 | 0x3164 | 0xFFA0 | 0xFF01..=0xFF5E => true,
+        _ if ch.is_control() || is_format(ch) => true,
+        _ if ch.is_whitespace() => ch != ' ',
+        _ if is_confusable_script(ch) => {
+            let word = |c: &char| c.is_alphanumeric() || *c == '_';
+            let start = line[..col]
+                .iter()
+                .rposition(|c| !word(c))
+                .map_or(0, |i| i + 1);
+            let end = line[col..]
+                .iter()
+                .position(|c| !word(c))
+                .map_or(line.len(), |i| col + i);
+            line[start..end].iter().any(|c| c.is_ascii_alphabetic())
+        }
+        _ => false,
+    }
+}
+
+/// What the screen shows for `ch`: controls their picture (`␍`, `␛`), and
+/// chars that take no cell or would steer the terminal (direction
+/// overrides) a visible box
+pub fn shown(ch: char) -> char {
+    match ch as u32 {
+        0x09 => ch,
+        0x115F | 0x1160 | 0x3164 | 0xFFA0 => '▯',
+        _ if ch.is_control() => screen::printable(ch),
+        _ if is_format(ch) => '▯',
+        _ => ch,
+    }
+}
@@ -0,0 +1,218 @@
+use crate::git::BlameLine;
+use crate::loader::LoadMessage;
+use crate::log;
+use crate::replace::Match;
+use crate::sidebar::FileEntry;
+use crate::symbols::Symbol;
+use crate::todos::Todo;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Instant;
+
+/// Upper bound on worker threads, whatever the machine offers
+const MAX_WORKERS: usize = 4;
+
+const SPINNER: [char; 4] = ['⠋', '⠙', '⠸', '⠴'];
+
+pub type JobId = usize;
+
+/// What a job reports back to the event loop
+pub enum JobMessage {
+    Load(LoadMessage),
+    /// Result of a TODO scan, sorted by file and line
+    Todos(Vec<Todo>),
+    /// Occurrences found by a project search, sorted by file and position
+    Matches(Vec<Match>),
+    /// Definitions found by indexing the project
+    Symbols(Vec<Symbol>),
+    /// The sidebar's root folder, read and sorted
+    Tree(Vec<FileEntry>),
+    /// Files scanned by a sidebar search since the last message, and the
+    /// ones whose name matched
+    Names(usize, Vec<PathBuf>),
+    /// `git blame` of a file, one entry per line
+    Blame(std::io::Result<Vec<BlameLine>>),
+    /// A line printed by a project task, as raw bytes
+    TaskOutput(Vec<u8>),
+    /// Exit code of a project task (`None` if it was killed)
+    TaskExit(Option<i32>),
+    /// Completion percentage shown next to the job's label
+    Progress(u64),
+    /// Sent by the pool after the job returns (true if it was cancelled)
+    Finished(bool),
+}
+
+type Task = Box<dyn FnOnce(&JobContext) + Send>;
+
+/// Handle given to a running job to report back and check for cancellation
+pub struct JobContext {
+    id: JobId,
+    sender: Sender<(JobId, JobMessage)>,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl JobContext {
+    /// Send a message to the event loop. Returns false once the job should stop.
+    pub fn send(&self, message: JobMessage) -> bool {
+        !self.is_cancelled() && self.sender.send((self.id, message)).is_ok()
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+struct JobInfo {
+    id: JobId,
+    label: String,
+    progress: Option<u64>,
+    cancelled: Arc<AtomicBool>,
+}
+
+/// Fixed pool of worker threads fed from a queue. Jobs talk to the event
+/// loop through one channel, drained by `poll` between key events.
+pub struct Jobs {
+    queue: Sender<(JobContext, Task)>,
+    sender: Sender<(JobId, JobMessage)>,
+    receiver: Receiver<(JobId, JobMessage)>,
+    running: Vec<JobInfo>,
+    next_id: JobId,
+    started_at: Instant,
+}
+
+impl Jobs {
+    pub fn new() -> Jobs {
+        let workers = thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1)
+            .min(MAX_WORKERS);
+        let (queue, tasks) = mpsc::channel::<(JobContext, Task)>();
+        let tasks = Arc::new(Mutex::new(tasks));
+
+        for _ in 0..workers {
+            let tasks = Arc::clone(&tasks);
+            thread::spawn(move || loop {
+                let next = tasks.lock().ok().and_then(|tasks| tasks.recv().ok());
+                let (context, task) = match next {
+                    Some(next) => next,
+                    // Editor gone
+                    None => return,
+                };
+                task(&context);
+                let cancelled = context.is_cancelled();
+                let _ = context
+                    .sender
+                    .send((context.id, JobMessage::Finished(cancelled)));
+            });
+        }
+
+        let (sender, receiver) = mpsc::channel();
+        Jobs {
+            queue,
+            sender,
+            receiver,
+            running: vec![],
+            next_id: 0,
+            started_at: Instant::now(),
+        }
+    }
+
+    /// Queue `task` on the pool. `label` names it in the status bar.
+    pub fn spawn(
+        &mut self,
+        label: String,
+        task: impl FnOnce(&JobContext) + Send + 'static,
+    ) -> JobId {
+        let id = self.next_id;
+        self.next_id += 1;
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let context = JobContext {
+            id,
+            sender: self.sender.clone(),
+            cancelled: Arc::clone(&cancelled),
+        };
+
+        if self.queue.send((context, Box::new(task))).is_ok() {
+            log::info!("tarefa", "#{} iniciada: {}", id, label);
+            self.running.push(JobInfo {
+                id,
+                label,
+                progress: None,
+                cancelled,
+            });
+        }
+        id
+    }
+
+    /// Messages sent by jobs since the last call. Progress is recorded here;
+    /// finished jobs leave the running list.
+    pub fn poll(&mut self) -> Vec<(JobId, JobMessage)> {
+        let messages: Vec<(JobId, JobMessage)> = self.receiver.try_iter().collect();
+        for (id, message) in &messages {
+            match message {
+                JobMessage::Progress(percent) => {
+                    if let Some(job) = self.running.iter_mut().find(|job| job.id == *id) {
+                        job.progress = Some(*percent);
+                    }
+                }
+                JobMessage::Finished(cancelled) => {
+                    log::info!(
+                        "tarefa",
+                        "#{} {}",
+                        id,
+                        if *cancelled {
+                            "cancelada"
+                        } else {
+                            "concluída"
+                        }
+                    );
+                    self.running.retain(|job| job.id != *id);
+                }
+                JobMessage::Load(_)
+                | JobMessage::Todos(_)
+                | JobMessage::Symbols(_)
+                | JobMessage::Matches(_)
+                | JobMessage::Tree(_)
+                | JobMessage::Names(..)
+                | JobMessage::Blame(_)
+                | JobMessage::TaskOutput(_)
+                | JobMessage::TaskExit(_) => {}
+            }
+        }
+        messages
+    }
+
+    pub fn cancel(&mut self, id: JobId) {
+        if let Some(job) = self.running.iter().find(|job| job.id == id) {
+            job.cancelled.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// Ask every running job to stop. Returns how many were running.
+    pub fn cancel_all(&mut self) -> usize {
+        for job in &self.running {
+            job.cancelled.store(true, Ordering::Relaxed);
+        }
+        self.running.len()
+    }
+
+    pub fn is_busy(&self) -> bool {
+        !self.running.is_empty()
+    }
+
+    /// Spinner plus the running job (or a count), e.g. "⠙ Carregando a.log 40%"
+    pub fn status_text(&self) -> String {
+        let frame = (self.started_at.elapsed().as_millis() / 100) as usize % SPINNER.len();
+        match self.running.as_slice() {
+            [] => String::new(),
+            [job] => match job.progress {
+                Some(percent) => format!("{} {} {}%", SPINNER[frame], job.label, percent),
+                None => format!("{} {}", SPINNER[frame], job.label),
+            },
+            jobs => format!("{} {} tarefas", SPINNER[frame], jobs.len()),
+        }
+    }
+}
@@ -0,0 +1,349 @@
+/// Nesting deeper than this is refused instead of overflowing the stack
+const MAX_DEPTH: usize = 512;
+
+/// A parsed JSON document. Numbers and strings keep their source text, so
+/// formatting never changes how they are written.
+pub enum Value {
+    Null,
+    Bool(bool),
+    Number(String),
+    /// Text between the quotes, escapes untouched
+    String(String),
+    Array(Vec<Value>),
+    /// Members in source order; duplicate keys are kept
+    Object(Vec<(String, Value)>),
+}
+
+/// Where and why parsing stopped. Line and column are 0-based, in chars.
+pub struct ParseError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+/// A key that repeats an earlier one of the same object: valid JSON, but
+/// most readers keep only the last value. Line and column (0-based) are
+/// those of its opening quote.
+pub struct DuplicateKey {
+    pub line: usize,
+    pub column: usize,
+    pub key: String,
+}
+
+/// Parse `text`, also reporting repeated keys
+pub fn parse(text: &str) -> Result<(Value, Vec<DuplicateKey>), ParseError> {
+    let mut parser = Parser {
+        chars: text.chars().collect(),
+        pos: 0,
+        line: 0,
+        column: 0,
+        duplicates: vec![],
+    };
+    parser.skip_whitespace();
+    let value = parser.value(0)?;
+    parser.skip_whitespace();
+    if parser.peek().is_some() {
+        return Err(parser.error("texto depois do fim do JSON"));
+    }
+    Ok((value, parser.duplicates))
+}
+
+/// One member or element per line, nested `indent` spaces deeper
+pub fn format(value: &Value, indent: usize) -> String {
+    let mut out = String::new();
+    write_value(value, Some(indent), 0, &mut out);
+    out
+}
+
+/// Everything on one line, without spaces
+pub fn minify(value: &Value) -> String {
+    let mut out = String::new();
+    write_value(value, None, 0, &mut out);
+    out
+}
+
+fn write_value(value: &Value, indent: Option<usize>, depth: usize, out: &mut String) {
+    match value {
+        Value::Null => out.push_str("null"),
+        Value::Bool(b) => out.push_str(if *b { "true" } else { "false" }),
+        Value::Number(text) => out.push_str(text),
+        Value::String(text) => {
+            out.push('"');
+            out.push_str(text);
+            out.push('"');
+        }
+        Value::Array(items) if items.is_empty() => out.push_str("[]"),
+        Value::Object(members) if members.is_empty() => out.push_str("{}"),
+        Value::Array(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                separator(i, indent, depth + 1, out);
+                write_value(item, indent, depth + 1, out);
+            }
+            newline(indent, depth, out);
+            out.push(']');
+        }
+        Value::Object(members) => {
+            out.push('{');
+            for (i, (key, item)) in members.iter().enumerate() {
+                separator(i, indent, depth + 1, out);
+                out.push('"');
+                out.push_str(key);
+                out.push_str(if indent.is_some() { "\": " } else { "\":" });
+                write_value(item, indent, depth + 1, out);
+            }
+            newline(indent, depth, out);
+            out.push('}');
+        }
+    }
+}
+
+fn separator(index: usize, indent: Option<usize>, depth: usize, out: &mut String) {
+    if index > 0 {
+        out.push(',');
+    }
+    newline(indent, depth, out);
+}
+
+fn newline(indent: Option<usize>, depth: usize, out: &mut String) {
+    if let Some(indent) = indent {
+        out.push('\n');
+        out.push_str(&" ".repeat(indent * depth));
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    line: usize,
+    column: usize,
+    duplicates: Vec<DuplicateKey>,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn advance(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += 1;
+        if c == '\n' {
+            self.line += 1;
+            self.column = 0;
+        } else {
+            self.column += 1;
+        }
+        Some(c)
+    }
+
+    fn error(&self, message: &str) -> ParseError {
+        ParseError {
+            line: self.line,
+            column: self.column,
+            message: message.to_string(),
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self
+            .peek()
+            .is_some_and(|c| matches!(c, ' ' | '\t' | '\n' | '\r'))
+        {
+            self.advance();
+        }
+    }
+
+    fn expect(&mut self, wanted: char) -> Result<(), ParseError> {
+        if self.peek() == Some(wanted) {
+            self.advance();
+            Ok(())
+        } else {
+            Err(self.error(&format!("esperado '{}'", wanted)))
+        }
+    }
+
+    fn value(&mut self, depth: usize) -> Result<Value, ParseError> {
+        if depth > MAX_DEPTH {
+            return Err(self.error("aninhamento profundo demais"));
+        }
+        match self.peek() {
+            None => Err(self.error("fim inesperado do texto")),
+            Some('{') => self.object(depth),
+            Some('[') => self.array(depth),
+            Some('"') => self.string().map(Value::String),
+            Some('-' | '0'..='9') => self.number(),
+            Some(_) => {
+                for (word, value) in [
+                    ("null", Value::Null),
+                    ("true", Value::Bool(true)),
+                    ("false", Value::Bool(false)),
+                ] {
+                    if self.chars[self.pos..].starts_with(&word.chars().collect::<Vec<_>>()) {
+                        for _ in 0..word.len() {
+                            self.advance();
+                        }
+                        return Ok(value);
+                    }
+                }
+                Err(self.error("valor inválido"))
+            }
+        }
+    }
+
+    fn object(&mut self, depth: usize) -> Result<Value, ParseError> {
+        self.advance();
+        let mut members = vec![];
+        self.skip_whitespace();
+        if self.peek() == Some('}') {
+            self.advance();
+            return Ok(Value::Object(members));
+        }
+        loop {
+            self.skip_whitespace();
+            if self.peek() != Some('"') {
+                return Err(self.error(if self.peek() == Some('}') {
+                    "vírgula sobrando"
+                } else {
+                    "esperado nome entre aspas"
+                }));
+            }
+            let (line, column) = (self.line, self.column);
+            let key = self.string()?;
+            if members.iter().any(|(existing, _)| *existing == key) {
+                self.duplicates.push(DuplicateKey {
+                    line,
+                    column,
+                    key: key.clone(),
+                });
+            }
+            self.skip_whitespace();
+            self.expect(':')?;
+            self.skip_whitespace();
+            members.push((key, self.value(depth + 1)?));
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => {
+                    self.advance();
+                }
+                Some('}') => {
+                    self.advance();
+                    return Ok(Value::Object(members));
+                }
+                _ => return Err(self.error("esperado ',' ou '}'")),
+            }
+        }
+    }
+
+    fn array(&mut self, depth: usize) -> Result<Value, ParseError> {
+        self.advance();
+        let mut items = vec![];
+        self.skip_whitespace();
+        if self.peek() == Some(']') {
+            self.advance();
+            return Ok(Value::Array(items));
+        }
+        loop {
+            self.skip_whitespace();
+            if self.peek() == Some(']') {
+                return Err(self.error("vírgula sobrando"));
+            }
+            items.push(self.value(depth + 1)?);
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => {
+                    self.advance();
+                }
+                Some(']') => {
+                    self.advance();
+                    return Ok(Value::Array(items));
+                }
+                _ => return Err(self.error("esperado ',' ou ']'")),
+            }
+        }
+    }
+
+    /// A string's text between the quotes, with escapes checked but kept
+    fn string(&mut self) -> Result<String, ParseError> {
+        self.advance();
+        let mut text = String::new();
+        loop {
+            match self.peek() {
+                None | Some('\n') => return Err(self.error("string sem aspas de fechamento")),
+                Some('"') => {
+                    self.advance();
+                    return Ok(text);
+                }
+                Some(c) if (c as u32) < 0x20 => {
+                    return Err(self.error("caractere de controle dentro de string"))
+                }
+                Some('\\') => {
+                    text.push('\\');
+                    self.advance();
+                    match self.peek() {
+                        Some(c @ ('"' | '\\' | '/' | 'b' | 'f' | 'n' | 'r' | 't')) => {
+                            text.push(c);
+                            self.advance();
+                        }
+                        Some('u') => {
+                            text.push('u');
+                            self.advance();
+                            for _ in 0..4 {
+                                match self.peek() {
+                                    Some(c) if c.is_ascii_hexdigit() => {
+                                        text.push(c);
+                                        self.advance();
+                                    }
+                                    _ => return Err(self.error("escape \\u inválido")),
+                                }
+                            }
+                        }
+                        _ => return Err(self.error("escape inválido")),
+                    }
+                }
+                Some(c) => {
+                    text.push(c);
+                    self.advance();
+                }
+            }
+        }
+    }
+
+    fn number(&mut self) -> Result<Value, ParseError> {
+        let start = self.pos;
+        if self.peek() == Some('-') {
+            self.advance();
+        }
+        match self.peek() {
+            Some('0') => {
+                self.advance();
+            }
+            Some('1'..='9') => self.digits(),
+            _ => return Err(self.error("número inválido")),
+        }
+        if self.peek() == Some('.') {
+            self.advance();
+            if !self.peek().is_some_and(|c| c.is_ascii_digit()) {
+                return Err(self.error("número inválido"));
+            }
+            self.digits();
+        }
+        if matches!(self.peek(), Some('e' | 'E')) {
+            self.advance();
+            if matches!(self.peek(), Some('+' | '-')) {
+                self.advance();
+            }
+            if !self.peek().is_some_and(|c| c.is_ascii_digit()) {
+                return Err(self.error("número inválido"));
+            }
+            self.digits();
+        }
+        Ok(Value::Number(self.chars[start..self.pos].iter().collect()))
+    }
+
+    fn digits(&mut self) {
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.advance();
+        }
+    }
+}
@@ -0,0 +1,294 @@
+use crate::commands::{self, Command};
+use crate::config::Config;
+use crate::log;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+/// Leader sequences available without configuration
+const DEFAULT_LEADER: &[(char, &str)] = &[
+    ('f', "oldfiles"),
+    ('m', "marks"),
+    ('t', "todos"),
+    ('r', "rename-symbol"),
+    ('R', "project-replace"),
+    ('b', "blame"),
+    ('h', "hover"),
+    ('n', "hunk-next"),
+    ('N', "hunk-prev"),
+    ('d', "diagnostic-next"),
+    ('D', "diagnostic-prev"),
+    ('g', "goto-line"),
+    ('l', "set-language"),
+    ('p', "paste-history"),
+    ('j', "indent-next"),
+    ('k', "indent-prev"),
+    ('[', "indent-start"),
+    (']', "indent-end"),
+    ('%', "tag-match"),
+    ('T', "tag-rename"),
+];
+
+/// Alt+<letter> shortcuts available without configuration
+const DEFAULT_ALT: &[(char, &str)] = &[
+    ('o', "oldfiles"),
+    ('m', "marks"),
+    ('n', "diagnostic-next"),
+    ('p', "diagnostic-prev"),
+];
+
+/// Key bindings beyond the fixed Ctrl shortcuts: Alt+<letter> anywhere,
+/// and the leader key in Normal mode followed by a letter. Both run
+/// palette commands, named in config.toml as in the palette, or macros of
+/// `[macros]`:
+///
+/// ```toml
+/// [keys]
+/// leader = "space"   # or any other key; "none" turns it off
+///
+/// [leader]
+/// w = "json-format"
+/// t = "none"         # drops a default binding
+/// H = "header"
+///
+/// [alt]
+/// j = "hunk-next"
+///
+/// [macros]
+/// header = "<goto-line>1<Enter>i// SPDX-License-Identifier: MIT<Enter><Esc>"
+/// ```
+pub struct Keymap {
+    pub leader: Option<char>,
+    leader_bindings: Vec<(char, Binding)>,
+    alt_bindings: Vec<(char, Binding)>,
+    macros: Vec<Macro>,
+}
+
+/// What a bound key does
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Binding {
+    Command(Command),
+    /// The macro at this index of `[macros]`
+    Macro(usize),
+}
+
+/// Keys and commands played back as if typed, one after the other
+struct Macro {
+    name: String,
+    steps: Vec<Step>,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum Step {
+    Key(KeyEvent),
+    Command(Command),
+}
+
+impl Keymap {
+    pub fn from_config(config: &Config) -> Keymap {
+        let leader = match config.get("keys", "leader") {
+            None | Some("space") => Some(' '),
+            Some("none") => None,
+            Some(key) => {
+                let mut chars = key.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => Some(c),
+                    _ => {
+                        log::warning!("config", "tecla líder inválida: {}", key);
+                        Some(' ')
+                    }
+                }
+            }
+        };
+        let macros: Vec<Macro> = config
+            .section("macros")
+            .filter_map(|(name, keys)| match parse_keys(keys) {
+                Ok(steps) => Some(Macro {
+                    name: name.to_string(),
+                    steps,
+                }),
+                Err(e) => {
+                    log::warning!("config", "[macros] {}: {}", name, e);
+                    None
+                }
+            })
+            .collect();
+        Keymap {
+            leader,
+            leader_bindings: bindings(config, "leader", DEFAULT_LEADER, &macros),
+            alt_bindings: bindings(config, "alt", DEFAULT_ALT, &macros),
+            macros,
+        }
+    }
+
+    /// What the leader key followed by `key` does
+    pub fn leader(&self, key: char) -> Option<Binding> {
+        find(&self.leader_bindings, key)
+    }
+
+    /// What Alt+`key` does
+    pub fn alt(&self, key: char) -> Option<Binding> {
+        find(&self.alt_bindings, key)
+    }
+
+    /// Name and steps of the macro at `index`
+    pub fn macro_steps(&self, index: usize) -> Option<(&str, &[Step])> {
+        let found = self.macros.get(index)?;
+        Some((&found.name, &found.steps))
+    }
+
+    /// One row per leader binding, for the popup shown while the next key
+    /// is awaited
+    pub fn leader_rows(&self) -> Vec<String> {
+        self.leader_bindings
+            .iter()
+            .filter_map(|&(key, binding)| match binding {
+                Binding::Command(command) => {
+                    let info = commands::COMMANDS.iter().find(|c| c.command == command)?;
+                    Some(format!("{}  {}", key, info.description))
+                }
+                Binding::Macro(index) => {
+                    Some(format!("{}  Macro {}", key, self.macros[index].name))
+                }
+            })
+            .collect()
+    }
+
+    /// How the leader key reads in the popup title
+    pub fn leader_name(&self) -> String {
+        match self.leader {
+            Some(' ') => String::from("Espaço"),
+            Some(key) => key.to_string(),
+            None => String::new(),
+        }
+    }
+}
+
+fn find(bindings: &[(char, Binding)], key: char) -> Option<Binding> {
+    bindings
+        .iter()
+        .find(|&&(bound, _)| bound == key)
+        .map(|&(_, binding)| binding)
+}
+
+/// `defaults`, overridden by the `section` of the config. A key set to
+/// "none" is left unbound.
+fn bindings(
+    config: &Config,
+    section: &str,
+    defaults: &[(char, &str)],
+    macros: &[Macro],
+) -> Vec<(char, Binding)> {
+    let mut names: Vec<(char, String)> = defaults
+        .iter()
+        .map(|&(key, name)| (key, name.to_string()))
+        .collect();
+    for (key, name) in config.section(section) {
+        let mut chars = key.chars();
+        let (Some(key), None) = (chars.next(), chars.next()) else {
+            log::warning!("config", "[{}] espera uma tecla, não '{}'", section, key);
+            continue;
+        };
+        names.retain(|(bound, _)| *bound != key);
+        names.push((key, name.to_string()));
+    }
+    names
+        .into_iter()
+        .filter(|(_, name)| name != "none")
+        .filter_map(|(key, name)| {
+            if let Some(command) = commands::by_name(&name) {
+                return Some((key, Binding::Command(command)));
+            }
+            match macros.iter().position(|found| found.name == name) {
+                Some(index) => Some((key, Binding::Macro(index))),
+                None => {
+                    log::warning!("config", "[{}] comando desconhecido: {}", section, name);
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+/// Steps of a macro as written in config.toml: each char is a key,
+/// `<Esc>`, `<Enter>`, `<Tab>`, `<BS>`, `<Del>`, arrows (`<Up>`...),
+/// `<Home>`, `<End>`, `<PageUp>`, `<PageDown>`, `<F1>`..`<F12>` and
+/// `<Space>` name the others, `<C-x>` and `<A-x>` hold Ctrl or Alt, `<lt>`
+/// is a `<`, and `<json-format>` runs a palette command
+pub fn parse_keys(text: &str) -> Result<Vec<Step>, String> {
+    let key = |code| Step::Key(KeyEvent::new(code, KeyModifiers::NONE));
+    let mut steps = vec![];
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        let name = rest
+            .strip_prefix('<')
+            .and_then(|after| after.split_once('>'))
+            .map(|(name, _)| name)
+            .filter(|name| !name.is_empty() && !name.contains('<'));
+        let Some(name) = name else {
+            steps.push(key(KeyCode::Char(c)));
+            rest = &rest[c.len_utf8()..];
+            continue;
+        };
+        rest = &rest[name.len() + 2..];
+        let lower = name.to_lowercase();
+        let modified = lower
+            .strip_prefix("c-")
+            .map(|rest| (KeyModifiers::CONTROL, rest))
+            .or_else(|| {
+                lower
+                    .strip_prefix("a-")
+                    .map(|rest| (KeyModifiers::ALT, rest))
+            })
+            .or_else(|| {
+                lower
+                    .strip_prefix("m-")
+                    .map(|rest| (KeyModifiers::ALT, rest))
+            });
+        if let Some((modifiers, rest)) = modified {
+            // The letter as written: `<A-J>` is Alt+Shift+J
+            let letter = &name[name.len() - rest.len()..];
+            let code = named_key(rest)
+                .or_else(|| single(letter).map(KeyCode::Char))
+                .ok_or_else(|| format!("tecla desconhecida: <{}>", name))?;
+            steps.push(Step::Key(KeyEvent::new(code, modifiers)));
+        } else if let Some(code) = named_key(&lower) {
+            steps.push(key(code));
+        } else if let Some(command) = commands::by_name(name) {
+            steps.push(Step::Command(command));
+        } else {
+            return Err(format!("tecla ou comando desconhecido: <{}>", name));
+        }
+    }
+    Ok(steps)
+}
+
+/// A key `<name>` stands for (lowercase), other than a char
+fn named_key(name: &str) -> Option<KeyCode> {
+    Some(match name {
+        "esc" => KeyCode::Esc,
+        "enter" | "cr" => KeyCode::Enter,
+        "tab" => KeyCode::Tab,
+        "s-tab" => KeyCode::BackTab,
+        "bs" | "backspace" => KeyCode::Backspace,
+        "del" | "delete" => KeyCode::Delete,
+        "up" => KeyCode::Up,
+        "down" => KeyCode::Down,
+        "left" => KeyCode::Left,
+        "right" => KeyCode::Right,
+        "home" => KeyCode::Home,
+        "end" => KeyCode::End,
+        "pageup" => KeyCode::PageUp,
+        "pagedown" => KeyCode::PageDown,
+        "space" => KeyCode::Char(' '),
+        "lt" => KeyCode::Char('<'),
+        _ => {
+            let number: u8 = name.strip_prefix('f')?.parse().ok()?;
+            (1..=12).contains(&number).then_some(KeyCode::F(number))?
+        }
+    })
+}
+
+/// The only char of `text`
+fn single(text: &str) -> Option<char> {
+    let mut chars = text.chars();
+    chars.next().filter(|_| chars.next().is_none())
+}
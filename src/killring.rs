@@ -0,0 +1,142 @@
+use crate::config::{self, Config};
+use crate::log;
+use std::collections::HashSet;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+/// Entries kept when `[kill_ring] size` isn't set
+const DEFAULT_SIZE: u64 = 20;
+
+/// The last texts yanked or deleted, newest first, for `p` and the
+/// `paste-history` picker. With `[kill_ring] persist = true` they are kept
+/// between sessions.
+pub struct KillRing {
+    entries: Vec<String>,
+    // Entries from private files (encrypted, root's), never saved
+    private: HashSet<String>,
+    size: usize,
+    persist: bool,
+}
+
+/// `kill-ring` in the state directory: one entry per line, with "\\" and
+/// "\n" escaped
+fn path() -> Option<PathBuf> {
+    config::state_dir().map(|dir| dir.join("kill-ring"))
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('\n', "\\n")
+}
+
+fn unescape(line: &str) -> String {
+    let mut text = String::new();
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match (c, chars.clone().next()) {
+            ('\\', Some('n')) => {
+                text.push('\n');
+                chars.next();
+            }
+            ('\\', Some('\\')) => {
+                text.push('\\');
+                chars.next();
+            }
+            _ => text.push(c),
+        }
+    }
+    text
+}
+
+impl KillRing {
+    pub fn from_config(config: &Config) -> KillRing {
+        let size = config.get_u64("kill_ring", "size", DEFAULT_SIZE).max(1) as usize;
+        let persist = config.get("kill_ring", "persist") == Some("true");
+        let mut entries: Vec<String> = match path().filter(|_| persist) {
+            Some(path) => fs::read_to_string(path)
+                .map(|text| text.lines().map(unescape).collect())
+                .unwrap_or_default(),
+            None => vec![],
+        };
+        entries.truncate(size);
+        KillRing {
+            entries,
+            private: HashSet::new(),
+            size,
+            persist,
+        }
+    }
+
+    /// Newest first
+    pub fn entries(&self) -> &[String] {
+        &self.entries
+    }
+
+    pub fn latest(&self) -> Option<&str> {
+        self.entries.first().map(String::as_str)
+    }
+
+    /// Whether `text` came from a private file
+    pub fn is_private(&self, text: &str) -> bool {
+        self.private.contains(text)
+    }
+
+    /// Put `text` first, dropping an older copy of it and whatever no
+    /// longer fits. `private` text (from a file whose filesystem
+    /// `is_private`) is kept only for this session.
+    pub fn push(&mut self, text: String, private: bool) {
+        if text.is_empty() {
+            return;
+        }
+        if private {
+            self.private.insert(text.clone());
+        } else {
+            self.private.remove(&text);
+        }
+        self.entries.retain(|entry| *entry != text);
+        self.entries.insert(0, text);
+        self.entries.truncate(self.size);
+        let entries = &self.entries;
+        self.private.retain(|text| entries.contains(text));
+        if self.persist {
+            if let Err(e) = self.save() {
+                log::warning!("kill-ring", "erro ao gravar o histórico: {}", e);
+            }
+        }
+    }
+
+    /// Write the entries that aren't private, readable only by the user,
+    /// through a temporary file so a crash never leaves half of them
+    fn save(&self) -> io::Result<()> {
+        let path = path().ok_or_else(|| io::Error::other("HOME não definido"))?;
+        let text: String = self
+            .entries
+            .iter()
+            .filter(|entry| !self.private.contains(*entry))
+            .map(|entry| format!("{}\n", escape(entry)))
+            .collect();
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let temp = path.with_extension("tmp");
+        let _ = fs::remove_file(&temp);
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let written = options
+            .open(&temp)
+            .and_then(|mut file| {
+                file.write_all(text.as_bytes())
+                    .and_then(|_| file.sync_all())
+            })
+            .and_then(|_| fs::rename(&temp, &path));
+        if written.is_err() {
+            let _ = fs::remove_file(&temp);
+        }
+        written
+    }
+}
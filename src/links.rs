@@ -0,0 +1,236 @@
+use std::io;
+use std::process::{Command, Stdio};
+use std::thread;
+
+/// Where a link in the text points
+#[derive(Clone, Debug, PartialEq)]
+pub enum Target {
+    Url(String),
+    /// A file, optionally with a 1-based line and column (`src/main.rs:12:5`)
+    File {
+        path: String,
+        line: Option<usize>,
+        column: Option<usize>,
+    },
+}
+
+/// A link found in a line; `start..end` are char columns
+#[derive(Clone, Debug, PartialEq)]
+pub struct Link {
+    pub start: usize,
+    pub end: usize,
+    pub target: Target,
+}
+
+const SCHEMES: &[&str] = &["https://", "http://", "file://"];
+
+/// URLs and file references in `line`, left to right. Paths count when they
+/// carry a `:line` suffix or start like a path (`/`, `./`, `../`, `~/`).
+pub fn find(line: &[char]) -> Vec<Link> {
+    let mut links = vec![];
+    let mut i = 0;
+    while i < line.len() {
+        // Links start at a word boundary
+        if i > 0 && is_path_char(line[i - 1]) {
+            i += 1;
+            continue;
+        }
+        let found = url_at(line, i).or_else(|| path_at(line, i));
+        match found {
+            Some(link) => {
+                i = link.end;
+                links.push(link);
+            }
+            None => i += 1,
+        }
+    }
+    links
+}
+
+/// The link covering column `col`, if any
+pub fn at(line: &[char], col: usize) -> Option<Link> {
+    find(line)
+        .into_iter()
+        .find(|link| col >= link.start && col < link.end)
+}
+
+/// The file reference covering column `col`, for `gf`. Besides what
+/// `find` recognizes, any word that could be a file name counts, and the
+/// location may also come as `path(12,5)` (MSVC, tsc) or
+/// `"path", line 12` (Python tracebacks).
+pub fn file_at(line: &[char], col: usize) -> Option<Link> {
+    if let Some(link) = at(line, col).filter(|link| matches!(link.target, Target::File { .. })) {
+        return Some(link);
+    }
+    let word = |c: char| is_path_char(c) || c == '\\';
+    if !line.get(col).is_some_and(|&c| word(c)) {
+        return None;
+    }
+    let start = line[..col]
+        .iter()
+        .rposition(|&c| !word(c))
+        .map_or(0, |i| i + 1);
+    let mut end = line[col..]
+        .iter()
+        .position(|&c| !word(c))
+        .map_or(line.len(), |i| col + i);
+    while end > start && line[end - 1] == '.' {
+        end -= 1;
+    }
+    let path: String = line[start..end].iter().collect();
+    if !path.chars().any(|c| c.is_alphabetic()) || col >= end {
+        return None;
+    }
+
+    let rest: String = line[end..].iter().take(32).collect();
+    let (line_nr, column, after) = if let Some(numbers) = rest.strip_prefix('(') {
+        // path(12,5) or path(12)
+        let inside = numbers.split(')').next().unwrap_or("");
+        let mut parts = inside.split(',').map(|n| n.trim().parse::<usize>().ok());
+        match (parts.next().flatten(), parts.next().flatten()) {
+            (Some(nr), column) if numbers.contains(')') => {
+                (Some(nr), column, end + inside.chars().count() + 2)
+            }
+            _ => (None, None, end),
+        }
+    } else if let Some(number) = rest.strip_prefix("\", line ") {
+        let digits: String = number.chars().take_while(|c| c.is_ascii_digit()).collect();
+        match digits.parse() {
+            Ok(nr) => (Some(nr), None, end + 8 + digits.len()),
+            Err(_) => (None, None, end),
+        }
+    } else {
+        let (line_nr, after_line) = number_after_colon(line, end);
+        match line_nr {
+            Some(_) => {
+                let (column, after_column) = number_after_colon(line, after_line);
+                (line_nr, column, after_column)
+            }
+            None => (None, None, end),
+        }
+    };
+    Some(Link {
+        start,
+        end: after,
+        target: Target::File {
+            path,
+            line: line_nr,
+            column,
+        },
+    })
+}
+
+fn url_at(line: &[char], start: usize) -> Option<Link> {
+    let rest: String = line[start..].iter().take(8).collect();
+    if !SCHEMES.iter().any(|scheme| rest.starts_with(scheme)) {
+        return None;
+    }
+    let mut end = start;
+    while end < line.len() && !line[end].is_whitespace() && !"<>\"'`".contains(line[end]) {
+        end += 1;
+    }
+    // Sentence punctuation and a closing bracket the URL didn't open
+    // belong to the text around it
+    while end > start {
+        let last = line[end - 1];
+        let text = &line[start..end - 1];
+        let count = |wanted: char| text.iter().filter(|&&c| c == wanted).count();
+        let trailing = ".,;:!?".contains(last)
+            || (last == ')' && count('(') <= count(')'))
+            || (last == ']' && count('[') <= count(']'));
+        if !trailing {
+            break;
+        }
+        end -= 1;
+    }
+    let url: String = line[start..end].iter().collect();
+    if SCHEMES.contains(&url.as_str()) {
+        return None;
+    }
+    Some(Link {
+        start,
+        end,
+        target: Target::Url(url),
+    })
+}
+
+fn is_path_char(c: char) -> bool {
+    c.is_alphanumeric() || "_./~+-".contains(c)
+}
+
+fn path_at(line: &[char], start: usize) -> Option<Link> {
+    let mut end = start;
+    while end < line.len() && is_path_char(line[end]) {
+        end += 1;
+    }
+    // A final '.' ends the sentence, not the file name
+    while end > start && line[end - 1] == '.' {
+        end -= 1;
+    }
+    let path: String = line[start..end].iter().collect();
+    if !path.chars().any(|c| c.is_alphabetic()) {
+        return None;
+    }
+
+    let (line_nr, after_line) = number_after_colon(line, end);
+    let (column, after_column) = match line_nr {
+        Some(_) => number_after_colon(line, after_line),
+        None => (None, after_line),
+    };
+    let looks_like_path = ["/", "./", "../", "~/"]
+        .iter()
+        .any(|prefix| path.starts_with(prefix))
+        && path.len() > 2;
+    let has_location = line_nr.is_some() && (path.contains('.') || path.contains('/'));
+    if !looks_like_path && !has_location {
+        return None;
+    }
+    Some(Link {
+        start,
+        end: after_column,
+        target: Target::File {
+            path,
+            line: line_nr,
+            column,
+        },
+    })
+}
+
+/// The number in ":123" at `pos` and the column after it
+fn number_after_colon(line: &[char], pos: usize) -> (Option<usize>, usize) {
+    if line.get(pos) != Some(&':') {
+        return (None, pos);
+    }
+    let digits: String = line[pos + 1..]
+        .iter()
+        .take_while(|c| c.is_ascii_digit())
+        .collect();
+    match digits.parse() {
+        Ok(n) => (Some(n), pos + 1 + digits.len()),
+        Err(_) => (None, pos),
+    }
+}
+
+/// Hand `url` to the system's default browser without waiting for it
+pub fn open_url(url: &str) -> io::Result<()> {
+    let mut command = if cfg!(target_os = "macos") {
+        Command::new("open")
+    } else if cfg!(windows) {
+        // Straight to the shell's URL handler: through `cmd /C start` the
+        // `&`, `|` and `^` URLs can have would run commands
+        let mut command = Command::new("rundll32");
+        command.arg("url.dll,FileProtocolHandler");
+        command
+    } else {
+        Command::new("xdg-open")
+    };
+    let mut child = command
+        .arg(url)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    // Reaped in the background so it doesn't linger as a zombie
+    thread::spawn(move || child.wait());
+    Ok(())
+}
@@ -0,0 +1,123 @@
+use crate::buffer_file::{LineEnding, UTF8_BOM};
+use crate::jobs::{JobContext, JobId, JobMessage, Jobs};
+use crate::vfs;
+use std::io::Read;
+use std::path::Path;
+
+/// Files at least this big are read on a worker thread
+pub const ASYNC_LOAD_MIN_BYTES: u64 = 1024 * 1024;
+
+const CHUNK_LEN: usize = 256 * 1024;
+
+pub enum LoadMessage {
+    /// Complete lines read so far
+    Lines(Vec<Vec<char>>),
+    Encoding(LineEnding, bool),
+    Failed(std::io::Error),
+}
+
+/// A file being read by a background job. Lines are sent as soon as they
+/// are decoded, so the first screenful shows up long before the file ends.
+pub struct FileLoad {
+    pub path: String,
+    pub job: JobId,
+    // Read-only state to restore once loading finishes
+    pub read_only: bool,
+    // Whether any line has arrived (replacing the placeholder empty line)
+    pub started: bool,
+    pub failed: bool,
+}
+
+impl FileLoad {
+    pub fn start(jobs: &mut Jobs, path: &str, total: u64, read_only: bool) -> FileLoad {
+        let thread_path = path.to_string();
+        let label = format!(
+            "Carregando {}",
+            std::path::Path::new(path)
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_else(|| path.to_string())
+        );
+
+        let job = jobs.spawn(label, move |context| {
+            if let Err(e) = read_lines(&thread_path, total, context) {
+                context.send(JobMessage::Load(LoadMessage::Failed(e)));
+            }
+        });
+
+        FileLoad {
+            path: path.to_string(),
+            job,
+            read_only,
+            started: false,
+            failed: false,
+        }
+    }
+}
+
+/// Read `path` in chunks, sending decoded lines and progress. Stops early
+/// when the job is cancelled.
+fn read_lines(path: &str, total: u64, context: &JobContext) -> std::io::Result<()> {
+    let send = |message: LoadMessage| context.send(JobMessage::Load(message));
+    let mut file = vfs::for_path(path).open(Path::new(path))?;
+    let mut chunk = vec![0u8; CHUNK_LEN];
+    let mut pending: Vec<u8> = vec![];
+    let mut consumed: u64 = 0;
+    let mut first = true;
+    let mut keep_cr = false;
+
+    loop {
+        let read = file.read(&mut chunk)?;
+        if read == 0 {
+            break;
+        }
+        consumed += read as u64;
+        pending.extend_from_slice(&chunk[..read]);
+
+        if first {
+            let has_bom = pending.starts_with(UTF8_BOM);
+            if has_bom {
+                pending.drain(..UTF8_BOM.len());
+            }
+            let line_ending = LineEnding::detect(&pending);
+            keep_cr = line_ending == LineEnding::Lf;
+            if !send(LoadMessage::Encoding(line_ending, has_bom)) {
+                return Ok(());
+            }
+            first = false;
+        }
+
+        // Only whole lines are decoded; '\n' never splits a UTF-8 sequence
+        if let Some(last_newline) = pending.iter().rposition(|&b| b == b'\n') {
+            let rest = pending.split_off(last_newline + 1);
+            if !send(LoadMessage::Lines(decode_lines(&pending, keep_cr))) {
+                return Ok(());
+            }
+            pending = rest;
+        }
+
+        let percent = (consumed * 100).checked_div(total).unwrap_or(100).min(100);
+        if !context.send(JobMessage::Progress(percent)) {
+            return Ok(());
+        }
+    }
+
+    if !pending.is_empty() {
+        send(LoadMessage::Lines(decode_lines(&pending, keep_cr)));
+    }
+    Ok(())
+}
+
+/// Lines of `bytes`. An LF file keeps the CR of lines ending in CRLF, as
+/// when it is read whole, so they are saved back unchanged.
+fn decode_lines(bytes: &[u8], keep_cr: bool) -> Vec<Vec<char>> {
+    let text = String::from_utf8_lossy(bytes);
+    if !keep_cr {
+        return text.lines().map(|line| line.chars().collect()).collect();
+    }
+    text.strip_suffix('\n')
+        .unwrap_or(&text)
+        .split('\n')
+        .map(|line| line.chars().collect())
+        .collect()
+}
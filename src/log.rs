@@ -0,0 +1,118 @@
+use crate::config;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::sync::{Mutex, OnceLock};
+use std::time::Instant;
+
+#[derive(Clone, Copy, PartialEq, PartialOrd)]
+pub enum Level {
+    Error,
+    Warn,
+    Info,
+    Debug,
+}
+
+impl Level {
+    pub fn parse(name: &str) -> Option<Level> {
+        match name.to_lowercase().as_str() {
+            "error" => Some(Level::Error),
+            "warn" => Some(Level::Warn),
+            "info" => Some(Level::Info),
+            "debug" => Some(Level::Debug),
+            _ => None,
+        }
+    }
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            Level::Error => "ERROR",
+            Level::Warn => "WARN ",
+            Level::Info => "INFO ",
+            Level::Debug => "DEBUG",
+        }
+    }
+}
+
+struct Logger {
+    level: Level,
+    path: PathBuf,
+    file: Mutex<File>,
+    started: Instant,
+}
+
+static LOGGER: OnceLock<Logger> = OnceLock::new();
+
+/// Start appending messages up to `level` to the log file. Nothing is
+/// logged unless this is called (`--log <nível>`).
+pub fn init(level: Level) -> io::Result<PathBuf> {
+    let path = path().ok_or_else(|| io::Error::other("HOME não definido"))?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let file = OpenOptions::new().create(true).append(true).open(&path)?;
+    let _ = LOGGER.set(Logger {
+        level,
+        path: path.clone(),
+        file: Mutex::new(file),
+        started: Instant::now(),
+    });
+    info!("log", "reditor {} iniciado", env!("CARGO_PKG_VERSION"));
+    Ok(path)
+}
+
+/// `reditor.log` in the state directory
+fn path() -> Option<PathBuf> {
+    config::state_dir().map(|dir| dir.join("reditor.log"))
+}
+
+/// Path of the log file when logging is on
+pub fn current_path() -> Option<PathBuf> {
+    LOGGER.get().map(|logger| logger.path.clone())
+}
+
+pub fn enabled(level: Level) -> bool {
+    LOGGER.get().is_some_and(|logger| level <= logger.level)
+}
+
+/// One line per message: seconds since start, level, subsystem, text
+pub fn write(level: Level, target: &str, message: &str) {
+    if let Some(logger) = LOGGER.get() {
+        if let Ok(mut file) = logger.file.lock() {
+            let _ = writeln!(
+                file,
+                "[{:>10.3}] {} {}: {}",
+                logger.started.elapsed().as_secs_f64(),
+                level.as_str(),
+                target,
+                message
+            );
+        }
+    }
+}
+
+macro_rules! log_at {
+    ($level:expr, $target:expr, $($arg:tt)*) => {
+        if $crate::log::enabled($level) {
+            $crate::log::write($level, $target, &format!($($arg)*));
+        }
+    };
+}
+
+macro_rules! error {
+    ($target:expr, $($arg:tt)*) => { $crate::log::log_at!($crate::log::Level::Error, $target, $($arg)*) };
+}
+
+macro_rules! warning {
+    ($target:expr, $($arg:tt)*) => { $crate::log::log_at!($crate::log::Level::Warn, $target, $($arg)*) };
+}
+
+macro_rules! info {
+    ($target:expr, $($arg:tt)*) => { $crate::log::log_at!($crate::log::Level::Info, $target, $($arg)*) };
+}
+
+macro_rules! debug {
+    ($target:expr, $($arg:tt)*) => { $crate::log::log_at!($crate::log::Level::Debug, $target, $($arg)*) };
+}
+
+pub(crate) use {debug, error, info, log_at, warning};
@@ -1,45 +1,249 @@
+mod archive;
+mod bench;
+mod breadcrumb;
 mod buffer_file;
+mod clipboard;
+mod collab;
+mod commands;
+mod config;
+mod crypt;
+mod diagnostics;
 mod display;
 mod editor;
+mod ex;
+mod expand;
+mod follow;
+mod git;
+mod grapheme;
+mod history;
+mod idle;
+mod inspect;
+mod jobs;
+mod json;
+mod keymap;
+mod killring;
+mod links;
+mod loader;
+mod log;
+mod marks;
+mod motion;
+mod options;
+mod overlay;
+mod pairs;
+mod patch;
+mod present;
+mod project;
+mod prompt;
+mod remote;
+mod rename;
+mod replace;
+mod screen;
+mod server;
 mod sidebar;
+mod snippets;
+mod startup;
+mod symbols;
 mod syntax;
+mod tags;
+mod tasks;
+mod testfile;
+mod textobj;
+mod time;
+mod todos;
+mod transform;
+mod trash;
+mod undo;
+mod vfs;
+mod view;
 mod welcome;
 mod workspace;
 
 use std::env;
 use std::io;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::Instant;
 
-fn main() -> io::Result<()> {
-    let args: Vec<String> = env::args().collect();
+use editor::Outcome;
+use startup::StartupTiming;
 
-    let mut workspace = workspace::Workspace::new();
+fn main() -> io::Result<ExitCode> {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let mut timing = StartupTiming::new(args.iter().any(|arg| arg == "--startup-timing"));
+    let mut path_args: Vec<String> = vec![];
+    let mut remote_wait: Option<bool> = None;
+    let mut new_instance = false;
+    let mut quick = false;
+    let mut insert = false;
+
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--bench" => {
+                bench::run(args.get(i + 1).map(String::as_str))?;
+                return Ok(ExitCode::SUCCESS);
+            }
+            "--log" => {
+                i += 1;
+                match args.get(i).and_then(|name| log::Level::parse(name)) {
+                    Some(level) => {
+                        if let Err(e) = log::init(level) {
+                            eprintln!("reditor: não foi possível abrir o log: {}", e);
+                        }
+                    }
+                    None => {
+                        eprintln!("reditor: --log espera error, warn, info ou debug");
+                        return Ok(Outcome::OpenFailed.exit_code());
+                    }
+                }
+            }
+            "--remote" => remote_wait = Some(false),
+            "--remote-wait" => remote_wait = Some(true),
+            "--new" => new_instance = true,
+            "--quick" => quick = true,
+            "--insert" => insert = true,
+            "--startup-timing" => {}
+            arg => {
+                // Quoted on the command line, or on systems without a shell
+                // that does it: ~, $VARS and globs are expanded here
+                let path = expand::expand(arg);
+                if expand::has_glob(&path) && !Path::new(&path).exists() {
+                    let found = expand::glob(&path);
+                    if found.is_empty() {
+                        eprintln!("reditor: nenhum arquivo casa com '{}'", arg);
+                        return Ok(Outcome::OpenFailed.exit_code());
+                    }
+                    path_args.extend(found.iter().map(|p| p.to_string_lossy().to_string()));
+                } else {
+                    path_args.push(path);
+                }
+            }
+        }
+        i += 1;
+    }
+    let path_arg = path_args.first();
+
+    // Hand the files to the running instance; without one they open here
+    if let Some(wait) = remote_wait.filter(|_| !path_args.is_empty()) {
+        match server::send(&path_args, wait) {
+            Ok(()) => return Ok(ExitCode::SUCCESS),
+            Err(e) => log::info!("servidor", "--remote sem instância aberta: {}", e),
+        }
+    }
+
+    if quick && path_arg.is_none() {
+        eprintln!("reditor: --quick espera um arquivo");
+        return Ok(Outcome::OpenFailed.exit_code());
+    }
+
+    let workspace = workspace::Workspace::new();
     let mut sidebar_instance: Option<sidebar::Sidebar> = None;
+    let mut startup_file: Option<String> = None;
+    // Files after the first, opened as more tabs
+    let mut more_files: Vec<String> = vec![];
+    for extra in path_args.iter().skip(1) {
+        match std::fs::canonicalize(extra) {
+            Ok(path) if path.is_file() => more_files.push(path.to_string_lossy().to_string()),
+            _ if remote::is_remote(extra) => more_files.push(extra.clone()),
+            _ => eprintln!("reditor: '{}' ignorado: não é um arquivo", extra),
+        }
+    }
 
-    if args.len() > 1 {
-        let path_arg = &args[1];
+    if let Some(path_arg) = path_arg.filter(|arg| remote::is_remote(arg)) {
+        // user@host:/caminho is copied over SSH once the editor is running
+        startup_file = Some(path_arg.clone());
+    } else if let Some(path_arg) = path_arg {
         let path = std::fs::canonicalize(PathBuf::from(path_arg))
             .unwrap_or_else(|_| PathBuf::from(path_arg));
 
-        if path.is_dir() {
+        if path.is_dir() && quick {
+            eprintln!("reditor: --quick espera um arquivo, não uma pasta");
+            return Ok(Outcome::OpenFailed.exit_code());
+        } else if path.is_dir() {
             // Open sidebar with directory
+            let since = Instant::now();
             sidebar_instance = Some(sidebar::Sidebar::new(path));
+            timing.record("árvore da sidebar", since);
         } else if path.is_file() {
-            // Open file directly
-            workspace.open_file(&path.to_string_lossy());
-            // Use parent dir for sidebar
-            if let Some(parent) = path.parent() {
+            // Open file directly (once the editor is running, so large or
+            // binary files can be confirmed first)
+            startup_file = Some(path.to_string_lossy().to_string());
+            // Use parent dir for sidebar (none in --quick: just the file)
+            if let Some(parent) = path.parent().filter(|_| !quick) {
+                let since = Instant::now();
                 sidebar_instance = Some(sidebar::Sidebar::new(parent.to_path_buf()));
+                timing.record("árvore da sidebar", since);
             }
         } else {
             eprintln!("reditor: '{}' não encontrado", path_arg);
-            return Ok(());
+            return Ok(Outcome::OpenFailed.exit_code());
         }
     }
     // No args = welcome screen (no sidebar, no files)
 
-    let mut editor = editor::Editor::new(workspace, sidebar_instance);
+    // Another instance editing this project takes the file instead, and
+    // this one waits for it to be closed there (so it still works as
+    // $EDITOR)
+    let project = sidebar_instance
+        .as_ref()
+        .map(|s| s.root_path.clone())
+        .filter(|_| !quick);
+    if let Some(root) = project.as_deref().filter(|_| !new_instance) {
+        if let Some(open) = server::project_instance(root) {
+            let Some(file) = &startup_file else {
+                eprintln!(
+                    "reditor: '{}' já está aberto em outra instância",
+                    open.display()
+                );
+                return Ok(ExitCode::SUCCESS);
+            };
+            eprintln!(
+                "reditor: aberto na instância que edita '{}'; aguardando a aba ser fechada (--new abre outra)",
+                open.display()
+            );
+            let files: Vec<String> = std::iter::once(file.clone())
+                .chain(more_files.iter().cloned())
+                .collect();
+            match server::send_to_project(&open, &files, true) {
+                Ok(()) => return Ok(ExitCode::SUCCESS),
+                Err(e) => log::warning!("servidor", "erro ao entregar '{}': {}", file, e),
+            }
+        }
+    }
+
+    let since = Instant::now();
+    let config = config::Config::load();
+    timing.record("config", since);
+    let since = Instant::now();
+    let mut editor = editor::Editor::new(workspace, sidebar_instance, config);
+    timing.record("editor", since);
+    editor.time_startup(timing);
+    if quick {
+        editor.quick(insert);
+    } else {
+        match server::Server::start() {
+            Ok(server) => editor.serve(server),
+            Err(e) => log::info!("servidor", "--remote desativado: {}", e),
+        }
+    }
+    if let Some(root) = project.filter(|_| !new_instance) {
+        match server::Server::start_for_project(&root) {
+            Ok(server) => editor.serve(server),
+            Err(e) => log::info!("servidor", "instância única desativada: {}", e),
+        }
+    }
+    if let Some(path) = startup_file {
+        editor.open_on_start(path, more_files);
+    }
     editor.run()?;
+    if let Some(report) = editor.startup_report() {
+        eprintln!("{}", report);
+    }
 
-    Ok(())
+    // Scripts (git, crontab...) see how it went
+    let outcome = editor.outcome();
+    if outcome != Outcome::Done {
+        log::info!("editor", "saindo com {:?}", outcome);
+    }
+    Ok(outcome.exit_code())
 }
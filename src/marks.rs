@@ -0,0 +1,81 @@
+use crate::config;
+use crate::remote;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+/// A position in a file saved under an uppercase letter (`mA`), kept
+/// between sessions
+pub struct Mark {
+    pub name: char,
+    pub path: String,
+    /// 0-based
+    pub row: usize,
+    pub col: usize,
+}
+
+/// `marks` in the state directory: one "name\trow\tcol\tpath" line per mark
+fn path() -> Option<PathBuf> {
+    config::state_dir().map(|dir| dir.join("marks"))
+}
+
+/// Whether `name` can name a global mark
+pub fn is_global(name: char) -> bool {
+    name.is_ascii_uppercase()
+}
+
+/// Every saved mark, by name
+pub fn load() -> Vec<Mark> {
+    let text = match path().and_then(|p| fs::read_to_string(p).ok()) {
+        Some(text) => text,
+        None => return vec![],
+    };
+    let mut marks: Vec<Mark> = text
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.splitn(4, '\t');
+            let name = fields.next()?.chars().next().filter(|&c| is_global(c))?;
+            Some(Mark {
+                name,
+                row: fields.next()?.parse().ok()?,
+                col: fields.next()?.parse().ok()?,
+                path: fields.next()?.to_string(),
+            })
+        })
+        .collect();
+    marks.sort_by_key(|mark| mark.name);
+    marks
+}
+
+pub fn get(name: char) -> Option<Mark> {
+    load().into_iter().find(|mark| mark.name == name)
+}
+
+/// Save (or move) mark `name` to a position of `file`. Local paths are
+/// stored absolute so the mark works from any directory.
+pub fn set(name: char, file: &str, row: usize, col: usize) -> io::Result<()> {
+    let file = if remote::is_remote(file) {
+        file.to_string()
+    } else {
+        fs::canonicalize(file)?.to_string_lossy().to_string()
+    };
+    let path = path().ok_or_else(|| io::Error::other("HOME não definido"))?;
+    let mut marks = load();
+    marks.retain(|mark| mark.name != name);
+    marks.push(Mark {
+        name,
+        path: file,
+        row,
+        col,
+    });
+    marks.sort_by_key(|mark| mark.name);
+
+    let text: String = marks
+        .iter()
+        .map(|mark| format!("{}\t{}\t{}\t{}\n", mark.name, mark.row, mark.col, mark.path))
+        .collect();
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(&path, text)
+}
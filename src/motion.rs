@@ -0,0 +1,126 @@
+/// An `f`, `F`, `t` or `T` search for a char in the cursor line, kept so
+/// `;` can repeat it and `,` repeat it the other way
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct CharFind {
+    pub ch: char,
+    // Towards the end of the line (`f`, `t`)
+    pub forward: bool,
+    // Stop next to the char instead of on it (`t`, `T`)
+    pub till: bool,
+}
+
+impl CharFind {
+    /// The search the motion `key` starts once `ch` is typed after it
+    pub fn new(key: char, ch: char) -> Option<CharFind> {
+        let (forward, till) = match key {
+            'f' => (true, false),
+            'F' => (false, false),
+            't' => (true, true),
+            'T' => (false, true),
+            _ => return None,
+        };
+        Some(CharFind { ch, forward, till })
+    }
+
+    /// The same search the other way, for `,`
+    pub fn reversed(self) -> CharFind {
+        CharFind {
+            forward: !self.forward,
+            ..self
+        }
+    }
+
+    /// Column the cursor at `col` moves to, or `None` when the char isn't
+    /// there. A repeated `t` already next to its char looks past it, so
+    /// `;` doesn't get stuck.
+    pub fn target(&self, line: &[char], col: usize, repeat: bool) -> Option<usize> {
+        let skip = usize::from(self.till && repeat);
+        if self.forward {
+            let from = col + 1 + skip;
+            let found = from + line.get(from..)?.iter().position(|&c| c == self.ch)?;
+            Some(if self.till { found - 1 } else { found })
+        } else {
+            let end = col.checked_sub(skip)?.min(line.len());
+            let found = line[..end].iter().rposition(|&c| c == self.ch)?;
+            Some(if self.till { found + 1 } else { found })
+        }
+    }
+}
+
+/// Row `}` (`forward`) or `{` moves to from `row`: the blank line past the
+/// paragraph at `row`, or past the next one when `row` is blank. Without
+/// one, the last or first line.
+pub fn paragraph_edge(lines: &[Vec<char>], row: usize, forward: bool) -> usize {
+    let blank = |row: usize| lines[row].iter().all(|c| c.is_whitespace());
+    let last = lines.len().saturating_sub(1);
+    let mut row = row.min(last);
+    let step = |row: usize| if forward { row + 1 } else { row - 1 };
+    let more = |row: usize| if forward { row < last } else { row > 0 };
+    while more(row) && blank(row) {
+        row = step(row);
+    }
+    while more(row) && !blank(row) {
+        row = step(row);
+    }
+    row
+}
+
+/// Columns of leading blanks of `line`, a tab being as wide as it's drawn
+/// (4); `None` for a blank line, which has no level of its own
+fn indent_of(line: &[char]) -> Option<usize> {
+    let mut width = 0;
+    for &c in line {
+        match c {
+            ' ' => width += 1,
+            '\t' => width += 4,
+            _ => return Some(width),
+        }
+    }
+    None
+}
+
+/// Indentation at `row`; a blank line takes that of the line above it
+fn level(lines: &[Vec<char>], row: usize) -> usize {
+    (0..=row)
+        .rev()
+        .find_map(|row| indent_of(&lines[row]))
+        .unwrap_or(0)
+}
+
+/// Next (or, not `forward`, previous) line indented just like `row`,
+/// skipping blank lines
+pub fn same_indent(lines: &[Vec<char>], row: usize, forward: bool) -> Option<usize> {
+    let level = level(lines, row);
+    let matches = |&row: &usize| indent_of(&lines[row]) == Some(level);
+    if forward {
+        (row + 1..lines.len()).find(matches)
+    } else {
+        (0..row).rev().find(matches)
+    }
+}
+
+/// Last (`forward`) or first line of the block indented at least as deep as
+/// `row`: the body of a Python function or of a YAML key. Blank lines stay
+/// inside the block unless they end it.
+pub fn indent_block_edge(lines: &[Vec<char>], row: usize, forward: bool) -> usize {
+    let level = level(lines, row);
+    let inside = |row: usize| indent_of(&lines[row]).is_none_or(|indent| indent >= level);
+    let mut edge = row;
+    let mut at = row;
+    loop {
+        let next = if forward {
+            Some(at + 1).filter(|&next| next < lines.len())
+        } else {
+            at.checked_sub(1)
+        };
+        match next {
+            Some(next) if inside(next) => {
+                at = next;
+                if indent_of(&lines[at]).is_some() {
+                    edge = at;
+                }
+            }
+            _ => return edge,
+        }
+    }
+}
@@ -0,0 +1,151 @@
+use crate::config::Config;
+use crate::log;
+
+/// Spaces Tab inserts when `[editor] tab_width` isn't set
+const DEFAULT_TAB_WIDTH: usize = 4;
+
+/// Options that change how the text is shown and edited, settable while the
+/// editor runs (`:set`, the palette's `options`). The global ones start
+/// from `[editor]` in config.toml; a buffer can override any of them
+/// (`:setlocal`).
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Options {
+    pub line_numbers: bool,
+    /// Long lines continue on the rows below instead of scrolling sideways
+    pub wrap: bool,
+    /// Spaces the Tab key inserts, and columns of a tab in presentation mode
+    pub tab_width: usize,
+    /// Modified files are saved whenever typing pauses
+    pub autosave: bool,
+    pub theme: Theme,
+    /// Lines kept visible above and below the cursor
+    pub scrolloff: usize,
+}
+
+/// Every option by name, with what it does
+pub const OPTIONS: &[(&str, &str)] = &[
+    ("line_numbers", "Números de linha"),
+    ("wrap", "Quebrar linhas longas"),
+    ("tab_width", "Espaços inseridos pelo Tab"),
+    ("autosave", "Salvar ao parar de digitar"),
+    ("theme", "Cores do fundo (dark, black, navy)"),
+    (
+        "scrolloff",
+        "Linhas sempre visíveis acima e abaixo do cursor",
+    ),
+];
+
+/// Background of the text area and its gutter
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Theme {
+    Dark,
+    Black,
+    Navy,
+}
+
+impl Theme {
+    fn parse(name: &str) -> Option<Theme> {
+        match name {
+            "dark" => Some(Theme::Dark),
+            "black" => Some(Theme::Black),
+            "navy" => Some(Theme::Navy),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Theme::Dark => "dark",
+            Theme::Black => "black",
+            Theme::Navy => "navy",
+        }
+    }
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options {
+            line_numbers: true,
+            wrap: false,
+            tab_width: DEFAULT_TAB_WIDTH,
+            autosave: false,
+            theme: Theme::Dark,
+            scrolloff: 0,
+        }
+    }
+}
+
+impl Options {
+    /// The defaults, with what `[editor]` sets; values that don't parse are
+    /// logged and left out
+    pub fn from_config(config: &Config) -> Options {
+        let mut options = Options::default();
+        for (name, _) in OPTIONS {
+            if let Some(value) = config.get("editor", name) {
+                if let Err(e) = options.set(name, value) {
+                    log::warning!("config", "[editor] {}", e);
+                }
+            }
+        }
+        options
+    }
+
+    pub fn is_option(name: &str) -> bool {
+        OPTIONS.iter().any(|(option, _)| *option == name)
+    }
+
+    /// Set option `name` from its text (`true`, `8`, `navy`...)
+    pub fn set(&mut self, name: &str, value: &str) -> Result<(), String> {
+        let invalid = || format!("Valor inválido para {}: '{}'", name, value);
+        let flag = || match value {
+            "true" | "on" | "yes" => Ok(true),
+            "false" | "off" | "no" => Ok(false),
+            _ => Err(invalid()),
+        };
+        let number = || value.parse::<usize>().map_err(|_| invalid());
+        match name {
+            "line_numbers" => self.line_numbers = flag()?,
+            "wrap" => self.wrap = flag()?,
+            "tab_width" => {
+                self.tab_width = number().and_then(|n| match n {
+                    1..=16 => Ok(n),
+                    _ => Err(invalid()),
+                })?
+            }
+            "autosave" => self.autosave = flag()?,
+            "theme" => self.theme = Theme::parse(value).ok_or_else(invalid)?,
+            "scrolloff" => self.scrolloff = number()?,
+            _ => return Err(format!("Opção desconhecida: {}", name)),
+        }
+        Ok(())
+    }
+
+    /// Value of option `name` as `set` reads it
+    pub fn get(&self, name: &str) -> Option<String> {
+        Some(match name {
+            "line_numbers" => self.line_numbers.to_string(),
+            "wrap" => self.wrap.to_string(),
+            "tab_width" => self.tab_width.to_string(),
+            "autosave" => self.autosave.to_string(),
+            "theme" => self.theme.name().to_string(),
+            "scrolloff" => self.scrolloff.to_string(),
+            _ => return None,
+        })
+    }
+
+    /// Whether `name` is an on/off option, which `:set name!` flips
+    pub fn is_flag(name: &str) -> bool {
+        matches!(name, "line_numbers" | "wrap" | "autosave")
+    }
+
+    /// These options with a buffer's overrides, (name, value) pairs set by
+    /// `:setlocal`, on top
+    pub fn with(&self, overrides: &[(String, String)]) -> Options {
+        let mut options = *self;
+        for (name, value) in overrides {
+            // Overrides were checked when set
+            let _ = options.set(name, value);
+        }
+        options
+    }
+}
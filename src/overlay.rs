@@ -0,0 +1,257 @@
+use crate::screen::{char_width, Screen};
+use crossterm::style::Color;
+
+/// Most list rows an overlay shows at once
+const MAX_LIST_ROWS: usize = 10;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Placement {
+    /// Middle of the screen (dialogs)
+    Center,
+    /// Horizontally centered just below the tab bar (pickers, palette)
+    Top,
+    /// Top-left corner at a screen cell (popups anchored to the editor)
+    At(u16, u16),
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum OverlayStyle {
+    Normal,
+    Warning,
+}
+
+/// A floating box drawn over the frame: a dialog, a text input or a list
+/// picker, depending on which fields are set.
+#[derive(Clone)]
+pub struct Overlay {
+    pub title: String,
+    pub lines: Vec<String>,
+    // Text field shown under the title, with the caret at its end
+    pub input: Option<String>,
+    // Highlighted entry when `lines` is a list
+    pub selected: Option<usize>,
+    pub placement: Placement,
+    pub style: OverlayStyle,
+}
+
+impl Overlay {
+    pub fn dialog(title: &str, lines: Vec<String>) -> Overlay {
+        Overlay {
+            title: title.to_string(),
+            lines,
+            input: None,
+            selected: None,
+            placement: Placement::Center,
+            style: OverlayStyle::Normal,
+        }
+    }
+
+    pub fn input(title: &str, input: &str) -> Overlay {
+        Overlay {
+            input: Some(input.to_string()),
+            placement: Placement::Top,
+            ..Overlay::dialog(title, vec![])
+        }
+    }
+
+    pub fn list(title: &str, input: &str, items: Vec<String>, selected: usize) -> Overlay {
+        Overlay {
+            lines: items,
+            selected: Some(selected),
+            ..Overlay::input(title, input)
+        }
+    }
+
+    pub fn at(mut self, placement: Placement) -> Overlay {
+        self.placement = placement;
+        self
+    }
+
+    pub fn warning(mut self) -> Overlay {
+        self.style = OverlayStyle::Warning;
+        self
+    }
+
+    /// Draw the box into `screen` (of `columns` x `rows`). Returns where the
+    /// caret of the input field is, if there is one.
+    pub fn draw(&self, screen: &mut Screen, columns: u16, rows: u16) -> Option<(u16, u16)> {
+        let (fg, bg, border) = match self.style {
+            OverlayStyle::Normal => (
+                Color::Rgb {
+                    r: 200,
+                    g: 220,
+                    b: 255,
+                },
+                Color::Rgb {
+                    r: 25,
+                    g: 35,
+                    b: 50,
+                },
+                Color::Rgb {
+                    r: 90,
+                    g: 120,
+                    b: 170,
+                },
+            ),
+            OverlayStyle::Warning => (
+                Color::Rgb {
+                    r: 255,
+                    g: 220,
+                    b: 220,
+                },
+                Color::Rgb {
+                    r: 80,
+                    g: 30,
+                    b: 30,
+                },
+                Color::Rgb {
+                    r: 200,
+                    g: 90,
+                    b: 90,
+                },
+            ),
+        };
+        let bg_selected = Color::Rgb {
+            r: 50,
+            g: 75,
+            b: 110,
+        };
+
+        // Inner width fits the longest text, within the screen
+        let widest = self
+            .lines
+            .iter()
+            .map(|line| text_width(line))
+            .chain(std::iter::once(text_width(&self.title) + 2))
+            .chain(self.input.iter().map(|input| text_width(input) + 1))
+            .max()
+            .unwrap_or(0);
+        let inner_width = widest.max(30).min(columns.saturating_sub(4) as usize);
+
+        let list_rows = if self.selected.is_some() {
+            self.lines.len().clamp(1, MAX_LIST_ROWS)
+        } else {
+            self.lines.len()
+        };
+        let inner_rows = list_rows + self.input.is_some() as usize;
+        let max_inner_rows = rows.saturating_sub(4).max(1) as usize;
+        let inner_rows = inner_rows.min(max_inner_rows);
+        let box_width = inner_width as u16 + 4;
+        let box_height = inner_rows as u16 + 2;
+
+        let (left, top) = match self.placement {
+            Placement::Center => (
+                columns.saturating_sub(box_width) / 2,
+                rows.saturating_sub(box_height) / 2,
+            ),
+            Placement::Top => (columns.saturating_sub(box_width) / 2, 1),
+            Placement::At(col, row) => (
+                col.min(columns.saturating_sub(box_width)),
+                row.min(rows.saturating_sub(box_height)),
+            ),
+        };
+
+        // Border, with the title in the top edge
+        let title = if self.title.is_empty() {
+            String::new()
+        } else {
+            format!(" {} ", self.title)
+        };
+        let top_edge = format!(
+            "┌─{}{}┐",
+            title,
+            "─".repeat((inner_width + 1).saturating_sub(text_width(&title)))
+        );
+        screen.put_str(left, top, border, bg, &top_edge);
+        for row in 1..box_height - 1 {
+            screen.put_str(left, top + row, border, bg, "│");
+            screen.put_str(left + 1, top + row, fg, bg, &" ".repeat(inner_width + 2));
+            screen.put_str(left + box_width - 1, top + row, border, bg, "│");
+        }
+        let bottom_edge = format!("└{}┘", "─".repeat(inner_width + 2));
+        screen.put_str(left, top + box_height - 1, border, bg, &bottom_edge);
+
+        let mut row = top + 1;
+        let mut caret = None;
+
+        if let Some(input) = &self.input {
+            let shown = fit(input, inner_width.saturating_sub(1), true);
+            screen.put_str(left + 2, row, fg, bg, &pad(&shown, inner_width));
+            caret = Some((left + 2 + text_width(&shown) as u16, row));
+            row += 1;
+        }
+
+        // Scroll the list so the selected entry stays visible
+        let visible = inner_rows.saturating_sub(self.input.is_some() as usize);
+        let first = match self.selected {
+            Some(selected) if selected >= visible => selected + 1 - visible,
+            _ => 0,
+        };
+        if self.selected.is_some() && self.lines.is_empty() && visible > 0 {
+            screen.put_str(
+                left + 2,
+                row,
+                fg,
+                bg,
+                &pad("(nenhum resultado)", inner_width),
+            );
+        }
+        for (i, line) in self.lines.iter().enumerate().skip(first).take(visible) {
+            let line_bg = if self.selected == Some(i) {
+                bg_selected
+            } else {
+                bg
+            };
+            screen.put_str(left + 1, row, fg, line_bg, " ");
+            screen.put_str(
+                left + 2,
+                row,
+                fg,
+                line_bg,
+                &pad(&fit(line, inner_width, false), inner_width),
+            );
+            screen.put_str(left + 2 + inner_width as u16, row, fg, line_bg, " ");
+            row += 1;
+        }
+
+        caret
+    }
+}
+
+fn text_width(text: &str) -> usize {
+    text.chars().map(char_width).sum()
+}
+
+/// Cut `text` to `width` columns, keeping its end when `keep_end` is set
+fn fit(text: &str, width: usize, keep_end: bool) -> String {
+    if text_width(text) <= width {
+        return text.to_string();
+    }
+    let chars: Vec<char> = text.chars().collect();
+    let mut taken = vec![];
+    let mut used = 0;
+    let ordered: Box<dyn Iterator<Item = &char>> = if keep_end {
+        Box::new(chars.iter().rev())
+    } else {
+        Box::new(chars.iter())
+    };
+    for &ch in ordered {
+        used += char_width(ch);
+        if used > width {
+            break;
+        }
+        taken.push(ch);
+    }
+    if keep_end {
+        taken.reverse();
+    }
+    taken.into_iter().collect()
+}
+
+fn pad(text: &str, width: usize) -> String {
+    format!(
+        "{}{}",
+        text,
+        " ".repeat(width.saturating_sub(text_width(text)))
+    )
+}
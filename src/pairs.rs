@@ -0,0 +1,75 @@
+use crate::syntax::{self, Context};
+
+/// What typing a char in Insert mode does
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Typed {
+    /// Just the char
+    Plain,
+    /// The char and its closing one after the cursor
+    Pair(char),
+    /// Nothing but moving past the same char, closed earlier by a `Pair`
+    StepOver,
+}
+
+/// The char that closes `open`, for the pairs typed together
+fn closer(open: char) -> Option<char> {
+    match open {
+        '(' => Some(')'),
+        '[' => Some(']'),
+        '{' => Some('}'),
+        '<' => Some('>'),
+        '"' | '\'' | '`' => Some(open),
+        _ => None,
+    }
+}
+
+/// What typing `ch` at `pos` does in a buffer of language `ext`. Brackets
+/// are closed in code but not in strings and comments, quotes when they
+/// start a string, and `<` only in front of generic arguments.
+pub fn on_type(lines: &[Vec<char>], ext: &str, pos: (usize, usize), ch: char) -> Typed {
+    let line = &lines[pos.0];
+    let next = line.get(pos.1).copied();
+    let quote = matches!(ch, '"' | '\'' | '`');
+    if next == Some(ch) && (quote || matches!(ch, ')' | ']' | '}' | '>')) {
+        // Closing a string steps over the quote; a quote inside it
+        // doesn't (`"it's"` isn't at its end)
+        let closes = !quote || syntax::closes_string(lines, ext, pos);
+        if closes {
+            return Typed::StepOver;
+        }
+    }
+    let Some(close) = closer(ch) else {
+        return Typed::Plain;
+    };
+    // Only in front of blanks and closing chars: `(` before a word wraps
+    // nothing
+    let room =
+        next.is_none_or(|next| next.is_whitespace() || matches!(next, ')' | ']' | '}' | ',' | ';'));
+    if !room || syntax::context_at(lines, ext, pos) != Context::Code {
+        return Typed::Plain;
+    }
+    let pairs = match ch {
+        '<' => syntax::opens_generics(lines, ext, pos),
+        _ if quote => syntax::opens_string(lines, ext, pos, ch),
+        _ => true,
+    };
+    if pairs {
+        Typed::Pair(close)
+    } else {
+        Typed::Plain
+    }
+}
+
+/// Whether typing `ch` may do more than insert it (`on_type` has to see it)
+pub fn acts_on(ch: char) -> bool {
+    closer(ch).is_some() || matches!(ch, ')' | ']' | '}' | '>')
+}
+
+/// Whether Backspace at `pos` sits between an empty pair (`(|)`, `"|"`)
+/// and takes both chars
+pub fn deletes_pair(line: &[char], col: usize) -> bool {
+    match (col.checked_sub(1).and_then(|i| line.get(i)), line.get(col)) {
+        (Some(&open), Some(&close)) => closer(open) == Some(close),
+        _ => false,
+    }
+}
@@ -0,0 +1,138 @@
+/// One `@@` hunk of a unified diff, as read from a `.diff`/`.patch` buffer
+pub struct PatchHunk {
+    /// The file it changes, as the diff names it (`src/main.rs`, without
+    /// git's `a/` and `b/`)
+    pub path: String,
+    /// The name as written, for diffs made without `a/` and `b/` whose
+    /// first folder is really called `a` or `b`
+    pub raw_path: String,
+    /// 0-based row of the old text in the file, from the header
+    pub row: usize,
+    /// Lines before the change (context and `-` lines)
+    pub old: Vec<Vec<char>>,
+    /// Lines after it (context and `+` lines)
+    pub new: Vec<Vec<char>>,
+    /// Lines of context before the first change
+    pub leading: usize,
+}
+
+/// The hunk the cursor at `row` is in (on its `@@` line or below it)
+pub fn hunk_at(lines: &[Vec<char>], row: usize) -> Option<PatchHunk> {
+    let text = |row: usize| -> String { lines[row].iter().collect() };
+    let header_row = (0..=row.min(lines.len().checked_sub(1)?))
+        .rev()
+        .find(|&r| {
+            let line = text(r);
+            line.starts_with("@@") || line.starts_with("diff ")
+        })
+        .filter(|&r| text(r).starts_with("@@"))?;
+    let old_start = old_start(&text(header_row))?;
+
+    // The file is named by the `+++` line above the hunk (`---` for a
+    // deleted file)
+    let mut path = None;
+    for r in (0..header_row).rev() {
+        let line = text(r);
+        if let Some(name) = line.strip_prefix("+++ ").map(file_name) {
+            path = Some(name);
+        } else if let Some(name) = line.strip_prefix("--- ").map(file_name) {
+            if path.as_deref().is_none_or(|path| path == "/dev/null") {
+                path = Some(name);
+            }
+            break;
+        }
+    }
+    let raw_path = path.filter(|path| path != "/dev/null")?;
+
+    let mut old = vec![];
+    let mut new = vec![];
+    let mut leading = None;
+    for line in &lines[header_row + 1..] {
+        match line.first() {
+            Some(' ') => {
+                old.push(line[1..].to_vec());
+                new.push(line[1..].to_vec());
+            }
+            // Context lines whose trailing space was trimmed
+            None => {
+                old.push(vec![]);
+                new.push(vec![]);
+            }
+            Some('-') if !line.starts_with(&['-', '-', '-', ' ']) => {
+                leading.get_or_insert(old.len());
+                old.push(line[1..].to_vec());
+            }
+            Some('+') if !line.starts_with(&['+', '+', '+', ' ']) => {
+                leading.get_or_insert(old.len());
+                new.push(line[1..].to_vec());
+            }
+            // "\ No newline at end of file"
+            Some('\\') => {}
+            _ => break,
+        }
+    }
+    // Blank lines after the last hunk aren't context
+    while old.last().is_some_and(|l| l.is_empty()) && new.last().is_some_and(|l| l.is_empty()) {
+        old.pop();
+        new.pop();
+    }
+    let path = raw_path
+        .strip_prefix("a/")
+        .or_else(|| raw_path.strip_prefix("b/"))
+        .unwrap_or(&raw_path)
+        .to_string();
+    Some(PatchHunk {
+        path,
+        raw_path,
+        row: old_start,
+        old,
+        new,
+        leading: leading?,
+    })
+}
+
+/// The name of a `---`/`+++` line, without the timestamp `diff -u` adds
+fn file_name(rest: &str) -> String {
+    rest.split('\t')
+        .next()
+        .unwrap_or(rest)
+        .trim_end()
+        .to_string()
+}
+
+/// 0-based start row of the "-a,b" part of a hunk header
+fn old_start(header: &str) -> Option<usize> {
+    let range = header.split(' ').find(|part| part.starts_with('-'))?;
+    let mut parts = range[1..].splitn(2, ',');
+    let start: usize = parts.next()?.parse().ok()?;
+    // With nothing removed, the new lines go after line `a`
+    if parts.next() == Some("0") {
+        return Some(start);
+    }
+    Some(start.saturating_sub(1))
+}
+
+/// Where `hunk` applies to `file`: the row whose lines match its old text
+/// closest to where the header says, as the file may have moved since the
+/// diff was made. `Err(true)` when the new text is there already.
+pub fn locate(file: &[Vec<char>], hunk: &PatchHunk) -> Result<usize, bool> {
+    let matches_at =
+        |lines: &[Vec<char>], row: usize| file.get(row..row + lines.len()) == Some(lines);
+    let nearest = |lines: &[Vec<char>]| {
+        let last = (file.len() + 1).saturating_sub(lines.len().max(1));
+        (0..=last)
+            .filter(|&row| matches_at(lines, row))
+            .min_by_key(|&row| row.abs_diff(hunk.row))
+    };
+    if hunk.old.is_empty() {
+        return match hunk.row {
+            row if matches_at(&hunk.new, row) => Err(true),
+            row if row <= file.len() => Ok(row),
+            _ => Err(false),
+        };
+    }
+    match nearest(&hunk.old) {
+        Some(row) => Ok(row),
+        None => Err(!hunk.new.is_empty() && nearest(&hunk.new).is_some()),
+    }
+}
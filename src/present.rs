@@ -0,0 +1,166 @@
+use crate::config::Config;
+use crate::log;
+use crossterm::style::Color;
+
+/// How presentation mode makes the text bigger
+#[derive(Clone, Copy, PartialEq)]
+pub enum Style {
+    /// Letters drawn in half-block characters, 3×5 dots each: works in any
+    /// terminal
+    Blocks,
+    /// The terminal's double-width, double-height lines (xterm, VTE-based
+    /// terminals such as GNOME Terminal, Terminal.app)
+    Double,
+}
+
+impl Style {
+    /// `[present] style = "blocks"` (default) or `"double"`
+    pub fn from_config(config: &Config) -> Style {
+        match config.get("present", "style") {
+            None | Some("blocks") => Style::Blocks,
+            Some("double") => Style::Double,
+            Some(other) => {
+                log::warning!("config", "[present] style desconhecido: {}", other);
+                Style::Blocks
+            }
+        }
+    }
+
+    /// Terminal rows a row of text takes
+    pub fn rows_per_line(self) -> usize {
+        match self {
+            Style::Blocks => 3,
+            Style::Double => 2,
+        }
+    }
+
+    /// Chars of text that fit in `width` terminal columns
+    pub fn chars_per_row(self, width: usize) -> usize {
+        match self {
+            Style::Blocks => width / 4,
+            Style::Double => width / 2,
+        }
+    }
+}
+
+/// Dots of each glyph, a row of 3 bits (left dot highest) per line.
+/// Lowercase letters use the capitals' glyphs.
+const FONT: &[(char, [u8; 5])] = &[
+    (' ', [0b000, 0b000, 0b000, 0b000, 0b000]),
+    ('!', [0b010, 0b010, 0b010, 0b000, 0b010]),
+    ('"', [0b101, 0b101, 0b000, 0b000, 0b000]),
+    ('#', [0b101, 0b111, 0b101, 0b111, 0b101]),
+    ('$', [0b011, 0b110, 0b010, 0b011, 0b110]),
+    ('%', [0b101, 0b001, 0b010, 0b100, 0b101]),
+    ('&', [0b010, 0b101, 0b010, 0b101, 0b011]),
+    ('\'', [0b010, 0b010, 0b000, 0b000, 0b000]),
+    ('(', [0b001, 0b010, 0b010, 0b010, 0b001]),
+    (')', [0b100, 0b010, 0b010, 0b010, 0b100]),
+    ('*', [0b000, 0b101, 0b010, 0b101, 0b000]),
+    ('+', [0b000, 0b010, 0b111, 0b010, 0b000]),
+    (',', [0b000, 0b000, 0b000, 0b010, 0b100]),
+    ('-', [0b000, 0b000, 0b111, 0b000, 0b000]),
+    ('.', [0b000, 0b000, 0b000, 0b000, 0b010]),
+    ('/', [0b001, 0b001, 0b010, 0b100, 0b100]),
+    ('0', [0b111, 0b101, 0b101, 0b101, 0b111]),
+    ('1', [0b010, 0b110, 0b010, 0b010, 0b111]),
+    ('2', [0b111, 0b001, 0b111, 0b100, 0b111]),
+    ('3', [0b111, 0b001, 0b111, 0b001, 0b111]),
+    ('4', [0b101, 0b101, 0b111, 0b001, 0b001]),
+    ('5', [0b111, 0b100, 0b111, 0b001, 0b111]),
+    ('6', [0b111, 0b100, 0b111, 0b101, 0b111]),
+    ('7', [0b111, 0b001, 0b001, 0b001, 0b001]),
+    ('8', [0b111, 0b101, 0b111, 0b101, 0b111]),
+    ('9', [0b111, 0b101, 0b111, 0b001, 0b111]),
+    (':', [0b000, 0b010, 0b000, 0b010, 0b000]),
+    (';', [0b000, 0b010, 0b000, 0b010, 0b100]),
+    ('<', [0b001, 0b010, 0b100, 0b010, 0b001]),
+    ('=', [0b000, 0b111, 0b000, 0b111, 0b000]),
+    ('>', [0b100, 0b010, 0b001, 0b010, 0b100]),
+    ('?', [0b111, 0b001, 0b010, 0b000, 0b010]),
+    ('@', [0b010, 0b101, 0b111, 0b100, 0b011]),
+    ('A', [0b010, 0b101, 0b111, 0b101, 0b101]),
+    ('B', [0b110, 0b101, 0b110, 0b101, 0b110]),
+    ('C', [0b011, 0b100, 0b100, 0b100, 0b011]),
+    ('D', [0b110, 0b101, 0b101, 0b101, 0b110]),
+    ('E', [0b111, 0b100, 0b110, 0b100, 0b111]),
+    ('F', [0b111, 0b100, 0b110, 0b100, 0b100]),
+    ('G', [0b011, 0b100, 0b101, 0b101, 0b011]),
+    ('H', [0b101, 0b101, 0b111, 0b101, 0b101]),
+    ('I', [0b111, 0b010, 0b010, 0b010, 0b111]),
+    ('J', [0b001, 0b001, 0b001, 0b101, 0b010]),
+    ('K', [0b101, 0b101, 0b110, 0b101, 0b101]),
+    ('L', [0b100, 0b100, 0b100, 0b100, 0b111]),
+    ('M', [0b101, 0b111, 0b111, 0b101, 0b101]),
+    ('N', [0b110, 0b101, 0b101, 0b101, 0b101]),
+    ('O', [0b010, 0b101, 0b101, 0b101, 0b010]),
+    ('P', [0b110, 0b101, 0b110, 0b100, 0b100]),
+    ('Q', [0b010, 0b101, 0b101, 0b110, 0b011]),
+    ('R', [0b110, 0b101, 0b110, 0b101, 0b101]),
+    ('S', [0b011, 0b100, 0b010, 0b001, 0b110]),
+    ('T', [0b111, 0b010, 0b010, 0b010, 0b010]),
+    ('U', [0b101, 0b101, 0b101, 0b101, 0b111]),
+    ('V', [0b101, 0b101, 0b101, 0b101, 0b010]),
+    ('W', [0b101, 0b101, 0b111, 0b111, 0b101]),
+    ('X', [0b101, 0b101, 0b010, 0b101, 0b101]),
+    ('Y', [0b101, 0b101, 0b010, 0b010, 0b010]),
+    ('Z', [0b111, 0b001, 0b010, 0b100, 0b111]),
+    ('[', [0b011, 0b010, 0b010, 0b010, 0b011]),
+    ('\\', [0b100, 0b100, 0b010, 0b001, 0b001]),
+    (']', [0b110, 0b010, 0b010, 0b010, 0b110]),
+    ('^', [0b010, 0b101, 0b000, 0b000, 0b000]),
+    ('_', [0b000, 0b000, 0b000, 0b000, 0b111]),
+    ('`', [0b100, 0b010, 0b000, 0b000, 0b000]),
+    ('{', [0b011, 0b010, 0b110, 0b010, 0b011]),
+    ('|', [0b010, 0b010, 0b010, 0b010, 0b010]),
+    ('}', [0b110, 0b010, 0b011, 0b010, 0b110]),
+    ('~', [0b000, 0b011, 0b110, 0b000, 0b000]),
+];
+
+/// The letter an accented Latin letter is drawn as
+fn base_letter(ch: char) -> char {
+    match ch {
+        'à' | 'á' | 'â' | 'ã' | 'ä' | 'À' | 'Á' | 'Â' | 'Ã' | 'Ä' => 'A',
+        'è' | 'é' | 'ê' | 'ë' | 'È' | 'É' | 'Ê' | 'Ë' => 'E',
+        'ì' | 'í' | 'î' | 'ï' | 'Ì' | 'Í' | 'Î' | 'Ï' => 'I',
+        'ò' | 'ó' | 'ô' | 'õ' | 'ö' | 'Ò' | 'Ó' | 'Ô' | 'Õ' | 'Ö' => 'O',
+        'ù' | 'ú' | 'û' | 'ü' | 'Ù' | 'Ú' | 'Û' | 'Ü' => 'U',
+        'ç' | 'Ç' => 'C',
+        'ñ' | 'Ñ' => 'N',
+        _ => ch.to_ascii_uppercase(),
+    }
+}
+
+fn glyph(ch: char) -> [u8; 5] {
+    let ch = base_letter(ch);
+    FONT.iter()
+        .find(|&&(c, _)| c == ch)
+        .or_else(|| FONT.iter().find(|&&(c, _)| c == '?'))
+        .map(|&(_, dots)| dots)
+        .unwrap_or_default()
+}
+
+/// The three terminal rows that draw `text` (each char with its color) in
+/// block letters, 4 columns per char
+pub fn block_rows(text: &[(char, Color)]) -> [Vec<(char, Color)>; 3] {
+    let mut rows: [Vec<(char, Color)>; 3] = Default::default();
+    for &(ch, color) in text {
+        let dots = glyph(ch);
+        for (row, cells) in rows.iter_mut().enumerate() {
+            // Each cell shows two dot lines: the top half and the bottom half
+            let top = dots[row * 2];
+            let bottom = dots.get(row * 2 + 1).copied().unwrap_or(0);
+            for bit in [0b100, 0b010, 0b001] {
+                let cell = match (top & bit != 0, bottom & bit != 0) {
+                    (true, true) => '█',
+                    (true, false) => '▀',
+                    (false, true) => '▄',
+                    (false, false) => ' ',
+                };
+                cells.push((cell, color));
+            }
+            cells.push((' ', color));
+        }
+    }
+    rows
+}
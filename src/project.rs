@@ -0,0 +1,59 @@
+use crate::jobs::JobContext;
+use crate::vfs::Filesystem;
+use std::path::Path;
+
+/// Files bigger than this are skipped (generated or data files)
+const MAX_FILE_BYTES: u64 = 1024 * 1024;
+
+/// Walk `dir` like the sidebar does, skipping hidden folders, build output
+/// and binary files, and hand each text file with its contents to `visit`
+pub fn walk_text_files(
+    fs: &dyn Filesystem,
+    dir: &Path,
+    context: &JobContext,
+    visit: &mut dyn FnMut(&Path, &str),
+) {
+    walk_files(fs, dir, context, &mut |path| {
+        if fs.size(path).map_or(true, |size| size > MAX_FILE_BYTES) {
+            return;
+        }
+        if let Ok(bytes) = fs.read(path) {
+            if !bytes.contains(&0) {
+                visit(path, &String::from_utf8_lossy(&bytes));
+            }
+        }
+    });
+}
+
+/// Walk `dir` like the sidebar does, skipping hidden folders and build
+/// output, and hand each file's path to `visit`
+pub fn walk_files(
+    fs: &dyn Filesystem,
+    dir: &Path,
+    context: &JobContext,
+    visit: &mut dyn FnMut(&Path),
+) {
+    let entries = match fs.read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+    for entry in entries {
+        if context.is_cancelled() {
+            return;
+        }
+        if entry.name.starts_with('.') || entry.name == "target" || entry.name == "node_modules" {
+            continue;
+        }
+        let path = dir.join(&entry.name);
+        // Linked folders may lead back up (and around forever), or list
+        // files that are already walked elsewhere
+        if entry.is_dir && entry.is_symlink {
+            continue;
+        }
+        if entry.is_dir {
+            walk_files(fs, &path, context, visit);
+            continue;
+        }
+        visit(&path);
+    }
+}
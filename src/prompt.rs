@@ -0,0 +1,375 @@
+use crate::overlay::{Overlay, Placement};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+enum PromptKind {
+    /// Free text typed into the input field
+    Input,
+    /// One of a few keys, shown as a dialog with `lines`
+    Confirm(Vec<String>, Vec<char>),
+    /// Entries filtered by the input field; `matches` indexes `items`
+    List {
+        items: Vec<String>,
+        matches: Vec<usize>,
+        selected: usize,
+    },
+}
+
+/// What a key did to an open prompt
+pub enum PromptEvent {
+    /// Still waiting for input
+    Pending,
+    Cancelled,
+    Text(String),
+    Choice(char),
+    /// Index into the list's items
+    Picked(usize),
+}
+
+/// A question waiting for the user. It doesn't read events itself: the
+/// event loop feeds it keys and draws it as an overlay, so the rest of the
+/// editor keeps rendering while it is open.
+pub struct Prompt {
+    title: String,
+    kind: PromptKind,
+    input: String,
+    placement: Option<Placement>,
+    warning: bool,
+    // List entries match the input as a subsequence rather than a substring
+    fuzzy: bool,
+    // Input shown as dots (passphrases)
+    secret: bool,
+    // Earlier inputs, recalled with Up and Down; where in them the input
+    // is, and what was typed before going up
+    history: Vec<String>,
+    history_pos: Option<usize>,
+    draft: String,
+    // Lines Tab offers for the input, and the one it shows
+    complete: Option<fn(&str) -> Vec<String>>,
+    completions: Option<(Vec<String>, usize)>,
+}
+
+impl Prompt {
+    pub fn input(title: &str) -> Prompt {
+        Prompt {
+            title: title.to_string(),
+            kind: PromptKind::Input,
+            input: String::new(),
+            placement: None,
+            warning: false,
+            fuzzy: false,
+            secret: false,
+            history: vec![],
+            history_pos: None,
+            draft: String::new(),
+            complete: None,
+            completions: None,
+        }
+    }
+
+    /// Dialog answered with one of `choices` (matched case-insensitively)
+    pub fn confirm(title: &str, lines: Vec<String>, choices: &[char]) -> Prompt {
+        Prompt {
+            kind: PromptKind::Confirm(lines, choices.to_vec()),
+            ..Prompt::input(title)
+        }
+    }
+
+    pub fn list(title: &str, items: Vec<String>) -> Prompt {
+        let mut prompt = Prompt {
+            kind: PromptKind::List {
+                items,
+                matches: vec![],
+                selected: 0,
+            },
+            ..Prompt::input(title)
+        };
+        prompt.filter();
+        prompt
+    }
+
+    /// Add entries to an open list as they keep arriving; the selection
+    /// stays on the entry it was on
+    pub fn extend(&mut self, more: Vec<String>) {
+        let PromptKind::List {
+            items,
+            matches,
+            selected,
+        } = &mut self.kind
+        else {
+            return;
+        };
+        let current = matches.get(*selected).copied();
+        items.extend(more);
+        self.filter();
+        if let PromptKind::List {
+            matches, selected, ..
+        } = &mut self.kind
+        {
+            *selected = current
+                .and_then(|current| matches.iter().position(|&i| i == current))
+                .unwrap_or(0);
+        }
+    }
+
+    pub fn set_title(&mut self, title: &str) {
+        self.title = title.to_string();
+    }
+
+    pub fn at(mut self, placement: Placement) -> Prompt {
+        self.placement = Some(placement);
+        self
+    }
+
+    /// Start with `text` already typed
+    pub fn with_input(mut self, text: &str) -> Prompt {
+        self.input = text.to_string();
+        self.filter();
+        self
+    }
+
+    pub fn warning(mut self) -> Prompt {
+        self.warning = true;
+        self
+    }
+
+    /// Filter the list by fuzzy matching, best matches first
+    pub fn fuzzy(mut self) -> Prompt {
+        self.fuzzy = true;
+        self.filter();
+        self
+    }
+
+    /// Hide what is typed, for passphrases
+    pub fn secret(mut self) -> Prompt {
+        self.secret = true;
+        self
+    }
+
+    /// Inputs Up and Down go back and forth through, oldest first
+    pub fn with_history(mut self, history: Vec<String>) -> Prompt {
+        self.history = history;
+        self
+    }
+
+    /// Complete the input with Tab, cycling through what `complete` offers
+    pub fn with_completion(mut self, complete: fn(&str) -> Vec<String>) -> Prompt {
+        self.complete = Some(complete);
+        self
+    }
+
+    /// Text typed so far
+    pub fn text(&self) -> &str {
+        &self.input
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) -> PromptEvent {
+        if key.code == KeyCode::Esc {
+            return PromptEvent::Cancelled;
+        }
+
+        match &mut self.kind {
+            PromptKind::Confirm(_, choices) => match key.code {
+                KeyCode::Char(c) if choices.contains(&c.to_ascii_lowercase()) => {
+                    PromptEvent::Choice(c.to_ascii_lowercase())
+                }
+                _ => PromptEvent::Pending,
+            },
+            PromptKind::List {
+                matches, selected, ..
+            } => match key.code {
+                KeyCode::Enter => match matches.get(*selected) {
+                    Some(&index) => PromptEvent::Picked(index),
+                    None => PromptEvent::Pending,
+                },
+                KeyCode::Up => {
+                    *selected = selected.saturating_sub(1);
+                    PromptEvent::Pending
+                }
+                KeyCode::Down | KeyCode::Tab => {
+                    *selected = (*selected + 1).min(matches.len().saturating_sub(1));
+                    PromptEvent::Pending
+                }
+                _ => {
+                    if self.edit_input(key) {
+                        self.filter();
+                    }
+                    PromptEvent::Pending
+                }
+            },
+            PromptKind::Input => {
+                if key.code != KeyCode::Tab {
+                    self.completions = None;
+                }
+                match key.code {
+                    KeyCode::Enter => return PromptEvent::Text(self.input.clone()),
+                    KeyCode::Up | KeyCode::Down => self.recall(key.code == KeyCode::Up),
+                    KeyCode::Tab => self.next_completion(),
+                    _ => {
+                        self.edit_input(key);
+                    }
+                }
+                PromptEvent::Pending
+            }
+        }
+    }
+
+    /// Show the entry before (`back`) or after the one in the input; past
+    /// the newest, what was being typed
+    fn recall(&mut self, back: bool) {
+        let pos = match (self.history_pos, back) {
+            (None, true) if !self.history.is_empty() => {
+                self.draft = self.input.clone();
+                Some(self.history.len() - 1)
+            }
+            (Some(pos), true) => Some(pos.saturating_sub(1)),
+            (Some(pos), false) if pos + 1 < self.history.len() => Some(pos + 1),
+            (Some(_), false) => None,
+            (None, _) => return,
+        };
+        self.history_pos = pos;
+        self.input = match pos {
+            Some(pos) => self.history[pos].clone(),
+            None => std::mem::take(&mut self.draft),
+        };
+    }
+
+    /// Replace the input with the next completion of what was typed
+    fn next_completion(&mut self) {
+        let Some(complete) = self.complete else {
+            return;
+        };
+        let (options, index) = match self.completions.take() {
+            Some((options, index)) => {
+                let next = (index + 1) % options.len();
+                (options, next)
+            }
+            None => (complete(&self.input), 0),
+        };
+        if let Some(option) = options.get(index) {
+            self.input = option.clone();
+            self.completions = Some((options, index));
+        }
+    }
+
+    /// Pasted text goes into the input field, joined into one line
+    pub fn paste(&mut self, text: &str) {
+        if matches!(self.kind, PromptKind::Confirm(..)) {
+            return;
+        }
+        self.input.extend(text.chars().filter(|c| !c.is_control()));
+        self.filter();
+    }
+
+    /// Apply a typing key to the input field. Returns true if it changed.
+    fn edit_input(&mut self, key: KeyEvent) -> bool {
+        match key.code {
+            KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
+                self.input.push(c);
+                true
+            }
+            KeyCode::Backspace => self.input.pop().is_some(),
+            _ => false,
+        }
+    }
+
+    /// Recompute list matches: entries starting with the input come first,
+    /// then the ones containing it anywhere. Fuzzy lists sort by score.
+    fn filter(&mut self) {
+        let query = self.input.trim().to_lowercase();
+        let fuzzy = self.fuzzy;
+        if let PromptKind::List {
+            items,
+            matches,
+            selected,
+        } = &mut self.kind
+        {
+            let lowered: Vec<String> = items.iter().map(|item| item.to_lowercase()).collect();
+            if fuzzy {
+                let mut scored: Vec<(usize, usize)> = (0..items.len())
+                    .filter_map(|i| fuzzy_score(&query, &lowered[i]).map(|score| (i, score)))
+                    .collect();
+                // Stable, so equal scores keep the list's order
+                scored.sort_by_key(|&(_, score)| std::cmp::Reverse(score));
+                *matches = scored.into_iter().map(|(i, _)| i).collect();
+            } else {
+                let (mut prefix, other): (Vec<usize>, Vec<usize>) = (0..items.len())
+                    .filter(|&i| lowered[i].contains(&query))
+                    .partition(|&i| lowered[i].starts_with(&query));
+                prefix.extend(other);
+                *matches = prefix;
+            }
+            *selected = 0;
+        }
+    }
+
+    pub fn overlay(&self) -> Overlay {
+        let overlay = match &self.kind {
+            PromptKind::Input if self.secret => {
+                Overlay::input(&self.title, &"•".repeat(self.input.chars().count()))
+            }
+            // Which of several completions the input shows
+            PromptKind::Input => match &self.completions {
+                Some((options, index)) if options.len() > 1 => Overlay::input(
+                    &format!("{} ({}/{})", self.title, index + 1, options.len()),
+                    &self.input,
+                ),
+                _ => Overlay::input(&self.title, &self.input),
+            },
+            PromptKind::Confirm(lines, _) => Overlay::dialog(&self.title, lines.clone()),
+            PromptKind::List {
+                items,
+                matches,
+                selected,
+            } => Overlay::list(
+                &self.title,
+                &self.input,
+                matches.iter().map(|&i| items[i].clone()).collect(),
+                *selected,
+            ),
+        };
+        let overlay = match self.placement {
+            Some(placement) => overlay.at(placement),
+            None => overlay,
+        };
+        if self.warning {
+            overlay.warning()
+        } else {
+            overlay
+        }
+    }
+}
+
+/// How well `query` matches `text` when its characters appear in order,
+/// not necessarily together: `None` if they don't. Runs of consecutive
+/// characters and matches at the start of a word or path component score
+/// higher. Both are expected in lowercase.
+pub fn fuzzy_score(query: &str, text: &str) -> Option<usize> {
+    fuzzy_match(query, text).map(|(score, _)| score)
+}
+
+/// `fuzzy_score` and the char indices of `text` the query matched, for
+/// highlighting them
+pub fn fuzzy_match(query: &str, text: &str) -> Option<(usize, Vec<usize>)> {
+    let mut score = 0;
+    let mut matched = vec![];
+    let mut previous: Option<char> = None;
+    let mut wanted = query.chars().filter(|c| !c.is_whitespace()).peekable();
+    for (i, c) in text.chars().enumerate() {
+        let Some(&next) = wanted.peek() else {
+            break;
+        };
+        if c == next {
+            score += 1;
+            if matched.last().is_some_and(|&last| last + 1 == i) {
+                score += 4;
+            }
+            if previous.is_none_or(|p| matches!(p, '/' | '\\' | '_' | '-' | '.' | ' ')) {
+                score += 3;
+            }
+            matched.push(i);
+            wanted.next();
+        }
+        previous = Some(c);
+    }
+    wanted.peek().is_none().then_some((score, matched))
+}
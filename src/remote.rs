@@ -0,0 +1,177 @@
+use crate::config;
+use crate::log;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// A file on another machine, named `[usuário@]host:/caminho` like scp does.
+/// It is copied to a temporary file to be read and copied back on save.
+pub struct RemotePath {
+    pub host: String,
+    pub path: String,
+}
+
+impl RemotePath {
+    /// `None` for local paths, including existing files with a colon in the name
+    pub fn parse(name: &str) -> Option<RemotePath> {
+        let (host, path) = name.split_once(':')?;
+        // "C:\..." and "./a:b" are local paths; a host starting with "-"
+        // would be read by ssh as an option
+        if host.len() < 2
+            || host.starts_with('-')
+            || host.contains('/')
+            || host.contains('\\')
+            || path.is_empty()
+            || std::path::Path::new(name).exists()
+        {
+            return None;
+        }
+        Some(RemotePath {
+            host: host.to_string(),
+            path: path.to_string(),
+        })
+    }
+}
+
+pub fn is_remote(name: &str) -> bool {
+    RemotePath::parse(name).is_some()
+}
+
+pub fn read(remote: &RemotePath) -> io::Result<Vec<u8>> {
+    let (local, _) = temp_file()?;
+    let result = transfer(
+        remote,
+        &format!("get {} {}", quote(&remote.path), quote(&local)),
+        &[format!("{}:{}", remote.host, remote.path), local.clone()],
+    )
+    .and_then(|_| fs::read(&local));
+    let _ = fs::remove_file(&local);
+    result
+}
+
+pub fn write(remote: &RemotePath, bytes: &[u8]) -> io::Result<()> {
+    let (local, mut file) = temp_file()?;
+    let result = file
+        .write_all(bytes)
+        .and_then(|_| file.sync_all())
+        .and_then(|_| {
+            transfer(
+                remote,
+                &format!("put {} {}", quote(&local), quote(&remote.path)),
+                &[local.clone(), format!("{}:{}", remote.host, remote.path)],
+            )
+        });
+    let _ = fs::remove_file(&local);
+    result
+}
+
+/// Run one sftp batch `command`, falling back to scp with `scp_args` when
+/// sftp is missing or fails. BatchMode keeps ssh from asking for a password
+/// on the raw-mode terminal; keys or an agent are needed.
+fn transfer(remote: &RemotePath, command: &str, scp_args: &[String]) -> io::Result<()> {
+    let sftp = Command::new("sftp")
+        .args(["-q", "-o", "BatchMode=yes", "-b", "-", "--", &remote.host])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .and_then(|mut child| {
+            if let Some(mut stdin) = child.stdin.take() {
+                writeln!(stdin, "{}", command)?;
+            }
+            child.wait_with_output()
+        });
+    match sftp {
+        Ok(output) if output.status.success() => return Ok(()),
+        Ok(output) => log::warning!(
+            "remoto",
+            "sftp falhou em {}: {}",
+            remote.host,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ),
+        Err(e) => log::warning!("remoto", "sftp indisponível: {}", e),
+    }
+
+    let output = Command::new("scp")
+        .args(["-q", "-o", "BatchMode=yes", "--"])
+        .args(scp_args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .output()?;
+    if output.status.success() {
+        return Ok(());
+    }
+    // The first line has the cause ("Could not resolve hostname", "Permission
+    // denied"); later ones just say the connection closed
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let reason = stderr
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+        .unwrap_or("falha na conexão");
+    Err(io::Error::other(reason.to_string()))
+}
+
+/// A new empty file for the text of a remote file on its way, readable
+/// only by this user and in a folder only this user can enter, so no one
+/// else can read the text or plant a link where it is written
+fn temp_file() -> io::Result<(String, File)> {
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    let dir = config::state_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join("remoto");
+    private_dir(&dir)?;
+    let path = dir.join(format!(
+        "{}-{}",
+        std::process::id(),
+        NEXT.fetch_add(1, Ordering::Relaxed)
+    ));
+    // Left over by an earlier run with the same pid
+    let _ = fs::remove_file(&path);
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let file = options.open(&path)?;
+    Ok((path.to_string_lossy().to_string(), file))
+}
+
+/// Create `dir` with mode 0700, or check that the one there is a folder
+/// of this user that no one else can enter
+#[cfg(unix)]
+fn private_dir(dir: &Path) -> io::Result<()> {
+    use std::os::unix::fs::{DirBuilderExt, MetadataExt};
+    if let Some(parent) = dir.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    match fs::DirBuilder::new().mode(0o700).create(dir) {
+        Err(e) if e.kind() != io::ErrorKind::AlreadyExists => return Err(e),
+        _ => {}
+    }
+    let meta = fs::symlink_metadata(dir)?;
+    // SAFETY: geteuid has no preconditions
+    let uid = unsafe { libc::geteuid() };
+    if !meta.is_dir() || meta.uid() != uid || meta.mode() & 0o077 != 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!("'{}' não é uma pasta particular", dir.display()),
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn private_dir(dir: &Path) -> io::Result<()> {
+    fs::create_dir_all(dir)
+}
+
+/// Quote a path for an sftp batch line
+fn quote(path: &str) -> String {
+    format!("\"{}\"", path.replace('\\', "\\\\").replace('"', "\\\""))
+}
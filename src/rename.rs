@@ -0,0 +1,189 @@
+use crate::vfs::{DirEntry, Filesystem, LocalFs};
+use std::fs;
+use std::io::{self, Cursor, Read};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// The entries of a folder as an editable list, one name per line (folders
+/// end in '/'). Writing the list renames every entry whose line changed.
+#[derive(Debug)]
+pub struct RenameFs {
+    dir: PathBuf,
+    names: Mutex<Vec<String>>,
+    // Renames done by the last write, for the editor to follow
+    applied: Mutex<Vec<(PathBuf, PathBuf)>>,
+}
+
+impl RenameFs {
+    pub fn new(dir: &Path) -> io::Result<RenameFs> {
+        let mut entries = LocalFs.read_dir(dir)?;
+        entries.sort_by(|a, b| {
+            b.is_dir
+                .cmp(&a.is_dir)
+                .then(a.name.to_lowercase().cmp(&b.name.to_lowercase()))
+        });
+        let names = entries
+            .into_iter()
+            .map(|entry| {
+                if entry.is_dir {
+                    format!("{}/", entry.name)
+                } else {
+                    entry.name
+                }
+            })
+            .collect();
+        Ok(RenameFs {
+            dir: dir.to_path_buf(),
+            names: Mutex::new(names),
+            applied: Mutex::new(vec![]),
+        })
+    }
+
+    /// Name of the buffer editing `dir`'s entries
+    pub fn buffer_name(dir: &Path) -> String {
+        format!("{} [renomear]", dir.display())
+    }
+
+    /// (old path, new path) of each entry the last save renamed
+    pub fn take_applied(&self) -> Vec<(PathBuf, PathBuf)> {
+        std::mem::take(&mut *self.applied.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+
+    /// Check the edited list against the current one and return the
+    /// (old, new) names that changed
+    fn plan(&self, old: &[String], text: &str) -> io::Result<Vec<(String, String)>> {
+        let new: Vec<&str> = text.lines().collect();
+        if new.len() != old.len() {
+            return Err(io::Error::other(format!(
+                "a lista tem {} linha(s), eram {}: renomeie sem apagar ou juntar linhas",
+                new.len(),
+                old.len()
+            )));
+        }
+        let mut changes = vec![];
+        for (old, new) in old.iter().zip(new) {
+            let old_name = old.trim_end_matches('/');
+            let new_name = new.trim().trim_end_matches('/');
+            if new_name.is_empty() || new_name == "." || new_name == ".." {
+                return Err(io::Error::other(format!(
+                    "nome inválido para '{}'",
+                    old_name
+                )));
+            }
+            if new_name.contains('/') || new_name.contains('\0') {
+                return Err(io::Error::other(format!(
+                    "'{}': mover para outra pasta não é suportado",
+                    new_name
+                )));
+            }
+            if new_name != old_name {
+                changes.push((old_name.to_string(), new_name.to_string()));
+            }
+        }
+
+        // Every final name must be unique, and must not take the place of
+        // an entry that stays
+        let finals: Vec<&str> = old
+            .iter()
+            .map(|name| {
+                let name = name.trim_end_matches('/');
+                changes
+                    .iter()
+                    .find(|(from, _)| from == name)
+                    .map(|(_, to)| to.as_str())
+                    .unwrap_or(name)
+            })
+            .collect();
+        for (i, name) in finals.iter().enumerate() {
+            if finals[..i].contains(name) {
+                return Err(io::Error::other(format!("'{}' aparece duas vezes", name)));
+            }
+        }
+        for (_, to) in &changes {
+            let listed = old.iter().any(|name| name.trim_end_matches('/') == to);
+            if !listed && fs::symlink_metadata(self.dir.join(to)).is_ok() {
+                return Err(io::Error::other(format!("'{}' já existe", to)));
+            }
+        }
+        Ok(changes)
+    }
+
+    /// Rename through temporary names, so swaps and cycles work; on failure
+    /// every step done so far is undone
+    fn apply(&self, changes: &[(String, String)]) -> io::Result<()> {
+        let temporary = |i: usize| {
+            self.dir
+                .join(format!(".reditor-renomear-{}-{}", std::process::id(), i))
+        };
+        let mut done: Vec<(PathBuf, PathBuf)> = vec![];
+        let mut result = Ok(());
+        let steps = changes
+            .iter()
+            .enumerate()
+            .map(|(i, (from, _))| (self.dir.join(from), temporary(i)))
+            .chain(
+                changes
+                    .iter()
+                    .enumerate()
+                    .map(|(i, (_, to))| (temporary(i), self.dir.join(to))),
+            );
+        for (from, to) in steps {
+            if let Err(e) = fs::rename(&from, &to) {
+                result = Err(io::Error::other(format!(
+                    "erro ao renomear '{}': {} (nada foi renomeado)",
+                    from.file_name().unwrap_or_default().to_string_lossy(),
+                    e
+                )));
+                break;
+            }
+            done.push((from, to));
+        }
+        if result.is_err() {
+            for (from, to) in done.iter().rev() {
+                let _ = fs::rename(to, from);
+            }
+        }
+        result
+    }
+}
+
+impl Filesystem for RenameFs {
+    fn open(&self, _path: &Path) -> io::Result<Box<dyn Read + Send>> {
+        let names = self.names.lock().unwrap_or_else(|e| e.into_inner());
+        Ok(Box::new(Cursor::new(names.join("\n").into_bytes())))
+    }
+
+    fn write(&self, _path: &Path, bytes: &[u8]) -> io::Result<()> {
+        let text = String::from_utf8_lossy(bytes);
+        let mut names = self.names.lock().unwrap_or_else(|e| e.into_inner());
+        let changes = self.plan(&names, &text)?;
+        self.apply(&changes)?;
+
+        for name in names.iter_mut() {
+            let is_dir = name.ends_with('/');
+            if let Some((_, to)) = changes
+                .iter()
+                .find(|(from, _)| from == name.trim_end_matches('/'))
+            {
+                *name = if is_dir {
+                    format!("{}/", to)
+                } else {
+                    to.clone()
+                };
+            }
+        }
+        *self.applied.lock().unwrap_or_else(|e| e.into_inner()) = changes
+            .into_iter()
+            .map(|(from, to)| (self.dir.join(from), self.dir.join(to)))
+            .collect();
+        Ok(())
+    }
+
+    fn size(&self, path: &Path) -> io::Result<u64> {
+        Ok(self.read(path)?.len() as u64)
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<DirEntry>> {
+        LocalFs.read_dir(path)
+    }
+}
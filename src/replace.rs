@@ -0,0 +1,487 @@
+use crate::jobs::{JobContext, JobId, JobMessage, Jobs};
+use crate::log;
+use crate::project;
+use crate::vfs::{self, DirEntry, Filesystem, LocalFs};
+use std::io::{self, BufRead, BufReader, Cursor, Read};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+/// Occurrences a project search lists at most
+pub const MAX_RESULTS: usize = 10_000;
+
+/// How often a project search sends what it found so far
+const BATCH_INTERVAL: Duration = Duration::from_millis(100);
+
+/// What searches the project's files
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Backend {
+    /// `rg`: skips what `.gitignore` and `.ignore` list, hidden and binary
+    /// files by itself
+    Ripgrep,
+    /// `grep -r`, told to skip the folders the sidebar hides and binary
+    /// files
+    Grep,
+    /// Reading the files here, when neither is installed
+    Walk,
+}
+
+/// The fastest backend installed, looked up once
+fn backend() -> Backend {
+    static BACKEND: OnceLock<Backend> = OnceLock::new();
+    *BACKEND.get_or_init(|| {
+        let runs = |program: &str| {
+            Command::new(program)
+                .arg("--version")
+                .stdin(Stdio::null())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .status()
+                .is_ok_and(|status| status.success())
+        };
+        let backend = if runs("rg") {
+            Backend::Ripgrep
+        } else if runs("grep") {
+            Backend::Grep
+        } else {
+            Backend::Walk
+        };
+        log::info!("busca", "busca no projeto por {:?}", backend);
+        backend
+    })
+}
+
+/// An occurrence of the searched text. Line and column are 0-based, in chars.
+#[derive(Clone, Debug)]
+pub struct Match {
+    pub path: String,
+    pub line: usize,
+    pub col: usize,
+    /// The whole line, for the preview
+    pub text: String,
+}
+
+/// Every occurrence of `query` (case-sensitive) in `lines` of the file at
+/// `path`, left to right and without overlaps
+pub fn find_in_lines<'a>(
+    path: &str,
+    lines: impl Iterator<Item = &'a str>,
+    query: &str,
+) -> Vec<Match> {
+    let mut found = vec![];
+    if query.is_empty() {
+        return found;
+    }
+    for (line, text) in lines.enumerate() {
+        for (byte, _) in text.match_indices(query) {
+            found.push(Match {
+                path: path.to_string(),
+                line,
+                col: text[..byte].chars().count(),
+                text: text.trim_end_matches('\r').to_string(),
+            });
+        }
+    }
+    found
+}
+
+/// Search the project under `root` on a worker thread. The result arrives
+/// as one `JobMessage::Matches`, sorted.
+pub fn start(jobs: &mut Jobs, root: &Path, query: &str) -> JobId {
+    let root = root.to_path_buf();
+    let query = query.to_string();
+    jobs.spawn(format!("Buscando '{}'", query), move |context| {
+        let mut found = vec![];
+        search(&root, &query, context, &mut |file| {
+            found.extend(file);
+            true
+        });
+        sort(&mut found);
+        context.send(JobMessage::Matches(found));
+    })
+}
+
+/// Search the project under `root` on a worker thread, sending what was
+/// found every so often as `JobMessage::Matches`, a file's occurrences
+/// together, in the order the files are searched. Stops at `MAX_RESULTS`.
+pub fn start_streaming(jobs: &mut Jobs, root: &Path, query: &str) -> JobId {
+    let root = root.to_path_buf();
+    let query = query.to_string();
+    jobs.spawn(format!("Buscando '{}'", query), move |context| {
+        let mut batch = vec![];
+        let mut sent = Instant::now();
+        let mut total = 0;
+        search(&root, &query, context, &mut |mut file| {
+            file.truncate(MAX_RESULTS - total);
+            total += file.len();
+            batch.extend(file);
+            if sent.elapsed() >= BATCH_INTERVAL {
+                sent = Instant::now();
+                if !context.send(JobMessage::Matches(std::mem::take(&mut batch))) {
+                    return false;
+                }
+            }
+            total < MAX_RESULTS
+        });
+        if !batch.is_empty() {
+            context.send(JobMessage::Matches(batch));
+        }
+    })
+}
+
+/// Every occurrence of `query` in the files under `root`, handed to
+/// `found` a file at a time until it returns false. Paths are joined to
+/// `root`; hidden entries, build output, binary and big files are skipped.
+fn search(
+    root: &Path,
+    query: &str,
+    context: &JobContext,
+    found: &mut dyn FnMut(Vec<Match>) -> bool,
+) {
+    if query.is_empty() {
+        return;
+    }
+    let backend = backend();
+    if backend != Backend::Walk {
+        match search_with(backend, root, query, context, found) {
+            Ok(()) => return,
+            Err(e) => log::warning!("busca", "{:?} falhou, buscando sem ele: {}", backend, e),
+        }
+    }
+    let mut stopped = false;
+    project::walk_text_files(vfs::local().as_ref(), root, context, &mut |path, text| {
+        if stopped {
+            return;
+        }
+        let file = find_in_lines(&path.to_string_lossy(), text.lines(), query);
+        if !file.is_empty() {
+            stopped = !found(file);
+        }
+    });
+}
+
+/// `search` through `rg` or `grep`, reading their output as it comes
+fn search_with(
+    backend: Backend,
+    root: &Path,
+    query: &str,
+    context: &JobContext,
+    found: &mut dyn FnMut(Vec<Match>) -> bool,
+) -> io::Result<()> {
+    let mut command = match backend {
+        Backend::Ripgrep => {
+            let mut command = Command::new("rg");
+            command.args([
+                "--fixed-strings",
+                "--line-number",
+                "--no-heading",
+                "--with-filename",
+                "--null",
+                "--color=never",
+                "--no-messages",
+                "--max-filesize=1M",
+                "-e",
+            ]);
+            command.arg(query).arg("--").arg(root);
+            command
+        }
+        _ => {
+            // From inside `root`, over what it holds: `--exclude-dir=.*`
+            // also skips the folders named on the command line, so a root
+            // under a hidden folder would be skipped whole
+            let mut entries: Vec<_> = std::fs::read_dir(root)?
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.file_name())
+                .filter(|name| {
+                    let name = name.to_string_lossy();
+                    !(name.starts_with('.') || name == "target" || name == "node_modules")
+                })
+                .collect();
+            if entries.is_empty() {
+                return Ok(());
+            }
+            entries.sort();
+            let mut command = Command::new("grep");
+            command.args([
+                "-r",
+                "-n",
+                "-I",
+                "-F",
+                "-Z",
+                "--exclude=.*",
+                "--exclude-dir=.*",
+                "--exclude-dir=target",
+                "--exclude-dir=node_modules",
+                "-e",
+            ]);
+            command.arg(query).arg("--").args(entries).current_dir(root);
+            command
+        }
+    };
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()?;
+    let Some(stdout) = child.stdout.take() else {
+        return Err(io::Error::other("sem saída"));
+    };
+    let mut reader = BufReader::new(stdout);
+    let mut line = vec![];
+    let mut file: Vec<Match> = vec![];
+    let mut read_any = false;
+    let mut stopped = false;
+    // `path\0line:text`, a line per matching line, a file's lines together
+    while reader.read_until(b'\n', &mut line)? > 0 {
+        if context.is_cancelled() {
+            stopped = true;
+            break;
+        }
+        read_any = true;
+        let text = String::from_utf8_lossy(&line);
+        let text = text.strip_suffix('\n').unwrap_or(&text);
+        if let Some((path, rest)) = text.split_once('\0') {
+            // grep names files from inside `root`
+            let path = match backend {
+                Backend::Ripgrep => path.to_string(),
+                _ => root.join(path).to_string_lossy().to_string(),
+            };
+            if let Some((number, content)) = rest.split_once(':') {
+                if file.first().is_some_and(|m| m.path != path) && !found(std::mem::take(&mut file))
+                {
+                    stopped = true;
+                    break;
+                }
+                let number = number.parse::<usize>().unwrap_or(1).saturating_sub(1);
+                file.extend(
+                    find_in_lines(&path, std::iter::once(content), query)
+                        .into_iter()
+                        .map(|m| Match { line: number, ..m }),
+                );
+            }
+        }
+        line.clear();
+    }
+    if stopped {
+        let _ = child.kill();
+    } else if !file.is_empty() {
+        found(file);
+    }
+    let status = child.wait()?;
+    // Both exit with 1 when nothing matched and 2 on errors, which an
+    // unreadable file alone causes: only failing without finding anything
+    // falls back
+    if !stopped && !read_any && status.code() == Some(2) {
+        return Err(io::Error::other(format!("saiu com {}", status)));
+    }
+    Ok(())
+}
+
+/// By file, then position
+pub fn sort(matches: &mut [Match]) {
+    matches.sort_by(|a, b| {
+        a.path
+            .cmp(&b.path)
+            .then(a.line.cmp(&b.line))
+            .then(a.col.cmp(&b.col))
+    });
+}
+
+/// Rows of the project search results: a header per file (relative to
+/// `root`) followed by its occurrences, and the (path, line, column) each
+/// row opens at
+pub fn panel_rows(matches: &[Match], root: &Path) -> (Vec<String>, Vec<(String, usize, usize)>) {
+    let mut rows = vec![];
+    let mut targets = vec![];
+    for (i, m) in matches.iter().enumerate() {
+        if i == 0 || matches[i - 1].path != m.path {
+            let count = matches[i..]
+                .iter()
+                .take_while(|other| other.path == m.path)
+                .count();
+            let shown = Path::new(&m.path)
+                .strip_prefix(root)
+                .map(|relative| relative.to_string_lossy().to_string())
+                .unwrap_or_else(|_| m.path.clone());
+            rows.push(format!("{} ({})", shown, count));
+            targets.push((m.path.clone(), m.line, m.col));
+        }
+        rows.push(format!("  {:>5}  {}", m.line + 1, m.text.trim()));
+        targets.push((m.path.clone(), m.line, m.col));
+    }
+    (rows, targets)
+}
+
+/// Replace `matches` (all in the file whose `lines` are given) with
+/// `replacement`, skipping any whose text is no longer `query`. Returns how
+/// many were replaced.
+pub fn apply_to_lines(
+    lines: &mut [Vec<char>],
+    matches: &[Match],
+    query: &str,
+    replacement: &str,
+) -> usize {
+    let query: Vec<char> = query.chars().collect();
+    let mut sorted: Vec<&Match> = matches.iter().collect();
+    // Right to left, so earlier columns stay valid
+    sorted.sort_by_key(|m| std::cmp::Reverse((m.line, m.col)));
+    let mut count = 0;
+    for m in sorted {
+        let Some(line) = lines.get_mut(m.line) else {
+            continue;
+        };
+        if line.get(m.col..m.col + query.len()) != Some(&query[..]) {
+            continue;
+        }
+        line.splice(m.col..m.col + query.len(), replacement.chars());
+        count += 1;
+    }
+    count
+}
+
+/// The occurrences of a project-wide replace as an editable list, grouped
+/// by file. Lines marked `[ ]` or deleted are skipped; saving the list
+/// hands the rest to the editor to replace.
+#[derive(Debug)]
+pub struct ReplaceFs {
+    root: PathBuf,
+    pub query: String,
+    pub replacement: String,
+    matches: Vec<Match>,
+    // Occurrences kept by the last save, waiting to be applied
+    picked: Mutex<Option<Vec<Match>>>,
+}
+
+impl ReplaceFs {
+    pub fn new(root: &Path, query: &str, replacement: &str, matches: Vec<Match>) -> ReplaceFs {
+        ReplaceFs {
+            root: root.to_path_buf(),
+            query: query.to_string(),
+            replacement: replacement.to_string(),
+            matches,
+            picked: Mutex::new(None),
+        }
+    }
+
+    /// Name of the buffer listing the occurrences
+    pub fn buffer_name(&self) -> String {
+        format!("'{}' → '{}' [substituir]", self.query, self.replacement)
+    }
+
+    /// Occurrences kept by the last save, once
+    pub fn take_picked(&self) -> Option<Vec<Match>> {
+        self.picked.lock().unwrap_or_else(|e| e.into_inner()).take()
+    }
+
+    fn relative(&self, path: &str) -> String {
+        Path::new(path)
+            .strip_prefix(&self.root)
+            .map(|relative| relative.to_string_lossy().to_string())
+            .unwrap_or_else(|_| path.to_string())
+    }
+
+    fn listing(&self) -> String {
+        let files = self
+            .matches
+            .iter()
+            .enumerate()
+            .filter(|(i, m)| *i == 0 || self.matches[i - 1].path != m.path)
+            .count();
+        let mut lines = vec![
+            format!(
+                "# '{}' → '{}': {} ocorrência(s) em {} arquivo(s)",
+                self.query,
+                self.replacement,
+                self.matches.len(),
+                files
+            ),
+            String::from(
+                "# Troque [x] por [ ] ou apague a linha para pular; salve (Ctrl+S) para substituir",
+            ),
+        ];
+        for (i, m) in self.matches.iter().enumerate() {
+            if i == 0 || self.matches[i - 1].path != m.path {
+                let count = self.matches[i..]
+                    .iter()
+                    .take_while(|other| other.path == m.path)
+                    .count();
+                lines.push(format!("{} ({})", self.relative(&m.path), count));
+            }
+            lines.push(format!(
+                "  [x] {}:{}  {}",
+                m.line + 1,
+                m.col + 1,
+                m.text.trim()
+            ));
+        }
+        lines.join("\n")
+    }
+
+    /// The occurrences still marked `[x]` in the edited list
+    fn parse(&self, text: &str) -> io::Result<Vec<Match>> {
+        let mut picked = vec![];
+        let mut file: Option<&str> = None;
+        for (i, line) in text.lines().enumerate() {
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if !line.starts_with(char::is_whitespace) {
+                let name = line.rsplit_once(" (").map_or(line, |(name, _)| name);
+                file = Some(name.trim());
+                continue;
+            }
+            let entry = line.trim_start();
+            let keep = if entry.starts_with("[x]") || entry.starts_with("[X]") {
+                true
+            } else if entry.starts_with("[ ]") {
+                false
+            } else {
+                return Err(io::Error::other(format!(
+                    "linha {}: marque com [x] ou [ ]",
+                    i + 1
+                )));
+            };
+            let position = entry[3..].split_whitespace().next().unwrap_or("");
+            let found = position.split_once(':').and_then(|(line, col)| {
+                let (line, col) = (line.parse::<usize>().ok()?, col.parse::<usize>().ok()?);
+                self.matches.iter().find(|m| {
+                    Some(self.relative(&m.path).as_str()) == file
+                        && m.line + 1 == line
+                        && m.col + 1 == col
+                })
+            });
+            match found {
+                Some(m) if keep => picked.push(m.clone()),
+                Some(_) => {}
+                None => {
+                    return Err(io::Error::other(format!(
+                        "linha {}: ocorrência desconhecida",
+                        i + 1
+                    )))
+                }
+            }
+        }
+        Ok(picked)
+    }
+}
+
+impl Filesystem for ReplaceFs {
+    fn open(&self, _path: &Path) -> io::Result<Box<dyn Read + Send>> {
+        Ok(Box::new(Cursor::new(self.listing().into_bytes())))
+    }
+
+    fn write(&self, _path: &Path, bytes: &[u8]) -> io::Result<()> {
+        let picked = self.parse(&String::from_utf8_lossy(bytes))?;
+        *self.picked.lock().unwrap_or_else(|e| e.into_inner()) = Some(picked);
+        Ok(())
+    }
+
+    fn size(&self, path: &Path) -> io::Result<u64> {
+        Ok(self.read(path)?.len() as u64)
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<DirEntry>> {
+        LocalFs.read_dir(path)
+    }
+}
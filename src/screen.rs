@@ -0,0 +1,337 @@
+use crossterm::style::Color;
+use crossterm::{cursor, queue, style, terminal};
+use std::io;
+use std::io::{BufWriter, Write};
+
+/// Marks the right half of a double-width character
+pub const WIDE_TAIL: char = '\0';
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct Cell {
+    pub ch: char,
+    pub fg: Color,
+    pub bg: Color,
+    pub underline: bool,
+}
+
+impl Cell {
+    const BLANK: Cell = Cell {
+        ch: ' ',
+        fg: Color::Reset,
+        bg: Color::Reset,
+        underline: false,
+    };
+}
+
+/// How the terminal draws a row (DEC line attributes): as usual, or with
+/// every cell twice as wide and, split over two rows, twice as tall. Only
+/// the left half of a double row's cells is visible.
+#[derive(Clone, Copy, PartialEq, Default)]
+pub enum LineSize {
+    #[default]
+    Normal,
+    DoubleTop,
+    DoubleBottom,
+}
+
+impl LineSize {
+    /// DECDHL / DECSWL sequence selecting the size
+    fn escape(self) -> &'static str {
+        match self {
+            LineSize::Normal => "\x1b#5",
+            LineSize::DoubleTop => "\x1b#3",
+            LineSize::DoubleBottom => "\x1b#4",
+        }
+    }
+}
+
+/// Off-screen frame. Everything is drawn into `cells`, then `flush` compares
+/// it with what the terminal shows and only writes the cells that changed.
+#[derive(Default)]
+pub struct Screen {
+    width: u16,
+    height: u16,
+    cells: Vec<Cell>,
+    // Last frame written to the terminal
+    shown: Vec<Cell>,
+    // Size of each row in this frame and as the terminal shows it
+    sizes: Vec<LineSize>,
+    shown_sizes: Vec<LineSize>,
+    cursor: Option<(u16, u16)>,
+    // Repaint every cell on the next flush (after a resize or Ctrl+L)
+    full_repaint: bool,
+}
+
+impl Screen {
+    pub fn new(width: u16, height: u16) -> Screen {
+        let mut screen = Screen::default();
+        screen.resize(width, height);
+        screen
+    }
+
+    pub fn resize(&mut self, width: u16, height: u16) {
+        let len = width as usize * height as usize;
+        self.width = width;
+        self.height = height;
+        self.cells = vec![Cell::BLANK; len];
+        self.shown = vec![Cell::BLANK; len];
+        self.sizes = vec![LineSize::Normal; height as usize];
+        self.shown_sizes = vec![LineSize::Normal; height as usize];
+        self.full_repaint = true;
+    }
+
+    pub fn size(&self) -> (u16, u16) {
+        (self.width, self.height)
+    }
+
+    /// Blank the frame before drawing a new one
+    pub fn clear(&mut self) {
+        self.cells.fill(Cell::BLANK);
+        self.sizes.fill(LineSize::Normal);
+    }
+
+    /// Draw `row` double-sized (or normal again) from the next flush
+    pub fn set_line_size(&mut self, row: u16, size: LineSize) {
+        if let Some(slot) = self.sizes.get_mut(row as usize) {
+            *slot = size;
+        }
+    }
+
+    /// Forget what the terminal shows, e.g. after another program wrote to it
+    pub fn invalidate(&mut self) {
+        self.full_repaint = true;
+    }
+
+    /// Where to show the terminal cursor after the next flush (`None` hides it)
+    pub fn set_cursor(&mut self, position: Option<(u16, u16)>) {
+        self.cursor = position;
+    }
+
+    /// Draw `text` starting at (col, row), clipped at the right edge.
+    /// Returns the column after the last cell written.
+    pub fn put_str(&mut self, col: u16, row: u16, fg: Color, bg: Color, text: &str) -> u16 {
+        if row >= self.height {
+            return col;
+        }
+        let mut x = col;
+        for ch in text.chars().map(printable) {
+            let width = char_width(ch);
+            if width == 0 {
+                continue;
+            }
+            if x + width as u16 > self.width {
+                break;
+            }
+            self.set(
+                x,
+                row,
+                Cell {
+                    ch,
+                    fg,
+                    bg,
+                    underline: false,
+                },
+            );
+            if width == 2 {
+                self.set(
+                    x + 1,
+                    row,
+                    Cell {
+                        ch: WIDE_TAIL,
+                        fg,
+                        bg,
+                        underline: false,
+                    },
+                );
+            }
+            x += width as u16;
+        }
+        x
+    }
+
+    /// Underline `width` cells already drawn from (col, row)
+    pub fn underline(&mut self, col: u16, row: u16, width: u16) {
+        if row >= self.height {
+            return;
+        }
+        for x in col..col.saturating_add(width).min(self.width) {
+            let index = self.index(x, row);
+            self.cells[index].underline = true;
+        }
+    }
+
+    /// Fade the text of the cells already drawn in a rectangle halfway into
+    /// their background (the pane without focus)
+    pub fn dim(&mut self, col: u16, row: u16, width: u16, height: u16) {
+        let right = col.saturating_add(width).min(self.width);
+        let bottom = row.saturating_add(height).min(self.height);
+        for y in row..bottom {
+            for x in col..right {
+                let index = self.index(x, y);
+                let cell = &mut self.cells[index];
+                cell.fg = match (cell.fg, cell.bg) {
+                    (
+                        Color::Rgb { r, g, b },
+                        Color::Rgb {
+                            r: br,
+                            g: bg,
+                            b: bb,
+                        },
+                    ) => Color::Rgb {
+                        r: ((r as u16 + br as u16) / 2) as u8,
+                        g: ((g as u16 + bg as u16) / 2) as u8,
+                        b: ((b as u16 + bb as u16) / 2) as u8,
+                    },
+                    // The terminal's own background, taken as black
+                    (Color::Rgb { r, g, b }, _) => Color::Rgb {
+                        r: r / 2,
+                        g: g / 2,
+                        b: b / 2,
+                    },
+                    _ => Color::DarkGrey,
+                };
+            }
+        }
+    }
+
+    fn set(&mut self, x: u16, y: u16, cell: Cell) {
+        let index = self.index(x, y);
+
+        // Overwriting half of a wide character blanks the other half
+        if self.cells[index].ch == WIDE_TAIL && cell.ch != WIDE_TAIL && x > 0 {
+            self.cells[index - 1].ch = ' ';
+        }
+        if char_width(self.cells[index].ch) == 2 && x + 1 < self.width {
+            self.cells[index + 1].ch = ' ';
+        }
+        self.cells[index] = cell;
+    }
+
+    fn index(&self, x: u16, y: u16) -> usize {
+        y as usize * self.width as usize + x as usize
+    }
+
+    /// Write the changed cells to the terminal with as few escape sequences
+    /// as possible: cursor moves only on gaps and colors only on change.
+    pub fn flush(&mut self) -> io::Result<()> {
+        let mut writer = BufWriter::with_capacity(64 * 1024, io::stdout());
+        queue!(writer, cursor::Hide)?;
+
+        if self.full_repaint {
+            queue!(
+                writer,
+                style::ResetColor,
+                terminal::Clear(terminal::ClearType::All)
+            )?;
+            // Clearing sets every row back to normal size
+            self.shown_sizes.fill(LineSize::Normal);
+        }
+
+        // Where the terminal cursor sits and which colors are active
+        let mut position: Option<(u16, u16)> = None;
+        let mut colors: Option<(Color, Color)> = None;
+        let mut underline = false;
+
+        for y in 0..self.height {
+            // A row changing size is redrawn whole
+            let resized = self.sizes[y as usize] != self.shown_sizes[y as usize];
+            if resized {
+                let size = self.sizes[y as usize];
+                queue!(writer, cursor::MoveTo(0, y), style::Print(size.escape()))?;
+                self.shown_sizes[y as usize] = size;
+                position = None;
+            }
+            // Cells past the middle of a double-width row aren't shown, and
+            // writing there would land on its last visible cell
+            let visible = match self.sizes[y as usize] {
+                LineSize::Normal => self.width,
+                _ => self.width / 2,
+            };
+            for x in 0..visible {
+                let index = self.index(x, y);
+                let cell = self.cells[index];
+                let unchanged = !self.full_repaint && !resized && cell == self.shown[index];
+                if cell.ch == WIDE_TAIL || unchanged {
+                    continue;
+                }
+
+                if position != Some((x, y)) {
+                    queue!(writer, cursor::MoveTo(x, y))?;
+                }
+                if colors != Some((cell.fg, cell.bg)) {
+                    queue!(
+                        writer,
+                        style::SetForegroundColor(cell.fg),
+                        style::SetBackgroundColor(cell.bg)
+                    )?;
+                    colors = Some((cell.fg, cell.bg));
+                }
+                if cell.underline != underline {
+                    let attribute = if cell.underline {
+                        style::Attribute::Underlined
+                    } else {
+                        style::Attribute::NoUnderline
+                    };
+                    queue!(writer, style::SetAttribute(attribute))?;
+                    underline = cell.underline;
+                }
+                queue!(writer, style::Print(cell.ch))?;
+
+                let width = char_width(cell.ch) as u16;
+                position = Some((x + width, y));
+                if width == 2 {
+                    // The tail is drawn together with its character
+                    self.shown[index + 1] = self.cells[index + 1];
+                }
+                self.shown[index] = cell;
+            }
+        }
+        self.full_repaint = false;
+
+        queue!(
+            writer,
+            style::SetAttribute(style::Attribute::NoUnderline),
+            style::ResetColor
+        )?;
+        if let Some((x, y)) = self.cursor {
+            queue!(writer, cursor::MoveTo(x, y), cursor::Show)?;
+        }
+        writer.flush()
+    }
+}
+
+/// What the terminal is sent for `ch`. Control chars would move its cursor
+/// (`\r`, tabs) or start a command (the ESC of an ANSI sequence), and a NUL
+/// is taken for `WIDE_TAIL`: they become their Unicode picture (`␍`, `␀`,
+/// `␛`), tabs a blank, and the C1 controls a box.
+pub fn printable(ch: char) -> char {
+    match ch {
+        '\t' => ' ',
+        '\0'..='\x1f' => char::from_u32(0x2400 + ch as u32).unwrap_or('▯'),
+        '\x7f' => '␡',
+        _ if ch.is_control() => '▯',
+        _ => ch,
+    }
+}
+
+/// Terminal columns taken by `ch`: 0 for combining marks and joiners,
+/// 2 for CJK and emoji, 1 otherwise.
+pub fn char_width(ch: char) -> usize {
+    match ch as u32 {
+        0x0300..=0x036F | 0x200B..=0x200F | 0xFE00..=0xFE0F => 0,
+        0x1100..=0x115F
+        | 0x2615
+        | 0x26A1
+        | 0x2E80..=0xA4CF
+        | 0xAC00..=0xD7A3
+        | 0xF900..=0xFAFF
+        | 0xFE30..=0xFE4F
+        | 0xFF00..=0xFF60
+        | 0xFFE0..=0xFFE6
+        | 0x1F300..=0x1F64F
+        | 0x1F680..=0x1F6FF
+        | 0x1F900..=0x1F9FF
+        | 0x20000..=0x3FFFD => 2,
+        _ => 1,
+    }
+}
@@ -0,0 +1,195 @@
+use crate::config;
+use crate::log;
+use crate::remote;
+use std::io::{self, BufRead, BufReader, Write};
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+#[cfg(unix)]
+use std::thread;
+
+/// A file sent by `reditor --remote`
+pub struct Request {
+    pub path: String,
+    /// Set for `--remote-wait`: the client blocks until the file is closed
+    pub waiter: Option<Waiter>,
+}
+
+/// A `--remote-wait` client, told when its file's buffer closes
+pub struct Waiter(Box<dyn Write + Send>);
+
+impl Waiter {
+    pub fn done(mut self) {
+        let _ = self.0.write_all(b"closed\n");
+    }
+}
+
+/// The socket other reditor processes send files to. Requests are read on
+/// a thread and picked up by the event loop with `poll`.
+pub struct Server {
+    path: PathBuf,
+    requests: Receiver<Request>,
+}
+
+/// `server.sock` in the state directory
+fn socket_path() -> io::Result<PathBuf> {
+    config::state_dir()
+        .map(|dir| dir.join("server.sock"))
+        .ok_or_else(|| io::Error::other("HOME não definido"))
+}
+
+/// Socket of the instance editing the project at `root`. It lives in the
+/// state directory, named by a hash of the path: socket paths are limited
+/// to about 100 bytes, and the project stays free of editor files.
+fn project_socket(root: &Path) -> io::Result<PathBuf> {
+    // FNV-1a, stable between builds unlike the std hasher
+    let hash = root
+        .to_string_lossy()
+        .bytes()
+        .fold(0xcbf29ce484222325u64, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+        });
+    config::state_dir()
+        .map(|dir| dir.join("projects").join(format!("{:016x}.sock", hash)))
+        .ok_or_else(|| io::Error::other("HOME não definido"))
+}
+
+/// The project folder holding `path` (or one of its parents) that another
+/// instance has open, if any
+#[cfg(unix)]
+pub fn project_instance(path: &Path) -> Option<PathBuf> {
+    path.ancestors()
+        .find(|dir| project_socket(dir).is_ok_and(|socket| UnixStream::connect(socket).is_ok()))
+        .map(Path::to_path_buf)
+}
+
+#[cfg(not(unix))]
+pub fn project_instance(_path: &Path) -> Option<PathBuf> {
+    None
+}
+
+impl Server {
+    /// Listen for `--remote` clients, unless another instance already does
+    pub fn start() -> io::Result<Server> {
+        Server::listen(socket_path()?)
+    }
+
+    /// Listen for instances launched on the project at `root`, which hand
+    /// their files over instead of opening a second editor on it
+    pub fn start_for_project(root: &Path) -> io::Result<Server> {
+        Server::listen(project_socket(root)?)
+    }
+
+    #[cfg(unix)]
+    fn listen(path: PathBuf) -> io::Result<Server> {
+        if UnixStream::connect(&path).is_ok() {
+            return Err(io::Error::new(
+                io::ErrorKind::AddrInUse,
+                "outra instância já atende este socket",
+            ));
+        }
+        // Left behind by an instance that didn't exit cleanly
+        let _ = std::fs::remove_file(&path);
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let listener = UnixListener::bind(&path)?;
+        let (sender, requests) = mpsc::channel();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                // One thread per client, so a slow one doesn't hold up the rest
+                let sender = sender.clone();
+                thread::spawn(move || {
+                    let Ok(reader) = stream.try_clone() else {
+                        return;
+                    };
+                    for line in BufReader::new(reader).lines().map_while(Result::ok) {
+                        let Some((kind, path)) = line.split_once('\t') else {
+                            continue;
+                        };
+                        let waiter = match kind {
+                            "wait" => stream
+                                .try_clone()
+                                .ok()
+                                .map(|stream| Waiter(Box::new(stream))),
+                            _ => None,
+                        };
+                        let request = Request {
+                            path: path.to_string(),
+                            waiter,
+                        };
+                        if sender.send(request).is_err() {
+                            return;
+                        }
+                    }
+                });
+            }
+        });
+        log::info!("servidor", "aguardando arquivos em {}", path.display());
+        Ok(Server { path, requests })
+    }
+
+    #[cfg(not(unix))]
+    fn listen(_path: PathBuf) -> io::Result<Server> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "--remote só existe em sistemas Unix",
+        ))
+    }
+
+    /// Files received since the last call
+    pub fn poll(&self) -> Vec<Request> {
+        self.requests.try_iter().collect()
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Hand `paths` to the running instance. With `wait`, return only once
+/// all of them were closed there (or it exited), as `$EDITOR` needs.
+pub fn send(paths: &[String], wait: bool) -> io::Result<()> {
+    send_to(&socket_path()?, paths, wait)
+}
+
+/// `send` to the instance editing the project at `root`
+pub fn send_to_project(root: &Path, paths: &[String], wait: bool) -> io::Result<()> {
+    send_to(&project_socket(root)?, paths, wait)
+}
+
+#[cfg(unix)]
+fn send_to(socket: &Path, paths: &[String], wait: bool) -> io::Result<()> {
+    let mut stream = UnixStream::connect(socket)?;
+    let kind = if wait { "wait" } else { "open" };
+    for path in paths {
+        // `user@host:/path` names a file there, not one under this folder
+        if remote::is_remote(path) {
+            writeln!(stream, "{}\t{}", kind, path)?;
+        } else {
+            let absolute = std::path::absolute(path)?;
+            writeln!(stream, "{}\t{}", kind, absolute.display())?;
+        }
+    }
+    stream.shutdown(std::net::Shutdown::Write)?;
+    if wait {
+        let closed = BufReader::new(stream)
+            .lines()
+            .map_while(Result::ok)
+            .take(paths.len())
+            .count();
+        log::debug!("servidor", "{} arquivo(s) fechado(s)", closed);
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn send_to(_socket: &Path, _paths: &[String], _wait: bool) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "--remote só existe em sistemas Unix",
+    ))
+}
@@ -1,26 +1,109 @@
-use std::fs;
-use std::path::PathBuf;
+use crate::archive;
+use crate::jobs::{JobId, JobMessage, Jobs};
+use crate::project;
+use crate::prompt;
+use crate::vfs::{self, Filesystem};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
 
 #[derive(Clone)]
 pub struct FileEntry {
     pub name: String,
     pub path: PathBuf,
     pub is_dir: bool,
+    pub is_symlink: bool,
+    pub is_executable: bool,
+    // A linked folder that leads back to one of its own parents, which
+    // would expand forever: it stays closed
+    pub is_loop: bool,
+    // A zip or tar file, which opens like a folder (and counts as one)
+    pub is_archive: bool,
     pub children: Vec<FileEntry>,
     pub expanded: bool,
     pub depth: usize,
 }
 
+/// Sidebar width when the terminal is wide enough
+const DEFAULT_WIDTH: u16 = 30;
+
+/// Columns always left to the text area when the sidebar is shrunk
+const MIN_TEXT_COLUMNS: u16 = 10;
+
+/// Entries of a folder listed at a time; the rest wait behind a "mais N
+/// itens" row, so a folder with tens of thousands of files stays quick
+pub const PAGE_SIZE: usize = 500;
+
+/// How often a running search sends what it found so far
+const SEARCH_BATCH: Duration = Duration::from_millis(100);
+
+/// Width of the compact sidebar: an icon per entry
+pub const COMPACT_WIDTH: u16 = 4;
+
+/// Narrowest sidebar that still shows the header buttons beside the name
+const MIN_BUTTONS_WIDTH: u16 = 20;
+
+/// Actions at the right of the sidebar's header, clickable and also on keys
+#[derive(Clone, Copy, PartialEq)]
+pub enum HeaderButton {
+    Refresh,
+    CollapseAll,
+    NewFile,
+    Compact,
+}
+
+impl HeaderButton {
+    const ALL: [HeaderButton; 4] = [
+        HeaderButton::Refresh,
+        HeaderButton::CollapseAll,
+        HeaderButton::NewFile,
+        HeaderButton::Compact,
+    ];
+
+    pub fn glyph(self) -> char {
+        match self {
+            HeaderButton::Refresh => '↻',
+            HeaderButton::CollapseAll => '⊟',
+            HeaderButton::NewFile => '+',
+            HeaderButton::Compact => '«',
+        }
+    }
+}
+
 pub struct Sidebar {
     pub root_path: PathBuf,
     pub entries: Vec<FileEntry>,
     pub selected_index: usize,
+    pub scroll_offset: usize,
     pub visible: bool,
     pub width: u16,
     pub search_query: String,
     pub search_active: bool,
+    // Only an icon per entry, names shown as tooltips
+    pub compact: bool,
+    // Entry under the mouse, for the compact sidebar's tooltip
+    pub hovered: Option<usize>,
+    // The root folder is still being read in the background
+    pub loading: bool,
+    // Folders listing more than `PAGE_SIZE` entries, and how many of them
+    // are shown
+    pages: Vec<(PathBuf, usize)>,
+    // Files of the whole tree whose name matches the search, found by
+    // `start_search` (folders that were never opened included)
+    found: Vec<PathBuf>,
+    // Files the search went through so far
+    pub scanned: usize,
+    pub scanning: bool,
+    // Search by the path's chars in order (`sbrs` finds `src/sidebar.rs`),
+    // best matches first, instead of by a piece of the name
+    pub fuzzy: bool,
+    // Files changed according to git, when only those are listed
+    changed_files: Option<Vec<PathBuf>>,
     flat_cache: Vec<FlatEntry>,
     cache_dirty: bool,
+    // When the root and each open folder last changed, as of the last read
+    stamps: Vec<(PathBuf, Option<SystemTime>)>,
+    fs: Arc<dyn Filesystem>,
 }
 
 #[derive(Clone)]
@@ -28,59 +111,120 @@ pub struct FlatEntry {
     pub name: String,
     pub path: PathBuf,
     pub is_dir: bool,
+    pub is_symlink: bool,
+    pub is_executable: bool,
+    pub is_loop: bool,
+    pub is_archive: bool,
     pub depth: usize,
     pub expanded: bool,
+    // On the row after a cut-off listing: how many entries of the folder
+    // at `path` aren't shown yet
+    pub more: usize,
+    // Chars of `name` the search matched, to be highlighted
+    pub matched: Vec<usize>,
 }
 
 impl Sidebar {
+    /// The root isn't read here: `start_load` reads it in the background,
+    /// as a huge or slow folder would hold up the editor's start
     pub fn new(root_path: PathBuf) -> Sidebar {
-        let entries = Sidebar::build_tree(&root_path, 0);
-        let mut sidebar = Sidebar {
+        Sidebar {
             root_path,
-            entries,
+            entries: vec![],
             selected_index: 0,
+            scroll_offset: 0,
             visible: true,
-            width: 30,
+            width: DEFAULT_WIDTH,
             search_query: String::new(),
             search_active: false,
+            compact: false,
+            hovered: None,
+            loading: true,
+            pages: vec![],
+            found: vec![],
+            scanned: 0,
+            scanning: false,
+            fuzzy: false,
+            changed_files: None,
             flat_cache: vec![],
             cache_dirty: true,
-        };
-        sidebar.rebuild_flat_cache();
-        sidebar
+            stamps: vec![],
+            fs: vfs::local(),
+        }
     }
 
-    fn build_tree(path: &PathBuf, depth: usize) -> Vec<FileEntry> {
-        let mut entries: Vec<FileEntry> = vec![];
+    /// Read the root folder on the job pool; `finish_load` takes the result
+    pub fn start_load(&self, jobs: &mut Jobs) -> JobId {
+        let fs = Arc::clone(&self.fs);
+        let root = self.root_path.clone();
+        jobs.spawn(String::from("Lendo a pasta"), move |context| {
+            context.send(JobMessage::Tree(Sidebar::build_tree(fs.as_ref(), &root, 0)));
+        })
+    }
 
-        if let Ok(read_dir) = fs::read_dir(path) {
-            let mut items: Vec<_> = read_dir.filter_map(|e| e.ok()).collect();
+    /// Show the root read by `start_load`, unless it was read again since
+    pub fn finish_load(&mut self, entries: Vec<FileEntry>) {
+        if self.loading {
+            self.entries = entries;
+            self.loading = false;
+            self.stamps = self.dir_stamps();
+            self.cache_dirty = true;
+        }
+    }
+
+    fn build_tree(fs: &dyn Filesystem, path: &Path, depth: usize) -> Vec<FileEntry> {
+        let mut entries: Vec<FileEntry> = vec![];
+        // Where `path` and the folders above it up to the root really are,
+        // read only if a linked folder needs checking for a loop
+        let mut parents: Option<Vec<PathBuf>> = None;
+
+        // An archive, or a folder in one, is listed from the archive
+        let in_archive = archive::split(path).is_some();
+        let items = match in_archive {
+            true => vfs::archive().read_dir(path),
+            false => fs.read_dir(path),
+        };
+        if let Ok(mut items) = items {
             items.sort_by(|a, b| {
-                let a_is_dir = a.file_type().map(|t| t.is_dir()).unwrap_or(false);
-                let b_is_dir = b.file_type().map(|t| t.is_dir()).unwrap_or(false);
-                b_is_dir.cmp(&a_is_dir).then(
-                    a.file_name()
-                        .to_string_lossy()
-                        .to_lowercase()
-                        .cmp(&b.file_name().to_string_lossy().to_lowercase()),
-                )
+                b.is_dir
+                    .cmp(&a.is_dir)
+                    .then(a.name.to_lowercase().cmp(&b.name.to_lowercase()))
             });
 
             for item in items {
-                let name = item.file_name().to_string_lossy().to_string();
+                let name = item.name;
 
                 // Skip hidden files and common non-essential dirs
                 if name.starts_with('.') || name == "target" || name == "node_modules" {
                     continue;
                 }
 
-                let item_path = item.path();
-                let is_dir = item.file_type().map(|t| t.is_dir()).unwrap_or(false);
+                let item_path = path.join(&name);
+                // Archives in archives stay files
+                let is_archive = !item.is_dir && !in_archive && archive::is_archive(&name);
+                let is_dir = item.is_dir || is_archive;
+                let is_loop = is_dir
+                    && item.is_symlink
+                    && fs.canonicalize(&item_path).is_ok_and(|target| {
+                        parents
+                            .get_or_insert_with(|| {
+                                path.ancestors()
+                                    .take(depth + 1)
+                                    .filter_map(|dir| fs.canonicalize(dir).ok())
+                                    .collect()
+                            })
+                            .iter()
+                            .any(|dir| dir.starts_with(&target))
+                    });
 
                 entries.push(FileEntry {
                     name,
                     path: item_path,
                     is_dir,
+                    is_symlink: item.is_symlink,
+                    is_executable: item.is_executable,
+                    is_loop,
+                    is_archive,
                     children: vec![], // Lazy-loaded
                     expanded: false,
                     depth,
@@ -94,54 +238,186 @@ impl Sidebar {
     fn rebuild_flat_cache(&mut self) {
         self.flat_cache.clear();
 
-        if self.search_query.is_empty() {
-            self.flatten_entries(&self.entries.clone());
+        if let Some(changed) = self.changed_files.clone() {
+            let query = self.search_query.to_lowercase();
+            self.flatten_files(&changed, &query);
+        } else if self.search_query.is_empty() {
+            let entries = std::mem::take(&mut self.entries);
+            self.flatten_entries(&entries, &self.root_path.clone());
+            self.entries = entries;
         } else {
             let query = self.search_query.to_lowercase();
-            self.flatten_entries_filtered(&self.entries.clone(), &query);
+            let found = std::mem::take(&mut self.found);
+            self.flatten_files(&found, &query);
+            self.found = found;
         }
 
         self.cache_dirty = false;
     }
 
-    fn flatten_entries(&mut self, entries: &[FileEntry]) {
-        for entry in entries {
+    /// The entries of folder `dir` shown so far, and a row for the rest
+    fn flatten_entries(&mut self, entries: &[FileEntry], dir: &Path) {
+        let shown = self.shown_in(dir);
+        for entry in &entries[..shown.min(entries.len())] {
             self.flat_cache.push(FlatEntry {
                 name: entry.name.clone(),
                 path: entry.path.clone(),
                 is_dir: entry.is_dir,
+                is_symlink: entry.is_symlink,
+                is_executable: entry.is_executable,
+                is_loop: entry.is_loop,
+                is_archive: entry.is_archive,
                 depth: entry.depth,
                 expanded: entry.expanded,
+                more: 0,
+                matched: vec![],
             });
 
             if entry.is_dir && entry.expanded {
-                self.flatten_entries(&entry.children);
+                self.flatten_entries(&entry.children, &entry.path);
             }
         }
+        if let Some(first) = entries.get(shown) {
+            self.flat_cache.push(FlatEntry {
+                name: format!("… mais {} itens", entries.len() - shown),
+                path: dir.to_path_buf(),
+                is_dir: false,
+                is_symlink: false,
+                is_executable: false,
+                is_loop: false,
+                is_archive: false,
+                depth: first.depth,
+                expanded: false,
+                more: entries.len() - shown,
+                matched: vec![],
+            });
+        }
     }
 
-    fn flatten_entries_filtered(&mut self, entries: &[FileEntry], query: &str) {
-        for entry in entries {
-            let matches = entry.name.to_lowercase().contains(query);
+    /// How many entries of folder `dir` are listed
+    fn shown_in(&self, dir: &Path) -> usize {
+        self.pages
+            .iter()
+            .find(|(path, _)| path == dir)
+            .map_or(PAGE_SIZE, |&(_, shown)| shown)
+    }
 
-            if matches || entry.is_dir {
-                if matches {
-                    self.flat_cache.push(FlatEntry {
-                        name: entry.name.clone(),
-                        path: entry.path.clone(),
-                        is_dir: entry.is_dir,
-                        depth: entry.depth,
-                        expanded: entry.expanded,
-                    });
-                }
+    /// On a "mais N itens" row, list the next page of its folder
+    pub fn show_more(&mut self) {
+        let index = self.selected_index;
+        let Some(row) = self.flat_entries().get(index).filter(|row| row.more > 0) else {
+            return;
+        };
+        let dir = row.path.clone();
+        let shown = self.shown_in(&dir) + PAGE_SIZE;
+        self.pages.retain(|(path, _)| *path != dir);
+        self.pages.push((dir, shown));
+        self.cache_dirty = true;
+    }
 
-                if entry.is_dir && entry.expanded {
-                    self.flatten_entries_filtered(&entry.children, query);
+    /// List `files` (changed according to git, or found by a search)
+    /// matching `query` under their folders, all expanded, whether or not
+    /// those were loaded in the tree
+    fn flatten_files(&mut self, files: &[PathBuf], query: &str) {
+        if self.fuzzy && !query.is_empty() {
+            self.flatten_fuzzy(files, query);
+            return;
+        }
+        let mut files: Vec<&PathBuf> = files
+            .iter()
+            .filter(|path| {
+                path.file_name()
+                    .is_some_and(|name| name.to_string_lossy().to_lowercase().contains(query))
+            })
+            .collect();
+        files.sort();
+
+        for file in files {
+            let relative = match file.strip_prefix(&self.root_path) {
+                Ok(relative) => relative,
+                Err(_) => continue,
+            };
+            let parts: Vec<String> = relative
+                .components()
+                .map(|part| part.as_os_str().to_string_lossy().to_string())
+                .collect();
+            let mut path = self.root_path.clone();
+            for (depth, name) in parts.iter().enumerate() {
+                path.push(name);
+                let is_dir = depth + 1 < parts.len();
+                if is_dir && self.flat_cache.iter().any(|entry| entry.path == path) {
+                    continue;
                 }
+                self.flat_cache.push(FlatEntry {
+                    name: name.clone(),
+                    path: path.clone(),
+                    is_dir,
+                    is_symlink: false,
+                    is_executable: !is_dir && vfs::is_executable(&path),
+                    is_loop: false,
+                    is_archive: false,
+                    depth,
+                    expanded: is_dir,
+                    more: 0,
+                    matched: match is_dir {
+                        true => vec![],
+                        false => substring_chars(name, query),
+                    },
+                });
             }
         }
     }
 
+    /// List `files` whose path under the root fuzzy-matches `query`, one
+    /// row each with that path as its name, best matches first
+    fn flatten_fuzzy(&mut self, files: &[PathBuf], query: &str) {
+        let mut scored: Vec<(usize, Vec<usize>, String, &PathBuf)> = files
+            .iter()
+            .filter_map(|file| {
+                let relative = file.strip_prefix(&self.root_path).ok()?;
+                let relative = relative.to_string_lossy().to_string();
+                let (score, matched) = prompt::fuzzy_match(query, &relative.to_lowercase())?;
+                Some((score, matched, relative, file))
+            })
+            .collect();
+        scored.sort_by(|a, b| b.0.cmp(&a.0).then(a.2.cmp(&b.2)));
+
+        for (_, matched, relative, file) in scored {
+            self.flat_cache.push(FlatEntry {
+                name: relative,
+                path: file.clone(),
+                is_dir: false,
+                is_symlink: false,
+                is_executable: vfs::is_executable(file),
+                is_loop: false,
+                is_archive: false,
+                depth: 0,
+                expanded: false,
+                more: 0,
+                matched,
+            });
+        }
+    }
+
+    /// Show only `files` (from `git status`), or the whole tree again with `None`
+    pub fn set_changed_files(&mut self, files: Option<Vec<PathBuf>>) {
+        self.changed_files = files;
+        self.cache_dirty = true;
+        self.selected_index = 0;
+    }
+
+    /// Replace the changed files shown, keeping the selection where it is
+    pub fn refresh_changed_files(&mut self, files: Vec<PathBuf>) {
+        if self.changed_files.as_ref() != Some(&files) {
+            self.changed_files = Some(files);
+            self.cache_dirty = true;
+        }
+    }
+
+    pub fn is_changed_filter_on(&self) -> bool {
+        self.changed_files.is_some()
+    }
+
     pub fn flat_entries(&mut self) -> &[FlatEntry] {
         if self.cache_dirty {
             self.rebuild_flat_cache();
@@ -156,10 +432,123 @@ impl Sidebar {
         self.flat_cache.len()
     }
 
+    /// Keep the selection inside a window of `visible_rows` entries and the
+    /// window inside the list (called on every render, so resizes re-clamp)
+    pub fn clamp_scroll(&mut self, visible_rows: usize) {
+        let len = self.flat_len();
+        self.selected_index = self.selected_index.min(len.saturating_sub(1));
+
+        if visible_rows == 0 {
+            self.scroll_offset = self.selected_index;
+            return;
+        }
+        if self.selected_index < self.scroll_offset {
+            self.scroll_offset = self.selected_index;
+        } else if self.selected_index >= self.scroll_offset + visible_rows {
+            self.scroll_offset = self.selected_index + 1 - visible_rows;
+        }
+        self.scroll_offset = self.scroll_offset.min(len.saturating_sub(visible_rows));
+    }
+
+    /// Shrink the sidebar on narrow terminals and grow it back afterwards
+    pub fn fit_width(&mut self, columns: u16) {
+        self.width = DEFAULT_WIDTH
+            .min(columns.saturating_sub(MIN_TEXT_COLUMNS))
+            .max(1);
+    }
+
     pub fn toggle_visible(&mut self) {
         self.visible = !self.visible;
     }
 
+    pub fn toggle_compact(&mut self) {
+        self.compact = !self.compact;
+        self.hovered = None;
+    }
+
+    /// Close every folder, keeping the selection on the top-level entry it
+    /// was under
+    pub fn collapse_all(&mut self) {
+        let selected = self.get_selected_path();
+        Self::collapse(&mut self.entries);
+        self.stamps = self.dir_stamps();
+        self.rebuild_flat_cache();
+        self.selected_index = selected
+            .and_then(|path| {
+                self.flat_cache
+                    .iter()
+                    .position(|entry| path.starts_with(&entry.path))
+            })
+            .unwrap_or(0);
+    }
+
+    fn collapse(entries: &mut [FileEntry]) {
+        for entry in entries.iter_mut().filter(|entry| entry.expanded) {
+            entry.expanded = false;
+            Self::collapse(&mut entry.children);
+        }
+    }
+
+    /// The header buttons shown, left to right: only the one that widens
+    /// the compact sidebar again, none when there's no room for them
+    pub fn header_buttons(&self) -> &'static [HeaderButton] {
+        if self.compact {
+            &HeaderButton::ALL[3..]
+        } else if self.width >= MIN_BUTTONS_WIDTH {
+            &HeaderButton::ALL
+        } else {
+            &[]
+        }
+    }
+
+    /// Header text for the buttons, a space before each glyph, laid out
+    /// against the right edge
+    pub fn header_buttons_text(&self) -> String {
+        let mut text: String = self
+            .header_buttons()
+            .iter()
+            .map(|&button| match button {
+                // Widens the compact sidebar back
+                HeaderButton::Compact if self.compact => String::from(" »"),
+                button => format!(" {}", button.glyph()),
+            })
+            .collect();
+        if !text.is_empty() {
+            text.push(' ');
+        }
+        text
+    }
+
+    /// The header button at screen column `col`
+    pub fn header_button_at(&self, col: u16) -> Option<HeaderButton> {
+        let buttons = self.header_buttons();
+        let start = (self.sidebar_offset() as usize).saturating_sub(buttons.len() * 2 + 1);
+        let offset = (col as usize).checked_sub(start)?;
+        if offset % 2 == 0 {
+            return None;
+        }
+        buttons.get(offset / 2).copied()
+    }
+
+    /// Screen row of the first entry, below the header and search bar
+    pub fn list_top(&self) -> u16 {
+        if self.search_active {
+            2
+        } else {
+            1
+        }
+    }
+
+    /// Index of the entry drawn at screen row `row`
+    pub fn entry_at_row(&mut self, row: u16) -> Option<usize> {
+        let top = self.list_top();
+        if row < top {
+            return None;
+        }
+        let index = self.scroll_offset + (row - top) as usize;
+        (index < self.flat_len()).then_some(index)
+    }
+
     pub fn select_next(&mut self) {
         let len = self.flat_len();
         if len > 0 && self.selected_index < len - 1 {
@@ -176,7 +565,10 @@ impl Sidebar {
     pub fn get_selected_path(&mut self) -> Option<PathBuf> {
         let idx = self.selected_index;
         let entries = self.flat_entries();
-        entries.get(idx).map(|e| e.path.clone())
+        entries
+            .get(idx)
+            .filter(|e| e.more == 0)
+            .map(|e| e.path.clone())
     }
 
     pub fn is_selected_dir(&mut self) -> bool {
@@ -185,61 +577,239 @@ impl Sidebar {
         entries.get(idx).map(|e| e.is_dir).unwrap_or(false)
     }
 
-    pub fn toggle_selected_dir(&mut self) {
+    /// Open or close the selected folder. Returns the folder if it was
+    /// opened.
+    pub fn toggle_selected_dir(&mut self) -> Option<PathBuf> {
         if self.cache_dirty {
             self.rebuild_flat_cache();
         }
 
         if let Some(flat) = self.flat_cache.get(self.selected_index) {
             if !flat.is_dir {
-                return;
+                return None;
             }
             let target_path = flat.path.clone();
             let target_depth = flat.depth;
 
             // Find and toggle in the actual tree
-            Self::toggle_dir_in_tree(&mut self.entries, &target_path, target_depth);
+            Self::toggle_dir_in_tree(
+                self.fs.as_ref(),
+                &mut self.entries,
+                &target_path,
+                target_depth,
+            );
             self.cache_dirty = true;
             self.rebuild_flat_cache();
         }
+        let selected = self.flat_cache.get(self.selected_index)?;
+        selected.expanded.then(|| selected.path.clone())
+    }
+
+    /// Open the folders from the root down to `dir` (itself included) and
+    /// select it, showing the whole tree again
+    pub fn reveal(&mut self, dir: &Path) {
+        let mut expanded = vec![];
+        Self::collect_expanded(&self.entries, &mut expanded);
+        expanded.extend(
+            dir.ancestors()
+                .take_while(|path| *path != self.root_path)
+                .map(Path::to_path_buf),
+        );
+        Self::expand_paths(self.fs.as_ref(), &mut self.entries, &expanded);
+        self.changed_files = None;
+        self.clear_search();
+        self.stamps = self.dir_stamps();
+        self.rebuild_flat_cache();
+        if let Some(index) = self.flat_cache.iter().position(|entry| entry.path == dir) {
+            self.selected_index = index;
+        }
     }
 
-    fn toggle_dir_in_tree(entries: &mut Vec<FileEntry>, target: &PathBuf, _depth: usize) -> bool {
+    fn toggle_dir_in_tree(
+        fs: &dyn Filesystem,
+        entries: &mut [FileEntry],
+        target: &PathBuf,
+        _depth: usize,
+    ) -> bool {
         for entry in entries.iter_mut() {
             if entry.path == *target && entry.is_dir {
-                entry.expanded = !entry.expanded;
+                entry.expanded = !entry.expanded && !entry.is_loop;
                 if entry.expanded && entry.children.is_empty() {
-                    entry.children = Sidebar::build_tree(&entry.path, entry.depth + 1);
+                    entry.children = Sidebar::build_tree(fs, &entry.path, entry.depth + 1);
                 }
                 return true;
             }
-            if entry.is_dir && entry.expanded {
-                if Self::toggle_dir_in_tree(&mut entry.children, target, _depth) {
-                    return true;
-                }
+            if entry.is_dir
+                && entry.expanded
+                && Self::toggle_dir_in_tree(fs, &mut entry.children, target, _depth)
+            {
+                return true;
             }
         }
         false
     }
 
+    /// Read the tree from disk again, e.g. after a file was moved to the
+    /// trash, keeping open folders open
+    pub fn refresh(&mut self) {
+        let mut expanded = vec![];
+        Self::collect_expanded(&self.entries, &mut expanded);
+        self.entries = Self::build_tree(self.fs.as_ref(), &self.root_path, 0);
+        self.loading = false;
+        Self::expand_paths(self.fs.as_ref(), &mut self.entries, &expanded);
+        self.stamps = self.dir_stamps();
+        self.cache_dirty = true;
+    }
+
+    /// Read the tree again if the root or an open folder changed on disk
+    /// since it was last read
+    pub fn refresh_if_changed(&mut self) {
+        if !self.loading && self.dir_stamps() != self.stamps {
+            self.refresh();
+        }
+    }
+
+    fn dir_stamps(&self) -> Vec<(PathBuf, Option<SystemTime>)> {
+        let mut dirs = vec![self.root_path.clone()];
+        Self::collect_expanded(&self.entries, &mut dirs);
+        dirs.into_iter()
+            .map(|dir| {
+                let stamp = self.fs.modified(&dir).ok();
+                (dir, stamp)
+            })
+            .collect()
+    }
+
+    fn collect_expanded(entries: &[FileEntry], expanded: &mut Vec<PathBuf>) {
+        for entry in entries.iter().filter(|entry| entry.expanded) {
+            expanded.push(entry.path.clone());
+            Self::collect_expanded(&entry.children, expanded);
+        }
+    }
+
+    fn expand_paths(fs: &dyn Filesystem, entries: &mut [FileEntry], expanded: &[PathBuf]) {
+        for entry in entries.iter_mut() {
+            if entry.is_dir && !entry.is_loop && expanded.contains(&entry.path) {
+                entry.expanded = true;
+                entry.children = Sidebar::build_tree(fs, &entry.path, entry.depth + 1);
+                Self::expand_paths(fs, &mut entry.children, expanded);
+            }
+        }
+    }
+
+    /// Search for `query`; `start_search` looks for the files
     pub fn set_search_query(&mut self, query: String) {
         self.search_query = query;
+        self.found.clear();
+        self.scanned = 0;
+        self.scanning = false;
         self.cache_dirty = true;
         self.selected_index = 0;
     }
 
     pub fn clear_search(&mut self) {
-        self.search_query.clear();
+        self.set_search_query(String::new());
         self.search_active = false;
+    }
+
+    /// Look through the whole tree, folders never opened included, for
+    /// files whose name has the search query. What's found is sent in
+    /// batches for `add_found`, so the list and the count of files scanned
+    /// grow while it runs.
+    pub fn start_search(&mut self, jobs: &mut Jobs) -> JobId {
+        self.found.clear();
+        self.scanned = 0;
+        self.scanning = true;
+        self.cache_dirty = true;
+        let fs = Arc::clone(&self.fs);
+        let root = self.root_path.clone();
+        let query = self.search_query.to_lowercase();
+        let fuzzy = self.fuzzy;
+        jobs.spawn(
+            format!("Buscando '{}'", self.search_query),
+            move |context| {
+                let mut scanned = 0;
+                let mut found = vec![];
+                let mut sent = Instant::now();
+                project::walk_files(fs.as_ref(), &root, context, &mut |path| {
+                    scanned += 1;
+                    if search_matches(&query, fuzzy, &root, path) {
+                        found.push(path.to_path_buf());
+                    }
+                    if sent.elapsed() >= SEARCH_BATCH {
+                        context.send(JobMessage::Names(scanned, std::mem::take(&mut found)));
+                        scanned = 0;
+                        sent = Instant::now();
+                    }
+                });
+                context.send(JobMessage::Names(scanned, found));
+            },
+        )
+    }
+
+    /// Switch between searching by a piece of the name and fuzzy search
+    pub fn toggle_fuzzy(&mut self) {
+        self.fuzzy = !self.fuzzy;
         self.cache_dirty = true;
         self.selected_index = 0;
     }
 
+    /// Files found and scanned by the search, shown beside it: "3 de
+    /// 1520…" while it runs
+    pub fn search_progress(&self) -> Option<String> {
+        if self.search_query.is_empty() || self.is_changed_filter_on() {
+            return None;
+        }
+        let running = if self.scanning { "…" } else { "" };
+        Some(format!(
+            "{} de {}{}",
+            self.found.len(),
+            self.scanned,
+            running
+        ))
+    }
+
+    /// A batch sent by the search `start_search` began
+    pub fn add_found(&mut self, scanned: usize, found: Vec<PathBuf>) {
+        self.scanned += scanned;
+        if !found.is_empty() {
+            self.found.extend(found);
+            self.cache_dirty = true;
+        }
+    }
+
     pub fn sidebar_offset(&self) -> u16 {
-        if self.visible {
-            self.width
-        } else {
-            0
+        match (self.visible, self.compact) {
+            (false, _) => 0,
+            (true, true) => COMPACT_WIDTH.min(self.width),
+            (true, false) => self.width,
         }
     }
 }
+
+/// Whether the search for `query` (lowercase) finds `path`: by a piece of
+/// its name, or fuzzy by its path under `root`
+fn search_matches(query: &str, fuzzy: bool, root: &Path, path: &Path) -> bool {
+    if fuzzy {
+        let relative = path.strip_prefix(root).unwrap_or(path);
+        prompt::fuzzy_score(query, &relative.to_string_lossy().to_lowercase()).is_some()
+    } else {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        name.to_lowercase().contains(query)
+    }
+}
+
+/// Char indices of the first piece of `name` that is `query`, ignoring case
+fn substring_chars(name: &str, query: &str) -> Vec<usize> {
+    let lowered: Vec<char> = name.chars().flat_map(char::to_lowercase).collect();
+    let query: Vec<char> = query.chars().collect();
+    // Only where lowercasing kept one char per char
+    if query.is_empty() || lowered.len() != name.chars().count() {
+        return vec![];
+    }
+    lowered
+        .windows(query.len())
+        .position(|window| window == query.as_slice())
+        .map(|start| (start..start + query.len()).collect())
+        .unwrap_or_default()
+}
@@ -0,0 +1,93 @@
+use crate::clipboard;
+use crate::config::Config;
+use crate::time;
+use std::env;
+use std::path::Path;
+
+/// Texts inserted by name at the cursor (`snippet` in the palette), and
+/// the ones new files start with, by extension. `\n` and `\t` stand for a
+/// line break and a tab, and `${NAME}` variables are filled in when the
+/// text goes in:
+///
+/// ```toml
+/// [snippets]
+/// note = "## ${DATE} ${TIME}\n\n"
+///
+/// [templates]
+/// rs = "// Copyright ${YEAR} ${USER}\n\n"
+/// ```
+pub fn snippets(config: &Config) -> Vec<(String, String)> {
+    config
+        .section("snippets")
+        .map(|(name, text)| (name.to_string(), unescape(text)))
+        .collect()
+}
+
+/// Text a new file called `path` starts with, from `[templates]`
+pub fn template(config: &Config, path: &Path) -> Option<String> {
+    let ext = path.extension()?.to_string_lossy().to_lowercase();
+    config.get("templates", &ext).map(unescape)
+}
+
+/// `text` with its variables replaced: `${DATE}` (2024-05-01), `${TIME}`
+/// (13:45), `${YEAR}`, `${FILENAME}` (of `path`), `${USER}` and
+/// `${CLIPBOARD}`. Unknown ones stay as written.
+pub fn expand(text: &str, path: &str) -> String {
+    let now = time::local_time();
+    let (date, time) = now.split_once('T').unwrap_or_default();
+    let mut out = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("${") {
+        out.push_str(&rest[..start]);
+        let Some(end) = rest[start..].find('}') else {
+            rest = &rest[start..];
+            break;
+        };
+        let name = &rest[start + 2..start + end];
+        let value = match name {
+            "DATE" => Some(date.to_string()),
+            "TIME" => Some(time.get(..5).unwrap_or(time).to_string()),
+            "YEAR" => Some(date.get(..4).unwrap_or(date).to_string()),
+            "FILENAME" => Some(
+                Path::new(path)
+                    .file_name()
+                    .map(|name| name.to_string_lossy().to_string())
+                    .unwrap_or_default(),
+            ),
+            "USER" => Some(
+                env::var("USER")
+                    .or_else(|_| env::var("USERNAME"))
+                    .unwrap_or_default(),
+            ),
+            "CLIPBOARD" => Some(clipboard::paste().unwrap_or_default()),
+            _ => None,
+        };
+        match value {
+            Some(value) => out.push_str(&value),
+            None => out.push_str(&rest[start..=start + end]),
+        }
+        rest = &rest[start + end + 1..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// `\n`, `\t` and `\\` of a config value as the chars they stand for
+fn unescape(text: &str) -> String {
+    let mut out = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('t') => out.push('\t'),
+            Some('\\') => out.push('\\'),
+            Some(next) => out.extend(['\\', next]),
+            None => out.push('\\'),
+        }
+    }
+    out
+}
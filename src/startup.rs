@@ -0,0 +1,81 @@
+use crate::log;
+use std::time::{Duration, Instant};
+
+/// Time spent in each phase of starting up, reported with
+/// `--startup-timing` to find what makes a start slow (usually the sidebar
+/// tree of a huge or network-mounted folder)
+pub struct StartupTiming {
+    enabled: bool,
+    started: Instant,
+    phases: Vec<(&'static str, Duration)>,
+    // Process start to the first frame showing the file
+    total: Option<Duration>,
+}
+
+impl StartupTiming {
+    /// Phases are timed either way; they're only reported when `enabled`
+    pub fn new(enabled: bool) -> StartupTiming {
+        StartupTiming {
+            enabled,
+            started: Instant::now(),
+            phases: vec![],
+            total: None,
+        }
+    }
+
+    /// `phase` took from `since` until now
+    pub fn record(&mut self, phase: &'static str, since: Instant) {
+        self.phases.push((phase, since.elapsed()));
+    }
+
+    /// Startup is over: the editor is waiting for input
+    pub fn finish(&mut self) {
+        if self.total.is_none() {
+            self.total = Some(self.started.elapsed());
+            if self.enabled {
+                for (phase, duration) in &self.phases {
+                    log::info!("início", "{}: {:?}", phase, duration);
+                }
+            }
+        }
+    }
+
+    /// One line for the status bar: the total and the slowest phase
+    pub fn summary(&self) -> Option<String> {
+        let total = self.total.filter(|_| self.enabled)?;
+        let slowest = self.phases.iter().max_by_key(|(_, duration)| *duration);
+        Some(match slowest {
+            Some((phase, duration)) => format!(
+                "Início em {} (mais lento: {}, {}) — detalhes ao sair",
+                format_duration(total),
+                phase,
+                format_duration(*duration)
+            ),
+            None => format!("Início em {}", format_duration(total)),
+        })
+    }
+
+    /// Table of every phase, printed once the terminal is back
+    pub fn report(&self) -> Option<String> {
+        let total = self.total.filter(|_| self.enabled)?;
+        let mut out = String::from("reditor --startup-timing:\n");
+        for (phase, duration) in &self.phases {
+            out.push_str(&format!(
+                "  {:<24} {:>12}\n",
+                phase,
+                format_duration(*duration)
+            ));
+        }
+        out.push_str(&format!("  {:<24} {:>12}", "total", format_duration(total)));
+        Some(out)
+    }
+}
+
+fn format_duration(duration: Duration) -> String {
+    let millis = duration.as_secs_f64() * 1000.0;
+    if millis >= 1000.0 {
+        format!("{:.2} s", millis / 1000.0)
+    } else {
+        format!("{:.2} ms", millis)
+    }
+}
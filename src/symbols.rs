@@ -0,0 +1,321 @@
+use crate::jobs::{JobId, JobMessage, Jobs};
+use crate::project;
+use crate::vfs;
+use std::path::Path;
+
+const FUNCTION: &str = "função";
+const TYPE: &str = "tipo";
+const CLASS: &str = "classe";
+const MODULE: &str = "módulo";
+const CONSTANT: &str = "constante";
+const MACRO: &str = "macro";
+
+/// Words that start a statement, never a C-style `type name(` definition
+const CONTROL: &[&str] = &[
+    "if", "for", "while", "switch", "return", "else", "new", "case", "do", "catch", "sizeof",
+    "throw", "delete", "await", "yield",
+];
+
+/// A definition found in the project. Line and column are 0-based, in chars.
+pub struct Symbol {
+    pub name: String,
+    /// What it defines ("função", "tipo"...), shown in the list
+    pub kind: &'static str,
+    pub path: String,
+    pub line: usize,
+    pub col: usize,
+}
+
+/// How a language writes its definitions
+struct Language {
+    /// Words a definition starts with, and what they define
+    keywords: &'static [(&'static str, &'static str)],
+    /// Words that may come before those (`pub`, `export`, `static`)
+    modifiers: &'static [&'static str],
+    /// Functions are also written `type name(...)`, as in C and Java
+    c_functions: bool,
+}
+
+fn language(ext: &str) -> Option<Language> {
+    let (keywords, modifiers, c_functions): (&[(&str, &str)], &[&str], bool) = match ext {
+        "rs" => (
+            &[
+                ("fn", FUNCTION),
+                ("struct", TYPE),
+                ("enum", TYPE),
+                ("union", TYPE),
+                ("trait", TYPE),
+                ("type", TYPE),
+                ("mod", MODULE),
+                ("const", CONSTANT),
+                ("static", CONSTANT),
+                ("macro_rules!", MACRO),
+            ],
+            &["pub", "async", "unsafe", "const", "extern", "default"],
+            false,
+        ),
+        "py" => (&[("def", FUNCTION), ("class", CLASS)], &["async"], false),
+        "go" => (&[("func", FUNCTION), ("type", TYPE)], &[], false),
+        "js" | "jsx" | "mjs" | "cjs" | "ts" | "tsx" => (
+            &[
+                ("function", FUNCTION),
+                ("class", CLASS),
+                ("interface", TYPE),
+                ("type", TYPE),
+                ("enum", TYPE),
+                ("const", FUNCTION),
+                ("let", FUNCTION),
+                ("var", FUNCTION),
+            ],
+            &["export", "default", "async", "declare", "abstract"],
+            false,
+        ),
+        "c" | "h" | "cpp" | "hpp" | "cc" | "cxx" | "hh" => (
+            &[
+                ("struct", TYPE),
+                ("enum", TYPE),
+                ("union", TYPE),
+                ("class", CLASS),
+                ("namespace", MODULE),
+            ],
+            &["typedef", "template", "static", "inline", "extern"],
+            true,
+        ),
+        "java" | "cs" => (
+            &[
+                ("class", CLASS),
+                ("interface", TYPE),
+                ("enum", TYPE),
+                ("record", TYPE),
+                ("struct", TYPE),
+                ("namespace", MODULE),
+            ],
+            &[
+                "public",
+                "private",
+                "protected",
+                "internal",
+                "static",
+                "final",
+                "abstract",
+                "sealed",
+                "partial",
+                "readonly",
+            ],
+            true,
+        ),
+        "kt" | "kts" | "swift" | "scala" => (
+            &[
+                ("fun", FUNCTION),
+                ("func", FUNCTION),
+                ("def", FUNCTION),
+                ("class", CLASS),
+                ("interface", TYPE),
+                ("protocol", TYPE),
+                ("struct", TYPE),
+                ("enum", TYPE),
+                ("object", CLASS),
+                ("trait", TYPE),
+                ("typealias", TYPE),
+            ],
+            &[
+                "public",
+                "private",
+                "protected",
+                "internal",
+                "fileprivate",
+                "open",
+                "override",
+                "abstract",
+                "final",
+                "static",
+                "data",
+                "sealed",
+                "inline",
+                "suspend",
+                "case",
+                "mutating",
+            ],
+            false,
+        ),
+        "rb" => (
+            &[("def", FUNCTION), ("class", CLASS), ("module", MODULE)],
+            &[],
+            false,
+        ),
+        "lua" => (&[("function", FUNCTION)], &["local"], false),
+        "php" => (
+            &[
+                ("function", FUNCTION),
+                ("class", CLASS),
+                ("interface", TYPE),
+                ("trait", TYPE),
+                ("enum", TYPE),
+            ],
+            &[
+                "public",
+                "private",
+                "protected",
+                "static",
+                "abstract",
+                "final",
+            ],
+            false,
+        ),
+        "sh" | "bash" | "zsh" => (&[("function", FUNCTION)], &[], false),
+        _ => return None,
+    };
+    Some(Language {
+        keywords,
+        modifiers,
+        c_functions,
+    })
+}
+
+/// Definitions in `lines` of the file at `path`, by the language of its
+/// extension; none for languages without a known way of writing them
+pub fn scan_lines<'a>(path: &str, lines: impl Iterator<Item = &'a str>) -> Vec<Symbol> {
+    let ext = Path::new(path)
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let Some(language) = language(&ext) else {
+        return vec![];
+    };
+    let shell = matches!(ext.as_str(), "sh" | "bash" | "zsh");
+    lines
+        .enumerate()
+        .filter_map(|(line, text)| {
+            let found = definition(text, &language)
+                .or_else(|| language.c_functions.then(|| c_function(text)).flatten())
+                .or_else(|| shell.then(|| shell_function(text)).flatten());
+            found.map(|(name, kind, byte)| Symbol {
+                name,
+                kind,
+                path: path.to_string(),
+                line,
+                col: text[..byte].chars().count(),
+            })
+        })
+        .collect()
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '$'
+}
+
+fn is_name(word: &str) -> bool {
+    word.chars().next().is_some_and(|c| !c.is_ascii_digit()) && word.chars().all(is_name_char)
+}
+
+/// Name, kind and byte offset of the name of a definition that starts with
+/// a keyword: `pub fn name`, `export class Name`, `func (r *T) Name`,
+/// `def self.name`
+fn definition(line: &str, language: &Language) -> Option<(String, &'static str, usize)> {
+    let mut rest = line.trim_start();
+    loop {
+        let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        let word = rest[..end].trim_end_matches('*');
+        let after = rest[end..].trim_start();
+        if let Some(&(_, kind)) = language.keywords.iter().find(|(k, _)| *k == word) {
+            if let Some(found) = defined_name(line, word, after, language, kind) {
+                return Some(found);
+            }
+        }
+        let modifier =
+            language.modifiers.contains(&word) || word.starts_with("pub(") || word.starts_with('"');
+        if !modifier || after.is_empty() {
+            return None;
+        }
+        rest = after;
+    }
+}
+
+/// The name `after` definition `keyword` starts with, if it is one
+fn defined_name(
+    line: &str,
+    keyword: &str,
+    mut after: &str,
+    language: &Language,
+    kind: &'static str,
+) -> Option<(String, &'static str, usize)> {
+    // The receiver of a Go method
+    if after.starts_with('(') {
+        after = after[after.find(')')? + 1..].trim_start();
+    }
+    let path_len = after
+        .find(|c: char| !is_name_char(c) && c != '.' && c != ':')
+        .unwrap_or(after.len());
+    let path = after[..path_len].trim_end_matches(':');
+    // `M.name`, `M:name`, `self.name`: the name is the last part
+    let start = path.rfind(['.', ':']).map_or(0, |i| i + 1);
+    let name = &path[start..];
+    let is_keyword = language.keywords.iter().any(|(k, _)| *k == name);
+    if !is_name(name) || is_keyword || language.modifiers.contains(&name) {
+        return None;
+    }
+    let trimmed = line.trim_end();
+    // Declared ahead (`struct foo;`, `class Foo;`), not defined
+    if language.c_functions && trimmed.ends_with(';') {
+        return None;
+    }
+    // `const name = (...) => ...` defines a function; other values
+    // aren't indexed
+    if kind == FUNCTION && matches!(keyword, "const" | "let" | "var") {
+        let value = after[path_len..].trim_start().strip_prefix('=')?;
+        if !(value.contains("=>") || value.contains("function")) {
+            return None;
+        }
+    }
+    let byte = line.len() - after.len() + start;
+    Some((name.to_string(), kind, byte))
+}
+
+/// `type name(...)`, as functions are written in C, C++ and Java: the
+/// name before the first `(`, after at least a return type, on a line
+/// that opens the body (or leaves it to the next line)
+fn c_function(line: &str) -> Option<(String, &'static str, usize)> {
+    let trimmed = line.trim_end();
+    if !(trimmed.ends_with('{') || trimmed.ends_with(')')) {
+        return None;
+    }
+    let open = trimmed.find('(')?;
+    let before = trimmed[..open].trim_end();
+    if before.contains(['=', '.', ';', '"', '#']) || before.contains("->") {
+        return None;
+    }
+    let start = before
+        .rfind(|c: char| !is_name_char(c))
+        .map_or(0, |i| i + 1);
+    let name = &before[start..];
+    let first = before[..start].split_whitespace().next()?;
+    if !is_name(name)
+        || CONTROL.contains(&name)
+        || CONTROL.contains(&first)
+        || !first.starts_with(|c: char| c.is_alphabetic() || c == '_')
+    {
+        return None;
+    }
+    Some((name.to_string(), FUNCTION, start))
+}
+
+/// `name() {`, a shell function written without `function`
+fn shell_function(line: &str) -> Option<(String, &'static str, usize)> {
+    let rest = line.trim_start();
+    let end = rest.find("()")?;
+    let name = &rest[..end];
+    is_name(name).then(|| (name.to_string(), FUNCTION, line.len() - rest.len()))
+}
+
+/// Index the definitions of the project under `root` on a worker thread.
+/// The result arrives as `JobMessage::Symbols`.
+pub fn start(jobs: &mut Jobs, root: &Path) -> JobId {
+    let root = root.to_path_buf();
+    jobs.spawn(String::from("Indexando símbolos"), move |context| {
+        let mut symbols = vec![];
+        project::walk_text_files(vfs::local().as_ref(), &root, context, &mut |path, text| {
+            symbols.extend(scan_lines(&path.to_string_lossy(), text.lines()));
+        });
+        context.send(JobMessage::Symbols(symbols));
+    })
+}
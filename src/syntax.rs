@@ -6,6 +6,8 @@ use crossterm::style::Color;
 pub struct ColoredChar {
     pub ch: char,
     pub fg: Color,
+    /// Background over the editor's, e.g. the swatch of a color literal
+    pub bg: Option<Color>,
 }
 
 /// Token types for syntax highlighting
@@ -15,14 +17,25 @@ enum TokenType {
     Keyword,
     String,
     Comment,
+    // `///`, `//!`, `/** */` and `/*! */`
+    DocComment,
+    // TODO, FIXME... inside a comment
+    CommentTag,
     Number,
     Type,
     Function,
+    // The name in a definition (`fn name`, `def name`)
+    FunctionDef,
+    // A parameter's name in a definition's signature
+    Parameter,
     Operator,
     Punctuation,
     Attribute,
     Macro,
     Lifetime,
+    // Lines a diff adds and removes
+    Inserted,
+    Deleted,
 }
 
 /// Colors for each token type
@@ -48,6 +61,16 @@ fn token_color(tt: TokenType) -> Color {
             g: 99,
             b: 112,
         }, // gray
+        TokenType::DocComment => Color::Rgb {
+            r: 127,
+            g: 148,
+            b: 168,
+        }, // blue gray
+        TokenType::CommentTag => Color::Rgb {
+            r: 255,
+            g: 184,
+            b: 64,
+        }, // amber
         TokenType::Number => Color::Rgb {
             r: 209,
             g: 154,
@@ -63,6 +86,16 @@ fn token_color(tt: TokenType) -> Color {
             g: 175,
             b: 239,
         }, // blue
+        TokenType::FunctionDef => Color::Rgb {
+            r: 128,
+            g: 212,
+            b: 255,
+        }, // light blue
+        TokenType::Parameter => Color::Rgb {
+            r: 224,
+            g: 108,
+            b: 117,
+        }, // red
         TokenType::Operator => Color::Rgb {
             r: 86,
             g: 182,
@@ -88,6 +121,16 @@ fn token_color(tt: TokenType) -> Color {
             g: 154,
             b: 102,
         }, // orange
+        TokenType::Inserted => Color::Rgb {
+            r: 110,
+            g: 200,
+            b: 110,
+        }, // green
+        TokenType::Deleted => Color::Rgb {
+            r: 230,
+            g: 95,
+            b: 95,
+        }, // red
     }
 }
 
@@ -100,6 +143,77 @@ struct Language {
     block_comment_end: &'static str,
     has_macros: bool,
     has_lifetimes: bool,
+    strings: Strings,
+}
+
+/// How a language writes string literals
+#[derive(Clone, Copy, PartialEq)]
+enum Strings {
+    /// "…", '…' and `…`, each ending with its line unless the line ends in
+    /// a backslash
+    Plain,
+    /// Plain, and `…` template literals spanning lines
+    JavaScript,
+    /// Plain, and raw `…` spanning lines
+    Go,
+    /// "…" spanning lines, with the b, c and r prefixes (`r#"…"#`, `br"…"`)
+    /// and byte chars `b'x'`
+    Rust,
+    /// r, b, u and f prefixes, """…""" and '''…''' spanning lines, and
+    /// f-string `{…}` fields
+    Python,
+}
+
+/// Languages offered by the language picker: (display name, canonical extension)
+pub const LANGUAGES: &[(&str, &str)] = &[
+    ("Texto", ""),
+    ("Rust", "rs"),
+    ("JavaScript", "js"),
+    ("TypeScript", "ts"),
+    ("Python", "py"),
+    ("C", "c"),
+    ("C++", "cpp"),
+    ("Go", "go"),
+    ("Java", "java"),
+    ("Kotlin", "kt"),
+    ("TOML", "toml"),
+    ("YAML", "yaml"),
+    ("Shell", "sh"),
+    ("CSS", "css"),
+    ("HTML", "html"),
+    ("XML", "xml"),
+    ("JSON", "json"),
+    ("Markdown", "md"),
+    ("SQL", "sql"),
+    ("Diff", "diff"),
+    ("Git Commit", "gitcommit"),
+];
+
+/// Human-readable language name for an extension, as shown in the status bar
+pub fn language_name(ext: &str) -> &'static str {
+    match ext {
+        "rs" => "Rust",
+        "js" | "jsx" | "mjs" => "JavaScript",
+        "ts" | "tsx" => "TypeScript",
+        "py" => "Python",
+        "c" | "h" => "C",
+        "cpp" | "cc" | "cxx" | "hpp" => "C++",
+        "go" => "Go",
+        "java" => "Java",
+        "kt" | "kts" => "Kotlin",
+        "toml" => "TOML",
+        "yaml" | "yml" => "YAML",
+        "sh" | "bash" | "zsh" => "Shell",
+        "css" | "scss" | "sass" => "CSS",
+        "html" | "htm" => "HTML",
+        "xml" | "svg" => "XML",
+        "json" => "JSON",
+        "md" | "markdown" => "Markdown",
+        "sql" => "SQL",
+        "diff" | "patch" => "Diff",
+        "gitcommit" => "Git Commit",
+        _ => "Texto",
+    }
 }
 
 fn language_for_ext(ext: &str) -> Option<Language> {
@@ -122,6 +236,7 @@ fn language_for_ext(ext: &str) -> Option<Language> {
             block_comment_end: "*/",
             has_macros: true,
             has_lifetimes: true,
+            strings: Strings::Rust,
         }),
         "js" | "jsx" | "ts" | "tsx" | "mjs" => Some(Language {
             keywords: &[
@@ -199,6 +314,7 @@ fn language_for_ext(ext: &str) -> Option<Language> {
             block_comment_end: "*/",
             has_macros: false,
             has_lifetimes: false,
+            strings: Strings::JavaScript,
         }),
         "py" => Some(Language {
             keywords: &[
@@ -231,6 +347,7 @@ fn language_for_ext(ext: &str) -> Option<Language> {
             block_comment_end: "",
             has_macros: false,
             has_lifetimes: false,
+            strings: Strings::Python,
         }),
         "c" | "h" => Some(Language {
             keywords: &[
@@ -250,6 +367,7 @@ fn language_for_ext(ext: &str) -> Option<Language> {
             block_comment_end: "*/",
             has_macros: false,
             has_lifetimes: false,
+            strings: Strings::Plain,
         }),
         "cpp" | "cc" | "cxx" | "hpp" => Some(Language {
             keywords: &[
@@ -329,6 +447,7 @@ fn language_for_ext(ext: &str) -> Option<Language> {
             block_comment_end: "*/",
             has_macros: false,
             has_lifetimes: false,
+            strings: Strings::Plain,
         }),
         "go" => Some(Language {
             keywords: &[
@@ -388,6 +507,7 @@ fn language_for_ext(ext: &str) -> Option<Language> {
             block_comment_end: "*/",
             has_macros: false,
             has_lifetimes: false,
+            strings: Strings::Go,
         }),
         "java" | "kt" | "kts" => Some(Language {
             keywords: &[
@@ -472,6 +592,7 @@ fn language_for_ext(ext: &str) -> Option<Language> {
             block_comment_end: "*/",
             has_macros: false,
             has_lifetimes: false,
+            strings: Strings::Plain,
         }),
         "toml" => Some(Language {
             keywords: &["true", "false"],
@@ -481,6 +602,7 @@ fn language_for_ext(ext: &str) -> Option<Language> {
             block_comment_end: "",
             has_macros: false,
             has_lifetimes: false,
+            strings: Strings::Plain,
         }),
         "yaml" | "yml" => Some(Language {
             keywords: &["true", "false", "null", "yes", "no", "on", "off"],
@@ -490,6 +612,7 @@ fn language_for_ext(ext: &str) -> Option<Language> {
             block_comment_end: "",
             has_macros: false,
             has_lifetimes: false,
+            strings: Strings::Plain,
         }),
         "sh" | "bash" | "zsh" => Some(Language {
             keywords: &[
@@ -503,6 +626,7 @@ fn language_for_ext(ext: &str) -> Option<Language> {
             block_comment_end: "",
             has_macros: false,
             has_lifetimes: false,
+            strings: Strings::Plain,
         }),
         "css" | "scss" | "sass" => Some(Language {
             keywords: &[
@@ -524,6 +648,7 @@ fn language_for_ext(ext: &str) -> Option<Language> {
             block_comment_end: "*/",
             has_macros: false,
             has_lifetimes: false,
+            strings: Strings::Plain,
         }),
         "html" | "htm" | "xml" | "svg" => Some(Language {
             keywords: &[],
@@ -533,6 +658,7 @@ fn language_for_ext(ext: &str) -> Option<Language> {
             block_comment_end: "-->",
             has_macros: false,
             has_lifetimes: false,
+            strings: Strings::Plain,
         }),
         "json" => Some(Language {
             keywords: &["true", "false", "null"],
@@ -542,6 +668,7 @@ fn language_for_ext(ext: &str) -> Option<Language> {
             block_comment_end: "",
             has_macros: false,
             has_lifetimes: false,
+            strings: Strings::Plain,
         }),
         "md" | "markdown" => Some(Language {
             keywords: &[],
@@ -551,6 +678,7 @@ fn language_for_ext(ext: &str) -> Option<Language> {
             block_comment_end: "",
             has_macros: false,
             has_lifetimes: false,
+            strings: Strings::Plain,
         }),
         "sql" => Some(Language {
             keywords: &[
@@ -679,6 +807,7 @@ fn language_for_ext(ext: &str) -> Option<Language> {
             block_comment_end: "*/",
             has_macros: false,
             has_lifetimes: false,
+            strings: Strings::Plain,
         }),
         _ => None,
     }
@@ -688,19 +817,424 @@ fn language_for_ext(ext: &str) -> Option<Language> {
 #[derive(Clone, Copy)]
 pub struct HighlightState {
     pub in_block_comment: bool,
+    // The open block comment is a doc comment
+    doc_comment: bool,
+    // Where a function definition is (signatures can span lines): after
+    // `fn`/`def`/... its name is next; after the name, its `(` opens the
+    // parameters; inside them, nesting depth and whether a parameter's
+    // name is next
+    fn_name_next: bool,
+    params_next: bool,
+    params_depth: usize,
+    param_next: bool,
+    // String literal still open at the end of the previous line
+    string: Option<OpenString>,
 }
 
 impl HighlightState {
     pub fn new() -> Self {
         HighlightState {
             in_block_comment: false,
+            doc_comment: false,
+            fn_name_next: false,
+            params_next: false,
+            params_depth: 0,
+            param_next: false,
+            string: None,
+        }
+    }
+}
+
+/// A string literal being read: how it ends and what it allows inside
+#[derive(Clone, Copy)]
+struct OpenString {
+    quote: char,
+    // Closed by three quotes (Python)
+    triple: bool,
+    // '#'s that follow the closing quote (Rust raw strings)
+    hashes: usize,
+    // A backslash takes the next char along (not in raw strings)
+    escapes: bool,
+    // `{…}` fields hold code (Python f-strings)
+    format: bool,
+    // Goes on past the end of the line
+    multiline: bool,
+}
+
+impl OpenString {
+    fn simple(quote: char, multiline: bool, escapes: bool) -> OpenString {
+        OpenString {
+            quote,
+            triple: false,
+            hashes: 0,
+            escapes,
+            format: false,
+            multiline,
+        }
+    }
+}
+
+/// The string literal starting at `i`, if any: how many chars its prefix
+/// and opening quote(s) take, and how it goes on
+fn string_start(line: &[char], i: usize, strings: Strings) -> Option<(usize, OpenString)> {
+    let after_word = i > 0 && (line[i - 1].is_alphanumeric() || line[i - 1] == '_');
+    match (strings, line[i]) {
+        (Strings::Rust, '"') => Some((1, OpenString::simple('"', true, true))),
+        (Strings::Rust, 'b' | 'c' | 'r') if !after_word => rust_prefixed_string(line, i),
+        // Char literals and lifetimes have their own branch
+        (Strings::Rust, _) => None,
+        (Strings::Python, _) => python_string(line, i, after_word),
+        (_, '"' | '\'') => Some((1, OpenString::simple(line[i], false, true))),
+        (Strings::JavaScript, '`') => Some((1, OpenString::simple('`', true, true))),
+        (Strings::Go, '`') => Some((1, OpenString::simple('`', true, false))),
+        (Strings::Plain, '`') => Some((1, OpenString::simple('`', false, true))),
+        _ => None,
+    }
+}
+
+/// `b"…"`, `c"…"`, `r#"…"#`, `br"…"`, `cr"…"` and the byte char `b'x'`
+fn rust_prefixed_string(line: &[char], i: usize) -> Option<(usize, OpenString)> {
+    let mut j = i;
+    if line[j] == 'b' && line.get(j + 1) == Some(&'\'') {
+        return Some((2, OpenString::simple('\'', false, true)));
+    }
+    if matches!(line[j], 'b' | 'c') {
+        j += 1;
+    }
+    let raw = line.get(j) == Some(&'r');
+    if raw {
+        j += 1;
+    }
+    let hashes = if raw {
+        line[j..].iter().take_while(|&&ch| ch == '#').count()
+    } else {
+        0
+    };
+    // `r#ident` is a raw identifier, `bar` just a word
+    if j == i || line.get(j + hashes) != Some(&'"') {
+        return None;
+    }
+    let open = OpenString {
+        quote: '"',
+        triple: false,
+        hashes,
+        escapes: !raw,
+        format: false,
+        multiline: true,
+    };
+    Some((j + hashes + 1 - i, open))
+}
+
+/// A Python string at `i`: up to two of the r, b, u and f prefixes, then
+/// one or three quotes. Raw strings still can't end in an escaped quote,
+/// so backslashes are read the same way.
+fn python_string(line: &[char], i: usize, after_word: bool) -> Option<(usize, OpenString)> {
+    let mut j = i;
+    let mut format = false;
+    if !after_word {
+        while j - i < 2
+            && j < line.len()
+            && matches!(line[j].to_ascii_lowercase(), 'r' | 'b' | 'u' | 'f')
+        {
+            format |= line[j].eq_ignore_ascii_case(&'f');
+            j += 1;
         }
     }
+    let quote = *line.get(j)?;
+    if quote != '"' && quote != '\'' {
+        return None;
+    }
+    let triple = starts_with_at(line, j, &[quote; 3]);
+    let open = OpenString {
+        quote,
+        triple,
+        hashes: 0,
+        escapes: true,
+        format,
+        multiline: triple,
+    };
+    Some((j - i + if triple { 3 } else { 1 }, open))
+}
+
+/// Color the string from `i` up to and including its closing delimiter.
+/// Returns where it stopped and whether the string goes on to the next
+/// line: when it may span lines, or the line ends in a backslash.
+fn scan_string(
+    line: &[char],
+    mut i: usize,
+    open: &OpenString,
+    result: &mut Vec<ColoredChar>,
+) -> (usize, bool) {
+    let len = line.len();
+    let push = |result: &mut Vec<ColoredChar>, ch| {
+        result.push(ColoredChar {
+            ch,
+            fg: token_color(TokenType::String),
+            bg: None,
+        })
+    };
+    while i < len {
+        let ch = line[i];
+        if open.escapes && ch == '\\' {
+            push(result, ch);
+            i += 1;
+            if i == len {
+                return (i, true);
+            }
+            push(result, line[i]);
+            i += 1;
+            continue;
+        }
+        if open.format && ch == '{' {
+            if line.get(i + 1) == Some(&'{') {
+                push(result, '{');
+                push(result, '{');
+                i += 2;
+            } else {
+                i = format_field(line, i, result);
+            }
+            continue;
+        }
+        if ch == open.quote {
+            let quotes = if open.triple { 3 } else { 1 };
+            let end = i + quotes + open.hashes;
+            let closes = starts_with_at(line, i, &[ch; 3][..quotes])
+                && line
+                    .get(i + quotes..end)
+                    .is_some_and(|hashes| hashes.iter().all(|&c| c == '#'));
+            if closes {
+                for &ch in &line[i..end] {
+                    push(result, ch);
+                }
+                return (end, false);
+            }
+        }
+        push(result, ch);
+        i += 1;
+    }
+    (i, open.multiline)
+}
+
+/// An f-string `{…}` field from the `{` at `i` to its `}`: code, colored
+/// as such, with any strings inside it. Returns where it ended.
+fn format_field(line: &[char], mut i: usize, result: &mut Vec<ColoredChar>) -> usize {
+    let mut depth = 0;
+    while i < line.len() {
+        let ch = line[i];
+        if ch == '"' || ch == '\'' {
+            result.push(ColoredChar {
+                ch,
+                fg: token_color(TokenType::String),
+                bg: None,
+            });
+            let inner = OpenString::simple(ch, false, true);
+            i = scan_string(line, i + 1, &inner, result).0;
+            continue;
+        }
+        let tt = match ch {
+            '{' => {
+                depth += 1;
+                TokenType::Punctuation
+            }
+            '}' => {
+                depth -= 1;
+                TokenType::Punctuation
+            }
+            _ => TokenType::Normal,
+        };
+        result.push(ColoredChar {
+            ch,
+            fg: token_color(tt),
+            bg: None,
+        });
+        i += 1;
+        if depth == 0 {
+            break;
+        }
+    }
+    i
+}
+
+/// Keywords that start a function definition, with its name next
+const DEFINITION_KEYWORDS: &[&str] = &["fn", "def", "function", "func", "fun"];
+
+/// Follow a function definition through the punctuation at `i`: open the
+/// parameter list at its `(` (right after the keyword too, for anonymous
+/// functions and Go receivers), track nesting inside it and where each
+/// parameter's name goes
+fn track_signature(state: &mut HighlightState, line: &[char], i: usize, lang: &Language) {
+    let ch = line[i];
+    if ch.is_whitespace() {
+        return;
+    }
+    if state.params_depth > 0 {
+        match ch {
+            '(' | '[' | '{' | '<' => state.params_depth += 1,
+            // `->` and `=>` inside a parameter's type
+            '>' if i > 0 && matches!(line[i - 1], '-' | '=') => {}
+            ')' | ']' | '}' | '>' => {
+                state.params_depth -= 1;
+                if state.params_depth == 0 {
+                    state.param_next = false;
+                }
+            }
+            ',' if state.params_depth == 1 => state.param_next = true,
+            // The type or default value follows
+            ':' | '=' if state.params_depth == 1 => state.param_next = false,
+            _ => {}
+        }
+        return;
+    }
+    // In Rust, `fn(u32) -> u32` is a function pointer type
+    let opens = ch == '(' && (state.params_next || (state.fn_name_next && !lang.has_macros));
+    if opens {
+        state.params_depth = 1;
+        state.param_next = true;
+        state.params_next = false;
+    } else if state.fn_name_next || matches!(ch, '{' | ';') {
+        state.fn_name_next = false;
+        state.params_next = false;
+    }
 }
 
 /// Highlight a single line given a language extension and carry-over state.
 /// Returns (colored chars, updated state).
 pub fn highlight_line(line: &[char], ext: &str, state: &mut HighlightState) -> Vec<ColoredChar> {
+    let mut result = highlight_tokens(line, ext, state);
+    emphasize_comment_tags(&mut result);
+    if has_color_swatches(ext) {
+        color_swatches(line, &mut result);
+    }
+    result
+}
+
+/// Lines read back for the state at a position; comments and strings
+/// opened farther up aren't seen
+const CONTEXT_LINES: usize = 500;
+
+/// What the text is at a position, for typing that depends on it
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Context {
+    Code,
+    String,
+    Comment,
+}
+
+/// Colors of the line `row` cut at `col` with `typed` after it, as the
+/// highlighter sees text not typed yet
+fn probe(
+    lines: &[Vec<char>],
+    ext: &str,
+    (row, col): (usize, usize),
+    typed: &str,
+) -> Vec<ColoredChar> {
+    let mut state = HighlightState::new();
+    for line in &lines[row.saturating_sub(CONTEXT_LINES)..row] {
+        highlight_tokens(line, ext, &mut state);
+    }
+    let mut line = lines[row][..col].to_vec();
+    line.extend(typed.chars());
+    highlight_tokens(&line, ext, &mut state)
+}
+
+/// Whether text typed at `pos` lands in code, a string or a comment
+pub fn context_at(lines: &[Vec<char>], ext: &str, pos: (usize, usize)) -> Context {
+    let colored = probe(lines, ext, pos, "x");
+    match colored.last().map(|colored| colored.fg) {
+        Some(fg) if fg == token_color(TokenType::String) => Context::String,
+        Some(fg)
+            if fg == token_color(TokenType::Comment)
+                || fg == token_color(TokenType::DocComment) =>
+        {
+            Context::Comment
+        }
+        _ => Context::Code,
+    }
+}
+
+/// Whether the quote at `pos` ends the string the cursor is in
+pub fn closes_string(lines: &[Vec<char>], ext: &str, (row, col): (usize, usize)) -> bool {
+    let colored = probe(lines, ext, (row, lines[row].len()), "");
+    let is_string = |i: usize| {
+        colored
+            .get(i)
+            .is_some_and(|c| c.fg == token_color(TokenType::String))
+    };
+    col > 0 && is_string(col - 1) && is_string(col)
+}
+
+/// Whether `quote` typed at `pos` opens a string: with its prefix, if
+/// any (`f"`, `b'`), and not as an apostrophe (`don't`) or a lifetime
+pub fn opens_string(
+    lines: &[Vec<char>],
+    ext: &str,
+    (row, col): (usize, usize),
+    quote: char,
+) -> bool {
+    let lifetimes = language_for_ext(ext).is_some_and(|lang| lang.has_lifetimes);
+    if lifetimes && quote == '\'' && lifetime_before(&lines[row][..col]) {
+        return false;
+    }
+    let colored = probe(lines, ext, (row, col), &format!("{}x{}", quote, quote));
+    let is_string = |i: usize| colored[i].fg == token_color(TokenType::String);
+    let after_word =
+        col > 0 && (colored[col - 1].ch.is_alphanumeric() || colored[col - 1].ch == '_');
+    is_string(col + 1) && (!after_word || is_string(col - 1))
+}
+
+/// Whether a `'` after `before` starts a Rust lifetime or label rather
+/// than a char: after `&`, `<`, a bound's `:` or `+`, a `,` between
+/// generics, or `break` and `continue`
+fn lifetime_before(before: &[char]) -> bool {
+    let text: String = before.iter().collect();
+    let text = text.trim_end();
+    let in_generics = text.matches('<').count() > text.matches('>').count();
+    match text.chars().last() {
+        Some('&' | '<' | ':' | '+') => true,
+        Some(',') => in_generics,
+        _ => text.ends_with("break") || text.ends_with("continue"),
+    }
+}
+
+/// Whether a `<` typed at `pos` opens generic arguments: right after a
+/// type (`Vec<`), a function being defined (`fn parse<`), `impl`,
+/// `template` or a turbofish `::`. Comparisons (`a < b`) don't.
+pub fn opens_generics(lines: &[Vec<char>], ext: &str, (row, col): (usize, usize)) -> bool {
+    let generics = matches!(
+        ext,
+        "rs" | "ts" | "tsx" | "cpp" | "cc" | "cxx" | "hpp" | "java" | "kt" | "kts"
+    );
+    if !generics || col == 0 {
+        return false;
+    }
+    let line = &lines[row][..col];
+    if line.ends_with(&[':', ':']) {
+        return true;
+    }
+    let colored = probe(lines, ext, (row, col), "");
+    let start = line
+        .iter()
+        .rposition(|&c| !c.is_alphanumeric() && c != '_')
+        .map_or(0, |i| i + 1);
+    let word: String = line[start..].iter().collect();
+    match colored.last() {
+        _ if word.is_empty() => false,
+        _ if matches!(word.as_str(), "impl" | "template") => true,
+        Some(last) => {
+            last.fg == token_color(TokenType::Type)
+                || last.fg == token_color(TokenType::FunctionDef)
+        }
+        None => false,
+    }
+}
+
+fn highlight_tokens(line: &[char], ext: &str, state: &mut HighlightState) -> Vec<ColoredChar> {
+    if ext == "gitcommit" {
+        return highlight_commit_line(line);
+    }
+    if ext == "diff" || ext == "patch" {
+        return highlight_diff_line(line);
+    }
     let lang = match language_for_ext(ext) {
         Some(l) => l,
         None => {
@@ -710,6 +1244,7 @@ pub fn highlight_line(line: &[char], ext: &str, state: &mut HighlightState) -> V
                 .map(|&ch| ColoredChar {
                     ch,
                     fg: token_color(TokenType::Normal),
+                    bg: None,
                 })
                 .collect();
         }
@@ -724,13 +1259,29 @@ pub fn highlight_line(line: &[char], ext: &str, state: &mut HighlightState) -> V
     let bc_end: Vec<char> = lang.block_comment_end.chars().collect();
 
     while i < len {
+        // --- String left open on the previous line ---
+        if let Some(open) = state.string {
+            let (end, carry) = scan_string(line, i, &open, &mut result);
+            i = end;
+            if !carry {
+                state.string = None;
+            }
+            continue;
+        }
+
         // --- Block comment continuation ---
         if state.in_block_comment {
+            let tt = if state.doc_comment {
+                TokenType::DocComment
+            } else {
+                TokenType::Comment
+            };
             if !bc_end.is_empty() && starts_with_at(line, i, &bc_end) {
                 for &ch in &bc_end {
                     result.push(ColoredChar {
                         ch,
-                        fg: token_color(TokenType::Comment),
+                        fg: token_color(tt),
+                        bg: None,
                     });
                 }
                 i += bc_end.len();
@@ -738,7 +1289,8 @@ pub fn highlight_line(line: &[char], ext: &str, state: &mut HighlightState) -> V
             } else {
                 result.push(ColoredChar {
                     ch: line[i],
-                    fg: token_color(TokenType::Comment),
+                    fg: token_color(tt),
+                    bg: None,
                 });
                 i += 1;
             }
@@ -748,10 +1300,21 @@ pub fn highlight_line(line: &[char], ext: &str, state: &mut HighlightState) -> V
         // --- Block comment start ---
         if !bc_start.is_empty() && starts_with_at(line, i, &bc_start) {
             state.in_block_comment = true;
+            // `/**` and `/*!`, but not the empty `/**/`
+            let next = line.get(i + bc_start.len()).copied();
+            state.doc_comment = bc_start == ['/', '*']
+                && (next == Some('!')
+                    || (next == Some('*') && line.get(i + bc_start.len() + 1) != Some(&'/')));
+            let tt = if state.doc_comment {
+                TokenType::DocComment
+            } else {
+                TokenType::Comment
+            };
             for &ch in &bc_start {
                 result.push(ColoredChar {
                     ch,
-                    fg: token_color(TokenType::Comment),
+                    fg: token_color(tt),
+                    bg: None,
                 });
             }
             i += bc_start.len();
@@ -760,44 +1323,43 @@ pub fn highlight_line(line: &[char], ext: &str, state: &mut HighlightState) -> V
 
         // --- Line comment ---
         if !lc_chars.is_empty() && starts_with_at(line, i, &lc_chars) {
+            // `///` and `//!`, but not a `////` separator
+            let next = line.get(i + lc_chars.len()).copied();
+            let doc = lc_chars == ['/', '/']
+                && (next == Some('!')
+                    || (next == Some('/') && line.get(i + lc_chars.len() + 1) != Some(&'/')));
+            let tt = if doc {
+                TokenType::DocComment
+            } else {
+                TokenType::Comment
+            };
             while i < len {
                 result.push(ColoredChar {
                     ch: line[i],
-                    fg: token_color(TokenType::Comment),
+                    fg: token_color(tt),
+                    bg: None,
                 });
                 i += 1;
             }
             break;
         }
 
-        // --- Strings (double-quoted) ---
-        if line[i] == '"' {
-            result.push(ColoredChar {
-                ch: '"',
-                fg: token_color(TokenType::String),
-            });
-            i += 1;
-            while i < len {
-                let ch = line[i];
+        // --- Strings, with any prefix ---
+        if let Some((opening, open)) = string_start(line, i, lang.strings) {
+            for &ch in &line[i..i + opening] {
                 result.push(ColoredChar {
                     ch,
                     fg: token_color(TokenType::String),
+                    bg: None,
                 });
-                i += 1;
-                if ch == '\\' && i < len {
-                    result.push(ColoredChar {
-                        ch: line[i],
-                        fg: token_color(TokenType::String),
-                    });
-                    i += 1;
-                } else if ch == '"' {
-                    break;
-                }
             }
+            let (end, carry) = scan_string(line, i + opening, &open, &mut result);
+            i = end;
+            state.string = carry.then_some(open);
             continue;
         }
 
-        // --- Strings (single-quoted) ---
+        // --- Char literals and lifetimes (Rust) ---
         if line[i] == '\'' {
             // In Rust, check for lifetime: 'a, 'static, etc.
             if lang.has_lifetimes && i + 1 < len && line[i + 1].is_alphabetic() {
@@ -808,18 +1370,20 @@ pub fn highlight_line(line: &[char], ext: &str, state: &mut HighlightState) -> V
                 while i < len && (line[i].is_alphanumeric() || line[i] == '_') {
                     i += 1;
                 }
-                // If it's a short word without closing quote, it's a lifetime
+                // Without a closing quote right after the word, it's a lifetime
                 let word: String = line[word_start..i].iter().collect();
-                if i >= len || line[i] != '\'' || word.len() <= 10 {
+                if i >= len || line[i] != '\'' {
                     // Lifetime
                     result.push(ColoredChar {
                         ch: '\'',
                         fg: token_color(TokenType::Lifetime),
+                        bg: None,
                     });
                     for ch in word.chars() {
                         result.push(ColoredChar {
                             ch,
                             fg: token_color(TokenType::Lifetime),
+                            bg: None,
                         });
                     }
                     continue;
@@ -832,6 +1396,7 @@ pub fn highlight_line(line: &[char], ext: &str, state: &mut HighlightState) -> V
             result.push(ColoredChar {
                 ch: '\'',
                 fg: token_color(TokenType::String),
+                bg: None,
             });
             i += 1;
             while i < len {
@@ -839,12 +1404,14 @@ pub fn highlight_line(line: &[char], ext: &str, state: &mut HighlightState) -> V
                 result.push(ColoredChar {
                     ch,
                     fg: token_color(TokenType::String),
+                    bg: None,
                 });
                 i += 1;
                 if ch == '\\' && i < len {
                     result.push(ColoredChar {
                         ch: line[i],
                         fg: token_color(TokenType::String),
+                        bg: None,
                     });
                     i += 1;
                 } else if ch == '\'' {
@@ -854,33 +1421,6 @@ pub fn highlight_line(line: &[char], ext: &str, state: &mut HighlightState) -> V
             continue;
         }
 
-        // --- Backtick strings (JS template literals) ---
-        if line[i] == '`' {
-            result.push(ColoredChar {
-                ch: '`',
-                fg: token_color(TokenType::String),
-            });
-            i += 1;
-            while i < len {
-                let ch = line[i];
-                result.push(ColoredChar {
-                    ch,
-                    fg: token_color(TokenType::String),
-                });
-                i += 1;
-                if ch == '\\' && i < len {
-                    result.push(ColoredChar {
-                        ch: line[i],
-                        fg: token_color(TokenType::String),
-                    });
-                    i += 1;
-                } else if ch == '`' {
-                    break;
-                }
-            }
-            continue;
-        }
-
         // --- Rust attributes: #[...] or #![...] ---
         if lang.has_macros
             && line[i] == '#'
@@ -891,6 +1431,7 @@ pub fn highlight_line(line: &[char], ext: &str, state: &mut HighlightState) -> V
                 result.push(ColoredChar {
                     ch: line[i],
                     fg: token_color(TokenType::Attribute),
+                    bg: None,
                 });
                 i += 1;
             }
@@ -898,6 +1439,7 @@ pub fn highlight_line(line: &[char], ext: &str, state: &mut HighlightState) -> V
                 result.push(ColoredChar {
                     ch: line[i],
                     fg: token_color(TokenType::Attribute),
+                    bg: None,
                 });
                 i += 1;
             }
@@ -924,6 +1466,7 @@ pub fn highlight_line(line: &[char], ext: &str, state: &mut HighlightState) -> V
                 result.push(ColoredChar {
                     ch: line[i],
                     fg: token_color(TokenType::Number),
+                    bg: None,
                 });
                 i += 1;
             }
@@ -944,11 +1487,13 @@ pub fn highlight_line(line: &[char], ext: &str, state: &mut HighlightState) -> V
                     result.push(ColoredChar {
                         ch,
                         fg: token_color(TokenType::Macro),
+                        bg: None,
                     });
                 }
                 result.push(ColoredChar {
                     ch: '!',
                     fg: token_color(TokenType::Macro),
+                    bg: None,
                 });
                 i += 1;
                 continue;
@@ -956,9 +1501,20 @@ pub fn highlight_line(line: &[char], ext: &str, state: &mut HighlightState) -> V
 
             // Check for function call: word(
             let is_fn_call = i < len && line[i] == '(';
+            let is_keyword = lang.keywords.contains(&word.as_str());
 
-            let tt = if lang.keywords.contains(&word.as_str()) {
+            let tt = if is_keyword {
+                if DEFINITION_KEYWORDS.contains(&word.as_str()) {
+                    state.fn_name_next = true;
+                }
                 TokenType::Keyword
+            } else if state.params_depth == 1 && state.param_next {
+                state.param_next = false;
+                TokenType::Parameter
+            } else if state.fn_name_next && state.params_depth == 0 {
+                state.fn_name_next = false;
+                state.params_next = true;
+                TokenType::FunctionDef
             } else if lang.types.contains(&word.as_str()) {
                 TokenType::Type
             } else if is_fn_call {
@@ -978,16 +1534,20 @@ pub fn highlight_line(line: &[char], ext: &str, state: &mut HighlightState) -> V
                 result.push(ColoredChar {
                     ch,
                     fg: token_color(tt),
+                    bg: None,
                 });
             }
             continue;
         }
 
+        track_signature(state, line, i, &lang);
+
         // --- Operators ---
         if "=+-*/<>!&|^%~?:".contains(line[i]) {
             result.push(ColoredChar {
                 ch: line[i],
                 fg: token_color(TokenType::Operator),
+                bg: None,
             });
             i += 1;
             continue;
@@ -998,6 +1558,7 @@ pub fn highlight_line(line: &[char], ext: &str, state: &mut HighlightState) -> V
             result.push(ColoredChar {
                 ch: line[i],
                 fg: token_color(TokenType::Punctuation),
+                bg: None,
             });
             i += 1;
             continue;
@@ -1007,6 +1568,7 @@ pub fn highlight_line(line: &[char], ext: &str, state: &mut HighlightState) -> V
         result.push(ColoredChar {
             ch: line[i],
             fg: token_color(TokenType::Normal),
+            bg: None,
         });
         i += 1;
     }
@@ -1014,6 +1576,203 @@ pub fn highlight_line(line: &[char], ext: &str, state: &mut HighlightState) -> V
     result
 }
 
+/// Words in comments that flag something for the reader
+const COMMENT_TAGS: &[&str] = &["TODO", "FIXME", "HACK", "NOTE", "SAFETY"];
+
+/// Paint the tags of `COMMENT_TAGS` found as whole words inside comments
+/// in a warning color
+fn emphasize_comment_tags(result: &mut [ColoredChar]) {
+    let in_comment = |colored: &ColoredChar| {
+        colored.fg == token_color(TokenType::Comment)
+            || colored.fg == token_color(TokenType::DocComment)
+    };
+    let is_word = |colored: &ColoredChar| colored.ch.is_alphanumeric() || colored.ch == '_';
+    let mut i = 0;
+    while i < result.len() {
+        if !in_comment(&result[i]) || (i > 0 && is_word(&result[i - 1])) {
+            i += 1;
+            continue;
+        }
+        let tag = COMMENT_TAGS.iter().find(|tag| {
+            let end = i + tag.len();
+            end <= result.len()
+                && result[i..end]
+                    .iter()
+                    .zip(tag.chars())
+                    .all(|(colored, ch)| colored.ch == ch && in_comment(colored))
+                && result.get(end).is_none_or(|colored| !is_word(colored))
+        });
+        match tag {
+            Some(tag) => {
+                for colored in &mut result[i..i + tag.len()] {
+                    colored.fg = token_color(TokenType::CommentTag);
+                    colored.bg = Some(Color::Rgb {
+                        r: 64,
+                        g: 48,
+                        b: 16,
+                    });
+                }
+                i += tag.len();
+            }
+            None => i += 1,
+        }
+    }
+}
+
+/// Stylesheets and config files, where color literals are common
+fn has_color_swatches(ext: &str) -> bool {
+    matches!(
+        ext,
+        "css" | "scss" | "sass" | "less" | "toml" | "yaml" | "yml" | "json" | "ini" | "conf"
+    )
+}
+
+/// Paint each `#RGB`, `#RRGGBB`, `#RRGGBBAA` and `rgb()`/`rgba()` literal
+/// with the color it names, the text in black or white for contrast
+fn color_swatches(line: &[char], result: &mut [ColoredChar]) {
+    let mut i = 0;
+    while i < line.len() {
+        let found = if line[i] == '#' {
+            hex_color(line, i)
+        } else if starts_with_at(line, i, &['r', 'g', 'b'])
+            && (i == 0 || !line[i - 1].is_alphanumeric())
+        {
+            rgb_function(line, i)
+        } else {
+            None
+        };
+        match found {
+            Some((len, (r, g, b))) => {
+                let luminance = 299 * r as u32 + 587 * g as u32 + 114 * b as u32;
+                let fg = if luminance > 128_000 {
+                    Color::Rgb { r: 0, g: 0, b: 0 }
+                } else {
+                    Color::Rgb {
+                        r: 255,
+                        g: 255,
+                        b: 255,
+                    }
+                };
+                for colored in result.iter_mut().skip(i).take(len) {
+                    colored.fg = fg;
+                    colored.bg = Some(Color::Rgb { r, g, b });
+                }
+                i += len;
+            }
+            None => i += 1,
+        }
+    }
+}
+
+/// Length and color of a hex color starting at the `#` in `line[start]`
+fn hex_color(line: &[char], start: usize) -> Option<(usize, (u8, u8, u8))> {
+    let digits: Vec<u8> = line[start + 1..]
+        .iter()
+        .take_while(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_digit(16).map(|d| d as u8))
+        .collect::<Option<_>>()?;
+    let (r, g, b) = match digits.len() {
+        3 | 4 => (digits[0] * 17, digits[1] * 17, digits[2] * 17),
+        6 | 8 => (
+            digits[0] * 16 + digits[1],
+            digits[2] * 16 + digits[3],
+            digits[4] * 16 + digits[5],
+        ),
+        _ => return None,
+    };
+    Some((digits.len() + 1, (r, g, b)))
+}
+
+/// Length and color of `rgb(...)` or `rgba(...)` at `line[start]`. Channels
+/// are numbers or percentages, split by commas or spaces; alpha is ignored.
+fn rgb_function(line: &[char], start: usize) -> Option<(usize, (u8, u8, u8))> {
+    let mut open = start + 3;
+    if line.get(open) == Some(&'a') {
+        open += 1;
+    }
+    if line.get(open) != Some(&'(') {
+        return None;
+    }
+    let close = open + line[open..].iter().position(|&c| c == ')')?;
+    let inner: String = line[open + 1..close].iter().collect();
+    let channels: Vec<u8> = inner
+        .split(|c: char| c == ',' || c == '/' || c.is_whitespace())
+        .filter(|part| !part.is_empty())
+        .take(3)
+        .map(|part| match part.strip_suffix('%') {
+            Some(percent) => percent
+                .parse::<f32>()
+                .ok()
+                .map(|p| (p.clamp(0.0, 100.0) * 2.55).round() as u8),
+            None => part.parse::<f32>().ok().map(|n| n.clamp(0.0, 255.0) as u8),
+        })
+        .collect::<Option<_>>()?;
+    if channels.len() != 3 {
+        return None;
+    }
+    Some((close - start + 1, (channels[0], channels[1], channels[2])))
+}
+
+/// Commit messages only have comments, and only at the start of a line
+/// ("Fix #12" is text)
+fn highlight_commit_line(line: &[char]) -> Vec<ColoredChar> {
+    let token = if line.first() == Some(&'#') {
+        TokenType::Comment
+    } else {
+        TokenType::Normal
+    };
+    line.iter()
+        .map(|&ch| ColoredChar {
+            ch,
+            fg: token_color(token),
+            bg: None,
+        })
+        .collect()
+}
+
+/// Diffs by the first char of each line: added and removed lines, the
+/// `@@` header of each hunk (the function it's in after it, as a comment)
+/// and the lines naming the files
+fn highlight_diff_line(line: &[char]) -> Vec<ColoredChar> {
+    let text: String = line.iter().collect();
+    let file_line = [
+        "diff ",
+        "index ",
+        "--- ",
+        "+++ ",
+        "new file",
+        "deleted file",
+        "similarity",
+        "rename ",
+    ]
+    .iter()
+    .any(|prefix| text.starts_with(prefix));
+    // Past the closing `@@`, the hunk's function
+    let header_end = text
+        .strip_prefix("@@")
+        .and_then(|rest| rest.find("@@"))
+        .map(|end| text[..end + 4].chars().count());
+    line.iter()
+        .enumerate()
+        .map(|(i, &ch)| {
+            let token = match line.first() {
+                _ if file_line => TokenType::Keyword,
+                _ if header_end.is_some_and(|end| i >= end) => TokenType::Comment,
+                _ if header_end.is_some() => TokenType::Function,
+                Some('+') => TokenType::Inserted,
+                Some('-') => TokenType::Deleted,
+                Some('\\') => TokenType::Comment,
+                _ => TokenType::Normal,
+            };
+            ColoredChar {
+                ch,
+                fg: token_color(token),
+                bg: None,
+            }
+        })
+        .collect()
+}
+
 fn starts_with_at(line: &[char], pos: usize, pattern: &[char]) -> bool {
     if pos + pattern.len() > line.len() {
         return false;
@@ -1055,6 +1814,7 @@ pub fn file_icon(filename: &str) -> &'static str {
         "txt" => "📄",
         "sh" | "bash" | "zsh" => "🖥️",
         "sql" => "🗃️",
+        "diff" | "patch" => "🩹",
         "dockerfile" | "docker" => "🐳",
         "git" | "gitignore" => "🔀",
         "lock" => "🔒",
@@ -1085,9 +1845,81 @@ pub fn file_icon(filename: &str) -> &'static str {
 
 /// Get the file extension from a filename/path
 pub fn get_extension(filename: &str) -> String {
-    if let Some(pos) = filename.rfind('.') {
-        filename[pos + 1..].to_lowercase()
+    // Messages git opens in the editor (GIT_EDITOR)
+    let name = filename.rsplit('/').next().unwrap_or(filename);
+    if matches!(
+        name,
+        "COMMIT_EDITMSG" | "MERGE_MSG" | "TAG_EDITMSG" | "SQUASH_MSG"
+    ) {
+        return String::from("gitcommit");
+    }
+    // Only the name's dot counts, not one of a folder (`~/.config/x`)
+    if let Some(pos) = name.rfind('.') {
+        name[pos + 1..].to_lowercase()
     } else {
         String::new()
     }
 }
+
+/// Lines of a file without an extension looked at to guess its language
+const SNIFF_LINES: usize = 5;
+
+/// Language of a file without an extension, guessed from its first lines:
+/// a shebang (`#!/usr/bin/env python3`), an XML prolog, a JSON object or
+/// array, a YAML document marker
+pub fn sniff_extension(lines: &[Vec<char>]) -> Option<&'static str> {
+    let first: String = lines.first()?.iter().collect();
+    if let Some(command) = first.strip_prefix("#!") {
+        return interpreter_extension(command);
+    }
+    let line = lines
+        .iter()
+        .take(SNIFF_LINES)
+        .map(|line| line.iter().collect::<String>())
+        .find(|line| !line.trim().is_empty())?;
+    let line = line.trim_start_matches('\u{feff}').trim();
+    if line.starts_with("<?xml") {
+        Some("xml")
+    } else if line == "---" || line.starts_with("%YAML") {
+        Some("yaml")
+    } else if line.starts_with('{') {
+        Some("json")
+    } else if let Some(rest) = line.strip_prefix('[') {
+        // Not an INI or TOML `[section]`
+        let value = rest
+            .chars()
+            .next()
+            .is_none_or(|c| c.is_whitespace() || "{[]\"-".contains(c) || c.is_ascii_digit());
+        value.then_some("json")
+    } else {
+        None
+    }
+}
+
+/// Language of the program a shebang runs: `/bin/bash`, `/usr/bin/env
+/// python3`, `/usr/bin/env -S node --harmony`
+fn interpreter_extension(command: &str) -> Option<&'static str> {
+    let mut words = command.split_whitespace();
+    let mut program = words.next()?.rsplit('/').next()?;
+    if program == "env" {
+        program = words.find(|word| !word.starts_with('-') && !word.contains('='))?;
+    }
+    // `python3.12` is `python`
+    let program = program.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.');
+    match program {
+        "python" | "pypy" => Some("py"),
+        "sh" | "bash" | "zsh" | "dash" | "ksh" | "ash" => Some("sh"),
+        "node" | "nodejs" | "deno" | "bun" => Some("js"),
+        "ts-node" | "tsx" => Some("ts"),
+        _ => None,
+    }
+}
+
+/// Spaces an indentation level usually takes in a language, where it isn't
+/// the editor's default
+pub fn indent_width(ext: &str) -> Option<usize> {
+    match ext {
+        "yaml" | "yml" | "json" => Some(2),
+        _ => None,
+    }
+}
@@ -0,0 +1,234 @@
+/// Lines above which tags aren't paired on every cursor move
+const MAX_LINES: usize = 20_000;
+
+/// HTML elements that have no closing tag
+const VOID: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "param", "source",
+    "track", "wbr",
+];
+
+/// HTML elements whose content is text, not tags
+const RAW: &[&str] = &["script", "style"];
+
+/// Whether the language `ext` is written in tags
+pub fn is_markup(ext: &str) -> bool {
+    matches!(ext, "html" | "htm" | "xml" | "svg")
+}
+
+fn is_html(ext: &str) -> bool {
+    matches!(ext, "html" | "htm")
+}
+
+/// An opening or closing tag of the document
+#[derive(Clone, PartialEq, Debug)]
+pub struct Tag {
+    pub name: String,
+    /// Position of the `<`
+    pub start: (usize, usize),
+    /// Position of the `>`
+    pub end: (usize, usize),
+    /// Column of the name, in the row of the `<`
+    pub name_col: usize,
+    pub closing: bool,
+    /// `<br/>`, or an HTML void element: no closing tag is looked for
+    pub single: bool,
+}
+
+impl Tag {
+    fn contains(&self, pos: (usize, usize)) -> bool {
+        self.start <= pos && pos <= self.end
+    }
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '-' | '_' | ':' | '.')
+}
+
+/// Every tag of the document, in order. Comments, `<!DOCTYPE>`, `<?xml?>`
+/// and the text of `<script>` and `<style>` are skipped.
+fn scan(lines: &[Vec<char>], html: bool) -> Vec<Tag> {
+    let mut tags = vec![];
+    let mut row = 0;
+    let mut col = 0;
+    // The closing tag that ends a raw text element
+    let mut raw_until: Option<String> = None;
+    while row < lines.len() {
+        let line = &lines[row];
+        if col >= line.len() {
+            row += 1;
+            col = 0;
+            continue;
+        }
+        if line[col] != '<' {
+            col += 1;
+            continue;
+        }
+        let rest: String = line[col + 1..].iter().take(3).collect();
+        if rest.starts_with("!--") {
+            (row, col) = skip_past(lines, (row, col), "-->");
+            continue;
+        }
+        if rest.starts_with(['!', '?']) {
+            (row, col) = skip_past(lines, (row, col), ">");
+            continue;
+        }
+        let closing = rest.starts_with('/');
+        let name_col = col + 1 + usize::from(closing);
+        let name_len = line[name_col.min(line.len())..]
+            .iter()
+            .take_while(|&&c| is_name_char(c))
+            .count();
+        let starts_name = line.get(name_col).is_some_and(|c| c.is_alphabetic());
+        if !starts_name {
+            col += 1;
+            continue;
+        }
+        let name: String = line[name_col..name_col + name_len].iter().collect();
+        if let Some(raw) = &raw_until {
+            if !closing || !name.eq_ignore_ascii_case(raw) {
+                col += 1;
+                continue;
+            }
+            raw_until = None;
+        }
+        let Some(end) = tag_end(lines, (row, name_col + name_len)) else {
+            col += 1;
+            continue;
+        };
+        let self_closed = end.1 > 0 && lines[end.0][end.1 - 1] == '/';
+        let void = html && VOID.iter().any(|v| name.eq_ignore_ascii_case(v));
+        if html && !closing && !self_closed && RAW.iter().any(|r| name.eq_ignore_ascii_case(r)) {
+            raw_until = Some(name.clone());
+        }
+        tags.push(Tag {
+            name,
+            start: (row, col),
+            end,
+            name_col,
+            closing,
+            single: !closing && (self_closed || void),
+        });
+        (row, col) = (end.0, end.1 + 1);
+    }
+    tags
+}
+
+/// Position right after the next `marker` from `from` on (the end of the
+/// document without one)
+fn skip_past(lines: &[Vec<char>], from: (usize, usize), marker: &str) -> (usize, usize) {
+    let marker: Vec<char> = marker.chars().collect();
+    let (mut row, mut col) = from;
+    while row < lines.len() {
+        let line = &lines[row];
+        if let Some(at) = line
+            .get(col..)
+            .and_then(|rest| rest.windows(marker.len()).position(|w| w == marker))
+        {
+            return (row, col + at + marker.len());
+        }
+        row += 1;
+        col = 0;
+    }
+    (row, 0)
+}
+
+/// The `>` that ends a tag whose attributes start at `from`; quoted values
+/// may hold a `>` of their own
+fn tag_end(lines: &[Vec<char>], from: (usize, usize)) -> Option<(usize, usize)> {
+    let mut quote: Option<char> = None;
+    let (mut row, mut col) = from;
+    while row < lines.len() {
+        for (i, &c) in lines[row].iter().enumerate().skip(col) {
+            match (quote, c) {
+                (Some(q), _) if c == q => quote = None,
+                (Some(_), _) => {}
+                (None, '"' | '\'') => quote = Some(c),
+                (None, '>') => return Some((row, i)),
+                // A new tag before this one ends: it was never closed
+                (None, '<') => return None,
+                _ => {}
+            }
+        }
+        row += 1;
+        col = 0;
+    }
+    None
+}
+
+/// Index of the tag each one pairs with, by nesting; tags left unclosed
+/// (as HTML allows for `<p>` and `<li>`) pair with none
+fn pair_up(tags: &[Tag], html: bool) -> Vec<Option<usize>> {
+    let same = |a: &str, b: &str| {
+        if html {
+            a.eq_ignore_ascii_case(b)
+        } else {
+            a == b
+        }
+    };
+    let mut partners = vec![None; tags.len()];
+    let mut open: Vec<usize> = vec![];
+    for (i, tag) in tags.iter().enumerate() {
+        if tag.single {
+            continue;
+        }
+        if !tag.closing {
+            open.push(i);
+            continue;
+        }
+        if let Some(depth) = open.iter().rposition(|&o| same(&tags[o].name, &tag.name)) {
+            let o = open[depth];
+            partners[o] = Some(i);
+            partners[i] = Some(o);
+            open.truncate(depth);
+        }
+    }
+    partners
+}
+
+/// The tag the cursor is on and the one it pairs with, in `ext` (`None`
+/// for the tag when it has no pair, `None` off a tag)
+pub fn pair_at(
+    lines: &[Vec<char>],
+    ext: &str,
+    cursor: (usize, usize),
+) -> Option<(Tag, Option<Tag>)> {
+    if !is_markup(ext) || lines.len() > MAX_LINES {
+        return None;
+    }
+    let html = is_html(ext);
+    let tags = scan(lines, html);
+    let at = tags.iter().position(|tag| tag.contains(cursor))?;
+    let partner = pair_up(&tags, html)[at].map(|p| tags[p].clone());
+    Some((tags[at].clone(), partner))
+}
+
+/// The closing tag to insert after a `>` just typed at `col` of `row`, when
+/// it ends an opening tag in `ext`
+pub fn closing_for(lines: &[Vec<char>], ext: &str, (row, col): (usize, usize)) -> Option<String> {
+    if !is_markup(ext) || lines.get(row)?.get(col) != Some(&'>') {
+        return None;
+    }
+    // Opening tags broken over a few lines are still found
+    let first = row.saturating_sub(10);
+    let mut start = None;
+    for r in (first..=row).rev() {
+        let upto = if r == row { col } else { lines[r].len() };
+        if let Some(c) = lines[r][..upto].iter().rposition(|&c| c == '<') {
+            start = Some((r, c));
+            break;
+        }
+    }
+    let start = start?;
+    let tag = scan(&lines[start.0..=row], is_html(ext))
+        .into_iter()
+        .find(|tag| tag.start == (0, start.1))?;
+    if tag.closing || tag.single || tag.end != (row - start.0, col) {
+        return None;
+    }
+    let closing = format!("</{}>", tag.name);
+    let after: String = lines[row][col + 1..].iter().collect();
+    if after.trim_start().starts_with(&closing) {
+        return None;
+    }
+    Some(closing)
+}
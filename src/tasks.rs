@@ -0,0 +1,309 @@
+use crate::config::Config;
+use crate::jobs::{JobContext, JobId, JobMessage, Jobs};
+use crossterm::style::Color;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+use std::thread;
+use std::time::Duration;
+
+/// Lines of output kept per run; older ones are dropped
+const MAX_OUTPUT_LINES: usize = 5000;
+
+/// How often a running task checks whether it was cancelled
+const WAIT_INTERVAL: Duration = Duration::from_millis(50);
+
+/// A line of output with the color of each char
+pub type ColoredLine = Vec<(char, Color)>;
+
+/// A named shell command of the project, e.g. `test = "cargo test"`
+#[derive(Clone)]
+pub struct Task {
+    pub name: String,
+    pub command: String,
+}
+
+/// Tasks of the project under `root`: the `[tasks]` of its `.reditor.toml`,
+/// then those under `[package.metadata.reditor.tasks]` (or `workspace.`) of
+/// its Cargo.toml, then the user's `[tasks]`. The first definition of a
+/// name wins.
+pub fn load(root: &Path, config: &Config) -> Vec<Task> {
+    let read = |name: &str| {
+        fs::read_to_string(root.join(name))
+            .map(|text| Config::parse(&text))
+            .ok()
+    };
+    let project = read(".reditor.toml");
+    let cargo = read("Cargo.toml");
+    let sources = [
+        project.as_ref().map(|c| c.section("tasks")),
+        cargo
+            .as_ref()
+            .map(|c| c.section("package.metadata.reditor.tasks")),
+        cargo
+            .as_ref()
+            .map(|c| c.section("workspace.metadata.reditor.tasks")),
+        Some(config.section("tasks")),
+    ];
+
+    let mut tasks: Vec<Task> = vec![];
+    for (name, command) in sources.into_iter().flatten().flatten() {
+        if !tasks.iter().any(|task| task.name == name) {
+            tasks.push(Task {
+                name: name.to_string(),
+                command: command.to_string(),
+            });
+        }
+    }
+    tasks
+}
+
+/// Run `task` in `root` on a worker thread. Its stdout and stderr arrive
+/// line by line as `JobMessage::TaskOutput`, then the exit code as
+/// `JobMessage::TaskExit`; cancelling the job kills the process.
+pub fn start(jobs: &mut Jobs, root: &Path, task: &Task) -> JobId {
+    let root = root.to_path_buf();
+    let command = task.command.clone();
+    jobs.spawn(task.name.clone(), move |context| {
+        let code = match run(&root, &command, context) {
+            Ok(code) => code,
+            Err(e) => {
+                let message = format!("Erro ao executar '{}': {}\n", command, e);
+                context.send(JobMessage::TaskOutput(message.into_bytes()));
+                None
+            }
+        };
+        context.send(JobMessage::TaskExit(code));
+    })
+}
+
+fn run(root: &Path, command: &str, context: &JobContext) -> io::Result<Option<i32>> {
+    let mut child = shell(command)
+        .current_dir(root)
+        // Output goes to a pipe; ask the usual tools for colors anyway
+        .env("CLICOLOR_FORCE", "1")
+        .env("CARGO_TERM_COLOR", "always")
+        .env("FORCE_COLOR", "1")
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let stdout = child.stdout.take();
+    let stderr = child.stderr.take();
+
+    thread::scope(|scope| {
+        if let Some(stderr) = stderr {
+            scope.spawn(|| forward_lines(stderr, context));
+        }
+        if let Some(stdout) = stdout {
+            scope.spawn(|| forward_lines(stdout, context));
+        }
+        wait(&mut child, context)
+    })
+}
+
+#[cfg(unix)]
+fn shell(command: &str) -> Command {
+    let mut shell = Command::new("sh");
+    shell.args(["-c", command]);
+    shell
+}
+
+#[cfg(not(unix))]
+fn shell(command: &str) -> Command {
+    let mut shell = Command::new("cmd");
+    shell.args(["/C", command]);
+    shell
+}
+
+/// Send each line read from `pipe` to the event loop, line break included
+fn forward_lines(pipe: impl Read, context: &JobContext) {
+    let mut reader = BufReader::new(pipe);
+    let mut line = vec![];
+    while matches!(reader.read_until(b'\n', &mut line), Ok(n) if n > 0) {
+        if !context.send(JobMessage::TaskOutput(std::mem::take(&mut line))) {
+            return;
+        }
+    }
+}
+
+/// Wait for `child` to exit, killing it if the job is cancelled
+fn wait(child: &mut Child, context: &JobContext) -> io::Result<Option<i32>> {
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(status.code());
+        }
+        if context.is_cancelled() {
+            child.kill()?;
+            child.wait()?;
+            return Ok(None);
+        }
+        thread::sleep(WAIT_INTERVAL);
+    }
+}
+
+/// Where a run is at
+#[derive(Clone, Copy, PartialEq)]
+pub enum Status {
+    Running,
+    /// Exit code; `None` when killed (by a signal or cancelled)
+    Exited(Option<i32>),
+}
+
+/// Output of the last task run, as colored lines for the output panel
+pub struct Output {
+    pub name: String,
+    pub status: Status,
+    pub lines: Vec<ColoredLine>,
+    // Color set by the last SGR sequence, which carries across lines
+    color: Color,
+    /// Lines scrolled up from the end; 0 follows the output
+    pub scroll: usize,
+}
+
+impl Output {
+    pub fn new(name: &str) -> Output {
+        Output {
+            name: name.to_string(),
+            status: Status::Running,
+            lines: vec![],
+            color: Color::Reset,
+            scroll: 0,
+        }
+    }
+
+    /// Add a line of raw output, interpreting its ANSI colors
+    pub fn push(&mut self, bytes: &[u8]) {
+        let text = String::from_utf8_lossy(bytes);
+        let text = text.strip_suffix('\n').unwrap_or(&text);
+        let text = text.strip_suffix('\r').unwrap_or(text);
+        // Progress bars redraw the line after a CR: only the last text stays
+        let text = text.rsplit('\r').next().unwrap_or(text);
+        let line = self.parse(text);
+        self.lines.push(line);
+        if self.lines.len() > MAX_OUTPUT_LINES {
+            self.lines.remove(0);
+        }
+    }
+
+    /// Colored chars of `text`, dropping escape sequences other than colors
+    fn parse(&mut self, text: &str) -> ColoredLine {
+        let mut line = vec![];
+        let mut chars = text.chars().peekable();
+        while let Some(ch) = chars.next() {
+            match ch {
+                '\x1b' if chars.peek() == Some(&'[') => {
+                    chars.next();
+                    let mut params = String::new();
+                    let mut end = None;
+                    for c in chars.by_ref() {
+                        if ('\x40'..='\x7e').contains(&c) {
+                            end = Some(c);
+                            break;
+                        }
+                        params.push(c);
+                    }
+                    if end == Some('m') {
+                        self.apply_sgr(&params);
+                    }
+                }
+                '\t' => {
+                    let spaces = 4 - line.len() % 4;
+                    line.extend(std::iter::repeat_n((' ', self.color), spaces));
+                }
+                _ if ch.is_control() => {}
+                _ => line.push((ch, self.color)),
+            }
+        }
+        line
+    }
+
+    /// Follow a "select graphic rendition" sequence (`1;31`), as far as the
+    /// foreground color goes
+    fn apply_sgr(&mut self, params: &str) {
+        let codes: Vec<u16> = params
+            .split(';')
+            .map(|code| code.parse().unwrap_or(0))
+            .collect();
+        let mut i = 0;
+        while i < codes.len() {
+            match codes[i] {
+                0 | 39 => self.color = Color::Reset,
+                code @ 30..=37 => self.color = ansi_color(code - 30, false),
+                code @ 90..=97 => self.color = ansi_color(code - 90, true),
+                38 => match codes.get(i + 1) {
+                    Some(5) => {
+                        self.color = Color::AnsiValue(codes.get(i + 2).copied().unwrap_or(0) as u8);
+                        i += 2;
+                    }
+                    Some(2) => {
+                        let part = |n: usize| codes.get(i + n).copied().unwrap_or(0) as u8;
+                        self.color = Color::Rgb {
+                            r: part(2),
+                            g: part(3),
+                            b: part(4),
+                        };
+                        i += 4;
+                    }
+                    _ => {}
+                },
+                // Background colors carry their own arguments to skip
+                48 => {
+                    i += match codes.get(i + 1) {
+                        Some(5) => 2,
+                        Some(2) => 4,
+                        _ => 0,
+                    }
+                }
+                _ => {}
+            }
+            i += 1;
+        }
+    }
+
+    /// Title of the panel: the task and how it's going
+    pub fn title(&self) -> String {
+        match self.status {
+            Status::Running => format!("{}: rodando…", self.name),
+            Status::Exited(Some(0)) => format!("{}: concluída", self.name),
+            Status::Exited(Some(code)) => format!("{}: falhou (código {})", self.name, code),
+            Status::Exited(None) => format!("{}: interrompida", self.name),
+        }
+    }
+
+    /// The `rows` lines the panel shows, given the scroll
+    pub fn visible(&self, rows: usize) -> Vec<ColoredLine> {
+        let end = self.lines.len().saturating_sub(self.scroll);
+        let start = end.saturating_sub(rows);
+        self.lines[start..end].to_vec()
+    }
+
+    /// Scroll by `delta` lines (positive goes back in the output)
+    pub fn scroll_by(&mut self, delta: isize, rows: usize) {
+        let max = self.lines.len().saturating_sub(rows);
+        self.scroll = self.scroll.saturating_add_signed(delta).min(max);
+    }
+}
+
+/// One of the 16 terminal colors, bright or not
+fn ansi_color(index: u16, bright: bool) -> Color {
+    match (index, bright) {
+        (0, false) => Color::Black,
+        (1, false) => Color::DarkRed,
+        (2, false) => Color::DarkGreen,
+        (3, false) => Color::DarkYellow,
+        (4, false) => Color::DarkBlue,
+        (5, false) => Color::DarkMagenta,
+        (6, false) => Color::DarkCyan,
+        (7, false) => Color::Grey,
+        (0, true) => Color::DarkGrey,
+        (1, true) => Color::Red,
+        (2, true) => Color::Green,
+        (3, true) => Color::Yellow,
+        (4, true) => Color::Blue,
+        (5, true) => Color::Magenta,
+        (6, true) => Color::Cyan,
+        _ => Color::White,
+    }
+}
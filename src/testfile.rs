@@ -0,0 +1,172 @@
+use std::path::{Path, PathBuf};
+
+/// Where `test-file` goes from a file
+pub enum Counterpart {
+    /// The `#[cfg(test)]` module at this row of the file itself
+    InFile(usize),
+    /// The test of a source file, with what it starts with when created
+    Test(PathBuf, String),
+    /// The source a test file is for; it isn't created when missing
+    Source(PathBuf),
+}
+
+/// The test of the source file at `path` by its language's convention, or
+/// the source of a test file:
+///
+/// - Rust: the file's `#[cfg(test)]` module, else `tests/foo.rs` of the crate
+/// - Python: `test_foo.py`, in a `tests` folder if the project has one
+/// - Go: `foo_test.go`
+/// - JavaScript/TypeScript: `foo.test.js` (`.spec.` counts too)
+/// - Java: `src/test/java/.../FooTest.java`
+pub fn counterpart(path: &Path, lines: &[Vec<char>]) -> Option<Counterpart> {
+    let dir = path.parent()?;
+    let name = path.file_name()?.to_string_lossy().to_string();
+    let (stem, ext) = name.rsplit_once('.')?;
+    let first_existing = |candidates: Vec<PathBuf>| {
+        let fallback = candidates.first().cloned();
+        candidates.into_iter().find(|c| c.is_file()).or(fallback)
+    };
+    match ext {
+        "rs" => {
+            let root = ancestor_with(dir, "Cargo.toml")?;
+            if dir == root.join("tests") {
+                let src = root.join("src");
+                let source = first_existing(vec![src.join(&name), src.join(stem).join("mod.rs")])?;
+                return Some(Counterpart::Source(source));
+            }
+            if let Some(row) = lines.iter().position(|line| {
+                let text: String = line.iter().collect();
+                text.trim() == "#[cfg(test)]"
+            }) {
+                return Some(Counterpart::InFile(row));
+            }
+            let module = match stem {
+                "mod" => dir.file_name()?.to_string_lossy().to_string(),
+                _ => stem.to_string(),
+            };
+            let skeleton = format!("#[test]\nfn {}_works() {{\n}}\n", module.replace('-', "_"));
+            Some(Counterpart::Test(
+                root.join("tests").join(format!("{}.rs", module)),
+                skeleton,
+            ))
+        }
+        "py" => {
+            if let Some(source) = stem
+                .strip_prefix("test_")
+                .or_else(|| stem.strip_suffix("_test"))
+            {
+                let file = format!("{}.py", source);
+                let mut candidates = vec![dir.join(&file)];
+                if dir.file_name().is_some_and(|d| d == "tests") {
+                    let above = dir.parent()?;
+                    candidates.push(above.join(&file));
+                    candidates.push(above.join("src").join(&file));
+                }
+                return first_existing(candidates).map(Counterpart::Source);
+            }
+            let file = format!("test_{}.py", stem);
+            let mut candidates = vec![dir.join("tests").join(&file)];
+            if let Some(tests) = ancestor_with(dir, "tests").map(|root| root.join("tests")) {
+                candidates.push(tests.join(&file));
+            }
+            candidates.push(dir.join(&file));
+            let test = candidates
+                .iter()
+                .find(|c| c.is_file())
+                .or_else(|| {
+                    candidates
+                        .iter()
+                        .find(|c| c.parent().is_some_and(Path::is_dir))
+                })
+                .cloned()?;
+            let skeleton = format!("def test_{}():\n    pass\n", stem.replace('-', "_"));
+            Some(Counterpart::Test(test, skeleton))
+        }
+        "go" => {
+            if let Some(source) = stem.strip_suffix("_test") {
+                return Some(Counterpart::Source(dir.join(format!("{}.go", source))));
+            }
+            let package = declared(lines, "package ").unwrap_or_else(|| String::from("main"));
+            let skeleton = format!(
+                "package {}\n\nimport \"testing\"\n\nfunc Test{}(t *testing.T) {{\n}}\n",
+                package,
+                capitalized(stem)
+            );
+            Some(Counterpart::Test(
+                dir.join(format!("{}_test.go", stem)),
+                skeleton,
+            ))
+        }
+        "js" | "jsx" | "ts" | "tsx" | "mjs" => {
+            for marker in [".test", ".spec"] {
+                if let Some(source) = stem.strip_suffix(marker) {
+                    let file = format!("{}.{}", source, ext);
+                    let mut candidates = vec![dir.join(&file)];
+                    if dir.file_name().is_some_and(|d| d == "__tests__") {
+                        candidates.push(dir.parent()?.join(&file));
+                    }
+                    return first_existing(candidates).map(Counterpart::Source);
+                }
+            }
+            let file = format!("{}.test.{}", stem, ext);
+            let test = first_existing(vec![
+                dir.join(&file),
+                dir.join("__tests__").join(&file),
+                dir.join(format!("{}.spec.{}", stem, ext)),
+            ])?;
+            let skeleton = format!(
+                "import {{}} from './{}';\n\ntest('{}', () => {{\n}});\n",
+                stem, stem
+            );
+            Some(Counterpart::Test(test, skeleton))
+        }
+        "java" => {
+            let text = path.to_string_lossy();
+            if let Some(source) = stem.strip_suffix("Test") {
+                let main = text
+                    .replacen("/src/test/java/", "/src/main/java/", 1)
+                    .replacen(&name, &format!("{}.java", source), 1);
+                return Some(Counterpart::Source(PathBuf::from(main)));
+            }
+            let test = text
+                .replacen("/src/main/java/", "/src/test/java/", 1)
+                .replacen(&name, &format!("{}Test.java", stem), 1);
+            let package = declared(lines, "package ")
+                .map(|package| format!("package {};\n\n", package.trim_end_matches(';')))
+                .unwrap_or_default();
+            let skeleton = format!("{}class {}Test {{\n}}\n", package, stem);
+            Some(Counterpart::Test(PathBuf::from(test), skeleton))
+        }
+        _ => None,
+    }
+}
+
+/// The nearest folder from `dir` up that has `entry` in it
+fn ancestor_with(dir: &Path, entry: &str) -> Option<PathBuf> {
+    dir.ancestors()
+        .find(|ancestor| ancestor.join(entry).exists())
+        .map(Path::to_path_buf)
+}
+
+/// What follows `keyword` on the first line that starts with it
+/// (`package foo`)
+fn declared(lines: &[Vec<char>], keyword: &str) -> Option<String> {
+    lines.iter().find_map(|line| {
+        let text: String = line.iter().collect();
+        let rest = text.trim_start().strip_prefix(keyword)?;
+        Some(rest.trim().to_string()).filter(|name| !name.is_empty())
+    })
+}
+
+/// `foo_bar` as `FooBar`, for Go test names
+fn capitalized(stem: &str) -> String {
+    stem.split(['_', '-', '.'])
+        .map(|part| {
+            let mut chars = part.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect(),
+                None => String::new(),
+            }
+        })
+        .collect()
+}
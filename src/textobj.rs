@@ -0,0 +1,271 @@
+use crate::buffer_file::is_word_char;
+
+/// Text a text object covers
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Span {
+    /// From (row, col) up to (row, col) excluded; empty for `i"` on `""`
+    Chars((usize, usize), (usize, usize)),
+    /// Whole lines, first to last included (paragraphs, the inside of a
+    /// block whose brackets sit on lines of their own)
+    Lines(usize, usize),
+}
+
+/// The text object `object` (`w`, `W`, `"`, `'`, `` ` ``, `(`/`)`/`b`,
+/// `[`/`]`, `{`/`}`/`B`, `<`/`>`, `p`) at `cursor`, the `i` variant
+/// (inner) or the `a` one (`around`, with the delimiters or the space
+/// after it)
+pub fn find(
+    lines: &[Vec<char>],
+    cursor: (usize, usize),
+    around: bool,
+    object: char,
+) -> Option<Span> {
+    match object {
+        'w' => word(lines, cursor, around, false),
+        'W' => word(lines, cursor, around, true),
+        '"' | '\'' | '`' => quoted(lines, cursor, around, object),
+        'p' => paragraph(lines, cursor.0, around),
+        _ => {
+            let (open, close) = pair(object)?;
+            block(lines, cursor, around, open, close)
+        }
+    }
+}
+
+/// Opening and closing bracket a text object names
+fn pair(object: char) -> Option<(char, char)> {
+    match object {
+        '(' | ')' | 'b' => Some(('(', ')')),
+        '[' | ']' => Some(('[', ']')),
+        '{' | '}' | 'B' => Some(('{', '}')),
+        '<' | '>' => Some(('<', '>')),
+        _ => None,
+    }
+}
+
+/// Where the bracket at `pos` is closed (or opened, for a closing one),
+/// skipping the pairs nested in between
+pub fn matching_bracket(lines: &[Vec<char>], pos: (usize, usize)) -> Option<(usize, usize)> {
+    let ch = *lines.get(pos.0)?.get(pos.1)?;
+    let brackets = [('(', ')'), ('[', ']'), ('{', '}'), ('<', '>')];
+    if let Some(&(open, close)) = brackets.iter().find(|(open, _)| *open == ch) {
+        scan_forward(lines, next(lines, pos)?, open, close)
+    } else if let Some(&(open, close)) = brackets.iter().find(|(_, close)| *close == ch) {
+        scan_back(lines, prev(lines, pos)?, open, close)
+    } else {
+        None
+    }
+}
+
+/// Position of the char after `pos`, across line breaks
+fn next(lines: &[Vec<char>], (row, col): (usize, usize)) -> Option<(usize, usize)> {
+    if col + 1 < lines[row].len() {
+        return Some((row, col + 1));
+    }
+    (row + 1..lines.len())
+        .find(|&row| !lines[row].is_empty())
+        .map(|row| (row, 0))
+}
+
+/// Position of the char before `pos`, across line breaks
+fn prev(lines: &[Vec<char>], (row, col): (usize, usize)) -> Option<(usize, usize)> {
+    if col > 0 && !lines[row].is_empty() {
+        return Some((row, (col - 1).min(lines[row].len() - 1)));
+    }
+    (0..row)
+        .rev()
+        .find(|&row| !lines[row].is_empty())
+        .map(|row| (row, lines[row].len() - 1))
+}
+
+/// First `close` from `from` on that isn't matched by an `open` after `from`
+fn scan_forward(
+    lines: &[Vec<char>],
+    from: (usize, usize),
+    open: char,
+    close: char,
+) -> Option<(usize, usize)> {
+    let mut depth = 0;
+    let mut pos = Some(from);
+    while let Some(at) = pos {
+        match lines[at.0][at.1] {
+            c if c == close && depth == 0 => return Some(at),
+            c if c == close => depth -= 1,
+            c if c == open => depth += 1,
+            _ => {}
+        }
+        pos = next(lines, at);
+    }
+    None
+}
+
+/// First `open` from `from` back that isn't matched by a `close` before
+/// `from`
+fn scan_back(
+    lines: &[Vec<char>],
+    from: (usize, usize),
+    open: char,
+    close: char,
+) -> Option<(usize, usize)> {
+    let mut depth = 0;
+    let mut pos = Some(from);
+    while let Some(at) = pos {
+        match lines[at.0][at.1] {
+            c if c == open && depth == 0 => return Some(at),
+            c if c == open => depth -= 1,
+            c if c == close => depth += 1,
+            _ => {}
+        }
+        pos = prev(lines, at);
+    }
+    None
+}
+
+/// The `open`…`close` block around `cursor`. Inside a block whose brackets
+/// end and start their lines (a function body), `i` takes the lines in
+/// between whole, leaving the brackets' lines as they are.
+fn block(
+    lines: &[Vec<char>],
+    cursor: (usize, usize),
+    around: bool,
+    open: char,
+    close: char,
+) -> Option<Span> {
+    let line = lines.get(cursor.0)?;
+    let at = (cursor.0, cursor.1.min(line.len().saturating_sub(1)));
+    let start = match line.get(at.1) {
+        None => scan_back(lines, prev(lines, at)?, open, close)?,
+        Some(&c) if c == open => at,
+        Some(&c) if c == close => matching_bracket(lines, at)?,
+        Some(_) => scan_back(lines, at, open, close)?,
+    };
+    let end = matching_bracket(lines, start)?;
+    if around {
+        return Some(Span::Chars(start, (end.0, end.1 + 1)));
+    }
+    let open_ends_line = start.1 + 1 == lines[start.0].len();
+    let close_starts_line = lines[end.0][..end.1].iter().all(|c| c.is_whitespace());
+    if open_ends_line && close_starts_line && end.0 > start.0 + 1 {
+        return Some(Span::Lines(start.0 + 1, end.0 - 1));
+    }
+    Some(Span::Chars((start.0, start.1 + 1), end))
+}
+
+/// The string between `quote`s that the cursor is in (or on), or else the
+/// next one in the line. Quotes escaped with `\` don't count.
+fn quoted(lines: &[Vec<char>], cursor: (usize, usize), around: bool, quote: char) -> Option<Span> {
+    let (row, col) = cursor;
+    let line = lines.get(row)?;
+    let quotes: Vec<usize> = (0..line.len())
+        .filter(|&i| line[i] == quote && (i == 0 || line[i - 1] != '\\'))
+        .collect();
+    let (start, end) = quotes
+        .chunks_exact(2)
+        .map(|pair| (pair[0], pair[1]))
+        .find(|&(start, end)| start <= col && col <= end)
+        .or_else(|| {
+            quotes
+                .chunks_exact(2)
+                .map(|pair| (pair[0], pair[1]))
+                .find(|&(start, _)| start > col)
+        })?;
+    if !around {
+        return Some(Span::Chars((row, start + 1), (row, end)));
+    }
+    let (start, end) = with_space(line, start, end + 1);
+    Some(Span::Chars((row, start), (row, end)))
+}
+
+/// `start..end` of `line` plus the blanks after it, or the ones before it
+/// when none follow (the space `aw` and `a"` take along)
+fn with_space(line: &[char], start: usize, end: usize) -> (usize, usize) {
+    let after = line[end..]
+        .iter()
+        .position(|c| !c.is_whitespace())
+        .unwrap_or(line.len() - end);
+    if after > 0 {
+        return (start, end + after);
+    }
+    let before = line[..start]
+        .iter()
+        .rev()
+        .position(|c| !c.is_whitespace())
+        .unwrap_or(start);
+    // Indentation stays: only blanks after other text are taken
+    if before == start {
+        return (start, end);
+    }
+    (start - before, end)
+}
+
+/// The word at the cursor: a run of identifier chars, of other symbols, or
+/// of blanks (any non-blanks for `W`)
+fn word(lines: &[Vec<char>], cursor: (usize, usize), around: bool, big: bool) -> Option<Span> {
+    let (row, col) = cursor;
+    let line = lines.get(row)?;
+    if line.is_empty() {
+        return None;
+    }
+    let col = col.min(line.len() - 1);
+    let class = |c: char| match c {
+        _ if c.is_whitespace() => 0,
+        _ if big || is_word_char(c) => 1,
+        _ => 2,
+    };
+    let run = |col: usize| {
+        let kind = class(line[col]);
+        let start = line[..col]
+            .iter()
+            .rposition(|&c| class(c) != kind)
+            .map_or(0, |i| i + 1);
+        let end = line[col..]
+            .iter()
+            .position(|&c| class(c) != kind)
+            .map_or(line.len(), |i| col + i);
+        (start, end)
+    };
+    let (start, end) = run(col);
+    if !around {
+        return Some(Span::Chars((row, start), (row, end)));
+    }
+    let (start, end) = if class(line[col]) != 0 {
+        with_space(line, start, end)
+    } else if end < line.len() {
+        // On blanks, `aw` takes them and the word after
+        (start, run(end).1)
+    } else {
+        (start, end)
+    };
+    Some(Span::Chars((row, start), (row, end)))
+}
+
+/// The lines of the paragraph at `row` (or of the blank lines, when `row`
+/// is blank). `ap` takes the lines after it too: the blank ones after a
+/// paragraph, or the paragraph after blank ones; at the end of the file, a
+/// paragraph takes the blank lines before it instead.
+fn paragraph(lines: &[Vec<char>], row: usize, around: bool) -> Option<Span> {
+    lines.get(row)?;
+    let blank = |row: usize| lines[row].iter().all(|c| c.is_whitespace());
+    let run = |row: usize| {
+        let kind = blank(row);
+        let first = (0..row)
+            .rev()
+            .find(|&r| blank(r) != kind)
+            .map_or(0, |r| r + 1);
+        let last = (row + 1..lines.len())
+            .find(|&r| blank(r) != kind)
+            .map_or(lines.len() - 1, |r| r - 1);
+        (first, last)
+    };
+    let (first, last) = run(row);
+    if !around {
+        return Some(Span::Lines(first, last));
+    }
+    if last + 1 < lines.len() {
+        return Some(Span::Lines(first, run(last + 1).1));
+    }
+    if first > 0 && !blank(row) {
+        return Some(Span::Lines(run(first - 1).0, last));
+    }
+    Some(Span::Lines(first, last))
+}
@@ -0,0 +1,24 @@
+/// "2024-05-01T13:45:00" in local time
+#[cfg(unix)]
+pub fn local_time() -> String {
+    // SAFETY: localtime_r only writes into the tm we pass it
+    unsafe {
+        let now = libc::time(std::ptr::null_mut());
+        let mut tm: libc::tm = std::mem::zeroed();
+        libc::localtime_r(&now, &mut tm);
+        format!(
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+            tm.tm_year + 1900,
+            tm.tm_mon + 1,
+            tm.tm_mday,
+            tm.tm_hour,
+            tm.tm_min,
+            tm.tm_sec
+        )
+    }
+}
+
+#[cfg(not(unix))]
+pub fn local_time() -> String {
+    String::new()
+}
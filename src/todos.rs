@@ -0,0 +1,87 @@
+use crate::jobs::{JobId, JobMessage, Jobs};
+use crate::project;
+use crate::vfs;
+use std::path::Path;
+
+/// Comment tags collected by the scanner
+const TAGS: &[&str] = &["TODO", "FIXME", "HACK"];
+
+/// Text that starts a comment in the supported languages
+const COMMENT_MARKERS: &[&str] = &["//", "/*", "#", "--", "<!--", ";", "*"];
+
+pub struct Todo {
+    pub path: String,
+    /// 0-based
+    pub line: usize,
+    /// The comment from the tag on, e.g. "FIXME: tratar erro"
+    pub text: String,
+}
+
+/// Find tagged comments in `lines` of the file at `path`
+pub fn scan_lines<'a>(path: &str, lines: impl Iterator<Item = &'a str>) -> Vec<Todo> {
+    lines
+        .enumerate()
+        .filter_map(|(line, text)| {
+            find_tag(text).map(|start| Todo {
+                path: path.to_string(),
+                line,
+                text: text[start..].trim_end().to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Byte offset of a tag written inside a comment, as a whole word
+fn find_tag(line: &str) -> Option<usize> {
+    let comment = COMMENT_MARKERS
+        .iter()
+        .filter_map(|marker| line.find(marker))
+        .min()?;
+    TAGS.iter()
+        .filter_map(|tag| {
+            let start = comment + line[comment..].find(tag)?;
+            let before = line[..start].chars().next_back();
+            let after = line[start + tag.len()..].chars().next();
+            let boundary = |c: Option<char>| !c.is_some_and(|c| c.is_alphanumeric() || c == '_');
+            (boundary(before) && boundary(after)).then_some(start)
+        })
+        .min()
+}
+
+/// Scan the project under `root` on a worker thread. The result arrives as
+/// `JobMessage::Todos`.
+pub fn start(jobs: &mut Jobs, root: &Path) -> JobId {
+    let root = root.to_path_buf();
+    jobs.spawn(String::from("Procurando TODOs"), move |context| {
+        let mut todos = vec![];
+        project::walk_text_files(vfs::local().as_ref(), &root, context, &mut |path, text| {
+            todos.extend(scan_lines(&path.to_string_lossy(), text.lines()));
+        });
+        todos.sort_by(|a, b| a.path.cmp(&b.path).then(a.line.cmp(&b.line)));
+        context.send(JobMessage::Todos(todos));
+    })
+}
+
+/// Panel rows grouped by file: a header per file followed by its entries,
+/// plus where each row jumps to (a header goes to the file's first entry)
+pub fn panel_rows(todos: &[Todo], root: Option<&Path>) -> (Vec<String>, Vec<(String, usize)>) {
+    let mut rows = vec![];
+    let mut targets = vec![];
+    for (i, todo) in todos.iter().enumerate() {
+        if i == 0 || todos[i - 1].path != todo.path {
+            let count = todos[i..]
+                .iter()
+                .take_while(|other| other.path == todo.path)
+                .count();
+            let shown = root
+                .and_then(|root| Path::new(&todo.path).strip_prefix(root).ok())
+                .map(|relative| relative.to_string_lossy().to_string())
+                .unwrap_or_else(|| todo.path.clone());
+            rows.push(format!("{} ({})", shown, count));
+            targets.push((todo.path.clone(), todo.line));
+        }
+        rows.push(format!("  {:>5}  {}", todo.line + 1, todo.text));
+        targets.push((todo.path.clone(), todo.line));
+    }
+    (rows, targets)
+}
@@ -0,0 +1,259 @@
+/// An encode/decode operation offered by the `transform` command
+pub struct Transform {
+    pub name: &'static str,
+    pub apply: fn(&str) -> Result<String, String>,
+}
+
+pub const TRANSFORMS: &[Transform] = &[
+    Transform {
+        name: "Base64: codificar",
+        apply: |text| Ok(base64_encode(text.as_bytes())),
+    },
+    Transform {
+        name: "Base64: decodificar",
+        apply: |text| utf8(base64_decode(text)?),
+    },
+    Transform {
+        name: "URL: codificar",
+        apply: |text| Ok(url_encode(text)),
+    },
+    Transform {
+        name: "URL: decodificar",
+        apply: |text| url_decode(text).ok_or_else(|| String::from("escape % inválido")),
+    },
+    Transform {
+        name: "HTML: escapar entidades",
+        apply: |text| Ok(html_escape(text)),
+    },
+    Transform {
+        name: "HTML: decodificar entidades",
+        apply: |text| Ok(html_unescape(text)),
+    },
+    Transform {
+        name: "JSON: escapar string",
+        apply: |text| Ok(json_escape(text)),
+    },
+    Transform {
+        name: "JSON: desescapar string",
+        apply: json_unescape,
+    },
+    Transform {
+        name: "Hex: codificar",
+        apply: |text| Ok(text.bytes().map(|b| format!("{:02x}", b)).collect()),
+    },
+    Transform {
+        name: "Hex: decodificar",
+        apply: |text| utf8(hex_decode(text)?),
+    },
+];
+
+fn utf8(bytes: Vec<u8>) -> Result<String, String> {
+    String::from_utf8(bytes).map_err(|_| String::from("o resultado não é texto UTF-8"))
+}
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn base64_encode(bytes: &[u8]) -> String {
+    let mut out = String::new();
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &b)| n | (b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(BASE64[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+/// Accepts the URL-safe alphabet, missing padding and line breaks
+fn base64_decode(text: &str) -> Result<Vec<u8>, String> {
+    let mut out = vec![];
+    let mut bits = 0u32;
+    let mut count = 0;
+    for c in text.chars().filter(|c| !c.is_whitespace()) {
+        let value = match c {
+            'A'..='Z' => c as u32 - 'A' as u32,
+            'a'..='z' => c as u32 - 'a' as u32 + 26,
+            '0'..='9' => c as u32 - '0' as u32 + 52,
+            '+' | '-' => 62,
+            '/' | '_' => 63,
+            '=' => break,
+            _ => return Err(format!("caractere inválido em Base64: '{}'", c)),
+        };
+        bits = bits << 6 | value;
+        count += 6;
+        if count >= 8 {
+            count -= 8;
+            out.push((bits >> count) as u8);
+        }
+    }
+    Ok(out)
+}
+
+/// Percent-encode everything but the unreserved characters of RFC 3986
+pub fn url_encode(text: &str) -> String {
+    text.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+/// Decode `%XX` escapes. `None` if one is malformed or the result isn't UTF-8.
+pub fn url_decode(text: &str) -> Option<String> {
+    let bytes = text.as_bytes();
+    let mut out: Vec<u8> = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = std::str::from_utf8(bytes.get(i + 1..i + 3)?).ok()?;
+            out.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            out.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(out).ok()
+}
+
+fn html_escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// Named entities for markup characters plus numeric ones (`&#233;`,
+/// `&#xE9;`); anything else is left as written
+fn html_unescape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let decoded = rest.find(';').filter(|&end| end <= 10).and_then(|end| {
+            let entity = &rest[1..end];
+            let c = match entity {
+                "amp" => Some('&'),
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                "nbsp" => Some('\u{a0}'),
+                _ => {
+                    let code = match entity.strip_prefix("#x").or(entity.strip_prefix("#X")) {
+                        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+                        None => entity.strip_prefix('#').and_then(|n| n.parse().ok()),
+                    };
+                    code.and_then(char::from_u32)
+                }
+            };
+            c.map(|c| (c, end))
+        });
+        match decoded {
+            Some((c, end)) => {
+                out.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Text ready to go between the quotes of a JSON string
+fn json_escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+fn json_unescape(text: &str) -> Result<String, String> {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('r') => out.push('\r'),
+            Some('t') => out.push('\t'),
+            Some('b') => out.push('\u{8}'),
+            Some('f') => out.push('\u{c}'),
+            Some(c @ ('"' | '\\' | '/')) => out.push(c),
+            Some('u') => {
+                let mut code = hex4(&mut chars)?;
+                // A surrogate pair spells one character outside the BMP
+                if (0xD800..0xDC00).contains(&code) {
+                    if chars.next() != Some('\\') || chars.next() != Some('u') {
+                        return Err(String::from("par \\u incompleto"));
+                    }
+                    let low = hex4(&mut chars)?;
+                    code = 0x10000 + ((code - 0xD800) << 10) + (low.wrapping_sub(0xDC00) & 0x3FF);
+                }
+                out.push(char::from_u32(code).ok_or("escape \\u inválido")?);
+            }
+            _ => return Err(String::from("escape inválido")),
+        }
+    }
+    Ok(out)
+}
+
+fn hex4(chars: &mut std::str::Chars) -> Result<u32, String> {
+    let digits: String = chars.take(4).collect();
+    if digits.len() != 4 {
+        return Err(String::from("escape \\u inválido"));
+    }
+    u32::from_str_radix(&digits, 16).map_err(|_| String::from("escape \\u inválido"))
+}
+
+/// Pairs of hex digits; spaces, line breaks and a `0x` prefix are skipped
+fn hex_decode(text: &str) -> Result<Vec<u8>, String> {
+    let text = text.trim();
+    let text = text.strip_prefix("0x").unwrap_or(text);
+    let digits: Vec<char> = text.chars().filter(|c| !c.is_whitespace()).collect();
+    if !digits.len().is_multiple_of(2) {
+        return Err(String::from("número ímpar de dígitos hex"));
+    }
+    digits
+        .chunks(2)
+        .map(|pair| {
+            let pair: String = pair.iter().collect();
+            u8::from_str_radix(&pair, 16).map_err(|_| format!("'{}' não é hex", pair))
+        })
+        .collect()
+}
@@ -0,0 +1,171 @@
+use crate::time;
+use crate::transform;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// A file or folder moved to the trash, with what it takes to put it back
+pub struct Trashed {
+    pub original: PathBuf,
+    location: PathBuf,
+    // The `.trashinfo` that describes it, on XDG systems
+    info: Option<PathBuf>,
+}
+
+/// Move `path` to the system trash: the XDG trash on Linux and BSD
+/// (`~/.local/share/Trash`), `~/.Trash` on macOS and the Recycle Bin on
+/// Windows.
+pub fn trash(path: &Path) -> io::Result<Trashed> {
+    let original = std::path::absolute(path)?;
+    if fs::symlink_metadata(&original).is_err() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            "arquivo não existe",
+        ));
+    }
+    if cfg!(windows) {
+        recycle(&original)?;
+        return Ok(Trashed {
+            location: original.clone(),
+            original,
+            info: None,
+        });
+    }
+    if cfg!(target_os = "macos") {
+        let dir = home()?.join(".Trash");
+        let location = free_name(&dir, &original, |_| true);
+        move_to(&original, &location)?;
+        return Ok(Trashed {
+            original,
+            location,
+            info: None,
+        });
+    }
+    xdg_trash(original)
+}
+
+/// Put a trashed file back where it was, unless something took its place
+pub fn restore(trashed: &Trashed) -> io::Result<()> {
+    if cfg!(windows) {
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "restaure pela Lixeira do Windows",
+        ));
+    }
+    if fs::symlink_metadata(&trashed.original).is_ok() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            "já existe um arquivo com esse nome",
+        ));
+    }
+    if let Some(parent) = trashed.original.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::rename(&trashed.location, &trashed.original)?;
+    if let Some(info) = &trashed.info {
+        let _ = fs::remove_file(info);
+    }
+    Ok(())
+}
+
+fn home() -> io::Result<PathBuf> {
+    std::env::var_os("HOME")
+        .map(PathBuf::from)
+        .ok_or_else(|| io::Error::other("HOME não definido"))
+}
+
+/// The trash of the freedesktop.org spec: the file goes to `files/` and a
+/// `.trashinfo` with its old path and the deletion date goes to `info/`
+fn xdg_trash(original: PathBuf) -> io::Result<Trashed> {
+    let dir = match std::env::var_os("XDG_DATA_HOME").filter(|d| !d.is_empty()) {
+        Some(data) => PathBuf::from(data).join("Trash"),
+        None => home()?.join(".local/share/Trash"),
+    };
+    let files = dir.join("files");
+    let infos = dir.join("info");
+    fs::create_dir_all(&files)?;
+    fs::create_dir_all(&infos)?;
+
+    let location = free_name(&files, &original, |name| {
+        !infos.join(format!("{}.trashinfo", name)).exists()
+    });
+    let name = location
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let info = infos.join(format!("{}.trashinfo", name));
+    let escaped: Vec<String> = original
+        .to_string_lossy()
+        .split('/')
+        .map(transform::url_encode)
+        .collect();
+    fs::write(
+        &info,
+        format!(
+            "[Trash Info]\nPath={}\nDeletionDate={}\n",
+            escaped.join("/"),
+            // Local time, as the trash spec wants
+            time::local_time()
+        ),
+    )?;
+    if let Err(e) = move_to(&original, &location) {
+        let _ = fs::remove_file(&info);
+        return Err(e);
+    }
+    Ok(Trashed {
+        original,
+        location,
+        info: Some(info),
+    })
+}
+
+/// `dir/name`, or `dir/name.2`, `dir/name.3`... for the first one that is
+/// free and that `also_free` accepts
+fn free_name(dir: &Path, original: &Path, also_free: impl Fn(&str) -> bool) -> PathBuf {
+    let name = original
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let mut candidate = name.clone();
+    let mut n = 1;
+    while fs::symlink_metadata(dir.join(&candidate)).is_ok() || !also_free(&candidate) {
+        n += 1;
+        candidate = format!("{}.{}", name, n);
+    }
+    dir.join(candidate)
+}
+
+/// Rename, with a clearer error when the trash is on another filesystem
+fn move_to(from: &Path, to: &Path) -> io::Result<()> {
+    fs::rename(from, to).map_err(|e| {
+        if e.kind() == io::ErrorKind::CrossesDevices {
+            io::Error::new(e.kind(), "a lixeira fica em outro sistema de arquivos")
+        } else {
+            e
+        }
+    })
+}
+
+/// Send a file to the Recycle Bin through PowerShell, which exposes the
+/// shell's recycle operation without extra dependencies
+fn recycle(path: &Path) -> io::Result<()> {
+    let method = if path.is_dir() {
+        "DeleteDirectory"
+    } else {
+        "DeleteFile"
+    };
+    let script = format!(
+        "Add-Type -AssemblyName Microsoft.VisualBasic; \
+         [Microsoft.VisualBasic.FileIO.FileSystem]::{}('{}', 'OnlyErrorDialogs', 'SendToRecycleBin')",
+        method,
+        path.to_string_lossy().replace('\'', "''")
+    );
+    let status = std::process::Command::new("powershell")
+        .args(["-NoProfile", "-NonInteractive", "-Command", &script])
+        .status()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other("a Lixeira recusou o arquivo"))
+    }
+}
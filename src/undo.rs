@@ -0,0 +1,121 @@
+use crate::buffer_file::LineEnding;
+
+/// One change to a buffer: the lines starting at `row` went from `before`
+/// to `after`. Undoing swaps them back.
+#[derive(Debug, Clone)]
+pub struct Edit {
+    pub row: usize,
+    pub before: Vec<Vec<char>>,
+    pub after: Vec<Vec<char>>,
+}
+
+impl Edit {
+    /// Replace this edit's `before` lines with its `after` lines
+    pub fn apply(&self, matrix: &mut Vec<Vec<char>>) {
+        let end = (self.row + self.before.len()).min(matrix.len());
+        matrix.splice(self.row..end, self.after.iter().cloned());
+    }
+
+    pub fn revert(&self, matrix: &mut Vec<Vec<char>>) {
+        let end = (self.row + self.after.len()).min(matrix.len());
+        matrix.splice(self.row..end, self.before.iter().cloned());
+    }
+}
+
+/// Edits undone and redone together, with the cursor on each side
+#[derive(Debug, Clone)]
+pub struct UndoGroup {
+    pub edits: Vec<Edit>,
+    // Line ending before and after, when the step changed it
+    pub line_ending: Option<(LineEnding, LineEnding)>,
+    pub cursor_before: (usize, usize),
+    pub cursor_after: (usize, usize),
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct UndoHistory {
+    undo: Vec<UndoGroup>,
+    redo: Vec<UndoGroup>,
+    pending: Option<UndoGroup>,
+    // Undo depth matching the file on disk; None once that state is unreachable
+    saved_depth: Option<usize>,
+}
+
+impl UndoHistory {
+    pub fn new() -> UndoHistory {
+        UndoHistory {
+            saved_depth: Some(0),
+            ..UndoHistory::default()
+        }
+    }
+
+    /// Add an edit to the open group, starting one if needed. Typing on the
+    /// same lines extends the previous edit instead of adding a new one.
+    pub fn record(&mut self, edit: Edit, cursor: (usize, usize)) {
+        let group = self.open_group(cursor);
+        if let Some(last) = group.edits.last_mut() {
+            if last.row == edit.row && last.after.len() == edit.before.len() {
+                last.after = edit.after;
+                return;
+            }
+        }
+        group.edits.push(edit);
+    }
+
+    /// Note in the open group that the line ending went from `before` to
+    /// `after`
+    pub fn record_line_ending(
+        &mut self,
+        before: LineEnding,
+        after: LineEnding,
+        cursor: (usize, usize),
+    ) {
+        let group = self.open_group(cursor);
+        group.line_ending = Some((before, after));
+    }
+
+    fn open_group(&mut self, cursor: (usize, usize)) -> &mut UndoGroup {
+        self.redo.clear();
+        self.pending.get_or_insert_with(|| UndoGroup {
+            edits: vec![],
+            line_ending: None,
+            cursor_before: cursor,
+            cursor_after: cursor,
+        })
+    }
+
+    /// Close the open group so the next edit starts a new undo step
+    pub fn commit(&mut self, cursor: (usize, usize)) {
+        if let Some(mut group) = self.pending.take() {
+            group.cursor_after = cursor;
+            if self
+                .saved_depth
+                .is_some_and(|depth| depth > self.undo.len())
+            {
+                self.saved_depth = None;
+            }
+            self.undo.push(group);
+        }
+    }
+
+    pub fn pop_undo(&mut self) -> Option<UndoGroup> {
+        let group = self.undo.pop()?;
+        self.redo.push(group.clone());
+        Some(group)
+    }
+
+    pub fn pop_redo(&mut self) -> Option<UndoGroup> {
+        let group = self.redo.pop()?;
+        self.undo.push(group.clone());
+        Some(group)
+    }
+
+    pub fn mark_saved(&mut self) {
+        self.saved_depth = Some(self.undo.len());
+    }
+
+    /// Whether the buffer differs from the last saved state
+    pub fn is_modified(&self) -> bool {
+        self.pending.is_some() || self.saved_depth != Some(self.undo.len())
+    }
+}
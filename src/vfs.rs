@@ -0,0 +1,469 @@
+use crate::archive;
+use crate::log;
+use crate::remote::{self, RemotePath};
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::fs;
+use std::io::{self, Cursor, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+pub struct DirEntry {
+    pub name: String,
+    // Links count as what they point to
+    pub is_dir: bool,
+    pub is_symlink: bool,
+    pub is_executable: bool,
+}
+
+/// Where files are read from and written to. Buffers keep the filesystem
+/// they were opened from, so saving goes back to the same place.
+pub trait Filesystem: Debug + Send + Sync {
+    /// Stream the contents of a file
+    fn open(&self, path: &Path) -> io::Result<Box<dyn Read + Send>>;
+    /// Replace the contents of a file, creating it if needed
+    fn write(&self, path: &Path, bytes: &[u8]) -> io::Result<()>;
+    fn size(&self, path: &Path) -> io::Result<u64>;
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<DirEntry>>;
+
+    /// When the file or folder last changed, where the filesystem knows
+    fn modified(&self, _path: &Path) -> io::Result<SystemTime> {
+        Err(io::Error::from(io::ErrorKind::Unsupported))
+    }
+
+    /// `path` with its links resolved, where the filesystem has links
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        Ok(path.to_path_buf())
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        let mut bytes = vec![];
+        self.open(path)?.read_to_end(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    /// Whether reading or writing talks to the user on the terminal, which
+    /// the editor has to hand back first
+    fn needs_terminal(&self) -> bool {
+        false
+    }
+
+    /// Whether text from its files must stay off the disk elsewhere, such
+    /// as the saved kill ring: encrypted files and the ones only root reads
+    fn is_private(&self) -> bool {
+        false
+    }
+}
+
+/// The filesystem a path names: remote for `usuário@host:/caminho`, an
+/// archive for `pacote.zip/dentro/dele`, the local disk otherwise
+pub fn for_path(path: &str) -> Arc<dyn Filesystem> {
+    if remote::is_remote(path) {
+        Arc::new(RemoteFs)
+    } else if archive::is_inside(path) {
+        archive()
+    } else {
+        local()
+    }
+}
+
+pub fn local() -> Arc<dyn Filesystem> {
+    Arc::new(LocalFs)
+}
+
+pub fn sudo() -> Arc<dyn Filesystem> {
+    Arc::new(SudoFs)
+}
+
+pub fn archive() -> Arc<dyn Filesystem> {
+    Arc::new(ArchiveFs)
+}
+
+/// Whether `a` and `b` name the same local file: the same path, or one
+/// reached through a symlink or a hard link to the other
+pub fn same_file(a: &str, b: &str) -> bool {
+    if a == b {
+        return true;
+    }
+    if a.is_empty() || b.is_empty() || remote::is_remote(a) || remote::is_remote(b) {
+        return false;
+    }
+    match (file_id(Path::new(a)), file_id(Path::new(b))) {
+        (Some(a), Some(b)) => a == b,
+        _ => false,
+    }
+}
+
+/// Device and inode, the same for every name of a file
+#[cfg(unix)]
+fn file_id(path: &Path) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    fs::metadata(path).ok().map(|meta| (meta.dev(), meta.ino()))
+}
+
+#[cfg(not(unix))]
+fn file_id(path: &Path) -> Option<PathBuf> {
+    fs::canonicalize(path).ok()
+}
+
+/// Put back the permissions and owner the file had before it was
+/// rewritten, for platforms and filesystems that reset them
+#[cfg(unix)]
+fn restore_metadata(path: &Path, before: &fs::Metadata) -> io::Result<()> {
+    use std::os::unix::fs::{chown, MetadataExt};
+    let after = fs::metadata(path)?;
+    if after.mode() != before.mode() {
+        fs::set_permissions(path, before.permissions())?;
+    }
+    if (after.uid(), after.gid()) != (before.uid(), before.gid()) {
+        // Only root can give a file away; the contents are saved anyway
+        if let Err(e) = chown(path, Some(before.uid()), Some(before.gid())) {
+            log::warning!(
+                "arquivo",
+                "dono de '{}' não restaurado: {}",
+                path.display(),
+                e
+            );
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn restore_metadata(path: &Path, before: &fs::Metadata) -> io::Result<()> {
+    if fs::metadata(path)?.permissions() != before.permissions() {
+        fs::set_permissions(path, before.permissions())?;
+    }
+    Ok(())
+}
+
+/// Whether the local file at `path` can be run (has an execute bit)
+#[cfg(unix)]
+pub fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    fs::metadata(path).is_ok_and(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+pub fn is_executable(_path: &Path) -> bool {
+    false
+}
+
+/// Give the file at `path` the execute bit wherever it has the read bit
+/// (user, group, others), as `chmod +x` does under the usual umask, or take
+/// every execute bit away
+#[cfg(unix)]
+pub fn set_executable(path: &Path, executable: bool) -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    let mut permissions = fs::metadata(path)?.permissions();
+    let mode = permissions.mode();
+    permissions.set_mode(if executable {
+        mode | (mode & 0o444) >> 2
+    } else {
+        mode & !0o111
+    });
+    fs::set_permissions(path, permissions)
+}
+
+#[cfg(not(unix))]
+pub fn set_executable(_path: &Path, _executable: bool) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "arquivos executáveis dependem da extensão neste sistema",
+    ))
+}
+
+/// Where the symlink at `path` leads, resolved to the file a save writes;
+/// `None` when `path` isn't a link
+pub fn link_target(path: &str) -> Option<PathBuf> {
+    let meta = fs::symlink_metadata(path).ok()?;
+    if !meta.file_type().is_symlink() {
+        return None;
+    }
+    fs::canonicalize(path).ok()
+}
+
+/// How many names (hard links) the file at `path` has
+#[cfg(unix)]
+pub fn hard_links(path: &str) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+    fs::metadata(path).map_or(1, |meta| meta.nlink())
+}
+
+#[cfg(not(unix))]
+pub fn hard_links(_path: &str) -> u64 {
+    1
+}
+
+#[derive(Debug)]
+pub struct LocalFs;
+
+impl Filesystem for LocalFs {
+    fn open(&self, path: &Path) -> io::Result<Box<dyn Read + Send>> {
+        Ok(Box::new(fs::File::open(path)?))
+    }
+
+    fn write(&self, path: &Path, bytes: &[u8]) -> io::Result<()> {
+        let before = fs::metadata(path).ok();
+        // A script saved into a new (or still empty) file can be run right
+        // away, as its shebang asks
+        let new_script = bytes.starts_with(b"#!")
+            && before.as_ref().is_none_or(|meta| meta.len() == 0)
+            && cfg!(unix);
+        fs::write(path, bytes)?;
+        if let Some(meta) = &before {
+            restore_metadata(path, meta)?;
+        }
+        if new_script {
+            set_executable(path, true)?;
+        }
+        Ok(())
+    }
+
+    fn size(&self, path: &Path) -> io::Result<u64> {
+        Ok(fs::metadata(path)?.len())
+    }
+
+    fn modified(&self, path: &Path) -> io::Result<SystemTime> {
+        fs::metadata(path)?.modified()
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        fs::canonicalize(path)
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<DirEntry>> {
+        Ok(fs::read_dir(path)?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| {
+                let file_type = entry.file_type().ok();
+                let is_symlink = file_type.is_some_and(|t| t.is_symlink());
+                let is_dir = if is_symlink {
+                    entry.path().is_dir()
+                } else {
+                    file_type.is_some_and(|t| t.is_dir())
+                };
+                DirEntry {
+                    name: entry.file_name().to_string_lossy().to_string(),
+                    is_dir,
+                    is_symlink,
+                    is_executable: !is_dir && is_executable(&entry.path()),
+                }
+            })
+            .collect())
+    }
+}
+
+/// Files kept in memory, for runs that must not touch the disk (--bench).
+/// Directories exist implicitly as the parents of files.
+#[derive(Debug, Default)]
+pub struct MemoryFs {
+    files: Mutex<HashMap<PathBuf, Vec<u8>>>,
+}
+
+impl MemoryFs {
+    fn not_found(path: &Path) -> io::Error {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("'{}' não existe", path.display()),
+        )
+    }
+
+    fn get(&self, path: &Path) -> io::Result<Vec<u8>> {
+        let files = self.files.lock().unwrap_or_else(|e| e.into_inner());
+        files
+            .get(path)
+            .cloned()
+            .ok_or_else(|| MemoryFs::not_found(path))
+    }
+}
+
+impl Filesystem for MemoryFs {
+    fn open(&self, path: &Path) -> io::Result<Box<dyn Read + Send>> {
+        Ok(Box::new(Cursor::new(self.get(path)?)))
+    }
+
+    fn write(&self, path: &Path, bytes: &[u8]) -> io::Result<()> {
+        let mut files = self.files.lock().unwrap_or_else(|e| e.into_inner());
+        files.insert(path.to_path_buf(), bytes.to_vec());
+        Ok(())
+    }
+
+    fn size(&self, path: &Path) -> io::Result<u64> {
+        let files = self.files.lock().unwrap_or_else(|e| e.into_inner());
+        files
+            .get(path)
+            .map(|bytes| bytes.len() as u64)
+            .ok_or_else(|| MemoryFs::not_found(path))
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<DirEntry>> {
+        let files = self.files.lock().unwrap_or_else(|e| e.into_inner());
+        let mut entries: Vec<DirEntry> = vec![];
+        for file in files.keys() {
+            let Ok(rest) = file.strip_prefix(path) else {
+                continue;
+            };
+            let mut parts = rest.components();
+            let Some(first) = parts.next() else {
+                continue;
+            };
+            let name = first.as_os_str().to_string_lossy().to_string();
+            if !entries.iter().any(|entry| entry.name == name) {
+                entries.push(DirEntry {
+                    name,
+                    is_dir: parts.next().is_some(),
+                    is_symlink: false,
+                    is_executable: false,
+                });
+            }
+        }
+        Ok(entries)
+    }
+}
+
+/// Files named `usuário@host:/caminho`, copied whole over SFTP/scp
+#[derive(Debug)]
+pub struct RemoteFs;
+
+impl RemoteFs {
+    fn remote_path(path: &Path) -> io::Result<RemotePath> {
+        RemotePath::parse(&path.to_string_lossy()).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("'{}' não é um caminho remoto", path.display()),
+            )
+        })
+    }
+}
+
+impl Filesystem for RemoteFs {
+    fn open(&self, path: &Path) -> io::Result<Box<dyn Read + Send>> {
+        let bytes = remote::read(&RemoteFs::remote_path(path)?)?;
+        Ok(Box::new(Cursor::new(bytes)))
+    }
+
+    fn write(&self, path: &Path, bytes: &[u8]) -> io::Result<()> {
+        remote::write(&RemoteFs::remote_path(path)?, bytes)
+    }
+
+    /// Needs a full copy; callers avoid it for remote files
+    fn size(&self, path: &Path) -> io::Result<u64> {
+        Ok(self.read(path)?.len() as u64)
+    }
+
+    fn read_dir(&self, _path: &Path) -> io::Result<Vec<DirEntry>> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "listar pastas remotas não é suportado",
+        ))
+    }
+}
+
+/// Local files the user can't write, saved by piping them to `sudo tee`.
+/// sudo asks for the password on the terminal.
+#[derive(Debug)]
+pub struct SudoFs;
+
+impl Filesystem for SudoFs {
+    fn open(&self, path: &Path) -> io::Result<Box<dyn Read + Send>> {
+        LocalFs.open(path)
+    }
+
+    fn write(&self, path: &Path, bytes: &[u8]) -> io::Result<()> {
+        let mut child = Command::new("sudo")
+            .arg("tee")
+            .arg("--")
+            .arg(path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .spawn()
+            .map_err(|e| io::Error::new(e.kind(), format!("sudo: {}", e)))?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(bytes)?;
+        }
+        if !child.wait()?.success() {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "sudo tee falhou",
+            ));
+        }
+        Ok(())
+    }
+
+    fn size(&self, path: &Path) -> io::Result<u64> {
+        LocalFs.size(path)
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<DirEntry>> {
+        LocalFs.read_dir(path)
+    }
+
+    fn needs_terminal(&self) -> bool {
+        true
+    }
+
+    fn is_private(&self) -> bool {
+        true
+    }
+}
+
+/// Files inside a zip or tar archive, at paths that go through the archive
+/// as if it were a folder. They are listed and read with `unzip` and `tar`
+/// and can't be written: `extract-file` copies one out to be edited.
+#[derive(Debug)]
+pub struct ArchiveFs;
+
+impl ArchiveFs {
+    fn split(path: &Path) -> io::Result<(PathBuf, String)> {
+        archive::split(path).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("'{}' não está num pacote", path.display()),
+            )
+        })
+    }
+}
+
+impl Filesystem for ArchiveFs {
+    fn open(&self, path: &Path) -> io::Result<Box<dyn Read + Send>> {
+        let (archive, inner) = ArchiveFs::split(path)?;
+        Ok(Box::new(Cursor::new(archive::read(&archive, &inner)?)))
+    }
+
+    fn write(&self, path: &Path, _bytes: &[u8]) -> io::Result<()> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!(
+                "'{}' está dentro de um pacote (extract-file extrai para editar)",
+                path.display()
+            ),
+        ))
+    }
+
+    /// Needs the file extracted; callers avoid it for big archives
+    fn size(&self, path: &Path) -> io::Result<u64> {
+        Ok(self.read(path)?.len() as u64)
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<DirEntry>> {
+        let (archive, inner) = ArchiveFs::split(path)?;
+        Ok(archive::list(&archive, &inner)?
+            .into_iter()
+            .map(|(name, is_dir)| DirEntry {
+                name,
+                is_dir,
+                is_symlink: false,
+                is_executable: false,
+            })
+            .collect())
+    }
+
+    /// The archive's, for everything in it
+    fn modified(&self, path: &Path) -> io::Result<SystemTime> {
+        let (archive, _) = ArchiveFs::split(path)?;
+        fs::metadata(archive)?.modified()
+    }
+}
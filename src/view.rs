@@ -0,0 +1,44 @@
+/// Editing mode, kept per buffer
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum EditorMode {
+    #[default]
+    Normal,
+    Insert,
+    /// Selecting text from `ViewState::anchor` to the cursor
+    Visual,
+}
+
+/// How a buffer is being looked at: cursor, scroll offsets and mode. Each
+/// buffer keeps its own, so switching tabs, cancelling a search or moving
+/// focus to the sidebar and back puts everything where it was.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ViewState {
+    // Cursor and scroll offsets, in file coordinates
+    pub cursor_row: usize,
+    pub cursor_col: usize,
+    pub initial_row: usize,
+    pub initial_column: usize,
+    // Column vertical movement tries to return to
+    pub preferred_col: usize,
+    pub mode: EditorMode,
+    // Where the selection started, in Visual mode
+    pub anchor: Option<(usize, usize)>,
+    // Selection held in place while the cursor moves through it (a search
+    // started in Visual mode looks only inside it)
+    pub scope: Option<((usize, usize), (usize, usize))>,
+}
+
+impl ViewState {
+    /// First and last (row, col) selected, both included, in Visual mode
+    pub fn selection(&self) -> Option<((usize, usize), (usize, usize))> {
+        if self.mode != EditorMode::Visual {
+            return None;
+        }
+        if self.scope.is_some() {
+            return self.scope;
+        }
+        let anchor = self.anchor?;
+        let cursor = (self.cursor_row, self.cursor_col);
+        Some((anchor.min(cursor), anchor.max(cursor)))
+    }
+}
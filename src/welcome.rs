@@ -1,5 +1,11 @@
 use crossterm::style::Color;
 
+/// Narrowest terminal that fits the ASCII-art banner
+const FULL_MIN_COLUMNS: usize = 56;
+
+/// Narrowest terminal that fits the compact logo and shortcut list
+const COMPACT_MIN_COLUMNS: usize = 18;
+
 pub struct WelcomeScreen;
 
 pub struct WelcomeChar {
@@ -40,7 +46,7 @@ impl WelcomeScreen {
             b: 80,
         };
 
-        let lines: Vec<(&str, Color)> = vec![
+        let full: Vec<(&str, Color)> = vec![
             ("", dim_color),
             (
                 "██████╗ ███████╗██████╗ ██╗████████╗ ██████╗ ██████╗",
@@ -78,6 +84,7 @@ impl WelcomeScreen {
             ("  Ctrl+W       Fechar aba", shortcut_desc_color),
             ("  Ctrl+Tab     Próxima aba", shortcut_desc_color),
             ("  Ctrl+F       Buscar no arquivo", shortcut_desc_color),
+            ("  Ctrl+P       Paleta de comandos", shortcut_desc_color),
             ("  Ctrl+Q       Sair", shortcut_desc_color),
             ("", dim_color),
             ("  i            Modo Insert", shortcut_desc_color),
@@ -88,6 +95,36 @@ impl WelcomeScreen {
             ("  Use: reditor <arquivo|pasta>", dim_color),
         ];
 
+        // Compact layout for terminals too narrow/short for the ASCII banner
+        let compact: Vec<(&str, Color)> = vec![
+            ("▄▀ reditor ▀▄", title_color),
+            ("v0.1.0", dim_color),
+            ("", dim_color),
+            ("  ^O  Abrir", shortcut_desc_color),
+            ("  ^T  Sidebar", shortcut_desc_color),
+            ("  ^S  Salvar", shortcut_desc_color),
+            ("  ^W  Fechar aba", shortcut_desc_color),
+            ("  ^F  Buscar", shortcut_desc_color),
+            ("  ^P  Comandos", shortcut_desc_color),
+            ("  ^Q  Sair", shortcut_desc_color),
+        ];
+
+        // Single-line hint for tiny panes
+        let tiny_text = if columns >= 29 {
+            "reditor · ^O abrir · ^Q sair"
+        } else {
+            "^O abrir ^Q sair"
+        };
+        let tiny: Vec<(&str, Color)> = vec![(tiny_text, title_color)];
+
+        let lines = if columns as usize >= FULL_MIN_COLUMNS && rows as usize >= full.len() {
+            full
+        } else if columns as usize >= COMPACT_MIN_COLUMNS && rows as usize >= compact.len() {
+            compact
+        } else {
+            tiny
+        };
+
         let mut matrix: Vec<Vec<WelcomeChar>> = vec![];
         let start_row = (rows as usize).saturating_sub(lines.len()) / 2;
 
@@ -114,12 +151,8 @@ impl WelcomeScreen {
                 let ch = if col >= padding && col < padding + char_count {
                     let ch = line_chars[col - padding];
                     // Color shortcut keys differently
-                    let fg = if line_text.starts_with("  Ctrl+")
-                        || line_text.starts_with("  i ")
-                        || line_text.starts_with("  Esc")
-                        || line_text.starts_with("  Home")
-                    {
-                        if col - padding < 14 {
+                    let fg = if let Some(key_len) = shortcut_key_len(line_text) {
+                        if col - padding < key_len {
                             shortcut_key_color
                         } else {
                             line_color
@@ -148,3 +181,18 @@ impl WelcomeScreen {
         matrix
     }
 }
+
+/// Number of leading characters of a shortcut line drawn in the key color
+fn shortcut_key_len(line: &str) -> Option<usize> {
+    if line.starts_with("  Ctrl+")
+        || line.starts_with("  i ")
+        || line.starts_with("  Esc")
+        || line.starts_with("  Home")
+    {
+        Some(14)
+    } else if line.starts_with("  ^") {
+        Some(4)
+    } else {
+        None
+    }
+}
@@ -1,7 +1,23 @@
 use crate::buffer_file::BufferFile;
+use crate::vfs;
+use crate::view::ViewState;
+
+/// One tab: a buffer seen through its own cursor, scroll and mode. Several
+/// views can show the same buffer, which stays open while any of them does.
+pub struct View {
+    // Index into `Workspace::buffers`
+    pub buffer: usize,
+    // Saved while the view is in the background; the active view's state
+    // lives in its buffer's `view` field, where editing updates it
+    pub state: ViewState,
+    // Transient tab opened from the sidebar; reused by the next preview
+    pub preview: bool,
+}
 
 pub struct Workspace {
     pub buffers: Vec<BufferFile>,
+    pub views: Vec<View>,
+    // Index into `views`
     pub active_index: usize,
 }
 
@@ -9,84 +25,235 @@ impl Workspace {
     pub fn new() -> Workspace {
         Workspace {
             buffers: vec![],
+            views: vec![],
             active_index: 0,
         }
     }
 
-    pub fn open_file(&mut self, path: &str) -> usize {
-        // Check if file is already open
-        for (i, buf) in self.buffers.iter().enumerate() {
-            if buf.filename == path {
-                self.active_index = i;
-                return i;
+    pub fn open_file(&mut self, path: &str) -> std::io::Result<usize> {
+        // Check if file is already open, maybe under another name (a
+        // symlink or hard link to it)
+        if let Some(i) = self.find_view(path) {
+            self.views[i].preview = false;
+            self.activate(i);
+            return Ok(i);
+        }
+
+        let buffer = BufferFile::new(path)?;
+        Ok(self.open_buffer(buffer))
+    }
+
+    /// Add an already-loaded buffer as a new tab and make it active. A buffer
+    /// for a file that is already open replaces the old contents.
+    pub fn open_buffer(&mut self, buffer: BufferFile) -> usize {
+        if let Some(b) = self.find_buffer(&buffer.filename) {
+            self.save_active_state();
+            self.buffers[b] = buffer;
+            for view in self.views.iter_mut().filter(|view| view.buffer == b) {
+                view.state = self.buffers[b].view;
             }
+            let i = self.find_view(&self.buffers[b].filename).unwrap_or(0);
+            self.active_index = i;
+            self.load_active_state();
+            return i;
         }
 
-        let buffer = BufferFile::new(path);
+        self.save_active_state();
+        self.push_view(buffer, self.views.len(), false)
+    }
+
+    /// Show `buffer` in the preview tab, replacing the previous preview.
+    /// A file that is already open just becomes the active tab.
+    pub fn open_preview(&mut self, buffer: BufferFile) -> usize {
+        if let Some(i) = self.find_view(&buffer.filename) {
+            self.activate(i);
+            return i;
+        }
+
+        self.save_active_state();
+        match self.views.iter().position(|view| view.preview) {
+            Some(i) => {
+                self.remove_view(i);
+                self.push_view(buffer, i, true)
+            }
+            None => self.push_view(buffer, self.views.len(), true),
+        }
+    }
+
+    /// Open another tab on the active buffer, starting where the active one is
+    pub fn duplicate_view(&mut self) -> Option<usize> {
+        let buffer = self.views.get(self.active_index)?.buffer;
+        let state = self.buffers[buffer].view;
+        self.save_active_state();
+        self.views.insert(
+            self.active_index + 1,
+            View {
+                buffer,
+                state,
+                preview: false,
+            },
+        );
+        self.active_index += 1;
+        Some(self.active_index)
+    }
+
+    /// Insert a tab for `buffer` at `at` and make it active. The current
+    /// view's state must be saved first.
+    fn push_view(&mut self, buffer: BufferFile, at: usize, preview: bool) -> usize {
         self.buffers.push(buffer);
-        self.active_index = self.buffers.len() - 1;
-        self.active_index
+        let buffer = self.buffers.len() - 1;
+        self.views.insert(
+            at,
+            View {
+                buffer,
+                state: self.buffers[buffer].view,
+                preview,
+            },
+        );
+        self.active_index = at;
+        at
+    }
+
+    /// Drop view `i`, and its buffer too unless another view shows it
+    fn remove_view(&mut self, i: usize) {
+        let buffer = self.views.remove(i).buffer;
+        if i < self.active_index {
+            self.active_index -= 1;
+        }
+        if self.views.iter().all(|view| view.buffer != buffer) {
+            self.buffers.remove(buffer);
+            for view in &mut self.views {
+                if view.buffer > buffer {
+                    view.buffer -= 1;
+                }
+            }
+        }
+    }
+
+    /// Index into `buffers` of the buffer for `path`
+    pub fn find_buffer(&self, path: &str) -> Option<usize> {
+        self.buffers.iter().position(|buf| buf.filename == path)
+    }
+
+    /// Index into `buffers` of the buffer for the file `path` names, which
+    /// may be open through another name for it (a symlink or hard link)
+    pub fn find_same_file(&self, path: &str) -> Option<usize> {
+        self.find_buffer(path).or_else(|| {
+            self.buffers
+                .iter()
+                .position(|buf| vfs::same_file(&buf.filename, path))
+        })
+    }
+
+    /// First tab showing the file `path` names
+    fn find_view(&self, path: &str) -> Option<usize> {
+        let buffer = self.find_same_file(path)?;
+        self.views.iter().position(|view| view.buffer == buffer)
+    }
+
+    /// Whether another tab shows the active buffer too
+    pub fn active_is_shared(&self) -> bool {
+        match self.views.get(self.active_index) {
+            Some(active) => {
+                self.views
+                    .iter()
+                    .filter(|view| view.buffer == active.buffer)
+                    .count()
+                    > 1
+            }
+            None => false,
+        }
     }
 
     pub fn close_active(&mut self) -> bool {
-        if self.buffers.is_empty() {
+        if self.views.is_empty() {
             return false;
         }
 
-        self.buffers.remove(self.active_index);
+        self.remove_view(self.active_index);
 
-        if self.buffers.is_empty() {
+        if self.views.is_empty() {
             self.active_index = 0;
             return true;
         }
 
-        if self.active_index >= self.buffers.len() {
-            self.active_index = self.buffers.len() - 1;
+        if self.active_index >= self.views.len() {
+            self.active_index = self.views.len() - 1;
         }
+        self.load_active_state();
 
         false
     }
 
     pub fn next_tab(&mut self) {
-        if self.buffers.len() > 1 {
-            self.save_cursor_position();
-            self.active_index = (self.active_index + 1) % self.buffers.len();
+        if self.views.len() > 1 {
+            self.activate((self.active_index + 1) % self.views.len());
         }
     }
 
     pub fn prev_tab(&mut self) {
-        if self.buffers.len() > 1 {
-            self.save_cursor_position();
+        if self.views.len() > 1 {
             if self.active_index == 0 {
-                self.active_index = self.buffers.len() - 1;
+                self.activate(self.views.len() - 1);
             } else {
-                self.active_index -= 1;
+                self.activate(self.active_index - 1);
             }
         }
     }
 
     #[allow(dead_code)]
     pub fn switch_to(&mut self, index: usize) {
-        if index < self.buffers.len() {
-            self.save_cursor_position();
-            self.active_index = index;
+        if index < self.views.len() {
+            self.activate(index);
         }
     }
 
-    fn save_cursor_position(&mut self) {
-        // Cursor position is saved externally by editor before switching
+    /// Make view `index` active, parking the current view's state
+    fn activate(&mut self, index: usize) {
+        if index == self.active_index {
+            return;
+        }
+        self.save_active_state();
+        self.active_index = index;
+        self.load_active_state();
+    }
+
+    fn save_active_state(&mut self) {
+        if let Some(view) = self.views.get_mut(self.active_index) {
+            view.state = self.buffers[view.buffer].view;
+        }
+    }
+
+    fn load_active_state(&mut self) {
+        if let Some(view) = self.views.get(self.active_index) {
+            let buffer = &mut self.buffers[view.buffer];
+            buffer.view = view.state;
+            // Another view may have removed lines since
+            buffer.clamp_cursor();
+        }
     }
 
     pub fn active(&self) -> Option<&BufferFile> {
-        self.buffers.get(self.active_index)
+        let view = self.views.get(self.active_index)?;
+        self.buffers.get(view.buffer)
     }
 
     pub fn active_mut(&mut self) -> Option<&mut BufferFile> {
-        self.buffers.get_mut(self.active_index)
+        let view = self.views.get(self.active_index)?;
+        self.buffers.get_mut(view.buffer)
+    }
+
+    /// Editing a preview turns it into a regular tab
+    pub fn keep_modified_previews(&mut self) {
+        for view in &mut self.views {
+            if view.preview && self.buffers[view.buffer].modified {
+                view.preview = false;
+            }
+        }
     }
 
     pub fn has_files(&self) -> bool {
-        !self.buffers.is_empty()
+        !self.views.is_empty()
     }
 
     pub fn is_any_modified(&self) -> bool {
@@ -100,15 +267,20 @@ impl Workspace {
         Ok(())
     }
 
-    pub fn tab_names(&self) -> Vec<(String, bool, bool)> {
-        self.buffers
+    /// (name, is active, is modified, is read-only) of each tab
+    pub fn tab_names(&self) -> Vec<(String, bool, bool, bool)> {
+        self.views
             .iter()
             .enumerate()
-            .map(|(i, b)| {
-                let name = b.short_name();
+            .map(|(i, view)| {
+                let b = &self.buffers[view.buffer];
+                let name = if view.preview {
+                    format!("{} (prévia)", b.short_name())
+                } else {
+                    b.short_name()
+                };
                 let is_active = i == self.active_index;
-                let is_modified = b.modified;
-                (name, is_active, is_modified)
+                (name, is_active, b.modified, b.read_only)
             })
             .collect()
     }